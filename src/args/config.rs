@@ -0,0 +1,411 @@
+use crate::args::Args;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Profiles available without a config file, a config profile of the same name replaces
+/// the built-in one.
+const BUILTIN_PROFILES: &str = r#"
+[quick]
+targets.ports = "top100"
+timing.timeout = 500
+timing.batch_size = 2000
+
+[full]
+targets.ports = "all"
+discovery.os_detect = true
+detection.tls_enum = true
+detection.script = ["default"]
+
+[stealth]
+targets.ports = "top100"
+discovery.ping = false
+timing.timeout = 3000
+timing.batch_size = 16
+"#;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Targets {
+    pub address: Option<String>,
+    pub ports: Option<String>,
+    pub top_ports: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Timing {
+    /// connect timeout of the port scan in milliseconds.
+    pub timeout: Option<u64>,
+    pub batch_size: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Discovery {
+    pub ping: Option<bool>,
+    pub ping_count: Option<u16>,
+    pub os_detect: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Detection {
+    pub tls_enum: Option<bool>,
+    pub dns_zone: Option<Vec<String>>,
+    pub check_credentials: Option<bool>,
+    pub max_login_attempts: Option<usize>,
+    pub cve_db: Option<String>,
+    pub script: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Output {
+    pub log_dir: Option<String>,
+    /// JSON scan result file.
+    pub file: Option<String>,
+    /// SQLite result store.
+    pub db: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Paths {
+    pub os_db: Option<String>,
+    pub services: Option<String>,
+    pub snmp_communities: Option<String>,
+    pub credentials: Option<String>,
+    pub script_dir: Option<String>,
+}
+
+/// The scan settings of a config file or profile, every one of them optional.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub targets: Targets,
+    pub timing: Timing,
+    pub discovery: Discovery,
+    pub detection: Detection,
+    pub output: Output,
+    pub fingerprint: Paths,
+}
+
+impl Settings {
+    /// `self` with the settings `overlay` sets replaced.
+    fn merge(&self, overlay: &Settings) -> Result<Settings, String> {
+        let mut base = serde_json::to_value(self).map_err(|error| error.to_string())?;
+        let overlay = serde_json::to_value(overlay).map_err(|error| error.to_string())?;
+        merge_value(&mut base, overlay);
+        serde_json::from_value(base).map_err(|error| error.to_string())
+    }
+}
+
+fn merge_value(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_value(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (_, Value::Null) => {}
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// A target group the monitor rescans on its own schedule.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MonitorGroup {
+    pub name: String,
+    pub address: String,
+    /// 5 field cron expression in UTC, e.g. `*/30 * * * *`, or `@hourly`, `@daily`...
+    pub schedule: String,
+    /// the ports of the profile or config when neither is given.
+    pub ports: Option<String>,
+    pub top_ports: Option<usize>,
+    pub profile: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Monitor {
+    /// SQLite result store every run is kept in.
+    pub db: Option<String>,
+    /// where the change events go: `stdout`, `file:<path>` or a webhook URL.
+    pub events: Vec<String>,
+    pub groups: Vec<MonitorGroup>,
+}
+
+/// A TOML or YAML config file: settings, named profiles and the profile used by default.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub profile: Option<String>,
+    pub profiles: HashMap<String, Settings>,
+    pub monitor: Monitor,
+    #[serde(flatten)]
+    pub settings: Settings,
+}
+
+impl Config {
+    /// Reads the config file at `path`, or `~/.config/magic-scanner/config.toml` when it
+    /// exists and no path is given.
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match Config::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|error| format!("open config file {} error: {}", path.display(), error))?;
+        Config::parse(&content, &path)
+            .map_err(|error| format!("parse config file {} error: {}", path.display(), error))
+    }
+
+    fn default_path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("magic-scanner").join("config.toml"))
+    }
+
+    /// Parses YAML for `.yaml` and `.yml` files, TOML otherwise.
+    pub fn parse(content: &str, path: &Path) -> Result<Config, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(content).map_err(|error| error.to_string())
+            }
+            _ => toml::from_str(content).map_err(|error| error.to_string()),
+        }
+    }
+
+    /// The config settings with `profile`, or the config default profile, applied on top.
+    pub fn settings(&self, profile: Option<&str>) -> Result<Settings, String> {
+        let name = match profile.or(self.profile.as_deref()) {
+            None => return Ok(self.settings.clone()),
+            Some(name) => name,
+        };
+        let builtin: HashMap<String, Settings> =
+            toml::from_str(BUILTIN_PROFILES).map_err(|error| error.to_string())?;
+        match self.profiles.get(name).or_else(|| builtin.get(name)) {
+            Some(profile) => self.settings.merge(profile),
+            None => Err(format!("unknown profile: {}", name)),
+        }
+    }
+
+    /// Fills the arguments not given on the command line from the config and profile.
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) -> Result<(), String> {
+        let settings = self.settings(args.profile.as_deref())?;
+        let Settings {
+            targets,
+            timing,
+            discovery,
+            detection,
+            output,
+            fingerprint,
+        } = settings;
+        set(
+            matches,
+            "address",
+            &mut args.address,
+            targets.address.map(Some),
+        );
+        // ports and top ports select the ports together, either one given on the command
+        // line replaces both of the config ones.
+        if !from_command_line(matches, "ports") && !from_command_line(matches, "top_ports") {
+            set(matches, "ports", &mut args.ports, targets.ports.map(Some));
+            set(
+                matches,
+                "top_ports",
+                &mut args.top_ports,
+                targets.top_ports.map(Some),
+            );
+        }
+        set(matches, "timeout", &mut args.timeout, timing.timeout);
+        set(
+            matches,
+            "batch_size",
+            &mut args.batch_size,
+            timing.batch_size,
+        );
+        set(matches, "ping", &mut args.ping, discovery.ping);
+        set(
+            matches,
+            "ping_count",
+            &mut args.ping_count,
+            discovery.ping_count,
+        );
+        set(
+            matches,
+            "os_detect",
+            &mut args.os_detect,
+            discovery.os_detect,
+        );
+        set(matches, "tls_enum", &mut args.tls_enum, detection.tls_enum);
+        set(matches, "dns_zone", &mut args.dns_zone, detection.dns_zone);
+        set(
+            matches,
+            "check_credentials",
+            &mut args.check_credentials,
+            detection.check_credentials,
+        );
+        set(
+            matches,
+            "max_login_attempts",
+            &mut args.max_login_attempts,
+            detection.max_login_attempts,
+        );
+        set(
+            matches,
+            "cve_db",
+            &mut args.cve_db,
+            detection.cve_db.map(Some),
+        );
+        set(matches, "script", &mut args.script, detection.script);
+        set(matches, "log_dir", &mut args.log_dir, output.log_dir);
+        set(matches, "output", &mut args.output, output.file.map(Some));
+        set(matches, "db", &mut args.db, output.db.map(Some));
+        set(matches, "os_db", &mut args.os_db, fingerprint.os_db);
+        set(
+            matches,
+            "services",
+            &mut args.services,
+            fingerprint.services,
+        );
+        set(
+            matches,
+            "snmp_communities",
+            &mut args.snmp_communities,
+            fingerprint.snmp_communities,
+        );
+        set(
+            matches,
+            "credentials",
+            &mut args.credentials,
+            fingerprint.credentials,
+        );
+        set(
+            matches,
+            "script_dir",
+            &mut args.script_dir,
+            fingerprint.script_dir,
+        );
+        Ok(())
+    }
+
+    /// The default arguments with the config settings and `profile` applied.
+    pub fn default_args(&self, profile: Option<String>) -> Result<Args, String> {
+        let matches = Args::command()
+            .try_get_matches_from(["magic-scanner"])
+            .map_err(|error| error.to_string())?;
+        let mut args = Args::from_arg_matches(&matches).map_err(|error| error.to_string())?;
+        args.profile = profile;
+        self.apply(&mut args, &matches)?;
+        Ok(args)
+    }
+
+    /// The scan arguments of a monitor group: the config settings with the group profile
+    /// applied, then the group targets.
+    pub fn group_args(&self, group: &MonitorGroup) -> Result<Args, String> {
+        let mut args = self
+            .default_args(group.profile.clone())
+            .map_err(|error| format!("group {}: {}", group.name, error))?;
+        args.address = Some(group.address.clone());
+        if group.ports.is_some() || group.top_ports.is_some() {
+            args.ports = group.ports.clone();
+            args.top_ports = group.top_ports;
+        }
+        Ok(args)
+    }
+}
+
+fn from_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Sets `target` to `value` unless the argument `id` was given on the command line.
+fn set<T>(matches: &ArgMatches, id: &str, target: &mut T, value: Option<T>) {
+    if from_command_line(matches, id) {
+        return;
+    }
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+#[cfg(test)]
+pub mod config_test {
+    use crate::args::config::Config;
+    use crate::args::Args;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::path::Path;
+
+    #[test]
+    fn config_apply_test() {
+        let config = Config::parse(
+            r#"
+profile = "lab"
+
+[targets]
+address = "10.0.0.0/24"
+ports = "web"
+
+[timing]
+timeout = 2000
+
+[profiles.lab.targets]
+ports = "T:22,U:53"
+
+[profiles.lab.detection]
+script = ["safe"]
+"#,
+            Path::new("config.toml"),
+        )
+        .unwrap();
+        let matches = Args::command()
+            .try_get_matches_from(["magic-scanner", "--timeout", "300"])
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!(args.address.as_deref(), Some("10.0.0.0/24"));
+        assert_eq!(args.ports.as_deref(), Some("T:22,U:53"));
+        assert_eq!(args.script, vec!["safe".to_string()]);
+        assert_eq!(args.timeout, 300);
+        assert_eq!(args.batch_size, 1000);
+
+        let matches = Args::command()
+            .try_get_matches_from(["magic-scanner", "--profile", "stealth", "-p", "80"])
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!(args.ports.as_deref(), Some("80"));
+        assert_eq!(
+            (args.timeout, args.batch_size, args.ping),
+            (3000, 16, false)
+        );
+
+        let matches = Args::command()
+            .try_get_matches_from(["magic-scanner", "--profile", "quick", "--top-ports", "10"])
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches).unwrap();
+        assert_eq!((args.ports, args.top_ports), (None, Some(10)));
+        assert_eq!(args.timeout, 500);
+
+        let yaml = Config::parse(
+            "targets:\n  address: 127.0.0.1\nprofile: nope\n",
+            Path::new("config.yaml"),
+        )
+        .unwrap();
+        assert_eq!(yaml.settings.targets.address.as_deref(), Some("127.0.0.1"));
+        assert!(yaml.settings(None).is_err());
+        assert!(Config::parse("[targets]\nports = 80\n", Path::new("config.toml")).is_err());
+    }
+}
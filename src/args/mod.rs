@@ -1,16 +1,357 @@
-use clap::Parser;
+use crate::report::diff::DiffFormat;
+use crate::report::ReportFormat;
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+pub mod config;
+
+/// Runs a scan when no subcommand is given, `magic-scanner -a 127.0.0.1` being
+/// `magic-scanner scan -a 127.0.0.1`.
+#[derive(Debug, Clone, Parser)]
+#[command(author, version = "0.2.0", about, long_about = None, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub scan: Args,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// scan ports and identify the services behind them.
+    Scan(Box<Args>),
+    /// find the hosts that answer to ping, without scanning them.
+    Discover(DiscoverArgs),
+    /// check, try out and import the service fingerprint database.
+    Fingerprint {
+        #[command(subcommand)]
+        command: FingerprintCommand,
+    },
+    /// convert a scan result written by scan --output to another format.
+    Report(ReportArgs),
+    /// compare two scan results written by scan --output.
+    Diff(DiffArgs),
+    /// query the scans kept by scan --db.
+    Store(StoreArgs),
+    /// rescan the monitor target groups of the config on their schedules and report the
+    /// changes between runs.
+    Monitor(MonitorArgs),
+    /// run the REST API to submit, follow, cancel and report scans.
+    Serve(ServeArgs),
+    /// split a scan into work units and hand them out to the workers connecting to it.
+    Coordinator(Box<CoordinatorArgs>),
+    /// scan the work units of a coordinator.
+    Worker(WorkerArgs),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct DiscoverArgs {
+    /// work address, accept ip, subnet mask, ip segment.
+    #[arg(short, long)]
+    pub address: String,
+    /// number of echo requests sent to each host.
+    #[arg(long, default_value_t = 1)]
+    pub ping_count: u16,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum FingerprintCommand {
+    /// report patterns that do not compile and other broken match rules.
+    Validate {
+        #[arg(long, default_value = "./fingerprint/fingerprint.json")]
+        file: String,
+    },
+    /// show the match rules a service response hits.
+    Test {
+        /// response to match, accept \r, \n, \t and \xHH escapes.
+        #[arg(long)]
+        banner: String,
+        /// only try the match rules of this probe.
+        #[arg(long)]
+        probe: Option<String>,
+        #[arg(long, default_value = "./fingerprint/fingerprint.json")]
+        file: String,
+    },
+    /// convert an nmap-service-probes file to the fingerprint format.
+    Import {
+        /// nmap-service-probes file.
+        input: String,
+        /// written to stdout when omitted.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ReportArgs {
+    /// scan result written by scan --output.
+    pub input: String,
+    #[arg(short, long, value_enum, default_value = "text")]
+    pub format: ReportFormat,
+    /// written to stdout when omitted.
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct DiffArgs {
+    /// earlier scan result.
+    pub before: String,
+    /// later scan result.
+    pub after: String,
+    #[arg(short, long, value_enum, default_value = "text")]
+    pub format: DiffFormat,
+    /// written to stdout when omitted.
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// exit with 1 when the scans differ, like git diff --exit-code, errors exit with 2.
+    #[arg(long)]
+    pub exit_code: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct StoreArgs {
+    /// SQLite result store written by scan --db.
+    #[arg(long, default_value = "./magic-scanner.db")]
+    pub db: String,
+    #[command(subcommand)]
+    pub command: StoreCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum StoreCommand {
+    /// list the stored scans.
+    Scans,
+    /// list the hosts with an open port or service, in the latest scan by default.
+    Hosts {
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// service name, http matches ssl/http as well.
+        #[arg(short, long)]
+        service: Option<String>,
+        /// scan id, see store scans.
+        #[arg(long, conflicts_with = "all")]
+        scan: Option<i64>,
+        /// search every stored scan.
+        #[arg(long)]
+        all: bool,
+    },
+    /// write a stored scan in one of the report formats.
+    Export {
+        /// scan id, see store scans.
+        scan: i64,
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: ReportFormat,
+        /// written to stdout when omitted.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct MonitorArgs {
+    /// config file with the [monitor] section, ~/.config/magic-scanner/config.toml when it
+    /// exists.
+    #[arg(long)]
+    pub config: Option<String>,
+    /// SQLite result store, monitor.db of the config or ./magic-scanner.db.
+    #[arg(long)]
+    pub db: Option<String>,
+    /// where the change events go instead of monitor.events: stdout, file:<path> or a
+    /// webhook URL, repeatable.
+    #[arg(long)]
+    pub event: Vec<String>,
+    /// run every group once right away and exit.
+    #[arg(long)]
+    pub once: bool,
+    /// directory of the daily rolling log file, logs go to stderr only when omitted or when the
+    /// events go to stdout.
+    #[arg(long)]
+    pub log_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ServeArgs {
+    /// address the API listens on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+    /// bearer token the requests must carry, the API is open when omitted.
+    #[arg(long, env = "MAGIC_SCANNER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// scans running at the same time, the others wait in the queue.
+    #[arg(long, default_value_t = 2)]
+    pub max_running: usize,
+    /// done scans kept for the API, the oldest are dropped first, --db keeps every scan.
+    #[arg(long, default_value_t = 100)]
+    pub max_done: usize,
+    /// config file the scan requests start from, ~/.config/magic-scanner/config.toml when
+    /// it exists.
+    #[arg(long)]
+    pub config: Option<String>,
+    /// keep the scan results in a SQLite store as well.
+    #[arg(long)]
+    pub db: Option<String>,
+    /// directory of the daily rolling log file, logs go to stderr only when omitted.
+    #[arg(long)]
+    pub log_dir: Option<String>,
+    /// let the scan requests check credentials and run scripts, which are refused otherwise
+    /// unless the server config enables them.
+    #[arg(long)]
+    pub allow_intrusive: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct CoordinatorArgs {
+    /// address the workers connect to.
+    #[arg(long, default_value = "127.0.0.1:7070")]
+    pub listen: String,
+    /// token the workers must register with, any worker is accepted when omitted.
+    #[arg(long, env = "MAGIC_SCANNER_CLUSTER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// milliseconds between the heartbeats of the workers.
+    #[arg(long, default_value_t = 5000)]
+    pub heartbeat_interval: u64,
+    /// milliseconds without a heartbeat after which the unit of a worker goes to another one.
+    #[arg(long, default_value_t = 15000)]
+    pub heartbeat_timeout: u64,
+    /// hosts of a work unit at most.
+    #[arg(long, default_value_t = 256)]
+    pub hosts_per_unit: usize,
+    /// ports of a work unit at most.
+    #[arg(long, default_value_t = 1000)]
+    pub ports_per_unit: usize,
+    #[command(flatten)]
+    pub scan: Args,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct WorkerArgs {
+    /// address of the coordinator.
+    #[arg(long, default_value = "127.0.0.1:7070")]
+    pub coordinator: String,
+    /// name the coordinator logs the worker with, worker-<process id> when omitted.
+    #[arg(long)]
+    pub name: Option<String>,
+    /// token the coordinator expects.
+    #[arg(long, env = "MAGIC_SCANNER_CLUSTER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// directory of the daily rolling log file, logs go to stderr only when omitted.
+    #[arg(long)]
+    pub log_dir: Option<String>,
+    #[command(flatten)]
+    pub files: WorkerFiles,
+}
+
+/// The files a worker scans with, read on the worker host whatever the paths of the
+/// coordinator are.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct WorkerFiles {
+    /// OS signature database used when the coordinator enables --os-detect.
+    #[arg(long, default_value = "./fingerprint/os-db.json")]
+    pub os_db: String,
+    /// community string wordlist tried against SNMP agents on UDP 161.
+    #[arg(long, default_value = "./fingerprint/snmp-communities.txt")]
+    pub snmp_communities: String,
+    /// default credentials file used when the coordinator enables --check-credentials.
+    #[arg(long, default_value = "./fingerprint/credentials.json")]
+    pub credentials: String,
+    /// offline CVE database used when the coordinator enables --cve-db.
+    #[arg(long)]
+    pub cve_db: Option<String>,
+    /// directory of the Rhai check scripts the coordinator selects with --script.
+    #[arg(long, default_value = "./scripts")]
+    pub script_dir: String,
+}
+
+impl WorkerFiles {
+    /// Replaces the file paths of the coordinator's `args` with the ones of the worker.
+    pub fn apply(&self, args: &mut Args) {
+        args.os_db = self.os_db.clone();
+        args.snmp_communities = self.snmp_communities.clone();
+        args.credentials = self.credentials.clone();
+        args.script_dir = self.script_dir.clone();
+        if args.cve_db.is_some() && self.cve_db.is_none() {
+            tracing::warn!(
+                "the coordinator matches CVEs but the worker has no --cve-db, skipping."
+            );
+        }
+        args.cve_db = args.cve_db.as_ref().and(self.cve_db.clone());
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
-#[command(author, version = "0.2.0", about, long_about = None)]
 pub struct Args {
     /// work address, accept ip, subnet mask, ip segment./n
     #[arg(short, long)]
-    pub address: String,
+    pub address: Option<String>,
+    /// config file, ~/.config/magic-scanner/config.toml when it exists, accept TOML or YAML.
+    #[arg(long)]
+    pub config: Option<String>,
+    /// scan profile of the config file or built in (quick, full, stealth), overridden by flags.
+    #[arg(long)]
+    pub profile: Option<String>,
     /// whether to ping before work.
     #[arg(long)]
     pub ping: bool,
-    /// work port, accept port, port range.
+    /// number of echo requests sent to each host when pinging.
+    #[arg(long, default_value_t = 1)]
+    pub ping_count: u16,
+    /// enable active OS detection, requires raw socket privileges.
+    #[arg(short = 'O', long)]
+    pub os_detect: bool,
+    /// OS signature database, accept a JSON signature list or an nmap-os-db file.
+    #[arg(long, default_value = "./fingerprint/os-db.json")]
+    pub os_db: String,
+    /// enumerate TLS protocol versions and cipher suites and report weak configurations.
+    #[arg(long)]
+    pub tls_enum: bool,
+    /// domains to attempt a zone transfer (AXFR) for on DNS services, comma separated.
+    #[arg(long, value_delimiter = ',')]
+    pub dns_zone: Vec<String>,
+    /// community string wordlist tried against SNMP agents on UDP 161.
+    #[arg(long, default_value = "./fingerprint/snmp-communities.txt")]
+    pub snmp_communities: String,
+    /// try the default and anonymous logins of the credentials file on identified services.
+    #[arg(long)]
+    pub check_credentials: bool,
+    /// default credentials file used by --check-credentials.
+    #[arg(long, default_value = "./fingerprint/credentials.json")]
+    pub credentials: String,
+    /// logins tried against a single host at most by --check-credentials.
+    #[arg(long, default_value_t = 10)]
+    pub max_login_attempts: usize,
+    /// offline CVE database matched against detected CPEs, accept an NVD JSON feed, an NVD
+    /// API response, a compact index or a directory of them.
+    #[arg(long)]
+    pub cve_db: Option<String>,
+    /// check scripts to run on identified ports, by name or category (default, safe, all...), comma separated.
+    #[arg(long, value_delimiter = ',')]
+    pub script: Vec<String>,
+    /// directory of the Rhai check scripts selected by --script.
+    #[arg(long, default_value = "./scripts")]
+    pub script_dir: String,
+    /// work port, accept port, port range, topN, named sets (web, db, windows, iot, mail, remote, all) and service names, TCP unless a U: or S: prefix makes the following ports UDP or SCTP (T: switches back).
     #[arg(short, long)]
     pub ports: Option<String>,
+    /// scan the N most frequent TCP ports of the service table, with a warning when the table lists fewer. Every TCP port of the table is scanned when -p is omitted too, 326 with the shipped one.
+    #[arg(long)]
+    pub top_ports: Option<usize>,
+    /// service table with the port frequencies and names used by -p and --top-ports.
+    #[arg(long, default_value = "./fingerprint/services.txt")]
+    pub services: String,
+    /// connect timeout of the port scan in milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    pub timeout: u64,
+    /// ports scanned at the same time.
+    #[arg(long, default_value_t = 1000)]
+    pub batch_size: usize,
+    /// directory of the daily rolling log file.
+    #[arg(long, default_value = "./log")]
+    pub log_dir: String,
+    /// write the scan result to a JSON file, the input of the report and diff commands.
+    #[arg(long)]
+    pub output: Option<String>,
+    /// keep the scan result in a SQLite store, the input of the store commands.
+    #[arg(long)]
+    pub db: Option<String>,
 }
@@ -0,0 +1,362 @@
+use crate::args::Args;
+use crate::cluster::protocol::{
+    read_message, read_message_limited, write_message, Message, MAX_REGISTER_SIZE,
+};
+use crate::cluster::WorkUnit;
+use crate::scanner::plugin::{HostReply, ResultSink};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::io::BufReader;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone)]
+pub struct CoordinatorOptions {
+    /// token the workers register with, any worker is accepted when `None`.
+    pub token: Option<String>,
+    pub heartbeat_interval: Duration,
+    /// a worker not heard of for that long is dead, its unit goes to another worker.
+    pub heartbeat_timeout: Duration,
+}
+
+impl Default for CoordinatorOptions {
+    fn default() -> Self {
+        CoordinatorOptions {
+            token: None,
+            heartbeat_interval: Duration::from_secs(5),
+            heartbeat_timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+struct Worker {
+    name: String,
+    last_seen: Instant,
+    unit: Option<WorkUnit>,
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: VecDeque<WorkUnit>,
+    workers: HashMap<u64, Worker>,
+    next_worker_id: u64,
+    hosts: BTreeMap<IpAddr, HostReply>,
+    done: usize,
+    total: usize,
+}
+
+/// Hands the work units out to the registered workers one at a time and merges their
+/// results per host.
+pub struct Coordinator {
+    args: Args,
+    options: CoordinatorOptions,
+    state: Mutex<State>,
+    sinks: Vec<Arc<dyn ResultSink>>,
+    finished: Notify,
+    connections: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Coordinator {
+    pub fn new(
+        args: Args,
+        units: Vec<WorkUnit>,
+        options: CoordinatorOptions,
+        sinks: Vec<Arc<dyn ResultSink>>,
+    ) -> Coordinator {
+        let state = State {
+            total: units.len(),
+            pending: units.into(),
+            ..Default::default()
+        };
+        Coordinator {
+            args,
+            options,
+            state: Mutex::new(state),
+            sinks,
+            finished: Notify::new(),
+            connections: Mutex::new(vec![]),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Accepts workers on `listener` until every unit is done, then returns the merged
+    /// hosts.
+    pub async fn run(self: Arc<Coordinator>, listener: TcpListener) -> Vec<HostReply> {
+        if self.state().total > 0 {
+            let accept = {
+                let coordinator = self.clone();
+                async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => {
+                                let connection =
+                                    tokio::spawn(coordinator.clone().serve_worker(stream));
+                                coordinator
+                                    .connections
+                                    .lock()
+                                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                                    .push(connection);
+                            }
+                            Err(error) => tracing::warn!("accept worker error: {}", error),
+                        }
+                    }
+                }
+            };
+            let watch = {
+                let coordinator = self.clone();
+                async move {
+                    loop {
+                        tokio::time::sleep(coordinator.options.heartbeat_interval).await;
+                        coordinator.reap_dead_workers();
+                    }
+                }
+            };
+            tokio::select! {
+                _ = accept => {}
+                _ = watch => {}
+                _ = self.finished.notified() => {}
+            }
+        }
+
+        let hosts = {
+            let mut state = self.state();
+            for (_, worker) in state.workers.drain() {
+                let _ = worker.sender.send(Message::Shutdown);
+            }
+            std::mem::take(&mut state.hosts)
+                .into_values()
+                .collect::<Vec<_>>()
+        };
+        // lets the workers read their shutdown before the process exits.
+        let connections = std::mem::take(
+            &mut *self
+                .connections
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        for connection in connections {
+            let _ = tokio::time::timeout(self.options.heartbeat_interval, connection).await;
+        }
+        for host in hosts.iter() {
+            for sink in self.sinks.iter() {
+                sink.host(host);
+            }
+        }
+        for sink in self.sinks.iter() {
+            sink.finish(&hosts);
+        }
+        hosts
+    }
+
+    /// Puts the unit of every worker not heard of within the heartbeat timeout back in
+    /// the queue.
+    fn reap_dead_workers(&self) {
+        let mut state = self.state();
+        let dead = state
+            .workers
+            .iter()
+            .filter(|(_, worker)| worker.last_seen.elapsed() > self.options.heartbeat_timeout)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in dead {
+            Coordinator::remove_worker(&mut state, id, "missed its heartbeats");
+        }
+        Coordinator::dispatch(&mut state);
+    }
+
+    fn remove_worker(state: &mut State, id: u64, reason: &str) {
+        let worker = match state.workers.remove(&id) {
+            Some(worker) => worker,
+            None => return,
+        };
+        match worker.unit {
+            Some(unit) => {
+                tracing::warn!(
+                    "worker {} ({}) {}, unit {} reassigned.",
+                    id,
+                    worker.name,
+                    reason,
+                    unit.id
+                );
+                state.pending.push_front(unit);
+            }
+            None => tracing::warn!("worker {} ({}) {}.", id, worker.name, reason),
+        }
+    }
+
+    /// Gives the next pending unit to every idle worker.
+    fn dispatch(state: &mut State) {
+        let idle = state
+            .workers
+            .iter()
+            .filter(|(_, worker)| worker.unit.is_none())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in idle {
+            let unit = match state.pending.pop_front() {
+                Some(unit) => unit,
+                None => return,
+            };
+            let worker = state.workers.get_mut(&id).unwrap();
+            tracing::info!(
+                "unit {} ({} sockets) assigned to worker {} ({}).",
+                unit.id,
+                unit.sockets(),
+                id,
+                worker.name
+            );
+            if worker
+                .sender
+                .send(Message::Assign { unit: unit.clone() })
+                .is_err()
+            {
+                state.pending.push_front(unit);
+                continue;
+            }
+            worker.unit = Some(unit);
+        }
+    }
+
+    fn complete_unit(&self, worker_id: u64, unit_id: u64, hosts: Vec<HostReply>) {
+        let mut state = self.state();
+        let worker = match state.workers.get_mut(&worker_id) {
+            Some(worker) => worker,
+            None => return,
+        };
+        // the unit was reassigned when the worker was late, the first result counts.
+        if worker.unit.as_ref().map(|unit| unit.id) != Some(unit_id) {
+            tracing::info!("late result of unit {} ignored.", unit_id);
+            return;
+        }
+        worker.unit = None;
+        for host in hosts {
+            for reply in host.ports() {
+                for sink in self.sinks.iter() {
+                    sink.port(reply);
+                }
+            }
+            match state.hosts.get_mut(&host.ip_addr()) {
+                Some(merged) => merged.merge(host),
+                None => {
+                    state.hosts.insert(host.ip_addr(), host);
+                }
+            }
+        }
+        state.done += 1;
+        tracing::info!(
+            "unit {} done by worker {}, {}/{} units.",
+            unit_id,
+            worker_id,
+            state.done,
+            state.total
+        );
+        for sink in self.sinks.iter() {
+            sink.progress(state.done, state.total);
+        }
+        if state.done == state.total {
+            self.finished.notify_one();
+            return;
+        }
+        Coordinator::dispatch(&mut state);
+    }
+
+    async fn serve_worker(self: Arc<Coordinator>, stream: TcpStream) {
+        let peer = stream
+            .peer_addr()
+            .map(|peer| peer.to_string())
+            .unwrap_or_default();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        // small and quick until the token is checked, a peer could hold the connection or
+        // a large buffer otherwise.
+        let register = read_message_limited(&mut reader, MAX_REGISTER_SIZE);
+        let name = match tokio::time::timeout(self.options.heartbeat_timeout, register).await {
+            Ok(Ok(Some(Message::Register { name, token }))) => {
+                let accepted = match &self.options.token {
+                    Some(expected) => ring::constant_time::verify_slices_are_equal(
+                        token.unwrap_or_default().as_bytes(),
+                        expected.as_bytes(),
+                    )
+                    .is_ok(),
+                    None => true,
+                };
+                if !accepted {
+                    tracing::warn!("worker {} at {} rejected: invalid token.", name, peer);
+                    let reason = "invalid token".to_string();
+                    let _ = write_message(&mut writer, &Message::Rejected { reason }).await;
+                    return;
+                }
+                name
+            }
+            Ok(_) | Err(_) => {
+                tracing::warn!("{} did not register as a worker.", peer);
+                return;
+            }
+        };
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let worker_id = {
+            let mut state = self.state();
+            state.next_worker_id += 1;
+            let worker_id = state.next_worker_id;
+            let _ = sender.send(Message::Welcome {
+                worker_id,
+                heartbeat_interval_ms: self.options.heartbeat_interval.as_millis() as u64,
+                args: Box::new(self.args.clone()),
+            });
+            state.workers.insert(
+                worker_id,
+                Worker {
+                    name: name.clone(),
+                    last_seen: Instant::now(),
+                    unit: None,
+                    sender,
+                },
+            );
+            tracing::info!("worker {} ({}) registered from {}.", worker_id, name, peer);
+            Coordinator::dispatch(&mut state);
+            worker_id
+        };
+
+        let send = async {
+            while let Some(message) = receiver.recv().await {
+                let shutdown = matches!(message, Message::Shutdown);
+                if write_message(&mut writer, &message).await.is_err() || shutdown {
+                    break;
+                }
+            }
+        };
+        let receive = async {
+            loop {
+                let message = match read_message(&mut reader).await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => return "disconnected".to_string(),
+                    Err(error) => return error,
+                };
+                if let Some(worker) = self.state().workers.get_mut(&worker_id) {
+                    worker.last_seen = Instant::now();
+                }
+                if let Message::UnitDone { unit_id, hosts } = message {
+                    self.complete_unit(worker_id, unit_id, hosts);
+                }
+            }
+        };
+        // the sender closes when the worker is removed, e.g. for missing its heartbeats.
+        let reason = tokio::select! {
+            _ = send => "was removed".to_string(),
+            reason = receive => reason,
+        };
+        let mut state = self.state();
+        Coordinator::remove_worker(&mut state, worker_id, &reason);
+        Coordinator::dispatch(&mut state);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::utils::port::PortSpec;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+mod coordinator;
+pub mod protocol;
+mod worker;
+
+pub use coordinator::{Coordinator, CoordinatorOptions};
+pub use worker::run_worker;
+
+/// Part of a scan handed to a single worker: every port of `ports` on every host of `ips`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkUnit {
+    pub id: u64,
+    pub ips: Vec<IpAddr>,
+    pub ports: PortSpec,
+}
+
+impl WorkUnit {
+    pub fn sockets(&self) -> usize {
+        self.ips.len() * self.ports.len()
+    }
+}
+
+/// Splits the target × port product into units of at most `hosts_per_unit` hosts and
+/// `ports_per_unit` ports, in the port-major order of `SocketIterator`.
+pub fn work_units(
+    ips: &[IpAddr],
+    ports: &PortSpec,
+    hosts_per_unit: usize,
+    ports_per_unit: usize,
+) -> Vec<WorkUnit> {
+    let mut units = vec![];
+    for ports in ports.chunks(ports_per_unit) {
+        for ips in ips.chunks(hosts_per_unit.max(1)) {
+            units.push(WorkUnit {
+                id: units.len() as u64 + 1,
+                ips: ips.to_vec(),
+                ports: ports.clone(),
+            });
+        }
+    }
+    units
+}
+
+#[cfg(test)]
+pub mod cluster_test {
+    use crate::args::{Args, WorkerFiles};
+    use crate::cluster::protocol::{read_message, write_message, Message};
+    use crate::cluster::{run_worker, work_units, Coordinator, CoordinatorOptions, WorkUnit};
+    use crate::utils::port::PortSpec;
+    use std::collections::BTreeSet;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn work_units_test() {
+        let ips: Vec<IpAddr> = vec![
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+            "10.0.0.3".parse().unwrap(),
+        ];
+        let ports = PortSpec {
+            tcp: BTreeSet::from([22, 80, 443]),
+            udp: BTreeSet::from([53]),
+            sctp: BTreeSet::new(),
+        };
+        let units = work_units(&ips, &ports, 2, 2);
+        assert_eq!(units.len(), 4);
+        assert_eq!(units[0].ips, ips[..2]);
+        assert_eq!(units[0].ports.tcp, BTreeSet::from([22, 80]));
+        assert_eq!(units[1].ips, ips[2..]);
+        assert_eq!(units[3].ports.udp, BTreeSet::from([53]));
+        assert_eq!(units.iter().map(WorkUnit::sockets).sum::<usize>(), 12);
+        assert_eq!(
+            units.iter().map(|unit| unit.id).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn worker_files_test() {
+        let mut args = Args {
+            os_db: "/coordinator/os-db.json".to_string(),
+            script_dir: "/coordinator/scripts".to_string(),
+            cve_db: Some("/coordinator/nvd".to_string()),
+            ..Default::default()
+        };
+        let files = WorkerFiles {
+            os_db: "./fingerprint/os-db.json".to_string(),
+            script_dir: "./scripts".to_string(),
+            ..Default::default()
+        };
+        files.apply(&mut args);
+        assert_eq!(args.os_db, "./fingerprint/os-db.json");
+        assert_eq!(args.script_dir, "./scripts");
+        assert_eq!(args.cve_db, None);
+
+        args.cve_db = None;
+        let files = WorkerFiles {
+            cve_db: Some("./nvd".to_string()),
+            ..files
+        };
+        files.apply(&mut args);
+        assert_eq!(args.cve_db, None);
+        args.cve_db = Some("/coordinator/nvd".to_string());
+        files.apply(&mut args);
+        assert_eq!(args.cve_db.as_deref(), Some("./nvd"));
+    }
+
+    #[tokio::test]
+    async fn coordinator_worker_test() {
+        let targets = (0..2)
+            .map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect::<Vec<_>>();
+        let ports = PortSpec {
+            tcp: targets
+                .iter()
+                .map(|target| target.local_addr().unwrap().port())
+                .collect(),
+            ..Default::default()
+        };
+        let ips = vec!["127.0.0.1".parse().unwrap()];
+        let args = Args {
+            timeout: 500,
+            batch_size: 16,
+            ..Default::default()
+        };
+        let options = CoordinatorOptions {
+            token: Some("secret".to_string()),
+            heartbeat_interval: Duration::from_millis(100),
+            heartbeat_timeout: Duration::from_millis(500),
+        };
+        let units = work_units(&ips, &ports, 1, 1);
+        let coordinator = Arc::new(Coordinator::new(args, units, options, vec![]));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let run = tokio::spawn(coordinator.run(listener));
+
+        let files = WorkerFiles::default();
+        let rejected = run_worker(&address, "intruder", None, &files).await;
+        assert!(rejected.unwrap_err().contains("invalid token"));
+
+        // a silent peer and an oversized register are dropped before being authenticated.
+        let silent = TcpStream::connect(&address).await.unwrap();
+        let mut silent = BufReader::new(silent);
+        let closed = tokio::time::timeout(Duration::from_secs(5), read_message(&mut silent));
+        assert!(matches!(closed.await, Ok(Ok(None)) | Ok(Err(_))));
+        let mut oversized = TcpStream::connect(&address).await.unwrap();
+        let _ = oversized.write_all(&[b'a'; 64 * 1024]).await;
+        let mut oversized = BufReader::new(oversized);
+        let closed = tokio::time::timeout(Duration::from_secs(5), read_message(&mut oversized));
+        assert!(matches!(closed.await, Ok(Ok(None)) | Ok(Err(_))));
+
+        // takes the first unit and goes silent, the unit must be scanned by another worker.
+        let stream = TcpStream::connect(&address).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let register = Message::Register {
+            name: "dead".to_string(),
+            token: Some("secret".to_string()),
+        };
+        write_message(&mut writer, &register).await.unwrap();
+        assert!(matches!(
+            read_message(&mut reader).await,
+            Ok(Some(Message::Welcome { .. }))
+        ));
+        assert!(matches!(
+            read_message(&mut reader).await,
+            Ok(Some(Message::Assign { .. }))
+        ));
+
+        let workers = (0..2)
+            .map(|n| {
+                let address = address.clone();
+                let files = files.clone();
+                tokio::spawn(async move {
+                    run_worker(
+                        &address,
+                        &format!("worker-{}", n),
+                        Some("secret".to_string()),
+                        &files,
+                    )
+                    .await
+                })
+            })
+            .collect::<Vec<_>>();
+        let hosts = tokio::time::timeout(Duration::from_secs(30), run)
+            .await
+            .unwrap()
+            .unwrap();
+        for worker in workers {
+            assert_eq!(worker.await.unwrap(), Ok(()));
+        }
+        assert_eq!(hosts.len(), 1);
+        let mut open = hosts[0].open_tcp_ports();
+        open.sort();
+        assert_eq!(open, ports.tcp.iter().copied().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::args::Args;
+use crate::cluster::WorkUnit;
+use crate::scanner::plugin::HostReply;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Longest message accepted, the results of a work unit being the largest.
+const MAX_MESSAGE_SIZE: u64 = 64 * 1024 * 1024;
+/// Longest `Register` accepted, read before the peer is authenticated.
+pub const MAX_REGISTER_SIZE: u64 = 4 * 1024;
+
+/// The messages between the coordinator and its workers, one JSON object per line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// first message of a worker.
+    Register {
+        name: String,
+        token: Option<String>,
+    },
+    /// the coordinator accepted the worker, `args` holding the scan options every unit is
+    /// scanned with, the file paths replaced with the ones of the worker.
+    Welcome {
+        worker_id: u64,
+        heartbeat_interval_ms: u64,
+        args: Box<Args>,
+    },
+    /// the coordinator refused the worker.
+    Rejected {
+        reason: String,
+    },
+    Heartbeat,
+    Assign {
+        unit: WorkUnit,
+    },
+    UnitDone {
+        unit_id: u64,
+        hosts: Vec<HostReply>,
+    },
+    /// every unit is done, the worker exits.
+    Shutdown,
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+) -> Result<(), String> {
+    let mut line = serde_json::to_vec(message).map_err(|error| error.to_string())?;
+    line.push(b'\n');
+    writer
+        .write_all(&line)
+        .await
+        .map_err(|error| format!("send message error: {}", error))
+}
+
+/// The next message, `None` once the peer closed the connection.
+pub async fn read_message<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Message>, String> {
+    read_message_limited(reader, MAX_MESSAGE_SIZE).await
+}
+
+/// The next message of at most `max_size` bytes, `None` once the peer closed the connection.
+pub async fn read_message_limited<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_size: u64,
+) -> Result<Option<Message>, String> {
+    let mut line = String::new();
+    let size = (&mut *reader)
+        .take(max_size)
+        .read_line(&mut line)
+        .await
+        .map_err(|error| format!("receive message error: {}", error))?;
+    if size == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err("receive message error: message too long or truncated".to_string());
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|error| format!("invalid message: {}", error))
+}
@@ -0,0 +1,131 @@
+use crate::args::{Args, WorkerFiles};
+use crate::cluster::protocol::{read_message, write_message, Message};
+use crate::cluster::WorkUnit;
+use crate::scanner::plugin::HostReply;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+/// Registers with the coordinator at `address` and scans the units it assigns until it
+/// shuts the worker down, with the options of the coordinator and the local `files`.
+pub async fn run_worker(
+    address: &str,
+    name: &str,
+    token: Option<String>,
+    files: &WorkerFiles,
+) -> Result<(), String> {
+    let stream = TcpStream::connect(address)
+        .await
+        .map_err(|error| format!("connect coordinator {} error: {}", address, error))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let register = Message::Register {
+        name: name.to_string(),
+        token,
+    };
+    write_message(&mut writer, &register).await?;
+    let (worker_id, heartbeat_interval, mut args) = match read_message(&mut reader).await? {
+        Some(Message::Welcome {
+            worker_id,
+            heartbeat_interval_ms,
+            args,
+        }) => (
+            worker_id,
+            Duration::from_millis(heartbeat_interval_ms.max(1)),
+            args,
+        ),
+        Some(Message::Rejected { reason }) => {
+            return Err(format!(
+                "coordinator {} rejected the worker: {}",
+                address, reason
+            ))
+        }
+        _ => {
+            return Err(format!(
+                "coordinator {} did not welcome the worker",
+                address
+            ))
+        }
+    };
+    tracing::info!(
+        "registered with coordinator {} as worker {}.",
+        address,
+        worker_id
+    );
+    files.apply(&mut args);
+
+    // the heartbeats go on while a unit is scanned, so everything is written by one task.
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let send = tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            if let Err(error) = write_message(&mut writer, &message).await {
+                tracing::warn!("{}", error);
+                break;
+            }
+        }
+    });
+    let heartbeat = {
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(heartbeat_interval);
+            loop {
+                interval.tick().await;
+                if sender.send(Message::Heartbeat).is_err() {
+                    break;
+                }
+            }
+        })
+    };
+
+    let result = loop {
+        match read_message(&mut reader).await {
+            Ok(Some(Message::Assign { unit })) => {
+                tracing::info!(
+                    "unit {} assigned, {} hosts and {} ports.",
+                    unit.id,
+                    unit.ips.len(),
+                    unit.ports.len()
+                );
+                let unit_id = unit.id;
+                let args = args.clone();
+                let hosts = match tokio::task::spawn_blocking(move || scan_unit(unit, &args)).await
+                {
+                    Ok(Ok(hosts)) => hosts,
+                    Ok(Err(error)) => break Err(error),
+                    Err(error) => break Err(format!("unit {} scan error: {}", unit_id, error)),
+                };
+                let done = Message::UnitDone { unit_id, hosts };
+                if sender.send(done).is_err() {
+                    break Err("coordinator connection closed".to_string());
+                }
+            }
+            Ok(Some(Message::Shutdown)) => {
+                tracing::info!("coordinator {} shut the worker down.", address);
+                break Ok(());
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => break Err(format!("coordinator {} closed the connection", address)),
+            Err(error) => break Err(error),
+        }
+    };
+    heartbeat.abort();
+    drop(sender);
+    let _ = send.await;
+    result
+}
+
+/// Scans the unit on a runtime of its own, the probes block their threads at times and the
+/// heartbeats must go on meanwhile.
+fn scan_unit(unit: WorkUnit, args: &Args) -> Result<Vec<HostReply>, String> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|error| format!("start scan runtime error: {}", error))?;
+    Ok(runtime.block_on(async {
+        crate::scanner_for(unit.ips, unit.ports, args)
+            .await
+            .run()
+            .await
+    }))
+}
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::io::{BufReader, Read};
+use std::collections::HashSet;
 use std::path::Path;
 
+pub mod nmap;
+pub mod technology;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Fingerprint {
     pub protocol: String,
@@ -10,18 +13,20 @@ pub struct Fingerprint {
 
 impl Fingerprint {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Vec<Self> {
-        let file = match std::fs::File::open(path) {
-            Ok(file) => file,
+        match Self::load(path) {
+            Ok(fingerprints) => fingerprints,
             Err(error) => {
-                tracing::warn!("open fingerprint file error: {}", error.to_string());
-                return vec![];
+                tracing::warn!("{}", error);
+                vec![]
             }
-        };
-        let mut reader = BufReader::new(file);
-        let mut content = String::new();
-        reader.read_to_string(&mut content).unwrap();
-        let fingerprint: Vec<Fingerprint> = serde_json::from_str(&content).unwrap();
-        fingerprint
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Self>, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|error| format!("open fingerprint file error: {}", error))?;
+        serde_json::from_str(&content)
+            .map_err(|error| format!("parse fingerprint file error: {}", error))
     }
 
     pub fn get_tcp_fingerprint() -> Option<Fingerprint> {
@@ -32,6 +37,121 @@ impl Fingerprint {
         }
         fingerprints.pop()
     }
+
+    /// The problems of the match rules: patterns that do not compile, missing service
+    /// names, CPE templates referencing groups the pattern lacks and duplicate probes.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut probe_names = HashSet::new();
+        for probe in self.probes.iter() {
+            let probe_name = probe.probe_name.as_deref().unwrap_or("*");
+            if !probe_names.insert(probe_name) {
+                problems.push(format!(
+                    "{} probe {}: duplicate probe",
+                    self.protocol, probe_name
+                ));
+            }
+            for (index, match_info) in probe.matches.iter().enumerate() {
+                let location = format!(
+                    "{} probe {} match {} ({})",
+                    self.protocol, probe_name, index, match_info.name
+                );
+                if match_info.name.is_empty() {
+                    problems.push(format!("{}: empty service name", location));
+                }
+                let regex = match regex::Regex::new(&match_info.pattern) {
+                    Ok(regex) if !match_info.pattern.is_empty() => regex,
+                    Ok(_) => {
+                        problems.push(format!("{}: empty pattern", location));
+                        continue;
+                    }
+                    Err(error) => {
+                        problems.push(format!("{}: invalid pattern: {}", location, error));
+                        continue;
+                    }
+                };
+                let version_info = match &match_info.version_info {
+                    None => continue,
+                    Some(data) => data,
+                };
+                for template in [&version_info.cpe_name, &version_info.version] {
+                    for group in template_groups(template) {
+                        let known = match group.parse::<usize>() {
+                            Ok(number) => number < regex.captures_len(),
+                            Err(_) => regex.capture_names().flatten().any(|name| name == group),
+                        };
+                        if !known {
+                            problems.push(format!("{}: unknown group ${}", location, group));
+                        }
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Resolves the `\r`, `\n`, `\t`, `\0`, `\\` and `\xHH` escapes of a banner typed on the
+/// command line, decoded the way the responses to the probes are.
+pub fn unescape_banner(banner: &str) -> String {
+    let mut bytes = vec![];
+    let mut chars = banner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) => bytes.push(byte),
+                    Err(_) => bytes.extend_from_slice(format!("\\x{}", hex).as_bytes()),
+                }
+            }
+            Some(other) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// The group names and numbers a `$name` or `${name}` template references.
+fn template_groups(template: &str) -> Vec<&str> {
+    let mut groups = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        rest = &rest[start + 1..];
+        let group = match rest.strip_prefix('{') {
+            Some(braced) => braced.split('}').next().unwrap_or_default(),
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                &rest[..end]
+            }
+        };
+        if !group.is_empty() {
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+/// A match rule that hit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceMatch {
+    pub name: String,
+    pub version: Option<String>,
+    pub cpe: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -49,7 +169,49 @@ pub struct Match {
     pub version_info: Option<VersionInfo>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Match {
+    /// Matches the response to a probe, the version is the `version` group of the pattern
+    /// or else the expanded version template.
+    pub fn match_banner(&self, banner: &str) -> Option<ServiceMatch> {
+        let regex = match regex::Regex::new(&self.pattern) {
+            Ok(regex) => regex,
+            Err(error) => {
+                tracing::warn!("invalid fingerprint pattern: {}", error);
+                return None;
+            }
+        };
+        let caps = regex.captures(banner)?;
+        let expand = |template: &str| {
+            let mut expanded = String::new();
+            caps.expand(template, &mut expanded);
+            expanded
+        };
+        let version = match caps.name("version") {
+            Some(version) => Some(version.as_str().to_string()),
+            None => self
+                .version_info
+                .as_ref()
+                .filter(|version_info| !version_info.version.is_empty())
+                .map(|version_info| expand(&version_info.version))
+                .filter(|version| !version.is_empty()),
+        };
+        let cpe = self
+            .version_info
+            .as_ref()
+            .filter(|version_info| !version_info.cpe_name.is_empty())
+            .map(|version_info| expand(&version_info.cpe_name));
+        Some(ServiceMatch {
+            name: self.name.clone(),
+            version,
+            cpe,
+        })
+    }
+}
+
+/// Details of a matched service, the fields may reference the named groups of the
+/// pattern, e.g. `cpe:2.3:a:vendor:product:${version}`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct VersionInfo {
     pub cpe_name: String,
     pub device_type: String,
@@ -0,0 +1,287 @@
+use crate::fingerprint::{Fingerprint, Match, Probe, VersionInfo};
+
+/// The fingerprints read from an nmap-service-probes file, with the reason every probe
+/// or match rule that could not be converted was skipped for.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub fingerprints: Vec<Fingerprint>,
+    pub skipped: Vec<String>,
+}
+
+impl Import {
+    pub fn matches(&self) -> usize {
+        self.fingerprints
+            .iter()
+            .flat_map(|fingerprint| fingerprint.probes.iter())
+            .map(|probe| probe.matches.len())
+            .sum()
+    }
+}
+
+/// Converts the `Probe`, `match` and `softmatch` lines of an nmap-service-probes file,
+/// the other directives are ignored. Patterns the regex crate does not support, e.g. with
+/// look-around or back references, and probes sending non ASCII bytes are skipped.
+pub fn import(content: &str) -> Import {
+    let mut import = Import::default();
+    // the match lines of a skipped probe are skipped with it.
+    let mut current: Option<(String, Probe)> = None;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let location = format!("line {}", number + 1);
+        if let Some(rest) = line.strip_prefix("Probe ") {
+            if let Some((protocol, probe)) = current.take() {
+                push_probe(&mut import.fingerprints, protocol, probe);
+            }
+            match parse_probe(rest) {
+                Ok(probe) => current = Some(probe),
+                Err(error) => import.skipped.push(format!("{}: {}", location, error)),
+            }
+            continue;
+        }
+        let (rest, discontinue) =
+            match (line.strip_prefix("match "), line.strip_prefix("softmatch ")) {
+                (Some(rest), _) => (rest, true),
+                (_, Some(rest)) => (rest, false),
+                _ => continue,
+            };
+        let probe = match current.as_mut() {
+            None => continue,
+            Some((_, probe)) => probe,
+        };
+        match parse_match(rest, discontinue) {
+            Ok(match_info) => probe.matches.push(match_info),
+            Err(error) => import.skipped.push(format!("{}: {}", location, error)),
+        }
+    }
+    if let Some((protocol, probe)) = current.take() {
+        push_probe(&mut import.fingerprints, protocol, probe);
+    }
+    import
+}
+
+fn push_probe(fingerprints: &mut Vec<Fingerprint>, protocol: String, probe: Probe) {
+    match fingerprints
+        .iter_mut()
+        .find(|fingerprint| fingerprint.protocol == protocol)
+    {
+        Some(fingerprint) => fingerprint.probes.push(probe),
+        None => fingerprints.push(Fingerprint {
+            protocol,
+            probes: vec![probe],
+        }),
+    }
+}
+
+/// `TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|`
+fn parse_probe(rest: &str) -> Result<(String, Probe), String> {
+    let mut fields = rest.splitn(3, ' ');
+    let (protocol, name, string) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(protocol), Some(name), Some(string)) => (protocol, name, string),
+        _ => return Err("incomplete probe".to_string()),
+    };
+    let (probe_string, _) = string
+        .strip_prefix('q')
+        .and_then(delimited)
+        .ok_or_else(|| format!("probe {}: invalid probe string", name))?;
+    let probe_string = unescape(probe_string)
+        .ok_or_else(|| format!("probe {}: probe string is not ASCII", name))?;
+    Ok((
+        protocol.to_uppercase(),
+        Probe {
+            probe_name: Some(name.to_string()),
+            probe_string,
+            matches: vec![],
+        },
+    ))
+}
+
+/// `ftp m/^220 ([\w.]+) FTP/i p/vsftpd/ v/$1/ cpe:/a:vsftpd:vsftpd:$1/`
+fn parse_match(rest: &str, discontinue: bool) -> Result<Match, String> {
+    let (name, rest) = rest
+        .split_once(' ')
+        .ok_or_else(|| "incomplete match".to_string())?;
+    let (pattern, rest) = rest
+        .strip_prefix('m')
+        .and_then(delimited)
+        .ok_or_else(|| format!("{}: invalid pattern", name))?;
+    let (flags, mut rest) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+    let mut prefix = String::new();
+    if flags.contains('i') {
+        prefix.push_str("(?i)");
+    }
+    if flags.contains('s') {
+        prefix.push_str("(?s)");
+    }
+    let pattern = format!("{}{}", prefix, convert_pattern(pattern));
+    if let Err(error) = regex::Regex::new(&pattern) {
+        let error = error.to_string();
+        let reason = error.lines().last().unwrap_or_default().trim();
+        return Err(format!("{}: unsupported pattern: {}", name, reason));
+    }
+
+    let mut version_info = VersionInfo::default();
+    loop {
+        rest = rest.trim_start();
+        let (key, field) = match rest.strip_prefix("cpe:") {
+            Some(field) => ("cpe", field),
+            None if rest.is_empty() => break,
+            None => rest.split_at(1),
+        };
+        let (value, after) =
+            delimited(field).ok_or_else(|| format!("{}: invalid {} field", name, key))?;
+        // trailing flags, e.g. the `a` of `cpe:/a:vendor:product/a`.
+        rest = after.trim_start_matches(|c: char| !c.is_whitespace());
+        let value = convert_template(value);
+        match key {
+            "p" => version_info.vendor_product_name = value,
+            "v" => version_info.version = value,
+            "i" => version_info.info = value,
+            "h" => version_info.host_name = value,
+            "o" => version_info.operating_system = value,
+            "d" => version_info.device_type = value,
+            "cpe" if version_info.cpe_name.is_empty() => {
+                version_info.cpe_name = format!("cpe:/{}", value)
+            }
+            _ => {}
+        }
+    }
+    let version_info = if version_info.vendor_product_name.is_empty()
+        && version_info.version.is_empty()
+        && version_info.cpe_name.is_empty()
+        && version_info.info.is_empty()
+        && version_info.operating_system.is_empty()
+        && version_info.device_type.is_empty()
+        && version_info.host_name.is_empty()
+    {
+        None
+    } else {
+        Some(version_info)
+    };
+    Ok(Match {
+        pattern,
+        name: name.to_string(),
+        discontinue,
+        version_info,
+    })
+}
+
+/// Splits `|content|rest` at the closing delimiter, the first character being the delimiter.
+fn delimited(field: &str) -> Option<(&str, &str)> {
+    let delimiter = field.chars().next()?;
+    let field = &field[delimiter.len_utf8()..];
+    let end = field.find(delimiter)?;
+    Some((&field[..end], &field[end + delimiter.len_utf8()..]))
+}
+
+/// The regex crate has no `\0` escape.
+fn convert_pattern(pattern: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            converted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => converted.push_str("\\x00"),
+            Some(escaped) => {
+                converted.push('\\');
+                converted.push(escaped);
+            }
+            None => converted.push('\\'),
+        }
+    }
+    converted
+}
+
+/// Rewrites `$1` and the `$P(1)`, `$SUBST(1,...)` and `$I(1,...)` helpers to `${1}`, the
+/// helpers are reduced to the plain group.
+fn convert_template(template: &str) -> String {
+    let helpers = regex::Regex::new(r"\$(?:P|SUBST|I)\((\d+)[^)]*\)").unwrap();
+    let template = helpers.replace_all(template, "$${${1}}");
+    let groups = regex::Regex::new(r"\$(\d+)").unwrap();
+    groups.replace_all(&template, "$${${1}}").to_string()
+}
+
+/// Resolves the C style escapes of a probe string, `None` when it holds non ASCII bytes
+/// as the probe strings are sent as UTF-8.
+fn unescape(string: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let byte = match chars.next()? {
+            'r' => b'\r',
+            'n' => b'\n',
+            't' => b'\t',
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'v' => 0x0b,
+            '0' => 0,
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).ok()?
+            }
+            other => {
+                unescaped.push(other);
+                continue;
+            }
+        };
+        if !byte.is_ascii() {
+            return None;
+        }
+        unescaped.push(byte as char);
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+pub mod nmap_test {
+    use crate::fingerprint::nmap::import;
+
+    #[test]
+    fn nmap_import_test() {
+        let import = import(
+            r#"# comment
+Exclude T:9100-9107
+Probe TCP NULL q||
+totalwaitms 6000
+match ftp m/^220 ([\w.]+) FTP server ready\r\n/i p/vsftpd/ v/$1/ cpe:/a:vsftpd:vsftpd:$P(1)/a
+softmatch ssh m|^SSH-([\d.]+)-| p/OpenSSH/
+match back m/^(\w)\1$/
+Probe TCP Binary q|\x80\x01|
+match binary m/^x/
+Probe UDP DNSStatusRequest q|\0\0\x10\0\0\0\0\0\0\0\0\0|
+match domain m|^\0\0\x90|s
+"#,
+        );
+        assert_eq!(import.fingerprints.len(), 2);
+        assert_eq!(import.matches(), 3);
+        assert_eq!(import.skipped.len(), 2, "{:?}", import.skipped);
+
+        let tcp = &import.fingerprints[0];
+        assert_eq!(tcp.protocol, "TCP");
+        assert!(tcp.validate().is_empty(), "{:?}", tcp.validate());
+        let ftp = &tcp.probes[0].matches[0];
+        assert_eq!(ftp.pattern, r"(?i)^220 ([\w.]+) FTP server ready\r\n");
+        assert!(ftp.discontinue);
+        let service = ftp.match_banner("220 3.0.5 ftp Server Ready\r\n").unwrap();
+        assert_eq!(service.version.as_deref(), Some("3.0.5"));
+        assert_eq!(service.cpe.as_deref(), Some("cpe:/a:vsftpd:vsftpd:3.0.5"));
+        let ssh = &tcp.probes[0].matches[1];
+        assert!(!ssh.discontinue);
+        assert_eq!(
+            ssh.version_info.as_ref().unwrap().vendor_product_name,
+            "OpenSSH"
+        );
+        assert_eq!(import.fingerprints[1].probes[0].probe_string.len(), 12);
+        assert_eq!(
+            import.fingerprints[1].probes[0].matches[0].pattern,
+            r"(?s)^\x00\x00\x90"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::utils::json;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const IMPLIED_CONFIDENCE: u8 = 100;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for OneOrMany {
+    fn default() -> Self {
+        OneOrMany::Many(vec![])
+    }
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(item) => vec![item],
+            OneOrMany::Many(items) => items,
+        }
+    }
+}
+
+/// A technology rule as written in the rules directory, using Wappalyzer's field names and
+/// pattern syntax: `regex\;version:\1\;confidence:50`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawTechnology {
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    cookies: HashMap<String, String>,
+    #[serde(default)]
+    meta: HashMap<String, OneOrMany>,
+    #[serde(default)]
+    script_src: OneOrMany,
+    #[serde(default)]
+    html: OneOrMany,
+    #[serde(default)]
+    implies: OneOrMany,
+    cpe: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    regex: Regex,
+    /// version template, `\1` is replaced with the first capture group.
+    version: Option<String>,
+    confidence: u8,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Option<Pattern> {
+        let mut parts = pattern.split("\\;");
+        let regex = match Regex::new(&format!("(?i){}", parts.next()?)) {
+            Ok(regex) => regex,
+            Err(error) => {
+                tracing::warn!("invalid technology pattern {}: {}", pattern, error);
+                return None;
+            }
+        };
+        let mut version = None;
+        let mut confidence = 100;
+        for tag in parts {
+            match tag.split_once(':') {
+                Some(("version", value)) => version = Some(value.to_string()),
+                Some(("confidence", value)) => confidence = value.parse().unwrap_or(100),
+                _ => {}
+            }
+        }
+        Some(Pattern {
+            regex,
+            version,
+            confidence,
+        })
+    }
+
+    /// Returns the confidence and the resolved version when `value` matches.
+    fn matches(&self, value: &str) -> Option<(u8, Option<String>)> {
+        let captures = self.regex.captures(value)?;
+        let version = self.version.as_ref().map(|template| {
+            let mut version = template.clone();
+            for index in (1..captures.len()).rev() {
+                let capture = captures.get(index).map_or("", |item| item.as_str());
+                version = version.replace(&format!("\\{}", index), capture);
+            }
+            resolve_ternary(&version)
+        });
+        Some((
+            self.confidence,
+            version.filter(|version| !version.is_empty()),
+        ))
+    }
+}
+
+/// Resolves Wappalyzer's `value?yes:no` version syntax after the captures are substituted.
+fn resolve_ternary(version: &str) -> String {
+    match version.split_once('?') {
+        None => version.to_string(),
+        Some((value, branches)) => {
+            let (yes, no) = branches.split_once(':').unwrap_or((branches, ""));
+            match value.is_empty() {
+                true => no.to_string(),
+                false => yes.to_string(),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Technology {
+    pub name: String,
+    pub categories: Vec<String>,
+    pub cpe: Option<String>,
+    headers: Vec<(String, Pattern)>,
+    /// cookies with no pattern only need to be present.
+    cookies: Vec<(String, Option<Pattern>)>,
+    meta: Vec<(String, Pattern)>,
+    script_src: Vec<Pattern>,
+    html: Vec<Pattern>,
+    implies: Vec<String>,
+}
+
+impl Technology {
+    fn compile(name: String, raw: RawTechnology) -> Technology {
+        let patterns = |items: Vec<String>| {
+            items
+                .iter()
+                .filter_map(|item| Pattern::parse(item))
+                .collect::<Vec<Pattern>>()
+        };
+        Technology {
+            name,
+            categories: raw.categories,
+            cpe: raw.cpe,
+            headers: raw
+                .headers
+                .into_iter()
+                .filter_map(|(header, pattern)| Some((header, Pattern::parse(&pattern)?)))
+                .collect(),
+            cookies: raw
+                .cookies
+                .into_iter()
+                .map(|(cookie, pattern)| {
+                    let pattern = match pattern.is_empty() {
+                        true => None,
+                        false => Pattern::parse(&pattern),
+                    };
+                    (cookie.to_lowercase(), pattern)
+                })
+                .collect(),
+            meta: raw
+                .meta
+                .into_iter()
+                .flat_map(|(meta, patterns)| {
+                    patterns.into_vec().into_iter().filter_map(move |pattern| {
+                        Some((meta.to_lowercase(), Pattern::parse(&pattern)?))
+                    })
+                })
+                .collect(),
+            script_src: patterns(raw.script_src.into_vec()),
+            html: patterns(raw.html.into_vec()),
+            implies: raw
+                .implies
+                .into_vec()
+                .into_iter()
+                .filter_map(|item| item.split("\\;").next().map(str::to_string))
+                .collect(),
+        }
+    }
+
+    /// Reads every `*.json` file in `dir`, each one maps technology names to their rules.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Vec<Technology> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::warn!("open technology rules directory error: {}", error);
+                return vec![];
+            }
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        let mut technologies = BTreeMap::new();
+        for path in paths {
+            let kind = format!("technology rules {}", path.display());
+            let rules: BTreeMap<String, RawTechnology> = json::load_json_file(&path, &kind);
+            technologies.extend(rules);
+        }
+        technologies
+            .into_iter()
+            .map(|(name, raw)| Technology::compile(name, raw))
+            .collect()
+    }
+
+    pub fn get_technologies() -> Vec<Technology> {
+        Self::from_dir("./fingerprint/technologies")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DetectedTechnology {
+    pub name: String,
+    pub version: Option<String>,
+    pub categories: Vec<String>,
+    pub confidence: u8,
+    pub cpe: Option<String>,
+}
+
+impl std::fmt::Display for DetectedTechnology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            None => write!(f, "{}", self.name),
+            Some(version) => write!(f, "{} {}", self.name, version),
+        }
+    }
+}
+
+/// The parts of an HTTP response the rules look at.
+pub struct WebPage<'a> {
+    pub headers: &'a [(String, String)],
+    pub body: &'a str,
+}
+
+impl WebPage<'_> {
+    fn cookies(&self) -> Vec<(String, String)> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+            .filter_map(|(_, value)| value.split(';').next()?.split_once('='))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect()
+    }
+
+    fn meta(&self) -> Vec<(String, String)> {
+        let tag = Regex::new(r"(?is)<meta\b[^>]*>").unwrap();
+        let name = Regex::new(r#"(?is)\b(?:name|property)\s*=\s*["']([^"']+)["']"#).unwrap();
+        let content = Regex::new(r#"(?is)\bcontent\s*=\s*["']([^"']*)["']"#).unwrap();
+        let meta = tag
+            .find_iter(self.body)
+            .filter_map(|tag| {
+                let name = name.captures(tag.as_str())?.get(1)?.as_str().to_lowercase();
+                let content = content.captures(tag.as_str())?.get(1)?.as_str().to_string();
+                Some((name, content))
+            })
+            .collect();
+        meta
+    }
+
+    fn script_src(&self) -> Vec<String> {
+        let script = Regex::new(r#"(?is)<script\b[^>]*\bsrc\s*=\s*["']?([^"'\s>]+)"#).unwrap();
+        let sources = script
+            .captures_iter(self.body)
+            .filter_map(|captures| Some(captures.get(1)?.as_str().to_string()))
+            .collect();
+        sources
+    }
+}
+
+/// Matches `page` against every technology and adds the technologies implied by the hits.
+pub fn detect(page: &WebPage, technologies: &[Technology]) -> Vec<DetectedTechnology> {
+    let cookies = page.cookies();
+    let meta = page.meta();
+    let script_src = page.script_src();
+    let mut detected: BTreeMap<String, DetectedTechnology> = BTreeMap::new();
+    for technology in technologies.iter() {
+        let mut hits = vec![];
+        for (header, pattern) in technology.headers.iter() {
+            let value = page
+                .headers
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(header));
+            hits.extend(value.filter_map(|(_, value)| pattern.matches(value)));
+        }
+        for (cookie, pattern) in technology.cookies.iter() {
+            for (_, value) in cookies.iter().filter(|(name, _)| name == cookie) {
+                match pattern {
+                    None => hits.push((100, None)),
+                    Some(pattern) => hits.extend(pattern.matches(value)),
+                }
+            }
+        }
+        for (name, pattern) in technology.meta.iter() {
+            let contents = meta.iter().filter(|(meta, _)| meta == name);
+            hits.extend(contents.filter_map(|(_, content)| pattern.matches(content)));
+        }
+        for pattern in technology.script_src.iter() {
+            hits.extend(script_src.iter().filter_map(|src| pattern.matches(src)));
+        }
+        for pattern in technology.html.iter() {
+            hits.extend(pattern.matches(page.body));
+        }
+        if hits.is_empty() {
+            continue;
+        }
+        let confidence = hits
+            .iter()
+            .map(|(confidence, _)| *confidence as u32)
+            .sum::<u32>()
+            .min(100) as u8;
+        let version = hits.into_iter().find_map(|(_, version)| version);
+        detected.insert(
+            technology.name.clone(),
+            DetectedTechnology {
+                name: technology.name.clone(),
+                version,
+                categories: technology.categories.clone(),
+                confidence,
+                cpe: technology.cpe.clone(),
+            },
+        );
+    }
+
+    let mut pending = detected.keys().cloned().collect::<Vec<String>>();
+    while let Some(name) = pending.pop() {
+        let technology = match technologies.iter().find(|item| item.name == name) {
+            None => continue,
+            Some(technology) => technology,
+        };
+        for implied in technology.implies.iter() {
+            if detected.contains_key(implied) {
+                continue;
+            }
+            let Some(rule) = technologies.iter().find(|item| &item.name == implied) else {
+                continue;
+            };
+            detected.insert(
+                implied.clone(),
+                DetectedTechnology {
+                    name: implied.clone(),
+                    version: None,
+                    categories: rule.categories.clone(),
+                    confidence: IMPLIED_CONFIDENCE,
+                    cpe: rule.cpe.clone(),
+                },
+            );
+            pending.push(implied.clone());
+        }
+    }
+    detected.into_values().collect()
+}
+
+#[cfg(test)]
+pub mod technology_test {
+    use crate::fingerprint::technology::{detect, RawTechnology, Technology, WebPage};
+    use std::collections::BTreeMap;
+
+    fn technologies() -> Vec<Technology> {
+        let rules = r#"{
+            "Nginx": {"categories": ["Web servers"], "headers": {"Server": "nginx(?:/([\\d.]+))?\\;version:\\1"}},
+            "WordPress": {
+                "categories": ["CMS"],
+                "meta": {"generator": "^WordPress ?([\\d.]+)?\\;version:\\1"},
+                "scriptSrc": "/wp-includes/",
+                "implies": ["PHP", "MySQL\\;confidence:50"]
+            },
+            "PHP": {"categories": ["Programming languages"], "cookies": {"PHPSESSID": ""}},
+            "MySQL": {"categories": ["Databases"]},
+            "jQuery": {"categories": ["JavaScript libraries"], "scriptSrc": ["jquery[.-]([\\d.]+)(?:\\.min)?\\.js\\;version:\\1"]}
+        }"#;
+        let rules: BTreeMap<String, RawTechnology> = serde_json::from_str(rules).unwrap();
+        rules
+            .into_iter()
+            .map(|(name, raw)| Technology::compile(name, raw))
+            .collect()
+    }
+
+    #[test]
+    fn detect_test() {
+        let headers = vec![
+            ("Server".to_string(), "nginx/1.24.0".to_string()),
+            (
+                "Set-Cookie".to_string(),
+                "PHPSESSID=abc; path=/".to_string(),
+            ),
+        ];
+        let body = r#"<meta name="generator" content="WordPress 6.4.2">
+            <script src="/wp-includes/js/jquery/jquery-3.7.1.min.js"></script>"#;
+        let page = WebPage {
+            headers: &headers,
+            body,
+        };
+        let detected = detect(&page, &technologies())
+            .into_iter()
+            .map(|item| (item.name.clone(), item.version.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            detected,
+            vec![
+                ("MySQL".to_string(), None),
+                ("Nginx".to_string(), Some("1.24.0".to_string())),
+                ("PHP".to_string(), None),
+                ("WordPress".to_string(), Some("6.4.2".to_string())),
+                ("jQuery".to_string(), Some("3.7.1".to_string())),
+            ]
+        );
+    }
+}
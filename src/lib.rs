@@ -0,0 +1,79 @@
+//! The scanner behind the magic-scanner command line. Other crates can run a scan with
+//! [`Scanner`] and extend it through the [`ServiceProbe`] and [`ResultSink`] traits of
+//! [`scanner::plugin`].
+use crate::args::Args;
+use crate::utils::address::ParseAddress;
+use crate::utils::port::{resolve_ports_or_default, PortSpec, ServiceTable};
+use std::net::IpAddr;
+use std::time::Duration;
+
+pub use crate::scanner::plugin::{ResultSink, ServiceProbe};
+pub use crate::scanner::Scanner;
+
+pub mod args;
+pub mod cluster;
+pub mod fingerprint;
+pub mod monitor;
+pub mod os;
+pub mod report;
+pub mod scanner;
+pub mod server;
+pub mod store;
+pub mod utils;
+pub mod vuln;
+
+/// A scanner for the targets, ports and checks of `args`, without any result sink.
+pub async fn new_scanner(args: &Args) -> Result<Scanner, String> {
+    let (ips, ports) = resolve_targets(args).await?;
+    Ok(scanner_for(ips, ports, args).await)
+}
+
+/// The hosts and ports `args` asks to scan.
+pub async fn resolve_targets(args: &Args) -> Result<(Vec<IpAddr>, PortSpec), String> {
+    let address = match &args.address {
+        Some(address) => address,
+        None => return Err("there is no address to perform the task, please reenter.".to_string()),
+    };
+    let parse_result = ParseAddress::resolve_ips(address.as_str()).await;
+    if !parse_result.invalid_address.is_empty() {
+        tracing::warn!("invalid address: {:?}", parse_result.invalid_address);
+    }
+    tracing::info!("address: {:?}", &parse_result.valid_address);
+    if parse_result.valid_address.is_empty() {
+        return Err("there is no address to perform the task, please reenter.".to_string());
+    }
+    let services = ServiceTable::from_file(&args.services);
+    let ports = resolve_ports_or_default(args.ports.clone(), args.top_ports, &services)
+        .map_err(|error| format!("{}, please reenter.", error))?;
+    tracing::info!(
+        "ports size: {:?} (tcp {}, udp {}, sctp {})",
+        ports.len(),
+        ports.tcp.len(),
+        ports.udp.len(),
+        ports.sctp.len()
+    );
+
+    Ok((Vec::from_iter(parse_result.valid_address), ports))
+}
+
+/// A scanner for `ips` and `ports` with the checks of `args`.
+pub async fn scanner_for(ips: Vec<IpAddr>, ports: PortSpec, args: &Args) -> Scanner {
+    let mut scanner = Scanner::new(ips, ports, args.batch_size, args.ping, args.ping_count).await;
+    scanner.set_connect_timeout(Duration::from_millis(args.timeout));
+    if args.os_detect {
+        scanner.set_os_detection(Some(args.os_db.clone()));
+    }
+    scanner.set_tls_enumeration(args.tls_enum);
+    scanner.set_dns_zones(args.dns_zone.clone());
+    scanner.set_snmp_communities(&args.snmp_communities);
+    if args.check_credentials {
+        scanner.set_credential_checks(&args.credentials, args.max_login_attempts);
+    }
+    if let Some(cve_db) = &args.cve_db {
+        scanner.set_vulnerability_database(cve_db);
+    }
+    if !args.script.is_empty() {
+        scanner.set_scripts(&args.script_dir, &args.script);
+    }
+    scanner
+}
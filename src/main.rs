@@ -1,51 +1,531 @@
-use crate::scanner::Scanner;
-use crate::utils::address::ParseAddress;
-use crate::utils::port::resolve_ports_or_all;
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use colorful::{Color, Colorful};
+use magic_scanner::args::config::Config;
+use magic_scanner::args::{
+    Args, Cli, Command, CoordinatorArgs, DiffArgs, DiscoverArgs, FingerprintCommand, MonitorArgs,
+    ReportArgs, ServeArgs, StoreArgs, StoreCommand, WorkerArgs,
+};
+use magic_scanner::cluster::{self, Coordinator, CoordinatorOptions};
+use magic_scanner::fingerprint::{self, nmap, Fingerprint};
+use magic_scanner::monitor::{EventTarget, Monitor};
+use magic_scanner::report::diff::ScanDiff;
+use magic_scanner::report::{self, ScanResult};
+use magic_scanner::scanner::ping_hosts;
+use magic_scanner::scanner::plugin::{JsonSink, LogSink, ResultSink, StoreSink};
+use magic_scanner::server::{self, job::JobQueue};
+use magic_scanner::store::{HostQuery, Store};
+use magic_scanner::utils::address::ParseAddress;
+use magic_scanner::{new_scanner, resolve_targets};
 use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
 
-mod args;
-mod fingerprint;
-mod scanner;
-mod utils;
-
 #[tokio::main]
 async fn main() {
-    let file_appender = tracing_appender::rolling::daily("./log", "magic.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(error) => error.exit(),
+    };
+    let code = match cli.command {
+        None => scan(cli.scan, &matches).await,
+        Some(Command::Scan(args)) => {
+            let matches = matches.subcommand_matches("scan").unwrap_or(&matches);
+            scan(*args, matches).await
+        }
+        Some(Command::Discover(args)) => discover(args).await,
+        Some(Command::Fingerprint { command }) => {
+            let _guard = init_logging(None);
+            fingerprint(command)
+        }
+        Some(Command::Report(args)) => {
+            let _guard = init_logging(None);
+            report(args)
+        }
+        Some(Command::Diff(args)) => {
+            let _guard = init_logging(None);
+            diff(args)
+        }
+        Some(Command::Store(args)) => {
+            let _guard = init_logging(None);
+            store(args)
+        }
+        Some(Command::Monitor(args)) => monitor(args).await,
+        Some(Command::Serve(args)) => serve(args).await,
+        Some(Command::Coordinator(args)) => {
+            let matches = matches
+                .subcommand_matches("coordinator")
+                .unwrap_or(&matches);
+            coordinator(*args, matches).await
+        }
+        Some(Command::Worker(args)) => worker(args).await,
+    };
+    std::process::exit(code);
+}
+
+/// Logs to stdout and a daily rolling file in `log_dir`, or only to stderr for the
+/// commands writing their output to stdout.
+fn init_logging(log_dir: Option<&str>) -> Option<WorkerGuard> {
+    let console = match log_dir {
+        Some(_) => BoxMakeWriter::new(io::stdout),
+        None => BoxMakeWriter::new(io::stderr),
+    };
+    init_logging_to(log_dir, console)
+}
+
+/// Logs to `console` and a daily rolling file in `log_dir` when given.
+fn init_logging_to(log_dir: Option<&str>, console: BoxMakeWriter) -> Option<WorkerGuard> {
+    let (file_layer, guard) = match log_dir {
+        Some(log_dir) => {
+            let file_appender = tracing_appender::rolling::daily(log_dir, "magic.log");
+            let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+            (
+                Some(fmt::Layer::new().with_writer(non_blocking)),
+                Some(guard),
+            )
+        }
+        None => (None, None),
+    };
     let subscriber = tracing_subscriber::registry()
         .with(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()))
-        .with(fmt::Layer::new().with_writer(io::stdout))
-        .with(fmt::Layer::new().with_writer(non_blocking));
+        .with(fmt::Layer::new().with_writer(console))
+        .with(file_layer);
     tracing::subscriber::set_global_default(subscriber).expect("Unable to set a global subscriber");
+    guard
+}
+
+async fn scan(mut args: Args, matches: &ArgMatches) -> i32 {
+    // applied before the logging is set up as the config may move the log directory.
+    let config =
+        Config::load(args.config.as_deref()).and_then(|config| config.apply(&mut args, matches));
+    let _guard = init_logging(Some(&args.log_dir));
     print_banner();
 
-    let args = args::Args::parse();
-    let address = args.address;
-    let parse_result = ParseAddress::resolve_ips(address.as_str()).await;
+    if let Err(error) = config {
+        tracing::warn!("{}", error);
+        return 1;
+    }
+    let address = args.address.clone().unwrap_or_default();
+    let mut scanner = match new_scanner(&args).await {
+        Ok(scanner) => scanner,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    scanner.register_sink(Arc::new(LogSink));
+    if let Some(output) = &args.output {
+        scanner.register_sink(Arc::new(JsonSink::new(output)));
+    }
+    let mut store_sink = None;
+    if let Some(db) = &args.db {
+        match Store::open(db) {
+            Ok(store) => {
+                let sink = Arc::new(StoreSink::new(Arc::new(store), &address, None));
+                scanner.register_sink(sink.clone());
+                store_sink = Some(sink);
+            }
+            Err(error) => {
+                tracing::warn!("{}", error);
+                return 1;
+            }
+        }
+    }
+    scanner.run().await;
+    if let Some(sink) = store_sink {
+        sink.flushed().await;
+    }
+    tracing::info!("running end.");
+    0
+}
+
+async fn monitor(args: MonitorArgs) -> i32 {
+    // loaded before the logging is set up as the config may send the events to stdout.
+    let config = Config::load(args.config.as_deref());
+    let events = match &config {
+        Ok(config) if args.event.is_empty() => config.monitor.events.clone(),
+        _ => args.event,
+    };
+    // the logs stay on stderr whenever the change events go to stdout.
+    let events_to_stdout = EventTarget::parse_all(&events)
+        .map(|targets| targets.contains(&EventTarget::Stdout))
+        .unwrap_or(true);
+    let _guard = if events_to_stdout {
+        init_logging_to(args.log_dir.as_deref(), BoxMakeWriter::new(io::stderr))
+    } else {
+        init_logging(args.log_dir.as_deref())
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    let db = args
+        .db
+        .or_else(|| config.monitor.db.clone())
+        .unwrap_or_else(|| "./magic-scanner.db".to_string());
+    let monitor = match Store::open(&db).and_then(|store| Monitor::new(config, store, &events)) {
+        Ok(monitor) => monitor,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    monitor.run(args.once).await;
+    0
+}
+
+async fn serve(args: ServeArgs) -> i32 {
+    let _guard = init_logging(args.log_dir.as_deref());
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    let store = match args.db.as_deref().map(Store::open).transpose() {
+        Ok(store) => store,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    // an empty token would accept the requests without an authorization header.
+    let token = args.token.filter(|token| !token.is_empty());
+    if token.is_none() {
+        tracing::warn!("no --token given, the API accepts every request.");
+    }
+    let jobs = Arc::new(JobQueue::new(args.max_running, args.max_done, store));
+    match server::serve(&args.listen, config, jobs, token, args.allow_intrusive).await {
+        Ok(()) => 0,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            1
+        }
+    }
+}
+
+async fn coordinator(mut args: CoordinatorArgs, matches: &ArgMatches) -> i32 {
+    let config = Config::load(args.scan.config.as_deref())
+        .and_then(|config| config.apply(&mut args.scan, matches));
+    let _guard = init_logging(Some(&args.scan.log_dir));
+    print_banner();
+
+    if let Err(error) = config {
+        tracing::warn!("{}", error);
+        return 1;
+    }
+    let (ips, ports) = match resolve_targets(&args.scan).await {
+        Ok(targets) => targets,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    let units = cluster::work_units(&ips, &ports, args.hosts_per_unit, args.ports_per_unit);
+    tracing::info!("{} work units.", units.len());
+
+    let mut sinks: Vec<Arc<dyn ResultSink>> = vec![Arc::new(LogSink)];
+    if let Some(output) = &args.scan.output {
+        sinks.push(Arc::new(JsonSink::new(output)));
+    }
+    let mut store_sink = None;
+    if let Some(db) = &args.scan.db {
+        let address = args.scan.address.clone().unwrap_or_default();
+        match Store::open(db) {
+            Ok(store) => {
+                let sink = Arc::new(StoreSink::new(Arc::new(store), &address, None));
+                sinks.push(sink.clone());
+                store_sink = Some(sink);
+            }
+            Err(error) => {
+                tracing::warn!("{}", error);
+                return 1;
+            }
+        }
+    }
+    let listener = match tokio::net::TcpListener::bind(&args.listen).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            tracing::warn!("listen {} error: {}", args.listen, error);
+            return 1;
+        }
+    };
+    // an empty token would accept the workers registering without one.
+    let token = args.token.filter(|token| !token.is_empty());
+    if token.is_none() {
+        tracing::warn!("no --token given, the coordinator accepts every worker.");
+    }
+    tracing::info!("waiting for workers on {}.", args.listen);
+    let options = CoordinatorOptions {
+        token,
+        heartbeat_interval: Duration::from_millis(args.heartbeat_interval),
+        heartbeat_timeout: Duration::from_millis(args.heartbeat_timeout),
+    };
+    let coordinator = Coordinator::new(args.scan, units, options, sinks);
+    Arc::new(coordinator).run(listener).await;
+    if let Some(sink) = store_sink {
+        sink.flushed().await;
+    }
+    tracing::info!("running end.");
+    0
+}
+
+async fn worker(args: WorkerArgs) -> i32 {
+    let _guard = init_logging(args.log_dir.as_deref());
+    let name = args
+        .name
+        .unwrap_or_else(|| format!("worker-{}", std::process::id()));
+    let token = args.token.filter(|token| !token.is_empty());
+    match cluster::run_worker(&args.coordinator, &name, token, &args.files).await {
+        Ok(()) => 0,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            1
+        }
+    }
+}
+
+async fn discover(args: DiscoverArgs) -> i32 {
+    let _guard = init_logging(Some("./log"));
+    print_banner();
+    let parse_result = ParseAddress::resolve_ips(args.address.as_str()).await;
     if !parse_result.invalid_address.is_empty() {
         tracing::warn!("invalid address: {:?}", parse_result.invalid_address);
     }
-    tracing::info!("address: {:?}", &parse_result.valid_address);
     if parse_result.valid_address.is_empty() {
         tracing::warn!("there is no address to perform the task, please reenter.");
-        return;
+        return 1;
     }
-    let ports = resolve_ports_or_all(args.ports);
-    tracing::info!("ports size: {:?}", ports.len());
+    let ips = Vec::from_iter(parse_result.valid_address);
+    let total = ips.len();
+    let statistics = ping_hosts(ips, args.ping_count).await;
+    let alive = statistics
+        .iter()
+        .filter(|statistics| statistics.is_alive())
+        .map(|statistics| statistics.host.to_string())
+        .collect::<Vec<_>>();
+    tracing::info!(
+        "{} of {} hosts alive: {}",
+        alive.len(),
+        total,
+        alive.join(",")
+    );
+    0
+}
 
-    let valid_address = parse_result.valid_address;
-    let scanner = Scanner::new(
-        Vec::from_iter(valid_address),
-        Vec::from_iter(ports),
-        4500,
-        args.ping,
+fn fingerprint(command: FingerprintCommand) -> i32 {
+    match command {
+        FingerprintCommand::Validate { file } => {
+            let fingerprints = match Fingerprint::load(&file) {
+                Ok(fingerprints) => fingerprints,
+                Err(error) => {
+                    tracing::warn!("{}", error);
+                    return 1;
+                }
+            };
+            let problems = fingerprints
+                .iter()
+                .flat_map(|fingerprint| fingerprint.validate())
+                .collect::<Vec<_>>();
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            let rules: usize = fingerprints
+                .iter()
+                .flat_map(|fingerprint| fingerprint.probes.iter())
+                .map(|probe| probe.matches.len())
+                .sum();
+            tracing::info!("{} match rules, {} problems", rules, problems.len());
+            i32::from(!problems.is_empty())
+        }
+        FingerprintCommand::Test {
+            banner,
+            probe,
+            file,
+        } => {
+            let fingerprints = match Fingerprint::load(&file) {
+                Ok(fingerprints) => fingerprints,
+                Err(error) => {
+                    tracing::warn!("{}", error);
+                    return 1;
+                }
+            };
+            let banner = fingerprint::unescape_banner(&banner);
+            let mut hits = 0;
+            for fingerprint in fingerprints.iter() {
+                for current in fingerprint.probes.iter() {
+                    let probe_name = current.probe_name.as_deref().unwrap_or("*");
+                    if probe.as_deref().is_some_and(|probe| probe != probe_name) {
+                        continue;
+                    }
+                    for match_info in current.matches.iter() {
+                        if let Some(service) = match_info.match_banner(&banner) {
+                            hits += 1;
+                            println!(
+                                "{} {} [{}|{}|{}] {}",
+                                fingerprint.protocol,
+                                probe_name,
+                                service.name,
+                                service.version.unwrap_or_else(|| "*".to_string()),
+                                service.cpe.unwrap_or_else(|| "*".to_string()),
+                                match_info.pattern
+                            );
+                        }
+                    }
+                }
+            }
+            if hits == 0 {
+                tracing::warn!("no match rule hits the banner");
+                return 1;
+            }
+            0
+        }
+        FingerprintCommand::Import { input, output } => {
+            let content = match std::fs::read_to_string(&input) {
+                Ok(content) => content,
+                Err(error) => {
+                    tracing::warn!("open {} error: {}", input, error);
+                    return 1;
+                }
+            };
+            let import = nmap::import(&content);
+            for skipped in import.skipped.iter() {
+                tracing::debug!("skipped {}", skipped);
+            }
+            tracing::info!(
+                "imported {} match rules, skipped {}",
+                import.matches(),
+                import.skipped.len()
+            );
+            let json = serde_json::to_string_pretty(&import.fingerprints).unwrap_or_default();
+            write_output(output.as_deref(), &json)
+        }
+    }
+}
+
+fn report(args: ReportArgs) -> i32 {
+    let result = match ScanResult::from_file(&args.input) {
+        Ok(result) => result,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    write_output(
+        args.output.as_deref(),
+        &report::render(&result, args.format),
     )
-    .await;
-    scanner.run().await;
-    tracing::info!("running end.");
+}
+
+/// Exits like diff(1): 0 when nothing changed, 1 when the scans differ with `--exit-code`
+/// and 2 when a scan can't be read or the changes can't be written.
+fn diff(args: DiffArgs) -> i32 {
+    let (before, after) = match (
+        ScanResult::from_file(&args.before),
+        ScanResult::from_file(&args.after),
+    ) {
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(error), _) | (_, Err(error)) => {
+            tracing::warn!("{}", error);
+            return 2;
+        }
+    };
+    let scan_diff = ScanDiff::new(&before, &after);
+    if write_output(
+        args.output.as_deref(),
+        &report::diff::render(&scan_diff, args.format),
+    ) != 0
+    {
+        return 2;
+    }
+    if args.exit_code && !scan_diff.is_empty() {
+        return 1;
+    }
+    0
+}
+
+fn store(args: StoreArgs) -> i32 {
+    let store = match Store::open(&args.db) {
+        Ok(store) => store,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return 1;
+        }
+    };
+    match args.command {
+        StoreCommand::Scans => match store.scans() {
+            Ok(scans) => {
+                for scan in scans {
+                    println!("{}", scan);
+                }
+                0
+            }
+            Err(error) => {
+                tracing::warn!("{}", error);
+                1
+            }
+        },
+        StoreCommand::Hosts {
+            port,
+            service,
+            scan,
+            all,
+        } => {
+            let query = HostQuery {
+                port,
+                service,
+                scan,
+                all,
+            };
+            match store.hosts(&query) {
+                Ok(hosts) => {
+                    for host in hosts {
+                        println!("{}", host);
+                    }
+                    0
+                }
+                Err(error) => {
+                    tracing::warn!("{}", error);
+                    1
+                }
+            }
+        }
+        StoreCommand::Export {
+            scan,
+            format,
+            output,
+        } => match store.load(scan) {
+            Ok(result) => write_output(output.as_deref(), &report::render(&result, format)),
+            Err(error) => {
+                tracing::warn!("{}", error);
+                1
+            }
+        },
+    }
+}
+
+/// Writes `content` to the file at `output`, or to stdout.
+fn write_output(output: Option<&str>, content: &str) -> i32 {
+    match output {
+        None => {
+            println!("{}", content);
+            0
+        }
+        Some(output) => match std::fs::write(output, content) {
+            Ok(()) => 0,
+            Err(error) => {
+                tracing::warn!("write {} error: {}", output, error);
+                1
+            }
+        },
+    }
 }
 
 pub fn print_banner() {
@@ -0,0 +1,326 @@
+use crate::args::config::{Config, MonitorGroup};
+use crate::report::diff::{Change, ScanDiff};
+use crate::scanner::plugin::StoreSink;
+use crate::store::Store;
+use futures::future::join_all;
+use schedule::Schedule;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+
+pub mod schedule;
+
+/// Where the change events go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventTarget {
+    /// one JSON object per line.
+    Stdout,
+    /// one JSON object per line, appended.
+    File(String),
+    /// the events of a run POSTed as a JSON array.
+    Webhook(String),
+}
+
+impl EventTarget {
+    /// `stdout`, `file:<path>` or a `http://` or `https://` URL.
+    pub fn parse(target: &str) -> Result<EventTarget, String> {
+        if target == "stdout" {
+            return Ok(EventTarget::Stdout);
+        }
+        if let Some(path) = target.strip_prefix("file:") {
+            return Ok(EventTarget::File(path.to_string()));
+        }
+        if target.starts_with("http://") || target.starts_with("https://") {
+            return Ok(EventTarget::Webhook(target.to_string()));
+        }
+        Err(format!(
+            "invalid event target {}, expected stdout, file:<path> or a webhook URL",
+            target
+        ))
+    }
+    /// Parses every target, stdout when there is none.
+    pub fn parse_all(targets: &[String]) -> Result<Vec<EventTarget>, String> {
+        if targets.is_empty() {
+            return Ok(vec![EventTarget::Stdout]);
+        }
+        targets
+            .iter()
+            .map(|target| EventTarget::parse(target))
+            .collect()
+    }
+}
+
+/// A change a monitor group run found compared to the run before it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub group: String,
+    pub scan_id: i64,
+    pub previous_scan_id: i64,
+    /// RFC 3339 time the run started at.
+    pub time: String,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+struct Group {
+    name: String,
+    schedule: Schedule,
+    config: MonitorGroup,
+}
+
+/// Rescans the target groups of the config on their schedules, stores every run and emits
+/// the changes since the previous run of the group.
+pub struct Monitor {
+    config: Config,
+    groups: Vec<Group>,
+    store: Arc<Store>,
+    events: Vec<EventTarget>,
+    client: reqwest::Client,
+}
+
+impl Monitor {
+    /// Fails on an invalid schedule or event target, or a group without a name or address,
+    /// before anything runs.
+    pub fn new(config: Config, store: Store, events: &[String]) -> Result<Monitor, String> {
+        let mut groups: Vec<Group> = vec![];
+        for group in config.monitor.groups.iter() {
+            if group.name.is_empty() || group.address.is_empty() {
+                return Err("every monitor group needs a name and an address".to_string());
+            }
+            if groups.iter().any(|other| other.name == group.name) {
+                return Err(format!("duplicate monitor group {}", group.name));
+            }
+            // fails early on an unknown profile.
+            config.group_args(group)?;
+            groups.push(Group {
+                name: group.name.clone(),
+                schedule: Schedule::parse(&group.schedule)
+                    .map_err(|error| format!("group {}: {}", group.name, error))?,
+                config: group.clone(),
+            });
+        }
+        if groups.is_empty() {
+            return Err("there is no monitor group in the config".to_string());
+        }
+        let events = EventTarget::parse_all(events)?;
+        Ok(Monitor {
+            config,
+            groups,
+            store: Arc::new(store),
+            events,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Runs every group on its schedule until interrupted, or every group once right away
+    /// with `once`.
+    pub async fn run(self, once: bool) {
+        let monitor = Arc::new(self);
+        let tasks = (0..monitor.groups.len()).map(|index| {
+            let monitor = monitor.clone();
+            async move {
+                let group = &monitor.groups[index];
+                loop {
+                    if !once {
+                        let next = match group.schedule.next_after(time::OffsetDateTime::now_utc())
+                        {
+                            Some(next) => next,
+                            None => {
+                                tracing::warn!("group {} schedule never matches.", group.name);
+                                return;
+                            }
+                        };
+                        tracing::info!(
+                            "group {} next run at {}.",
+                            group.name,
+                            next.format(&Rfc3339).unwrap_or_default()
+                        );
+                        let wait = next - time::OffsetDateTime::now_utc();
+                        tokio::time::sleep(wait.try_into().unwrap_or_default()).await;
+                    }
+                    if let Err(error) = monitor.run_group(group).await {
+                        tracing::warn!("group {} run failed: {}", group.name, error);
+                    }
+                    if once {
+                        return;
+                    }
+                }
+            }
+        });
+        tokio::select! {
+            _ = join_all(tasks) => {}
+            _ = tokio::signal::ctrl_c() => {
+                tracing::info!("monitor interrupted, the running scans are left unfinished.");
+            }
+        }
+    }
+
+    /// Scans the group, then emits the changes since its previous finished run.
+    async fn run_group(&self, group: &Group) -> Result<(), String> {
+        let previous = self.store.latest_group_scan(&group.name)?;
+        let args = self.config.group_args(&group.config)?;
+        tracing::info!("group {} run started.", group.name);
+        let mut scanner = crate::new_scanner(&args).await?;
+        let sink = Arc::new(StoreSink::new(
+            self.store.clone(),
+            &group.config.address,
+            Some(&group.name),
+        ));
+        scanner.register_sink(sink.clone());
+        scanner.run().await;
+        sink.flushed().await;
+        let scan_id = sink
+            .scan_id()
+            .ok_or_else(|| "the run could not be stored".to_string())?;
+        let current = self.store.load(scan_id)?;
+
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                tracing::info!(
+                    "group {} run finished as scan {}, the baseline of the next runs.",
+                    group.name,
+                    scan_id
+                );
+                return Ok(());
+            }
+        };
+        let scan_diff = ScanDiff::new(&self.store.load(previous)?, &current);
+        tracing::info!(
+            "group {} run finished as scan {}: {}.",
+            group.name,
+            scan_id,
+            scan_diff.summary()
+        );
+        let events = scan_diff
+            .changes
+            .into_iter()
+            .map(|change| ChangeEvent {
+                group: group.name.clone(),
+                scan_id,
+                previous_scan_id: previous,
+                time: current.started.clone(),
+                change,
+            })
+            .collect::<Vec<_>>();
+        if !events.is_empty() {
+            self.emit(&events).await;
+        }
+        Ok(())
+    }
+
+    async fn emit(&self, events: &[ChangeEvent]) {
+        for target in self.events.iter() {
+            if let Err(error) = self.emit_to(target, events).await {
+                tracing::warn!("emit change events error: {}", error);
+            }
+        }
+    }
+
+    async fn emit_to(&self, target: &EventTarget, events: &[ChangeEvent]) -> Result<(), String> {
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event).map_err(|error| error.to_string())?);
+            lines.push('\n');
+        }
+        match target {
+            EventTarget::Stdout => {
+                print!("{}", lines);
+                std::io::stdout().flush().map_err(|error| error.to_string())
+            }
+            EventTarget::File(path) => std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(lines.as_bytes()))
+                .map_err(|error| format!("write {} error: {}", path, error)),
+            EventTarget::Webhook(url) => {
+                let response = self
+                    .client
+                    .post(url)
+                    .json(events)
+                    .timeout(std::time::Duration::from_secs(10))
+                    .send()
+                    .await
+                    .map_err(|error| format!("post {} error: {}", url, error))?;
+                if !response.status().is_success() {
+                    return Err(format!("post {} error: {}", url, response.status()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod monitor_test {
+    use crate::args::config::Config;
+    use crate::monitor::{ChangeEvent, EventTarget};
+    use crate::report::diff::Change;
+    use std::path::Path;
+
+    #[test]
+    fn monitor_config_test() {
+        let config = Config::parse(
+            r#"
+[timing]
+timeout = 2000
+
+[monitor]
+events = ["stdout", "file:./events.jsonl", "https://hooks.example.com/scan"]
+
+[[monitor.groups]]
+name = "dmz"
+address = "192.168.1.0/24"
+schedule = "*/30 * * * *"
+profile = "quick"
+
+[[monitor.groups]]
+name = "db"
+address = "10.0.0.5"
+schedule = "@daily"
+ports = "db"
+"#,
+            Path::new("config.toml"),
+        )
+        .unwrap();
+        let events = config
+            .monitor
+            .events
+            .iter()
+            .map(|target| EventTarget::parse(target).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                EventTarget::Stdout,
+                EventTarget::File("./events.jsonl".to_string()),
+                EventTarget::Webhook("https://hooks.example.com/scan".to_string()),
+            ]
+        );
+        assert!(EventTarget::parse("syslog").is_err());
+
+        let dmz = config.group_args(&config.monitor.groups[0]).unwrap();
+        assert_eq!(dmz.address.as_deref(), Some("192.168.1.0/24"));
+        assert_eq!((dmz.ports.as_deref(), dmz.timeout), (Some("top100"), 500));
+        let db = config.group_args(&config.monitor.groups[1]).unwrap();
+        assert_eq!((db.ports.as_deref(), db.timeout), (Some("db"), 2000));
+
+        let event = ChangeEvent {
+            group: "dmz".to_string(),
+            scan_id: 2,
+            previous_scan_id: 1,
+            time: "2026-10-19T00:00:00Z".to_string(),
+            change: Change::HostUp {
+                ip_addr: "192.168.1.7".parse().unwrap(),
+            },
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"group":"dmz","scan_id":2,"previous_scan_id":1,"time":"2026-10-19T00:00:00Z","change":"host_up","ip_addr":"192.168.1.7"}"#
+        );
+        assert_eq!(serde_json::from_str::<ChangeEvent>(&json).unwrap(), event);
+    }
+}
@@ -0,0 +1,201 @@
+use time::{Date, Duration, Month, OffsetDateTime};
+
+/// A cron schedule: minute, hour, day of month, month and day of week, evaluated in UTC.
+/// Fields accept `*`, values, `a-b` ranges, `/n` steps and `,` lists, the day of week
+/// 0 or 7 for Sunday.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// a day matches either the day of month or the day of week when both are restricted.
+    any_day: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!(
+                "invalid schedule {}: expected 5 fields, got {}",
+                expression,
+                fields.len()
+            ));
+        }
+        let field = |index: usize, min: u32, max: u32| {
+            parse_field(fields[index], min, max)
+                .map_err(|error| format!("invalid schedule {}: {}", expression, error))
+        };
+        let mut weekdays = field(4, 0, 7)?;
+        // 7 is Sunday as well.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Schedule {
+            minutes: field(0, 0, 59)?,
+            hours: field(1, 0, 23)?,
+            days: field(2, 1, 31)?,
+            months: field(3, 1, 12)?,
+            weekdays,
+            any_day: fields[2] != "*" && fields[4] != "*",
+        })
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().number_days_from_sunday()) != 0;
+        if self.any_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first minute strictly after `after` the schedule matches, `None` when there is
+    /// none within 4 years, e.g. for `0 0 31 2 *`.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let mut time = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::MINUTE;
+        let limit = time + Duration::days(4 * 366);
+        while time < limit {
+            if self.months & (1 << u8::from(time.month())) == 0 {
+                let (year, month) = match time.month() {
+                    Month::December => (time.year() + 1, Month::January),
+                    month => (time.year(), month.next()),
+                };
+                time = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .midnight()
+                    .assume_utc();
+                continue;
+            }
+            if !self.day_matches(time.date()) {
+                time = time.date().next_day()?.midnight().assume_utc();
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.replace_minute(0).ok()? + Duration::HOUR;
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += Duration::MINUTE;
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
+
+/// The bit set of the values a field matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut values = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in {}", item))?;
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, min, max)?, parse_value(end, min, max)?),
+                // `5/15` runs from 5 to the end of the field.
+                None if step > 1 => (parse_value(range, min, max)?, max),
+                None => {
+                    let value = parse_value(range, min, max)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("invalid range {}", item));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("{} is not in {}-{}", value, min, max))
+}
+
+#[cfg(test)]
+pub mod schedule_test {
+    use crate::monitor::schedule::Schedule;
+    use time::format_description::well_known::Rfc3339;
+    use time::OffsetDateTime;
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        let after = OffsetDateTime::parse(after, &Rfc3339).unwrap();
+        Schedule::parse(expression)
+            .unwrap()
+            .next_after(after)
+            .map(|time| time.format(&Rfc3339).unwrap())
+    }
+
+    #[test]
+    fn cron_schedule_test() {
+        let after = "2026-10-19T10:07:30Z";
+        assert_eq!(
+            next("*/15 * * * *", after).as_deref(),
+            Some("2026-10-19T10:15:00Z")
+        );
+        assert_eq!(
+            next("* * * * *", "2026-10-19T10:07:00Z").as_deref(),
+            Some("2026-10-19T10:08:00Z")
+        );
+        assert_eq!(
+            next("@daily", after).as_deref(),
+            Some("2026-10-20T00:00:00Z")
+        );
+        assert_eq!(
+            next("30 2 * * 1-5", "2026-10-23T03:00:00Z").as_deref(),
+            Some("2026-10-26T02:30:00Z")
+        );
+        // Sunday as 7, the 19th being a Monday.
+        assert_eq!(
+            next("0 12 * * 7", after).as_deref(),
+            Some("2026-10-25T12:00:00Z")
+        );
+        // either the first of the month or a Friday.
+        assert_eq!(
+            next("0 0 1 * 5", after).as_deref(),
+            Some("2026-10-23T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 29 2 *", after).as_deref(),
+            Some("2028-02-29T00:00:00Z")
+        );
+        assert_eq!(next("0 0 31 2 *", after), None);
+        assert_eq!(
+            next("5/20 8,20 * 12 *", after).as_deref(),
+            Some("2026-12-01T08:05:00Z")
+        );
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("10-5 * * * *").is_err());
+    }
+}
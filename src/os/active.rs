@@ -0,0 +1,1187 @@
+use crate::os::passive::initial_ttl;
+use crate::os::OsGuess;
+use crate::utils::json;
+use pnet::packet::icmp::destination_unreachable::DestinationUnreachablePacket;
+use pnet::packet::icmp::echo_reply::EchoReplyPacket;
+use pnet::packet::icmp::echo_request::MutableEchoRequestPacket;
+use pnet::packet::icmp::{IcmpCode, IcmpPacket, IcmpTypes};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::{Ipv4Flags, Ipv4Packet, MutableIpv4Packet};
+use pnet::packet::tcp::{MutableTcpPacket, TcpFlags, TcpPacket};
+use pnet::packet::udp::{MutableUdpPacket, UdpPacket};
+use pnet::packet::Packet;
+use pnet::transport::{
+    ipv4_packet_iter, transport_channel, TransportChannelType, TransportReceiver, TransportSender,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const SEQ_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1500);
+const MIN_CONFIDENCE: u8 = 80;
+const U1_IP_ID: u16 = 0x1042;
+const U1_PAYLOAD_SIZE: usize = 300;
+const IE_SEQUENCE: u16 = 295;
+
+/// Attribute weights used when the database doesn't carry its own `MatchPoints` section,
+/// these are the ones nmap ships with.
+const DEFAULT_MATCH_POINTS: &str = "\
+SEQ(SP=25%GCD=75%ISR=25%TI=100%CI=50%II=100%SS=80%TS=100)
+OPS(O1=20%O2=20%O3=20%O4=20%O5=20%O6=20)
+WIN(W1=15%W2=15%W3=15%W4=15%W5=15%W6=15)
+ECN(R=100%DF=20%T=15%TG=15%W=15%O=15%CC=100%Q=20)
+T1(R=100%DF=20%T=15%TG=15%S=20%A=20%F=30%RD=20%Q=20)
+T2(R=80%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+T3(R=80%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+T4(R=100%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+T5(R=100%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+T6(R=100%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+T7(R=80%DF=20%T=15%TG=15%W=25%S=20%A=20%F=30%O=10%RD=20%Q=20)
+U1(R=50%DF=20%T=15%TG=15%IPL=100%UN=100%RIPL=100%RID=100%RIPCK=100%RUCK=100%RUD=100)
+IE(R=50%DFI=40%T=15%TG=15%CD=100)";
+
+/// Test results keyed by test name (`SEQ`, `T1`, ...) and then by attribute name.
+pub type TestResults = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OsClass {
+    pub vendor: String,
+    pub family: String,
+    pub generation: Option<String>,
+    pub device_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OsSignature {
+    pub name: String,
+    pub classes: Vec<OsClass>,
+    #[serde(default)]
+    pub cpe: Vec<String>,
+    pub tests: TestResults,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsDatabase {
+    pub match_points: TestResults,
+    pub signatures: Vec<OsSignature>,
+}
+
+impl OsDatabase {
+    /// Loads either a JSON signature list or a raw `nmap-os-db` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
+        let content = json::read_file(path, "os database").unwrap_or_default();
+        if !content.trim_start().starts_with('[') {
+            return Self::from_nmap_db(&content);
+        }
+        Self {
+            match_points: parse_tests(DEFAULT_MATCH_POINTS),
+            signatures: json::parse_json(&content, "os database"),
+        }
+    }
+
+    /// Parses the `nmap-os-db` text format.
+    pub fn from_nmap_db(content: &str) -> Self {
+        let mut match_points = TestResults::new();
+        let mut signatures: Vec<OsSignature> = vec![];
+        let mut in_match_points = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "MatchPoints" {
+                in_match_points = true;
+            } else if let Some(name) = line.strip_prefix("Fingerprint ") {
+                in_match_points = false;
+                signatures.push(OsSignature {
+                    name: name.trim().to_string(),
+                    classes: vec![],
+                    cpe: vec![],
+                    tests: TestResults::new(),
+                });
+            } else if let Some(class) = line.strip_prefix("Class ") {
+                if let Some(signature) = signatures.last_mut() {
+                    let fields = class.split('|').map(str::trim).collect::<Vec<&str>>();
+                    if fields.len() == 4 {
+                        signature.classes.push(OsClass {
+                            vendor: fields[0].to_string(),
+                            family: fields[1].to_string(),
+                            generation: Some(fields[2].to_string()).filter(|item| !item.is_empty()),
+                            device_type: fields[3].to_string(),
+                        });
+                    }
+                }
+            } else if let Some(cpe) = line.strip_prefix("CPE ") {
+                if let Some(signature) = signatures.last_mut() {
+                    let cpe = cpe.trim_end_matches(" auto").trim();
+                    signature.cpe.push(cpe.to_string());
+                }
+            } else if let Some((test, attributes)) = parse_test_line(line) {
+                if in_match_points {
+                    match_points.insert(test, attributes);
+                } else if let Some(signature) = signatures.last_mut() {
+                    signature.tests.insert(test, attributes);
+                }
+            }
+        }
+        if match_points.is_empty() {
+            match_points = parse_tests(DEFAULT_MATCH_POINTS);
+        }
+        Self {
+            match_points,
+            signatures,
+        }
+    }
+
+    fn score(&self, fingerprint: &TestResults, signature: &OsSignature) -> (u32, u32) {
+        let mut matched = 0;
+        let mut possible = 0;
+        for (test, attributes) in fingerprint {
+            let expected = match signature.tests.get(test) {
+                None => continue,
+                Some(data) => data,
+            };
+            for (attribute, value) in attributes {
+                let expression = match expected.get(attribute) {
+                    None => continue,
+                    Some(data) => data,
+                };
+                let points = self
+                    .match_points
+                    .get(test)
+                    .and_then(|points| points.get(attribute))
+                    .and_then(|points| points.parse::<u32>().ok())
+                    .unwrap_or(1);
+                possible += points;
+                if expression_matches(expression, value) {
+                    matched += points;
+                }
+            }
+        }
+        (matched, possible)
+    }
+
+    /// Returns the best matching signature, or `None` when nothing is confident enough.
+    pub fn matches(&self, fingerprint: &TestResults) -> Option<OsGuess> {
+        self.signatures
+            .iter()
+            .filter_map(|signature| {
+                let (matched, possible) = self.score(fingerprint, signature);
+                if possible == 0 {
+                    return None;
+                }
+                Some(((matched * 100 / possible) as u8, signature))
+            })
+            .filter(|(confidence, _)| *confidence >= MIN_CONFIDENCE)
+            .max_by_key(|(confidence, _)| *confidence)
+            .map(|(confidence, signature)| {
+                let class = signature.classes.first();
+                OsGuess {
+                    family: class
+                        .map(|class| class.family.clone())
+                        .unwrap_or_else(|| signature.name.clone()),
+                    name: Some(signature.name.clone()),
+                    confidence,
+                    device_type: class.map(|class| class.device_type.clone()),
+                    cpe: signature.cpe.clone(),
+                }
+            })
+    }
+}
+
+fn parse_test_line(line: &str) -> Option<(String, BTreeMap<String, String>)> {
+    let (test, rest) = line.split_once('(')?;
+    let body = rest.strip_suffix(')')?;
+    let mut attributes = BTreeMap::new();
+    for attribute in body.split('%').filter(|item| !item.is_empty()) {
+        let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        attributes.insert(name.to_string(), value.to_string());
+    }
+    Some((test.to_string(), attributes))
+}
+
+fn parse_tests(content: &str) -> TestResults {
+    content.lines().filter_map(parse_test_line).collect()
+}
+
+/// Formats test results the way nmap prints a fingerprint.
+pub fn format_tests(tests: &TestResults) -> String {
+    tests
+        .iter()
+        .map(|(test, attributes)| {
+            let attributes = attributes
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join("%");
+            format!("{}({})", test, attributes)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Evaluates an nmap-os-db expression: `|` separated alternatives of literals,
+/// hexadecimal ranges `a-b` and comparisons `>a` / `<a`.
+fn expression_matches(expression: &str, value: &str) -> bool {
+    expression.split('|').any(|alternative| {
+        if alternative == value {
+            return true;
+        }
+        let number = match u64::from_str_radix(value, 16) {
+            Ok(number) => number,
+            Err(_) => return false,
+        };
+        let parse = |item: &str| u64::from_str_radix(item, 16).ok();
+        if let Some(bound) = alternative.strip_prefix('>').and_then(parse) {
+            number > bound
+        } else if let Some(bound) = alternative.strip_prefix('<').and_then(parse) {
+            number < bound
+        } else if let Some((low, high)) = alternative.split_once('-') {
+            match (parse(low), parse(high)) {
+                (Some(low), Some(high)) => low <= number && number <= high,
+                _ => false,
+            }
+        } else {
+            false
+        }
+    })
+}
+
+struct TcpProbe {
+    name: &'static str,
+    closed: bool,
+    flags: u16,
+    window: u16,
+    options: &'static [u8],
+    dont_fragment: bool,
+}
+
+// raw TCP option bytes, timestamps carry TSval 0xFFFFFFFF and TSecr 0.
+const P1_OPTIONS: [u8; 20] = [
+    3, 3, 10, 1, 2, 4, 0x05, 0xb4, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 4, 2,
+];
+const P2_OPTIONS: [u8; 20] = [
+    2, 4, 0x05, 0x78, 3, 3, 0, 4, 2, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0,
+];
+const P3_OPTIONS: [u8; 20] = [
+    8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 1, 1, 3, 3, 5, 1, 2, 4, 0x02, 0x80,
+];
+const P4_OPTIONS: [u8; 16] = [4, 2, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 3, 3, 10, 0];
+const P5_OPTIONS: [u8; 20] = [
+    2, 4, 0x02, 0x18, 4, 2, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 3, 3, 10, 0,
+];
+const P6_OPTIONS: [u8; 16] = [
+    2, 4, 0x01, 0x09, 4, 2, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0,
+];
+const ECN_OPTIONS: [u8; 12] = [3, 3, 10, 1, 2, 4, 0x05, 0xb4, 4, 2, 1, 1];
+const T_OPTIONS: [u8; 20] = [
+    3, 3, 10, 1, 2, 4, 0x01, 0x09, 8, 10, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 4, 2,
+];
+
+const ECN_FLAGS: u16 = TcpFlags::SYN | TcpFlags::ECE | TcpFlags::CWR;
+const ECN_URGENT_POINTER: u16 = 0xf7f5;
+
+fn tcp_probes() -> Vec<TcpProbe> {
+    let syn = |name, window, options: &'static [u8]| TcpProbe {
+        name,
+        closed: false,
+        flags: TcpFlags::SYN,
+        window,
+        options,
+        dont_fragment: false,
+    };
+    vec![
+        syn("P1", 1, &P1_OPTIONS),
+        syn("P2", 63, &P2_OPTIONS),
+        syn("P3", 4, &P3_OPTIONS),
+        syn("P4", 4, &P4_OPTIONS),
+        syn("P5", 16, &P5_OPTIONS),
+        syn("P6", 512, &P6_OPTIONS),
+        TcpProbe {
+            name: "ECN",
+            closed: false,
+            flags: ECN_FLAGS,
+            window: 3,
+            options: &ECN_OPTIONS,
+            dont_fragment: false,
+        },
+        TcpProbe {
+            name: "T2",
+            closed: false,
+            flags: 0,
+            window: 128,
+            options: &T_OPTIONS,
+            dont_fragment: true,
+        },
+        TcpProbe {
+            name: "T3",
+            closed: false,
+            flags: TcpFlags::SYN | TcpFlags::FIN | TcpFlags::URG | TcpFlags::PSH,
+            window: 256,
+            options: &T_OPTIONS,
+            dont_fragment: false,
+        },
+        TcpProbe {
+            name: "T4",
+            closed: false,
+            flags: TcpFlags::ACK,
+            window: 1024,
+            options: &T_OPTIONS,
+            dont_fragment: true,
+        },
+        TcpProbe {
+            name: "T5",
+            closed: true,
+            flags: TcpFlags::SYN,
+            window: 31337,
+            options: &T_OPTIONS,
+            dont_fragment: false,
+        },
+        TcpProbe {
+            name: "T6",
+            closed: true,
+            flags: TcpFlags::ACK,
+            window: 32768,
+            options: &T_OPTIONS,
+            dont_fragment: true,
+        },
+        TcpProbe {
+            name: "T7",
+            closed: true,
+            flags: TcpFlags::FIN | TcpFlags::PSH | TcpFlags::URG,
+            window: 65535,
+            options: &T_OPTIONS,
+            dont_fragment: false,
+        },
+    ]
+}
+
+struct EchoProbe {
+    sequence: u16,
+    tos: u8,
+    code: u8,
+    payload_size: usize,
+    dont_fragment: bool,
+}
+
+const ECHO_PROBES: [EchoProbe; 2] = [
+    EchoProbe {
+        sequence: IE_SEQUENCE,
+        tos: 0,
+        code: 9,
+        payload_size: 120,
+        dont_fragment: true,
+    },
+    EchoProbe {
+        sequence: IE_SEQUENCE + 1,
+        tos: 4,
+        code: 0,
+        payload_size: 150,
+        dont_fragment: false,
+    },
+];
+
+#[derive(Debug, Clone)]
+struct SentProbe {
+    name: &'static str,
+    source_port: u16,
+    destination_port: u16,
+    sequence: u32,
+    acknowledgement: u32,
+    sent_at: Instant,
+}
+
+/// Everything captured while probing a single host, kept separate from the packet I/O so
+/// the fingerprint can be computed from recorded packets.
+#[derive(Debug, Default)]
+struct ProbeCapture {
+    sent: Vec<SentProbe>,
+    udp_source_port: u16,
+    udp_checksum: u16,
+    udp_ip_checksum: u16,
+    icmp_ident: u16,
+    tcp: Vec<Vec<u8>>,
+    icmp: Vec<Vec<u8>>,
+}
+
+/// Sends the probe battery to `target` and returns the resulting fingerprint.
+/// Needs raw socket privileges, blocking, so run it on a blocking thread.
+pub fn fingerprint_host(
+    target: Ipv4Addr,
+    open_port: u16,
+    closed_port: u16,
+    closed_udp_port: u16,
+) -> Result<TestResults, String> {
+    let source = local_address(target)?;
+    let open_channel = |protocol: IpNextHeaderProtocol| {
+        transport_channel(4096, TransportChannelType::Layer3(protocol))
+            .map_err(|error| format!("open raw socket error: {}", error))
+    };
+    let (mut tcp_tx, tcp_rx) = open_channel(IpNextHeaderProtocols::Tcp)?;
+    let (mut icmp_tx, icmp_rx) = open_channel(IpNextHeaderProtocols::Icmp)?;
+    let (mut udp_tx, _) = open_channel(IpNextHeaderProtocols::Udp)?;
+
+    let deadline = Instant::now() + SEQ_PROBE_INTERVAL * 6 + RESPONSE_TIMEOUT;
+    let tcp_receiver = spawn_receiver(tcp_rx, target, deadline);
+    let icmp_receiver = spawn_receiver(icmp_rx, target, deadline);
+
+    let mut capture = ProbeCapture::default();
+    let base_port = 40000 + rand::random::<u16>() % 20000;
+    let mut ip_id = rand::random::<u16>();
+    for (index, probe) in tcp_probes().iter().enumerate() {
+        let sent = SentProbe {
+            name: probe.name,
+            source_port: base_port + index as u16,
+            destination_port: if probe.closed { closed_port } else { open_port },
+            sequence: rand::random(),
+            acknowledgement: rand::random(),
+            sent_at: Instant::now(),
+        };
+        ip_id = ip_id.wrapping_add(1);
+        let packet = build_tcp_packet(source, target, probe, &sent, ip_id);
+        send(&mut tcp_tx, packet, target)?;
+        capture.sent.push(sent);
+        if probe.name.starts_with('P') {
+            std::thread::sleep(SEQ_PROBE_INTERVAL);
+        }
+    }
+
+    capture.udp_source_port = base_port + 100;
+    let packet = build_udp_packet(source, target, capture.udp_source_port, closed_udp_port);
+    {
+        let ip = Ipv4Packet::new(&packet).unwrap();
+        capture.udp_ip_checksum = ip.get_checksum();
+        capture.udp_checksum = UdpPacket::new(ip.payload()).unwrap().get_checksum();
+    }
+    send(&mut udp_tx, packet, target)?;
+
+    capture.icmp_ident = rand::random();
+    for probe in ECHO_PROBES.iter() {
+        let packet = build_echo_packet(source, target, capture.icmp_ident, probe);
+        send(&mut icmp_tx, packet, target)?;
+    }
+
+    capture.tcp = tcp_receiver.join().unwrap_or_default();
+    capture.icmp = icmp_receiver.join().unwrap_or_default();
+    Ok(analyze(&capture))
+}
+
+fn send(tx: &mut TransportSender, packet: Vec<u8>, target: Ipv4Addr) -> Result<(), String> {
+    let packet = Ipv4Packet::owned(packet).unwrap();
+    tx.send_to(packet, IpAddr::V4(target))
+        .map(|_| ())
+        .map_err(|error| format!("send probe error: {}", error))
+}
+
+/// The local address the kernel would route `target` from.
+fn local_address(target: Ipv4Addr) -> Result<Ipv4Addr, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|error| error.to_string())?;
+    socket
+        .connect((target, 9))
+        .map_err(|error| error.to_string())?;
+    match socket.local_addr().map_err(|error| error.to_string())?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(ip) => Err(format!("unexpected local address: {}", ip)),
+    }
+}
+
+fn spawn_receiver(
+    mut rx: TransportReceiver,
+    target: Ipv4Addr,
+    deadline: Instant,
+) -> JoinHandle<Vec<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut packets = vec![];
+        let mut iter = ipv4_packet_iter(&mut rx);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            match iter.next_with_timeout(remaining) {
+                Ok(Some((packet, addr))) if addr == IpAddr::V4(target) => {
+                    packets.push(packet.packet().to_vec())
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        packets
+    })
+}
+
+fn build_ipv4_header(
+    buf: &mut [u8],
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: IpNextHeaderProtocol,
+    ip_id: u16,
+    tos: u8,
+    dont_fragment: bool,
+) {
+    let total_length = buf.len() as u16;
+    let mut ip = MutableIpv4Packet::new(buf).unwrap();
+    ip.set_version(4);
+    ip.set_header_length(5);
+    ip.set_dscp(tos >> 2);
+    ip.set_ecn(tos & 0x03);
+    ip.set_total_length(total_length);
+    ip.set_identification(ip_id);
+    ip.set_flags(if dont_fragment {
+        Ipv4Flags::DontFragment
+    } else {
+        0
+    });
+    ip.set_ttl(64);
+    ip.set_next_level_protocol(protocol);
+    ip.set_source(source);
+    ip.set_destination(destination);
+    let checksum = pnet::packet::ipv4::checksum(&ip.to_immutable());
+    ip.set_checksum(checksum);
+}
+
+fn build_tcp_packet(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    probe: &TcpProbe,
+    sent: &SentProbe,
+    ip_id: u16,
+) -> Vec<u8> {
+    let tcp_length = 20 + probe.options.len();
+    let mut buf = vec![0u8; 20 + tcp_length];
+    {
+        let mut tcp = MutableTcpPacket::new(&mut buf[20..]).unwrap();
+        tcp.set_source(sent.source_port);
+        tcp.set_destination(sent.destination_port);
+        tcp.set_sequence(sent.sequence);
+        if probe.flags & TcpFlags::ACK != 0 {
+            tcp.set_acknowledgement(sent.acknowledgement);
+        }
+        tcp.set_data_offset((tcp_length / 4) as u8);
+        tcp.set_flags(probe.flags);
+        tcp.set_window(probe.window);
+        if probe.flags == ECN_FLAGS {
+            tcp.set_reserved(0x08);
+            tcp.set_urgent_ptr(ECN_URGENT_POINTER);
+        }
+        tcp.get_options_raw_mut().copy_from_slice(probe.options);
+        let checksum = pnet::packet::tcp::ipv4_checksum(&tcp.to_immutable(), &source, &destination);
+        tcp.set_checksum(checksum);
+    }
+    build_ipv4_header(
+        &mut buf,
+        source,
+        destination,
+        IpNextHeaderProtocols::Tcp,
+        ip_id,
+        0,
+        probe.dont_fragment,
+    );
+    buf
+}
+
+fn build_udp_packet(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: u16,
+    destination_port: u16,
+) -> Vec<u8> {
+    let udp_length = 8 + U1_PAYLOAD_SIZE;
+    let mut buf = vec![0u8; 20 + udp_length];
+    {
+        let mut udp = MutableUdpPacket::new(&mut buf[20..]).unwrap();
+        udp.set_source(source_port);
+        udp.set_destination(destination_port);
+        udp.set_length(udp_length as u16);
+        udp.set_payload(&[b'C'; U1_PAYLOAD_SIZE]);
+        let checksum = pnet::packet::udp::ipv4_checksum(&udp.to_immutable(), &source, &destination);
+        udp.set_checksum(checksum);
+    }
+    build_ipv4_header(
+        &mut buf,
+        source,
+        destination,
+        IpNextHeaderProtocols::Udp,
+        U1_IP_ID,
+        0,
+        false,
+    );
+    buf
+}
+
+fn build_echo_packet(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    ident: u16,
+    probe: &EchoProbe,
+) -> Vec<u8> {
+    let mut buf = vec![0u8; 20 + 8 + probe.payload_size];
+    {
+        let mut echo = MutableEchoRequestPacket::new(&mut buf[20..]).unwrap();
+        echo.set_icmp_type(IcmpTypes::EchoRequest);
+        echo.set_icmp_code(IcmpCode(probe.code));
+        echo.set_identifier(ident);
+        echo.set_sequence_number(probe.sequence);
+        let checksum = pnet::packet::util::checksum(echo.packet(), 1);
+        echo.set_checksum(checksum);
+    }
+    build_ipv4_header(
+        &mut buf,
+        source,
+        destination,
+        IpNextHeaderProtocols::Icmp,
+        rand::random(),
+        probe.tos,
+        probe.dont_fragment,
+    );
+    buf
+}
+
+fn analyze(capture: &ProbeCapture) -> TestResults {
+    let mut tests = TestResults::new();
+    let mut responses: BTreeMap<&str, (&SentProbe, Ipv4Packet)> = BTreeMap::new();
+    for packet in capture.tcp.iter() {
+        let ip = match Ipv4Packet::new(packet) {
+            Some(ip) if ip.get_next_level_protocol() == IpNextHeaderProtocols::Tcp => ip,
+            _ => continue,
+        };
+        let (source, destination) = match TcpPacket::new(ip.payload()) {
+            None => continue,
+            Some(tcp) => (tcp.get_source(), tcp.get_destination()),
+        };
+        let sent = capture
+            .sent
+            .iter()
+            .find(|sent| sent.source_port == destination && sent.destination_port == source);
+        if let Some(sent) = sent {
+            responses.entry(sent.name).or_insert((sent, ip));
+        }
+    }
+
+    let mut ops = BTreeMap::new();
+    let mut win = BTreeMap::new();
+    let mut sequence_responses = vec![];
+    for index in 1..=6 {
+        if let Some((sent, ip)) = responses.get(format!("P{}", index).as_str()) {
+            let tcp = TcpPacket::new(ip.payload()).unwrap();
+            ops.insert(format!("O{}", index), format_options(&tcp));
+            win.insert(format!("W{}", index), format!("{:X}", tcp.get_window()));
+            sequence_responses.push((sent.sent_at, ip, tcp));
+        }
+    }
+    if !sequence_responses.is_empty() {
+        tests.insert("SEQ".to_string(), sequence_test(&sequence_responses));
+        tests.insert("OPS".to_string(), ops);
+        tests.insert("WIN".to_string(), win);
+    }
+
+    let tcp_test_names = [("T1", "P1"), ("ECN", "ECN"), ("T2", "T2"), ("T3", "T3")];
+    let tcp_test_names =
+        tcp_test_names
+            .into_iter()
+            .chain([("T4", "T4"), ("T5", "T5"), ("T6", "T6"), ("T7", "T7")]);
+    for (test, probe) in tcp_test_names {
+        let attributes = match responses.get(probe) {
+            None => BTreeMap::from([("R".to_string(), "N".to_string())]),
+            Some((sent, ip)) => tcp_test(test, sent, ip),
+        };
+        tests.insert(test.to_string(), attributes);
+    }
+
+    tests.insert("U1".to_string(), udp_test(capture));
+    tests.insert("IE".to_string(), echo_test(capture));
+    tests
+}
+
+fn ttl_attributes(attributes: &mut BTreeMap<String, String>, ip: &Ipv4Packet) {
+    let guess = format!("{:X}", initial_ttl(ip.get_ttl()));
+    attributes.insert("T".to_string(), guess.clone());
+    attributes.insert("TG".to_string(), guess);
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "Y" } else { "N" }.to_string()
+}
+
+fn tcp_test(test: &str, sent: &SentProbe, ip: &Ipv4Packet) -> BTreeMap<String, String> {
+    let tcp = TcpPacket::new(ip.payload()).unwrap();
+    let flags = tcp.get_flags();
+    let mut attributes = BTreeMap::new();
+    attributes.insert("R".to_string(), "Y".to_string());
+    attributes.insert(
+        "DF".to_string(),
+        yes_no(ip.get_flags() & Ipv4Flags::DontFragment != 0),
+    );
+    ttl_attributes(&mut attributes, ip);
+    if test != "T1" {
+        attributes.insert("W".to_string(), format!("{:X}", tcp.get_window()));
+        attributes.insert("O".to_string(), format_options(&tcp));
+    }
+    if test == "ECN" {
+        let cc = match (flags & TcpFlags::ECE != 0, flags & TcpFlags::CWR != 0) {
+            (true, false) => "Y",
+            (false, false) => "N",
+            (true, true) => "S",
+            (false, true) => "O",
+        };
+        attributes.insert("CC".to_string(), cc.to_string());
+    } else {
+        let sequence = tcp.get_sequence();
+        let s = if sequence == 0 {
+            "Z"
+        } else if sequence == sent.acknowledgement {
+            "A"
+        } else if sequence == sent.acknowledgement.wrapping_add(1) {
+            "A+"
+        } else {
+            "O"
+        };
+        attributes.insert("S".to_string(), s.to_string());
+        let acknowledgement = tcp.get_acknowledgement();
+        let a = if acknowledgement == 0 {
+            "Z"
+        } else if acknowledgement == sent.sequence {
+            "S"
+        } else if acknowledgement == sent.sequence.wrapping_add(1) {
+            "S+"
+        } else {
+            "O"
+        };
+        attributes.insert("A".to_string(), a.to_string());
+        attributes.insert("F".to_string(), format_flags(flags));
+        let rd = if flags & TcpFlags::RST != 0 && !tcp.payload().is_empty() {
+            format!("{:X}", crc32(tcp.payload()))
+        } else {
+            "0".to_string()
+        };
+        attributes.insert("RD".to_string(), rd);
+    }
+    let mut quirks = String::new();
+    if tcp.get_reserved() != 0 {
+        quirks.push('R');
+    }
+    if flags & TcpFlags::URG == 0 && tcp.get_urgent_ptr() != 0 {
+        quirks.push('U');
+    }
+    attributes.insert("Q".to_string(), quirks);
+    attributes
+}
+
+fn format_flags(flags: u16) -> String {
+    [
+        (TcpFlags::ECE, 'E'),
+        (TcpFlags::URG, 'U'),
+        (TcpFlags::ACK, 'A'),
+        (TcpFlags::PSH, 'P'),
+        (TcpFlags::RST, 'R'),
+        (TcpFlags::SYN, 'S'),
+        (TcpFlags::FIN, 'F'),
+    ]
+    .iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+/// Formats TCP options in nmap's `O` notation, e.g. `M5B4ST11NW7`.
+fn format_options(tcp: &TcpPacket) -> String {
+    let options = tcp.get_options_raw();
+    let mut result = String::new();
+    let mut index = 0;
+    while index < options.len() {
+        let kind = options[index];
+        match kind {
+            0 => {
+                result.push('L');
+                break;
+            }
+            1 => {
+                result.push('N');
+                index += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let length = match options.get(index + 1) {
+            Some(length) if *length >= 2 => *length as usize,
+            _ => break,
+        };
+        let data = match options.get(index + 2..index + length) {
+            None => break,
+            Some(data) => data,
+        };
+        match (kind, data.len()) {
+            (2, 2) => result.push_str(&format!("M{:X}", u16::from_be_bytes([data[0], data[1]]))),
+            (3, 1) => result.push_str(&format!("W{:X}", data[0])),
+            (4, 0) => result.push('S'),
+            (8, 8) => {
+                let value = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                let echo = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                result.push('T');
+                result.push(if value == 0 { '0' } else { '1' });
+                result.push(if echo == 0 { '0' } else { '1' });
+            }
+            _ => {}
+        }
+        index += length;
+    }
+    result
+}
+
+fn timestamp_value(tcp: &TcpPacket) -> Option<u32> {
+    let options = tcp.get_options_raw();
+    let mut index = 0;
+    while index < options.len() {
+        match options[index] {
+            0 => return None,
+            1 => index += 1,
+            8 => {
+                let data = options.get(index + 2..index + 6)?;
+                return Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+            }
+            _ => index += (*options.get(index + 1)? as usize).max(2),
+        }
+    }
+    None
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Sequence generation analysis over the SYN/ACKs of the six `SEQ` probes.
+fn sequence_test(responses: &[(Instant, &Ipv4Packet, TcpPacket)]) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let pairs = responses.windows(2).collect::<Vec<_>>();
+    if !pairs.is_empty() {
+        let mut divisor = 0;
+        let mut rates = vec![];
+        for pair in pairs.iter() {
+            let difference = pair[1]
+                .2
+                .get_sequence()
+                .wrapping_sub(pair[0].2.get_sequence());
+            let difference = difference.min(difference.wrapping_neg()) as u64;
+            divisor = gcd(divisor, difference);
+            let seconds = pair[1].0.duration_since(pair[0].0).as_secs_f64();
+            if seconds > 0.0 {
+                rates.push(difference as f64 / seconds);
+            }
+        }
+        attributes.insert("GCD".to_string(), format!("{:X}", divisor));
+        if !rates.is_empty() {
+            let average = rates.iter().sum::<f64>() / rates.len() as f64;
+            let isr = if average < 1.0 {
+                0
+            } else {
+                (8.0 * average.log2()).round() as u64
+            };
+            attributes.insert("ISR".to_string(), format!("{:X}", isr));
+        }
+    }
+
+    let ids = responses
+        .iter()
+        .map(|(_, ip, _)| ip.get_identification())
+        .collect::<Vec<u16>>();
+    if let Some(ti) = ip_id_sequence(&ids) {
+        attributes.insert("TI".to_string(), ti);
+    }
+
+    let timestamps = responses
+        .iter()
+        .map(|(sent_at, _, tcp)| timestamp_value(tcp).map(|value| (*sent_at, value)))
+        .collect::<Option<Vec<(Instant, u32)>>>();
+    let ts = match timestamps {
+        None => Some("U".to_string()),
+        Some(timestamps) if timestamps.iter().any(|(_, value)| *value == 0) => {
+            Some("0".to_string())
+        }
+        Some(timestamps) => {
+            let rates = timestamps
+                .windows(2)
+                .filter_map(|pair| {
+                    let seconds = pair[1].0.duration_since(pair[0].0).as_secs_f64();
+                    let difference = pair[1].1.wrapping_sub(pair[0].1) as f64;
+                    Some(difference / seconds).filter(|_| seconds > 0.0)
+                })
+                .collect::<Vec<f64>>();
+            if rates.is_empty() {
+                None
+            } else {
+                let average = rates.iter().sum::<f64>() / rates.len() as f64;
+                Some(match average {
+                    rate if rate < 5.66 => "1".to_string(),
+                    rate if (70.0..150.0).contains(&rate) => "7".to_string(),
+                    rate if (150.0..350.0).contains(&rate) => "8".to_string(),
+                    rate => format!("{:X}", rate.log2().round() as u64),
+                })
+            }
+        }
+    };
+    if let Some(ts) = ts {
+        attributes.insert("TS".to_string(), ts);
+    }
+    attributes
+}
+
+/// Classifies a sequence of IP IDs: zero, random, broken increment, incremental,
+/// random positive increments or a constant value.
+fn ip_id_sequence(ids: &[u16]) -> Option<String> {
+    if ids.len() < 2 {
+        return None;
+    }
+    if ids.iter().all(|id| *id == 0) {
+        return Some("Z".to_string());
+    }
+    if ids.iter().all(|id| *id == ids[0]) {
+        return Some(format!("{:X}", ids[0]));
+    }
+    let differences = ids
+        .windows(2)
+        .map(|pair| pair[1].wrapping_sub(pair[0]))
+        .collect::<Vec<u16>>();
+    if differences.iter().any(|difference| *difference >= 20000) {
+        return Some("RD".to_string());
+    }
+    if differences
+        .iter()
+        .all(|difference| difference % 256 == 0 && *difference <= 5120)
+    {
+        return Some("BI".to_string());
+    }
+    if differences.iter().all(|difference| *difference < 10) {
+        return Some("I".to_string());
+    }
+    if differences.iter().all(|difference| *difference < 1000) {
+        return Some("RI".to_string());
+    }
+    None
+}
+
+fn udp_test(capture: &ProbeCapture) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::from([("R".to_string(), "N".to_string())]);
+    for packet in capture.icmp.iter() {
+        let ip = match Ipv4Packet::new(packet) {
+            None => continue,
+            Some(ip) => ip,
+        };
+        let unreachable = match DestinationUnreachablePacket::new(ip.payload()) {
+            Some(data) if data.get_icmp_type() == IcmpTypes::DestinationUnreachable => data,
+            _ => continue,
+        };
+        let original = match Ipv4Packet::new(unreachable.payload()) {
+            Some(data) if data.get_next_level_protocol() == IpNextHeaderProtocols::Udp => data,
+            _ => continue,
+        };
+        let header_length = original.get_header_length() as usize * 4;
+        let udp = match unreachable
+            .payload()
+            .get(header_length..)
+            .and_then(UdpPacket::new)
+        {
+            Some(udp) if udp.get_source() == capture.udp_source_port => udp,
+            _ => continue,
+        };
+        let unused = ip
+            .payload()
+            .get(4..8)
+            .map(|data| u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+            .unwrap_or_default();
+        let expected_length = (20 + 8 + U1_PAYLOAD_SIZE) as u16;
+        let returned_length = original.get_total_length();
+        let returned_id = original.get_identification();
+        let returned_checksum = original.get_checksum();
+        let returned_udp_checksum = udp.get_checksum();
+        attributes.insert("R".to_string(), "Y".to_string());
+        attributes.insert(
+            "DF".to_string(),
+            yes_no(ip.get_flags() & Ipv4Flags::DontFragment != 0),
+        );
+        ttl_attributes(&mut attributes, &ip);
+        attributes.insert("IPL".to_string(), format!("{:X}", ip.get_total_length()));
+        attributes.insert("UN".to_string(), format!("{:X}", unused));
+        attributes.insert(
+            "RIPL".to_string(),
+            if returned_length == expected_length {
+                "G".to_string()
+            } else {
+                format!("{:X}", returned_length)
+            },
+        );
+        attributes.insert(
+            "RID".to_string(),
+            if returned_id == U1_IP_ID {
+                "G".to_string()
+            } else {
+                format!("{:X}", returned_id)
+            },
+        );
+        let ripck = if returned_checksum == capture.udp_ip_checksum {
+            "G"
+        } else if returned_checksum == 0 {
+            "Z"
+        } else {
+            "I"
+        };
+        attributes.insert("RIPCK".to_string(), ripck.to_string());
+        attributes.insert(
+            "RUCK".to_string(),
+            if returned_udp_checksum == capture.udp_checksum {
+                "G".to_string()
+            } else {
+                format!("{:X}", returned_udp_checksum)
+            },
+        );
+        let rud = if udp.payload().iter().all(|byte| *byte == b'C') {
+            "G"
+        } else {
+            "I"
+        };
+        attributes.insert("RUD".to_string(), rud.to_string());
+        break;
+    }
+    attributes
+}
+
+fn echo_test(capture: &ProbeCapture) -> BTreeMap<String, String> {
+    let mut replies: [Option<(bool, u8, u8)>; 2] = [None, None];
+    for packet in capture.icmp.iter() {
+        let ip = match Ipv4Packet::new(packet) {
+            None => continue,
+            Some(ip) => ip,
+        };
+        let icmp = match IcmpPacket::new(ip.payload()) {
+            Some(icmp) if icmp.get_icmp_type() == IcmpTypes::EchoReply => icmp,
+            _ => continue,
+        };
+        let reply = match EchoReplyPacket::new(icmp.packet()) {
+            None => continue,
+            Some(reply) => reply,
+        };
+        let index = reply.get_sequence_number().wrapping_sub(IE_SEQUENCE) as usize;
+        if index < ECHO_PROBES.len() && reply.get_identifier() == capture.icmp_ident {
+            let dont_fragment = ip.get_flags() & Ipv4Flags::DontFragment != 0;
+            replies[index] = Some((dont_fragment, icmp.get_icmp_code().0, ip.get_ttl()));
+        }
+    }
+    let (first, second) = match replies {
+        [Some(first), Some(second)] => (first, second),
+        _ => return BTreeMap::from([("R".to_string(), "N".to_string())]),
+    };
+    let dfi = match (first.0, second.0) {
+        (false, false) => "N",
+        (true, false) => "S",
+        (true, true) => "Y",
+        (false, true) => "O",
+    };
+    let cd = match (first.1, second.1) {
+        (0, 0) => "Z".to_string(),
+        (9, 0) => "S".to_string(),
+        (a, b) if a == b => format!("{:X}", a),
+        _ => "O".to_string(),
+    };
+    let guess = format!("{:X}", initial_ttl(first.2));
+    BTreeMap::from([
+        ("R".to_string(), "Y".to_string()),
+        ("DFI".to_string(), dfi.to_string()),
+        ("T".to_string(), guess.clone()),
+        ("TG".to_string(), guess),
+        ("CD".to_string(), cd),
+    ])
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+pub mod active_test {
+    use crate::os::active::{expression_matches, ip_id_sequence, OsDatabase};
+    use std::collections::BTreeMap;
+
+    const NMAP_DB: &str = "\
+# sample
+MatchPoints
+T1(R=100%DF=20%F=30)
+IE(R=50%CD=100)
+
+Fingerprint Linux 5.0 - 5.14
+Class Linux | Linux | 5.X | general purpose
+CPE cpe:/o:linux:linux_kernel:5 auto
+T1(R=Y%DF=Y%F=AS)
+IE(R=Y%CD=S)
+
+Fingerprint Microsoft Windows 10
+Class Microsoft | Windows | 10 | general purpose
+CPE cpe:/o:microsoft:windows_10
+T1(R=Y%DF=Y%F=AS)
+IE(R=Y%CD=Z)
+";
+
+    #[test]
+    fn expression_matches_test() {
+        assert!(expression_matches("M5B4ST11NW7|M5B4ST11NW6", "M5B4ST11NW6"));
+        assert!(expression_matches("FB-105", "100"));
+        assert!(!expression_matches("FB-105", "106"));
+        assert!(expression_matches(">A", "B"));
+        assert!(expression_matches("<A", "9"));
+        assert!(expression_matches("", ""));
+        assert!(!expression_matches("Z", "RD"));
+    }
+
+    #[test]
+    fn ip_id_sequence_test() {
+        assert_eq!(ip_id_sequence(&[0, 0, 0]).unwrap(), "Z");
+        assert_eq!(ip_id_sequence(&[10, 11, 13]).unwrap(), "I");
+        assert_eq!(ip_id_sequence(&[256, 512, 1024]).unwrap(), "BI");
+        assert_eq!(ip_id_sequence(&[100, 40000, 2000]).unwrap(), "RD");
+    }
+
+    #[test]
+    fn nmap_db_match_test() {
+        let database = OsDatabase::from_nmap_db(NMAP_DB);
+        assert_eq!(database.signatures.len(), 2);
+        assert_eq!(database.match_points["IE"]["CD"], "100");
+        let linux = &database.signatures[0];
+        assert_eq!(linux.classes[0].generation.as_deref(), Some("5.X"));
+        assert_eq!(linux.cpe, vec!["cpe:/o:linux:linux_kernel:5"]);
+
+        let mut fingerprint = BTreeMap::new();
+        fingerprint.insert(
+            "T1".to_string(),
+            BTreeMap::from([
+                ("R".to_string(), "Y".to_string()),
+                ("DF".to_string(), "Y".to_string()),
+                ("F".to_string(), "AS".to_string()),
+            ]),
+        );
+        fingerprint.insert(
+            "IE".to_string(),
+            BTreeMap::from([
+                ("R".to_string(), "Y".to_string()),
+                ("CD".to_string(), "Z".to_string()),
+            ]),
+        );
+        let result = database.matches(&fingerprint).unwrap();
+        assert_eq!(result.family, "Windows");
+        assert_eq!(result.confidence, 100);
+        assert_eq!(result.device_type.as_deref(), Some("general purpose"));
+    }
+}
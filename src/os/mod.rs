@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+pub mod active;
+pub mod passive;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OsGuess {
+    pub family: String,
+    pub name: Option<String>,
+    /// match confidence between 0 and 100.
+    pub confidence: u8,
+    pub device_type: Option<String>,
+    #[serde(default)]
+    pub cpe: Vec<String>,
+}
+
+impl std::fmt::Display for OsGuess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            None => write!(f, "{}|{}%", self.family, self.confidence),
+            Some(name) => write!(f, "{}|{}|{}%", self.family, name, self.confidence),
+        }
+    }
+}
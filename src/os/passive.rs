@@ -0,0 +1,287 @@
+use crate::os::OsGuess;
+use crate::utils::json;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const TTL_WEIGHT: u32 = 4;
+const WINDOW_WEIGHT: u32 = 2;
+const WINDOW_SCALE_WEIGHT: u32 = 1;
+const MSS_WEIGHT: u32 = 1;
+const OPTIONS_WEIGHT: u32 = 2;
+const MIN_CONFIDENCE: u8 = 50;
+
+/// A passive OS signature, `options` uses p0f style option kinds: `M` mss, `N` nop,
+/// `W` window scale, `S` sack permitted, `T` timestamps, `E` end of options.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PassiveSignature {
+    pub family: String,
+    pub name: Option<String>,
+    pub ttl: u8,
+    pub window: Option<Vec<u16>>,
+    pub window_scale: Option<Vec<u8>>,
+    pub mss: Option<Vec<u16>>,
+    pub options: Option<String>,
+}
+
+impl PassiveSignature {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Vec<Self> {
+        json::load_json_file(path, "os signature")
+    }
+
+    pub fn get_signatures() -> Vec<Self> {
+        Self::from_file("./fingerprint/os.json")
+    }
+}
+
+/// The TCP options seen on a SYN/ACK. `TCP_INFO` only exposes which options were
+/// negotiated, so the layout is unordered unless it was read from a raw packet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TcpOptionLayout {
+    pub kinds: Vec<char>,
+    pub ordered: bool,
+}
+
+impl TcpOptionLayout {
+    fn matches(&self, signature: &str) -> bool {
+        let expected = signature
+            .split(',')
+            .filter_map(|kind| kind.trim().chars().next())
+            .collect::<Vec<char>>();
+        if self.ordered {
+            return expected == self.kinds;
+        }
+        // padding options carry no information without their position.
+        let mut expected = expected
+            .into_iter()
+            .filter(|kind| *kind != 'N' && *kind != 'E')
+            .collect::<Vec<char>>();
+        let mut kinds = self.kinds.clone();
+        expected.sort_unstable();
+        expected.dedup();
+        kinds.sort_unstable();
+        kinds.dedup();
+        expected == kinds
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PassiveObservation {
+    pub ttl: Option<u8>,
+    pub window: Option<u16>,
+    pub window_scale: Option<u8>,
+    pub mss: Option<u16>,
+    pub options: Option<TcpOptionLayout>,
+}
+
+impl PassiveObservation {
+    /// Fills the fields missing in `self` with the ones seen in `other`.
+    pub fn merge(&mut self, other: &PassiveObservation) {
+        self.ttl = self.ttl.or(other.ttl);
+        self.window = self.window.or(other.window);
+        self.window_scale = self.window_scale.or(other.window_scale);
+        self.mss = self.mss.or(other.mss);
+        if self.options.is_none() {
+            self.options = other.options.clone();
+        }
+    }
+}
+
+/// Rounds an observed TTL up to the closest common initial TTL.
+pub fn initial_ttl(ttl: u8) -> u8 {
+    match ttl {
+        0..=32 => 32,
+        33..=64 => 64,
+        65..=128 => 128,
+        _ => 255,
+    }
+}
+
+fn score_field<T: PartialEq>(
+    observed: Option<T>,
+    expected: &Option<Vec<T>>,
+    weight: u32,
+    score: &mut u32,
+    total: &mut u32,
+) {
+    let observed = match observed {
+        None => return,
+        Some(data) => data,
+    };
+    *total += weight;
+    match expected {
+        // a signature without the field neither confirms nor rules out the observation.
+        None => *score += weight / 2,
+        Some(values) if values.contains(&observed) => *score += weight,
+        Some(_) => {}
+    }
+}
+
+fn confidence(observation: &PassiveObservation, signature: &PassiveSignature) -> u8 {
+    let mut score = 0;
+    let mut total = 0;
+    if let Some(ttl) = observation.ttl {
+        total += TTL_WEIGHT;
+        if initial_ttl(ttl) == signature.ttl {
+            score += TTL_WEIGHT;
+        }
+    }
+    score_field(
+        observation.window,
+        &signature.window,
+        WINDOW_WEIGHT,
+        &mut score,
+        &mut total,
+    );
+    score_field(
+        observation.window_scale,
+        &signature.window_scale,
+        WINDOW_SCALE_WEIGHT,
+        &mut score,
+        &mut total,
+    );
+    score_field(
+        observation.mss,
+        &signature.mss,
+        MSS_WEIGHT,
+        &mut score,
+        &mut total,
+    );
+    if let Some(options) = &observation.options {
+        total += OPTIONS_WEIGHT;
+        match &signature.options {
+            None => score += OPTIONS_WEIGHT / 2,
+            Some(expected) if options.matches(expected) => score += OPTIONS_WEIGHT,
+            Some(_) => {}
+        }
+    }
+    if total == 0 {
+        return 0;
+    }
+    (score * 100 / total) as u8
+}
+
+/// Returns the best matching signature, or `None` when nothing is confident enough.
+pub fn guess(observation: &PassiveObservation, signatures: &[PassiveSignature]) -> Option<OsGuess> {
+    signatures
+        .iter()
+        .map(|signature| (confidence(observation, signature), signature))
+        .filter(|(confidence, _)| *confidence >= MIN_CONFIDENCE)
+        .max_by_key(|(confidence, _)| *confidence)
+        .map(|(confidence, signature)| OsGuess {
+            family: signature.family.clone(),
+            name: signature.name.clone(),
+            confidence,
+            device_type: None,
+            cpe: vec![],
+        })
+}
+
+/// Reads the peer's SYN/ACK parameters negotiated on a connected socket from `TCP_INFO`.
+/// Must be called before any data is exchanged, as the send window moves afterwards.
+#[cfg(target_os = "linux")]
+pub fn tcp_info_observation<S: std::os::fd::AsRawFd>(stream: &S) -> Option<PassiveObservation> {
+    const TCPI_OPT_TIMESTAMPS: u8 = 1;
+    const TCPI_OPT_SACK: u8 = 2;
+    const TCPI_OPT_WSCALE: u8 = 4;
+    // timestamps take 10 bytes plus 2 bytes of padding out of every segment.
+    const TIMESTAMPS_LENGTH: u32 = 12;
+
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut length = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let mut kinds = vec!['M'];
+    let mut mss = info.tcpi_snd_mss;
+    if info.tcpi_options & TCPI_OPT_SACK != 0 {
+        kinds.push('S');
+    }
+    if info.tcpi_options & TCPI_OPT_TIMESTAMPS != 0 {
+        kinds.push('T');
+        mss += TIMESTAMPS_LENGTH;
+    }
+    let window_scale = if info.tcpi_options & TCPI_OPT_WSCALE != 0 {
+        kinds.push('W');
+        Some(info.tcpi_snd_rcv_wscale & 0x0f)
+    } else {
+        None
+    };
+    // kernels before 5.19 don't report the send window and leave it zeroed.
+    let window = match info.tcpi_snd_wnd {
+        0 => None,
+        window => u16::try_from(window).ok(),
+    };
+    Some(PassiveObservation {
+        ttl: None,
+        window,
+        window_scale,
+        mss: u16::try_from(mss).ok(),
+        options: Some(TcpOptionLayout {
+            kinds,
+            ordered: false,
+        }),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn tcp_info_observation<S>(_stream: &S) -> Option<PassiveObservation> {
+    None
+}
+
+#[cfg(test)]
+pub mod passive_test {
+    use crate::os::passive::TcpOptionLayout;
+    use crate::os::passive::{guess, initial_ttl, PassiveObservation, PassiveSignature};
+
+    fn signatures() -> Vec<PassiveSignature> {
+        serde_json::from_str(include_str!("../../fingerprint/os.json")).unwrap()
+    }
+
+    #[test]
+    fn initial_ttl_test() {
+        assert_eq!(initial_ttl(52), 64);
+        assert_eq!(initial_ttl(117), 128);
+        assert_eq!(initial_ttl(240), 255);
+    }
+
+    #[test]
+    fn guess_test() {
+        let linux = PassiveObservation {
+            ttl: Some(57),
+            window: Some(65160),
+            window_scale: Some(7),
+            mss: Some(1460),
+            options: Some(TcpOptionLayout {
+                kinds: vec!['M', 'S', 'T', 'W'],
+                ordered: false,
+            }),
+        };
+        let result = guess(&linux, &signatures()).unwrap();
+        assert_eq!(result.family, "Linux");
+
+        let windows = PassiveObservation {
+            ttl: Some(120),
+            window: Some(64240),
+            window_scale: Some(8),
+            mss: Some(1460),
+            options: Some(TcpOptionLayout {
+                kinds: vec!['M', 'N', 'W', 'N', 'N', 'S'],
+                ordered: true,
+            }),
+        };
+        let result = guess(&windows, &signatures()).unwrap();
+        assert_eq!(result.family, "Windows");
+        assert_eq!(result.confidence, 100);
+
+        assert!(guess(&PassiveObservation::default(), &signatures()).is_none());
+    }
+}
@@ -0,0 +1,494 @@
+use crate::report::{protocol, ScanResult};
+use crate::scanner::plugin::ScannerReply;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+
+/// What changed on the network between two scans.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    HostUp {
+        ip_addr: IpAddr,
+    },
+    HostDown {
+        ip_addr: IpAddr,
+    },
+    PortOpened {
+        ip_addr: IpAddr,
+        protocol: String,
+        port: u16,
+        service: String,
+    },
+    PortClosed {
+        ip_addr: IpAddr,
+        protocol: String,
+        port: u16,
+        service: String,
+    },
+    ServiceChanged {
+        ip_addr: IpAddr,
+        protocol: String,
+        port: u16,
+        before: String,
+        after: String,
+    },
+    /// the leaf certificate was replaced, or TLS was turned on or off.
+    CertificateChanged {
+        ip_addr: IpAddr,
+        protocol: String,
+        port: u16,
+        before: String,
+        after: String,
+    },
+}
+
+impl Change {
+    pub fn kind(&self) -> &str {
+        match self {
+            Change::HostUp { .. } => "host up",
+            Change::HostDown { .. } => "host down",
+            Change::PortOpened { .. } => "port opened",
+            Change::PortClosed { .. } => "port closed",
+            Change::ServiceChanged { .. } => "service changed",
+            Change::CertificateChanged { .. } => "certificate changed",
+        }
+    }
+
+    /// The host, `protocol/port` and what the port held before and after the change.
+    fn columns(&self) -> (IpAddr, String, String, String) {
+        match self {
+            Change::HostUp { ip_addr } | Change::HostDown { ip_addr } => {
+                (*ip_addr, String::new(), String::new(), String::new())
+            }
+            Change::PortOpened {
+                ip_addr,
+                protocol,
+                port,
+                service,
+            } => (
+                *ip_addr,
+                format!("{}/{}", protocol, port),
+                String::new(),
+                service.clone(),
+            ),
+            Change::PortClosed {
+                ip_addr,
+                protocol,
+                port,
+                service,
+            } => (
+                *ip_addr,
+                format!("{}/{}", protocol, port),
+                service.clone(),
+                String::new(),
+            ),
+            Change::ServiceChanged {
+                ip_addr,
+                protocol,
+                port,
+                before,
+                after,
+            }
+            | Change::CertificateChanged {
+                ip_addr,
+                protocol,
+                port,
+                before,
+                after,
+            } => (
+                *ip_addr,
+                format!("{}/{}", protocol, port),
+                before.clone(),
+                after.clone(),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::HostUp { ip_addr } => write!(f, "+ {} [HOST|up]", ip_addr),
+            Change::HostDown { ip_addr } => write!(f, "- {} [HOST|down]", ip_addr),
+            Change::PortOpened {
+                ip_addr,
+                protocol,
+                port,
+                service,
+            } => write!(f, "+ {}:{} [{}|{}]", ip_addr, port, protocol, service),
+            Change::PortClosed {
+                ip_addr,
+                protocol,
+                port,
+                service,
+            } => write!(f, "- {}:{} [{}|{}]", ip_addr, port, protocol, service),
+            Change::ServiceChanged {
+                ip_addr,
+                protocol,
+                port,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ {}:{} [{}|{} -> {}]",
+                ip_addr, port, protocol, before, after
+            ),
+            Change::CertificateChanged {
+                ip_addr,
+                port,
+                before,
+                after,
+                ..
+            } => write!(f, "~ {}:{} [CERT|{} -> {}]", ip_addr, port, before, after),
+        }
+    }
+}
+
+/// The changes between two scans, with the time they started at.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanDiff {
+    pub before: String,
+    pub after: String,
+    pub changes: Vec<Change>,
+}
+
+impl ScanDiff {
+    /// The hosts that came up or went down, then the ports that opened, closed or changed
+    /// service or certificate, ordered by host and port.
+    pub fn new(before: &ScanResult, after: &ScanResult) -> ScanDiff {
+        ScanDiff {
+            before: before.started.clone(),
+            after: after.started.clone(),
+            changes: diff(before, after),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// `2 host up, 1 port opened`, in the order the kinds first appear.
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<(&str, usize)> = vec![];
+        for change in self.changes.iter() {
+            match counts.iter_mut().find(|(kind, _)| *kind == change.kind()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((change.kind(), 1)),
+            }
+        }
+        if counts.is_empty() {
+            return "no changes".to_string();
+        }
+        counts
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    Markdown,
+}
+
+pub fn render(diff: &ScanDiff, format: DiffFormat) -> String {
+    match format {
+        DiffFormat::Text => {
+            let mut lines = vec![format!(
+                "{} -> {}: {}",
+                diff.before,
+                diff.after,
+                diff.summary()
+            )];
+            lines.extend(diff.changes.iter().map(|change| change.to_string()));
+            lines.join("\n")
+        }
+        DiffFormat::Json => serde_json::to_string_pretty(diff).unwrap_or_default(),
+        DiffFormat::Markdown => {
+            let mut lines = vec![
+                "# Scan diff".to_string(),
+                String::new(),
+                format!(
+                    "From the scan started {} to the one started {}: {}.",
+                    diff.before,
+                    diff.after,
+                    diff.summary()
+                ),
+            ];
+            if !diff.is_empty() {
+                lines.push(String::new());
+                lines.push("| Change | Host | Port | Before | After |".to_string());
+                lines.push("| --- | --- | --- | --- | --- |".to_string());
+            }
+            for change in diff.changes.iter() {
+                let (ip_addr, port, before, after) = change.columns();
+                lines.push(format!(
+                    "| {} | {} | {} | {} | {} |",
+                    change.kind(),
+                    ip_addr,
+                    port,
+                    before.replace('|', "\\|"),
+                    after.replace('|', "\\|")
+                ));
+            }
+            lines.join("\n")
+        }
+    }
+}
+
+/// `name|version`, `*` standing for an unknown version.
+fn service(reply: &ScannerReply) -> String {
+    format!(
+        "{}|{}",
+        reply.name(),
+        reply.version().unwrap_or_else(|| "*".to_string())
+    )
+}
+
+/// `subject|not after|fingerprint prefix` of the leaf certificate, `none` without TLS.
+fn certificate(reply: &ScannerReply) -> String {
+    match reply.tls().and_then(|tls_info| tls_info.leaf()) {
+        None => "none".to_string(),
+        Some(leaf) => format!(
+            "{}|{}|{}",
+            leaf.subject,
+            leaf.not_after,
+            leaf.sha256_fingerprint
+                .get(..16)
+                .unwrap_or(&leaf.sha256_fingerprint)
+        ),
+    }
+}
+
+fn certificate_fingerprint(reply: &ScannerReply) -> Option<&str> {
+    reply
+        .tls()
+        .and_then(|tls_info| tls_info.leaf())
+        .map(|leaf| leaf.sha256_fingerprint.as_str())
+}
+
+/// The open ports of a scan keyed by host, protocol and port.
+fn ports(result: &ScanResult) -> BTreeMap<(IpAddr, String, u16), &ScannerReply> {
+    result
+        .hosts
+        .iter()
+        .flat_map(|host| {
+            host.ports().iter().map(move |reply| {
+                (
+                    (
+                        host.ip_addr(),
+                        protocol(reply),
+                        reply.port().unwrap_or_default(),
+                    ),
+                    reply,
+                )
+            })
+        })
+        .collect()
+}
+
+pub fn diff(before: &ScanResult, after: &ScanResult) -> Vec<Change> {
+    let mut changes = vec![];
+    let hosts_before = before
+        .hosts
+        .iter()
+        .map(|host| host.ip_addr())
+        .collect::<BTreeSet<_>>();
+    let hosts_after = after
+        .hosts
+        .iter()
+        .map(|host| host.ip_addr())
+        .collect::<BTreeSet<_>>();
+    for ip_addr in hosts_after.iter() {
+        if !hosts_before.contains(ip_addr) {
+            changes.push(Change::HostUp { ip_addr: *ip_addr });
+        }
+    }
+    for ip_addr in hosts_before.iter() {
+        if !hosts_after.contains(ip_addr) {
+            changes.push(Change::HostDown { ip_addr: *ip_addr });
+        }
+    }
+
+    let ports_before = ports(before);
+    let ports_after = ports(after);
+    for ((ip_addr, protocol, port), reply) in ports_after.iter() {
+        let (ip_addr, port) = (*ip_addr, *port);
+        let previous = match ports_before.get(&(ip_addr, protocol.clone(), port)) {
+            Some(previous) => previous,
+            None => {
+                changes.push(Change::PortOpened {
+                    ip_addr,
+                    protocol: protocol.clone(),
+                    port,
+                    service: service(reply),
+                });
+                continue;
+            }
+        };
+        if service(previous) != service(reply) {
+            changes.push(Change::ServiceChanged {
+                ip_addr,
+                protocol: protocol.clone(),
+                port,
+                before: service(previous),
+                after: service(reply),
+            });
+        }
+        if certificate_fingerprint(previous) != certificate_fingerprint(reply) {
+            changes.push(Change::CertificateChanged {
+                ip_addr,
+                protocol: protocol.clone(),
+                port,
+                before: certificate(previous),
+                after: certificate(reply),
+            });
+        }
+    }
+    for ((ip_addr, protocol, port), reply) in ports_before.iter() {
+        if !ports_after.contains_key(&(*ip_addr, protocol.clone(), *port)) {
+            changes.push(Change::PortClosed {
+                ip_addr: *ip_addr,
+                protocol: protocol.clone(),
+                port: *port,
+                service: service(reply),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+pub mod diff_test {
+    use crate::report::diff::{diff, render, Change, DiffFormat, ScanDiff};
+    use crate::report::{self, ReportFormat, ScanResult};
+    use crate::scanner::plugin::{HostReply, ScannerReply, TransportLayerProtocol};
+    use std::net::IpAddr;
+
+    fn host(ip_addr: &str, ports: &[(u16, &str, Option<&str>)]) -> HostReply {
+        let ip_addr: IpAddr = ip_addr.parse().unwrap();
+        let mut host = HostReply::new(ip_addr);
+        for (port, name, version) in ports {
+            let mut reply = ScannerReply::new();
+            reply.set_ip_addr(Some(ip_addr));
+            reply.set_port(Some(*port));
+            reply.set_transport_layer_protocol(Some(TransportLayerProtocol::TCP));
+            reply.set_name(name.to_string());
+            if let Some(version) = version {
+                reply.set_version(version.to_string());
+            }
+            host.add_port(reply);
+        }
+        host
+    }
+
+    #[test]
+    fn scan_diff_test() {
+        let before = ScanResult {
+            started: "2026-10-18T00:00:00Z".to_string(),
+            finished: "2026-10-18T00:01:00Z".to_string(),
+            hosts: vec![
+                host(
+                    "10.0.0.1",
+                    &[(22, "ssh", Some("OpenSSH_9.3")), (80, "http", None)],
+                ),
+                host("10.0.0.2", &[(443, "ssl/http", None)]),
+            ],
+        };
+        let after = ScanResult {
+            started: "2026-10-19T00:00:00Z".to_string(),
+            finished: "2026-10-19T00:01:00Z".to_string(),
+            hosts: vec![
+                host(
+                    "10.0.0.1",
+                    &[(22, "ssh", Some("OpenSSH_9.6")), (3306, "mysql", None)],
+                ),
+                host("10.0.0.3", &[(23, "telnet", None)]),
+            ],
+        };
+        let changes = diff(&before, &after)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                "+ 10.0.0.3 [HOST|up]",
+                "- 10.0.0.2 [HOST|down]",
+                "~ 10.0.0.1:22 [TCP|ssh|OpenSSH_9.3 -> ssh|OpenSSH_9.6]",
+                "+ 10.0.0.1:3306 [TCP|mysql|*]",
+                "+ 10.0.0.3:23 [TCP|telnet|*]",
+                "- 10.0.0.1:80 [TCP|http|*]",
+                "- 10.0.0.2:443 [TCP|ssl/http|*]",
+            ]
+        );
+        assert!(diff(&after, &after).is_empty());
+
+        let mut renewed = after.clone();
+        let mut tls_before = after.clone();
+        for (result, fingerprint) in [(&mut tls_before, "aa"), (&mut renewed, "bb")] {
+            let mut reply = ScannerReply::new();
+            reply.set_port(Some(8443));
+            reply.set_transport_layer_protocol(Some(TransportLayerProtocol::TCP));
+            reply.set_name("ssl/http".to_string());
+            reply.set_tls(Some(
+                serde_json::from_value(serde_json::json!({
+                    "version": "TLSv1.3",
+                    "cipher_suite": "TLS13_AES_256_GCM_SHA384",
+                    "certificates": [{
+                        "subject": "CN=example.com",
+                        "issuer": "CN=ca",
+                        "subject_alt_names": [],
+                        "serial": "01",
+                        "not_before": "2026-01-01",
+                        "not_after": "2027-01-01",
+                        "expired": false,
+                        "key_type": "RSA",
+                        "key_size": 2048,
+                        "signature_algorithm": "sha256WithRSAEncryption",
+                        "sha256_fingerprint": fingerprint.repeat(32),
+                    }],
+                }))
+                .unwrap(),
+            ));
+            result.hosts[0].add_port(reply);
+        }
+        let scan_diff = ScanDiff::new(&tls_before, &renewed);
+        assert_eq!(
+            scan_diff.changes,
+            vec![Change::CertificateChanged {
+                ip_addr: "10.0.0.1".parse().unwrap(),
+                protocol: "TCP".to_string(),
+                port: 8443,
+                before: format!("CN=example.com|2027-01-01|{}", "aa".repeat(8)),
+                after: format!("CN=example.com|2027-01-01|{}", "bb".repeat(8)),
+            }]
+        );
+        assert_eq!(scan_diff.summary(), "1 certificate changed");
+        let json = render(&scan_diff, DiffFormat::Json);
+        assert!(json.contains("\"change\": \"certificate_changed\""));
+        assert_eq!(serde_json::from_str::<ScanDiff>(&json).unwrap(), scan_diff);
+        assert!(render(&scan_diff, DiffFormat::Markdown)
+            .contains("| certificate changed | 10.0.0.1 | TCP/8443 | CN=example.com\\|"));
+        let text = render(&ScanDiff::new(&after, &after), DiffFormat::Text);
+        assert_eq!(
+            text,
+            "2026-10-19T00:00:00Z -> 2026-10-19T00:00:00Z: no changes"
+        );
+
+        let json = report::render(&after, ReportFormat::Json);
+        assert_eq!(serde_json::from_str::<ScanResult>(&json).unwrap(), after);
+        let csv = report::render(&after, ReportFormat::Csv);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("10.0.0.1,TCP,22,ssh,OpenSSH_9.6,,false,")
+        );
+        assert!(
+            report::render(&after, ReportFormat::Markdown).contains("| 23 | TCP | telnet |  |  |")
+        );
+    }
+}
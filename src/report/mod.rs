@@ -0,0 +1,170 @@
+use crate::scanner::plugin::{HostReply, ScannerReply};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub mod diff;
+
+/// A finished scan as written by `scan --output`, read back by the report and diff commands.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanResult {
+    /// RFC 3339 time the scan started and finished at.
+    pub started: String,
+    pub finished: String,
+    pub hosts: Vec<HostReply>,
+}
+
+impl ScanResult {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ScanResult, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|error| format!("open scan result {} error: {}", path.display(), error))?;
+        serde_json::from_str(&content)
+            .map_err(|error| format!("parse scan result {} error: {}", path.display(), error))
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, content)
+            .map_err(|error| format!("write scan result {} error: {}", path.display(), error))
+    }
+}
+
+/// The current time in RFC 3339.
+pub fn now() -> String {
+    time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Text,
+    Markdown,
+}
+
+pub fn render(result: &ScanResult, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(result).unwrap_or_default(),
+        ReportFormat::Csv => render_csv(result),
+        ReportFormat::Text => render_text(result),
+        ReportFormat::Markdown => render_markdown(result),
+    }
+}
+
+/// `TCP` or `UDP`.
+pub(crate) fn protocol(reply: &ScannerReply) -> String {
+    reply
+        .transport_layer_protocol()
+        .map(|protocol| format!("{:?}", protocol))
+        .unwrap_or_default()
+}
+
+fn render_csv(result: &ScanResult) -> String {
+    let mut lines = vec!["ip,protocol,port,service,version,cpe,tls,cves".to_string()];
+    for host in result.hosts.iter() {
+        for reply in host.ports() {
+            let fields = [
+                host.ip_addr().to_string(),
+                protocol(reply),
+                reply.port().unwrap_or_default().to_string(),
+                reply.name(),
+                reply.version().unwrap_or_default(),
+                reply.cpe().unwrap_or_default(),
+                reply.tls().is_some().to_string(),
+                reply
+                    .vulnerabilities()
+                    .iter()
+                    .map(|vulnerability| vulnerability.id.clone())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ];
+            lines.push(
+                fields
+                    .iter()
+                    .map(|field| csv_field(field))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+    }
+    lines.join("\n")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The `ip:port [TAG|...]` lines of the scan log.
+fn render_text(result: &ScanResult) -> String {
+    let mut lines = vec![];
+    for host in result.hosts.iter() {
+        if let Some(os_guess) = host.os_guess() {
+            lines.push(format!("{} [OS|{}]", host.ip_addr(), os_guess));
+        }
+        for reply in host.ports() {
+            let address = format!("{}:{}", host.ip_addr(), reply.port().unwrap_or_default());
+            lines.push(format!(
+                "{} [{}|{}|{}]",
+                address,
+                protocol(reply),
+                reply.name(),
+                reply.version().unwrap_or_else(|| "*".to_string())
+            ));
+            if let Some(tls_info) = reply.tls() {
+                lines.push(format!("{} [TLS|{}]", address, tls_info));
+            }
+            for vulnerability in reply.vulnerabilities() {
+                lines.push(format!("{} [CVE|{}]", address, vulnerability));
+            }
+            for finding in reply.credentials() {
+                lines.push(format!("{} [CRED|{}]", address, finding));
+            }
+            for finding in reply.scripts() {
+                lines.push(format!("{} [SCRIPT|{}]", address, finding));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+fn render_markdown(result: &ScanResult) -> String {
+    let mut lines = vec![
+        "# Scan report".to_string(),
+        String::new(),
+        format!("Started {}, finished {}.", result.started, result.finished),
+    ];
+    for host in result.hosts.iter() {
+        lines.push(String::new());
+        lines.push(format!("## {}", host.ip_addr()));
+        lines.push(String::new());
+        if let Some(os_guess) = host.os_guess() {
+            lines.push(format!("OS: {}", os_guess));
+            lines.push(String::new());
+        }
+        lines.push("| Port | Protocol | Service | Version | CVEs |".to_string());
+        lines.push("| --- | --- | --- | --- | --- |".to_string());
+        for reply in host.ports() {
+            lines.push(format!(
+                "| {} | {} | {} | {} | {} |",
+                reply.port().unwrap_or_default(),
+                protocol(reply),
+                reply.name(),
+                reply.version().unwrap_or_default().replace('|', "\\|"),
+                reply
+                    .vulnerabilities()
+                    .iter()
+                    .map(|vulnerability| vulnerability.id.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    lines.join("\n")
+}
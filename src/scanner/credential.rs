@@ -0,0 +1,485 @@
+use crate::scanner::http;
+use crate::utils::json;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::net::TcpStream;
+
+const CHECK_TIMEOUT: Duration = Duration::from_millis(5000);
+/// FTP replies longer than this many lines are not worth reading.
+const MAX_FTP_REPLY_LINES: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Credential {
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+/// How the credentials of a check are tried.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckProtocol {
+    Ftp,
+    Redis,
+    Memcached,
+    Elasticsearch,
+    HttpBasic,
+    /// matched against the communities the SNMP probe got answers for.
+    Snmp,
+}
+
+/// One entry of the credentials file. A check runs on ports identified as one of
+/// `services`, unidentified ports fall back to `ports`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CredentialCheck {
+    pub name: String,
+    pub protocol: CheckProtocol,
+    #[serde(default)]
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// request paths, only used by http checks.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    pub credentials: Vec<Credential>,
+}
+
+impl CredentialCheck {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Vec<Self> {
+        json::load_json_file(path, "credentials")
+    }
+
+    fn applies(&self, name: &str, port: u16) -> bool {
+        match name {
+            "*" => self.ports.contains(&port),
+            name => self.services.iter().any(|service| service == name),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CredentialFinding {
+    pub check: String,
+    pub username: String,
+    pub password: String,
+    pub detail: Option<String>,
+}
+
+impl std::fmt::Display for CredentialFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let login = match (self.username.is_empty(), self.password.is_empty()) {
+            (true, true) => "anonymous".to_string(),
+            (true, false) => self.password.clone(),
+            _ => format!("{}:{}", self.username, self.password),
+        };
+        write!(
+            f,
+            "{}|{}|{}",
+            self.check,
+            login,
+            self.detail.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// Counts the logins tried against every host, shared by all the port scans.
+#[derive(Debug)]
+struct AttemptLimiter {
+    limit: usize,
+    attempts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl AttemptLimiter {
+    fn acquire(&self, ip_addr: IpAddr) -> bool {
+        let mut attempts = self.attempts.lock().unwrap();
+        let count = attempts.entry(ip_addr).or_insert(0);
+        if *count >= self.limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// Runs the checks of the credentials file against identified services.
+#[derive(Debug)]
+pub struct CredentialChecker {
+    checks: Vec<CredentialCheck>,
+    limiter: AttemptLimiter,
+}
+
+impl CredentialChecker {
+    /// `max_attempts` caps the logins tried against a single host over the whole scan.
+    pub fn new(checks: Vec<CredentialCheck>, max_attempts: usize) -> CredentialChecker {
+        CredentialChecker {
+            checks,
+            limiter: AttemptLimiter {
+                limit: max_attempts,
+                attempts: Mutex::new(HashMap::new()),
+            },
+        }
+    }
+
+    /// Communities of the SNMP checks, probed along with the community wordlist.
+    pub fn snmp_communities(&self) -> Vec<String> {
+        self.checks
+            .iter()
+            .filter(|check| check.protocol == CheckProtocol::Snmp)
+            .flat_map(|check| check.credentials.iter())
+            .map(|credential| credential.password.clone())
+            .collect()
+    }
+
+    /// Reports the default communities among the ones an SNMP agent accepted.
+    pub fn snmp_findings(&self, accepted: &[String]) -> Vec<CredentialFinding> {
+        let mut findings = vec![];
+        for check in self
+            .checks
+            .iter()
+            .filter(|check| check.protocol == CheckProtocol::Snmp)
+        {
+            for credential in check.credentials.iter() {
+                if accepted.contains(&credential.password) {
+                    findings.push(CredentialFinding {
+                        check: check.name.clone(),
+                        username: String::new(),
+                        password: credential.password.clone(),
+                        detail: None,
+                    });
+                }
+            }
+        }
+        findings
+    }
+
+    /// Tries the checks that apply to the service `name` found at `socket_addr`, a check
+    /// stops at its first successful login.
+    pub async fn run(
+        &self,
+        socket_addr: SocketAddr,
+        name: &str,
+        tls: bool,
+    ) -> Vec<CredentialFinding> {
+        let mut findings = vec![];
+        for check in self
+            .checks
+            .iter()
+            .filter(|check| check.applies(name, socket_addr.port()))
+        {
+            let finding = match check.protocol {
+                CheckProtocol::HttpBasic => self.run_http_basic(check, socket_addr, tls).await,
+                CheckProtocol::Elasticsearch => {
+                    self.run_elasticsearch(check, socket_addr, tls).await
+                }
+                CheckProtocol::Snmp => None,
+                protocol => {
+                    self.run_logins(check, protocol, socket_addr, tls, None)
+                        .await
+                }
+            };
+            findings.extend(finding);
+        }
+        findings
+    }
+
+    async fn run_logins(
+        &self,
+        check: &CredentialCheck,
+        protocol: CheckProtocol,
+        socket_addr: SocketAddr,
+        tls: bool,
+        path: Option<&str>,
+    ) -> Option<CredentialFinding> {
+        for credential in check.credentials.iter() {
+            if !self.limiter.acquire(socket_addr.ip()) {
+                tracing::debug!(
+                    "{} login attempt limit reached, skip {}",
+                    socket_addr.ip(),
+                    check.name
+                );
+                return None;
+            }
+            let login = async {
+                match protocol {
+                    CheckProtocol::Ftp => ftp_login(socket_addr, credential).await,
+                    CheckProtocol::Redis => redis_login(socket_addr, credential).await,
+                    CheckProtocol::Memcached => memcached_stats(socket_addr).await,
+                    CheckProtocol::Elasticsearch => {
+                        elasticsearch_login(socket_addr, tls, credential).await
+                    }
+                    CheckProtocol::HttpBasic => {
+                        http_basic_login(socket_addr, tls, path?, credential).await
+                    }
+                    CheckProtocol::Snmp => None,
+                }
+            };
+            if let Ok(Some(detail)) = tokio::time::timeout(CHECK_TIMEOUT, login).await {
+                return Some(CredentialFinding {
+                    check: check.name.clone(),
+                    username: credential.username.clone(),
+                    password: credential.password.clone(),
+                    detail: Some(detail).filter(|detail| !detail.is_empty()),
+                });
+            }
+        }
+        None
+    }
+
+    /// Looks at the index page first, an open cluster is reported without trying logins
+    /// and other web servers are left alone.
+    async fn run_elasticsearch(
+        &self,
+        check: &CredentialCheck,
+        socket_addr: SocketAddr,
+        tls: bool,
+    ) -> Option<CredentialFinding> {
+        let response = http::fetch(socket_addr, tls, "/", None).await.ok()?;
+        if let Some(detail) = elasticsearch_cluster(&response) {
+            return Some(CredentialFinding {
+                check: check.name.clone(),
+                username: String::new(),
+                password: String::new(),
+                detail: Some(detail),
+            });
+        }
+        let body = String::from_utf8_lossy(&response.body);
+        if response.status != 401 || !body.contains("security_exception") {
+            return None;
+        }
+        self.run_logins(check, CheckProtocol::Elasticsearch, socket_addr, tls, None)
+            .await
+    }
+
+    /// Only paths that answer 401 without credentials are tried.
+    async fn run_http_basic(
+        &self,
+        check: &CredentialCheck,
+        socket_addr: SocketAddr,
+        tls: bool,
+    ) -> Option<CredentialFinding> {
+        for path in check.paths.iter() {
+            match http::fetch(socket_addr, tls, path, None).await {
+                Ok(response) if response.status == 401 => {}
+                _ => continue,
+            }
+            let finding = self
+                .run_logins(
+                    check,
+                    CheckProtocol::HttpBasic,
+                    socket_addr,
+                    tls,
+                    Some(path),
+                )
+                .await;
+            if finding.is_some() {
+                return finding;
+            }
+        }
+        None
+    }
+}
+
+/// Reads one, possibly multi-line, FTP reply and returns its code and last line.
+fn parse_ftp_reply(lines: &[String]) -> Option<(u16, String)> {
+    let last = lines.last()?;
+    let code = last.get(..3)?.parse::<u16>().ok()?;
+    if last.as_bytes().get(3) == Some(&b'-') {
+        return None;
+    }
+    Some((code, last.get(4..).unwrap_or("").trim().to_string()))
+}
+
+async fn read_ftp_reply(
+    reader: &mut AsyncBufReader<tokio::net::tcp::OwnedReadHalf>,
+) -> Option<(u16, String)> {
+    let mut lines = vec![];
+    while lines.len() < MAX_FTP_REPLY_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+        lines.push(line);
+        if let Some(reply) = parse_ftp_reply(&lines) {
+            return Some(reply);
+        }
+    }
+    None
+}
+
+async fn ftp_login(socket_addr: SocketAddr, credential: &Credential) -> Option<String> {
+    let stream = TcpStream::connect(socket_addr).await.ok()?;
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = AsyncBufReader::new(read_half);
+    if read_ftp_reply(&mut reader).await?.0 != 220 {
+        return None;
+    }
+    write_half
+        .write_all(format!("USER {}\r\n", credential.username).as_bytes())
+        .await
+        .ok()?;
+    let (mut code, mut message) = read_ftp_reply(&mut reader).await?;
+    if code == 331 {
+        write_half
+            .write_all(format!("PASS {}\r\n", credential.password).as_bytes())
+            .await
+            .ok()?;
+        (code, message) = read_ftp_reply(&mut reader).await?;
+    }
+    let _ = write_half.write_all(b"QUIT\r\n").await;
+    (code == 230).then_some(message)
+}
+
+async fn read_some(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = vec![0u8; 8192];
+    let size = stream.read(&mut buf).await.ok()?;
+    Some(String::from_utf8_lossy(&buf[..size]).to_string())
+}
+
+async fn redis_login(socket_addr: SocketAddr, credential: &Credential) -> Option<String> {
+    let mut stream = TcpStream::connect(socket_addr).await.ok()?;
+    if !credential.password.is_empty() {
+        let command = match credential.username.is_empty() {
+            true => format!("AUTH {}\r\n", credential.password),
+            false => format!("AUTH {} {}\r\n", credential.username, credential.password),
+        };
+        stream.write_all(command.as_bytes()).await.ok()?;
+        if !read_some(&mut stream).await?.starts_with("+OK") {
+            return None;
+        }
+    }
+    stream.write_all(b"INFO server\r\n").await.ok()?;
+    let reply = read_some(&mut stream).await?;
+    if !reply.starts_with('$') {
+        return None;
+    }
+    Some(
+        reply
+            .lines()
+            .find_map(|line| line.strip_prefix("redis_version:"))
+            .map(|version| format!("redis {}", version.trim()))
+            .unwrap_or_default(),
+    )
+}
+
+async fn memcached_stats(socket_addr: SocketAddr) -> Option<String> {
+    let mut stream = TcpStream::connect(socket_addr).await.ok()?;
+    stream.write_all(b"stats\r\n").await.ok()?;
+    let reply = read_some(&mut stream).await?;
+    if !reply.starts_with("STAT ") {
+        return None;
+    }
+    Some(
+        reply
+            .lines()
+            .find_map(|line| line.strip_prefix("STAT version "))
+            .map(|version| format!("memcached {}", version.trim()))
+            .unwrap_or_default(),
+    )
+}
+
+fn basic_authorization(credential: &Credential) -> Option<String> {
+    if credential.username.is_empty() && credential.password.is_empty() {
+        return None;
+    }
+    let token = base64::engine::general_purpose::STANDARD
+        .encode(format!("{}:{}", credential.username, credential.password));
+    Some(format!("Basic {}", token))
+}
+
+async fn elasticsearch_login(
+    socket_addr: SocketAddr,
+    tls: bool,
+    credential: &Credential,
+) -> Option<String> {
+    let authorization = basic_authorization(credential)?;
+    let response = http::fetch(socket_addr, tls, "/", Some(&authorization))
+        .await
+        .ok()?;
+    elasticsearch_cluster(&response)
+}
+
+/// Cluster name and version of an Elasticsearch index page.
+fn elasticsearch_cluster(response: &http::HttpResponse) -> Option<String> {
+    if response.status != 200 {
+        return None;
+    }
+    let body = serde_json::from_slice::<serde_json::Value>(&response.body).ok()?;
+    let cluster = body.get("cluster_name")?.as_str()?;
+    let version = body
+        .get("version")
+        .and_then(|version| version.get("number"))
+        .and_then(|number| number.as_str())
+        .unwrap_or("*");
+    Some(format!("cluster {} version {}", cluster, version))
+}
+
+async fn http_basic_login(
+    socket_addr: SocketAddr,
+    tls: bool,
+    path: &str,
+    credential: &Credential,
+) -> Option<String> {
+    let authorization = basic_authorization(credential)?;
+    let response = http::fetch(socket_addr, tls, path, Some(&authorization))
+        .await
+        .ok()?;
+    (200..400)
+        .contains(&response.status)
+        .then(|| format!("{} {}", path, response.status))
+}
+
+#[cfg(test)]
+pub mod credential_test {
+    use crate::scanner::credential::{
+        parse_ftp_reply, CheckProtocol, Credential, CredentialCheck, CredentialChecker,
+    };
+    use std::net::IpAddr;
+
+    #[test]
+    fn credential_check_test() {
+        let lines = ["230-Welcome\r\n".to_string()];
+        assert!(parse_ftp_reply(&lines).is_none());
+        let lines = [lines[0].clone(), "230 Login successful.\r\n".to_string()];
+        assert_eq!(
+            parse_ftp_reply(&lines),
+            Some((230, "Login successful.".to_string()))
+        );
+
+        let check = CredentialCheck {
+            name: "ftp-anonymous".to_string(),
+            protocol: CheckProtocol::Ftp,
+            services: vec!["ftp".to_string()],
+            ports: vec![21],
+            paths: vec![],
+            credentials: vec![Credential {
+                username: "anonymous".to_string(),
+                password: "anonymous@".to_string(),
+            }],
+        };
+        assert!(check.applies("ftp", 2121));
+        assert!(check.applies("*", 21));
+        assert!(!check.applies("*", 2121));
+        assert!(!check.applies("http", 21));
+
+        let checker = CredentialChecker::new(vec![check], 2);
+        let ip_addr: IpAddr = "192.0.2.1".parse().unwrap();
+        assert!(checker.limiter.acquire(ip_addr));
+        assert!(checker.limiter.acquire(ip_addr));
+        assert!(!checker.limiter.acquire(ip_addr));
+        assert!(checker.limiter.acquire("192.0.2.2".parse().unwrap()));
+
+        let checks = CredentialCheck::from_file("./fingerprint/credentials.json");
+        assert!(checks
+            .iter()
+            .any(|check| check.protocol == CheckProtocol::Ftp));
+        assert!(CredentialCheck::from_file("./fingerprint/missing.json").is_empty());
+        assert!(CredentialCheck::from_file("./fingerprint/services.txt").is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::TcpStream;
+
+mod mongodb;
+mod mssql;
+mod mysql;
+mod postgres;
+mod redis;
+
+const DATABASE_TIMEOUT: Duration = Duration::from_millis(5000);
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Engine {
+    MySql,
+    PostgreSql,
+    Redis,
+    MongoDb,
+    MsSql,
+}
+
+impl Engine {
+    const ALL: [Engine; 5] = [
+        Engine::MySql,
+        Engine::PostgreSql,
+        Engine::Redis,
+        Engine::MongoDb,
+        Engine::MsSql,
+    ];
+
+    /// Service name the port is reported under once the probe succeeded.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::MySql => "mysql",
+            Engine::PostgreSql => "postgresql",
+            Engine::Redis => "redis",
+            Engine::MongoDb => "mongodb",
+            Engine::MsSql => "ms-sql-s",
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match self {
+            Engine::MySql => 3306,
+            Engine::PostgreSql => 5432,
+            Engine::Redis => 6379,
+            Engine::MongoDb => 27017,
+            Engine::MsSql => 1433,
+        }
+    }
+
+    /// Picks the engine from the matched service name, unidentified services fall back
+    /// to the engine registered for the port.
+    pub fn detect(name: &str, port: u16) -> Option<Engine> {
+        match Engine::ALL.iter().find(|engine| engine.name() == name) {
+            Some(engine) => Some(*engine),
+            None if name == "*" => Engine::ALL
+                .iter()
+                .find(|engine| engine.default_port() == port)
+                .copied(),
+            None => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatabaseInfo {
+    pub engine: Engine,
+    pub version: Option<String>,
+    /// authentication plugin or method the server asked for.
+    pub auth: Option<String>,
+    /// whether the server offers TLS, None when the protocol does not tell.
+    pub tls: Option<bool>,
+    /// whether commands run without credentials, None when it was not tested.
+    pub unauthenticated: Option<bool>,
+    pub details: Vec<String>,
+}
+
+impl DatabaseInfo {
+    fn new(engine: Engine) -> DatabaseInfo {
+        DatabaseInfo {
+            engine,
+            version: None,
+            auth: None,
+            tls: None,
+            unauthenticated: None,
+            details: vec![],
+        }
+    }
+
+    /// CPE of the engine and version, SQL Server is left out as NVD names its releases by
+    /// year rather than by the version the server reports.
+    pub fn cpe(&self) -> Option<String> {
+        let version = self.version.as_deref()?;
+        let product = match self.engine {
+            Engine::MySql if version.contains("MariaDB") => "mariadb:mariadb",
+            Engine::MySql => "oracle:mysql",
+            Engine::PostgreSql => "postgresql:postgresql",
+            Engine::Redis => "redis:redis",
+            Engine::MongoDb => "mongodb:mongodb",
+            Engine::MsSql => return None,
+        };
+        // distribution suffixes such as `-0ubuntu0.22.04.1` or ` (Debian 15.6-0+deb12u1)`.
+        let version = version
+            .split(|c: char| c == '-' || c.is_whitespace())
+            .next()
+            .filter(|version| !version.is_empty())?;
+        Some(format!("cpe:2.3:a:{}:{}:*:*:*:*:*:*:*", product, version))
+    }
+}
+
+impl std::fmt::Display for DatabaseInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = |value: Option<bool>| match value {
+            None => "*",
+            Some(true) => "yes",
+            Some(false) => "no",
+        };
+        write!(
+            f,
+            "{}|{}|auth {}|tls {}|unauthenticated {}",
+            self.engine.name(),
+            self.version.as_deref().unwrap_or("*"),
+            self.auth.as_deref().unwrap_or("*"),
+            flag(self.tls),
+            flag(self.unauthenticated)
+        )
+    }
+}
+
+async fn connect(socket_addr: SocketAddr) -> Option<TcpStream> {
+    tokio::time::timeout(DATABASE_TIMEOUT, TcpStream::connect(socket_addr))
+        .await
+        .ok()?
+        .ok()
+}
+
+async fn read_exact<S: AsyncRead + Unpin>(stream: &mut S, length: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; length];
+    tokio::time::timeout(DATABASE_TIMEOUT, stream.read_exact(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+    Some(buf)
+}
+
+/// Runs the native protocol probe of `engine`, None when the server did not speak it.
+pub async fn probe(socket_addr: SocketAddr, engine: Engine) -> Option<DatabaseInfo> {
+    match engine {
+        Engine::MySql => mysql::probe(socket_addr).await,
+        Engine::PostgreSql => postgres::probe(socket_addr).await,
+        Engine::Redis => redis::probe(socket_addr).await,
+        Engine::MongoDb => mongodb::probe(socket_addr).await,
+        Engine::MsSql => mssql::probe(socket_addr).await,
+    }
+}
@@ -0,0 +1,336 @@
+use crate::scanner::database::{connect, read_exact, DatabaseInfo, Engine};
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+const OP_REPLY: i32 = 1;
+const OP_QUERY: i32 = 2004;
+const OP_MSG: i32 = 2013;
+/// OP_MSG is understood from wire version 6 (MongoDB 3.6) on, OP_QUERY commands
+/// other than the handshake were removed in 5.1.
+const OP_MSG_WIRE_VERSION: i64 = 6;
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// MongoDB's Unauthorized error code.
+const UNAUTHORIZED: i64 = 13;
+
+/// Named fields of a BSON document, in wire order.
+type Fields = Vec<(String, Bson)>;
+
+/// The BSON types the probe reads, everything else is skipped as `Other`.
+#[derive(Debug, Clone, PartialEq)]
+enum Bson {
+    Double(f64),
+    String(String),
+    Document(Fields),
+    Array(Vec<Bson>),
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Other,
+}
+
+impl Bson {
+    fn get(&self, key: &str) -> Option<&Bson> {
+        match self {
+            Bson::Document(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Bson::Double(value) => Some(*value as i64),
+            Bson::Int32(value) => Some(*value as i64),
+            Bson::Int64(value) => Some(*value),
+            Bson::Bool(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Bson::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+fn encode_cstring(buf: &mut Vec<u8>, value: &str) {
+    buf.extend(value.as_bytes());
+    buf.push(0);
+}
+
+fn encode_document(fields: &[(&str, Bson)]) -> Vec<u8> {
+    let mut body = vec![];
+    for (name, value) in fields.iter() {
+        match value {
+            Bson::Double(value) => {
+                body.push(0x01);
+                encode_cstring(&mut body, name);
+                body.extend(value.to_le_bytes());
+            }
+            Bson::String(value) => {
+                body.push(0x02);
+                encode_cstring(&mut body, name);
+                body.extend(((value.len() + 1) as i32).to_le_bytes());
+                encode_cstring(&mut body, value);
+            }
+            Bson::Bool(value) => {
+                body.push(0x08);
+                encode_cstring(&mut body, name);
+                body.push(*value as u8);
+            }
+            Bson::Int32(value) => {
+                body.push(0x10);
+                encode_cstring(&mut body, name);
+                body.extend(value.to_le_bytes());
+            }
+            _ => {}
+        }
+    }
+    let mut document = ((body.len() + 5) as i32).to_le_bytes().to_vec();
+    document.extend(body);
+    document.push(0);
+    document
+}
+
+fn decode_cstring(data: &[u8]) -> Option<(String, &[u8])> {
+    let end = data.iter().position(|byte| *byte == 0)?;
+    Some((
+        String::from_utf8_lossy(&data[..end]).to_string(),
+        &data[end + 1..],
+    ))
+}
+
+fn decode_i32(data: &[u8]) -> Option<i32> {
+    Some(i32::from_le_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+/// Decodes one document and returns it with the bytes that follow it.
+fn decode_document(data: &[u8]) -> Option<(Fields, &[u8])> {
+    let length = usize::try_from(decode_i32(data)?).ok()?;
+    let mut body = data.get(4..length.checked_sub(1)?)?;
+    let rest = data.get(length..)?;
+    let mut fields = vec![];
+    while !body.is_empty() {
+        let kind = body[0];
+        let (name, data) = decode_cstring(&body[1..])?;
+        let (value, size) = match kind {
+            0x01 => (
+                Bson::Double(f64::from_le_bytes(data.get(..8)?.try_into().ok()?)),
+                8,
+            ),
+            0x02 => {
+                let length = usize::try_from(decode_i32(data)?).ok()?;
+                let value = data.get(4..4 + length.checked_sub(1)?)?;
+                (
+                    Bson::String(String::from_utf8_lossy(value).to_string()),
+                    4 + length,
+                )
+            }
+            0x03 | 0x04 => {
+                let (document, rest) = decode_document(data)?;
+                let size = data.len() - rest.len();
+                let value = match kind {
+                    0x03 => Bson::Document(document),
+                    _ => Bson::Array(document.into_iter().map(|(_, value)| value).collect()),
+                };
+                (value, size)
+            }
+            0x05 => (Bson::Other, 5 + usize::try_from(decode_i32(data)?).ok()?),
+            0x07 => (Bson::Other, 12),
+            0x08 => (Bson::Bool(*data.first()? != 0), 1),
+            0x09 | 0x11 => (Bson::Other, 8),
+            0x0a | 0x7f | 0xff => (Bson::Other, 0),
+            0x10 => (Bson::Int32(decode_i32(data)?), 4),
+            0x12 => (
+                Bson::Int64(i64::from_le_bytes(data.get(..8)?.try_into().ok()?)),
+                8,
+            ),
+            0x13 => (Bson::Other, 16),
+            _ => return None,
+        };
+        fields.push((name, value));
+        body = data.get(size..)?;
+    }
+    Some((fields, rest))
+}
+
+fn message(request_id: i32, op_code: i32, body: &[u8]) -> Vec<u8> {
+    let mut message = ((body.len() + 16) as i32).to_le_bytes().to_vec();
+    message.extend(request_id.to_le_bytes());
+    message.extend(0i32.to_le_bytes());
+    message.extend(op_code.to_le_bytes());
+    message.extend(body);
+    message
+}
+
+fn command_message(request_id: i32, op_msg: bool, command: &[(&str, Bson)]) -> Vec<u8> {
+    let mut body = 0i32.to_le_bytes().to_vec();
+    if op_msg {
+        let mut command = command.to_vec();
+        command.push(("$db", Bson::String("admin".to_string())));
+        body.push(0);
+        body.extend(encode_document(&command));
+        message(request_id, OP_MSG, &body)
+    } else {
+        encode_cstring(&mut body, "admin.$cmd");
+        // skip nothing and return a single document.
+        body.extend(0i32.to_le_bytes());
+        body.extend((-1i32).to_le_bytes());
+        body.extend(encode_document(command));
+        message(request_id, OP_QUERY, &body)
+    }
+}
+
+/// The reply document of an OP_REPLY or an OP_MSG.
+fn parse_reply(op_code: i32, body: &[u8]) -> Option<Bson> {
+    let document = match op_code {
+        // flags, cursor id, starting from and number returned.
+        OP_REPLY => body.get(20..)?,
+        // flags and the kind of the body section.
+        OP_MSG if *body.get(4)? == 0 => body.get(5..)?,
+        _ => return None,
+    };
+    decode_document(document).map(|(fields, _)| Bson::Document(fields))
+}
+
+struct MongoClient {
+    stream: TcpStream,
+    request_id: i32,
+    op_msg: bool,
+}
+
+impl MongoClient {
+    async fn command(&mut self, command: &[(&str, Bson)]) -> Option<Bson> {
+        self.request_id += 1;
+        let request = command_message(self.request_id, self.op_msg, command);
+        self.stream.write_all(&request).await.ok()?;
+        let header = read_exact(&mut self.stream, 16).await?;
+        let length = usize::try_from(decode_i32(&header)?).ok()?;
+        if !(16..=MAX_MESSAGE_SIZE).contains(&length) {
+            return None;
+        }
+        let op_code = decode_i32(&header[12..])?;
+        let body = read_exact(&mut self.stream, length - 16).await?;
+        parse_reply(op_code, &body)
+    }
+}
+
+pub async fn probe(socket_addr: SocketAddr) -> Option<DatabaseInfo> {
+    let stream = connect(socket_addr).await?;
+    let mut client = MongoClient {
+        stream,
+        request_id: 0,
+        op_msg: false,
+    };
+    let is_master = client.command(&[("isMaster", Bson::Int32(1))]).await?;
+    is_master.get("ok")?;
+    let mut info = DatabaseInfo::new(Engine::MongoDb);
+    let wire_version = is_master
+        .get("maxWireVersion")
+        .and_then(|version| version.as_i64())
+        .unwrap_or(0);
+    client.op_msg = wire_version >= OP_MSG_WIRE_VERSION;
+    if let Some(set_name) = is_master.get("setName").and_then(|name| name.as_str()) {
+        info.details.push(format!("replica set: {}", set_name));
+    }
+
+    if let Some(build_info) = client.command(&[("buildInfo", Bson::Int32(1))]).await {
+        info.version = build_info
+            .get("version")
+            .and_then(|version| version.as_str())
+            .map(|version| version.to_string());
+        if let Some(Bson::Array(modules)) = build_info.get("modules") {
+            if modules
+                .iter()
+                .any(|module| module.as_str() == Some("enterprise"))
+            {
+                info.details.push("enterprise".to_string());
+            }
+        }
+    }
+
+    let databases = client
+        .command(&[
+            ("listDatabases", Bson::Int32(1)),
+            ("nameOnly", Bson::Bool(true)),
+        ])
+        .await?;
+    if databases.get("ok").and_then(|ok| ok.as_i64()) == Some(1) {
+        info.auth = Some("none".to_string());
+        info.unauthenticated = Some(true);
+        if let Some(Bson::Array(list)) = databases.get("databases") {
+            let names = list
+                .iter()
+                .filter_map(|database| database.get("name").and_then(|name| name.as_str()))
+                .collect::<Vec<&str>>();
+            info.details.push(format!("databases: {}", names.join(",")));
+        }
+    } else {
+        let code = databases.get("code").and_then(|code| code.as_i64());
+        info.unauthenticated = Some(false);
+        if code == Some(UNAUTHORIZED) {
+            info.auth = Some("required".to_string());
+        }
+        if let Some(message) = databases.get("errmsg").and_then(|message| message.as_str()) {
+            info.details.push(message.to_string());
+        }
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod mongodb_test {
+    use crate::scanner::database::mongodb::{
+        command_message, decode_document, encode_document, parse_reply, Bson, OP_MSG,
+    };
+
+    #[test]
+    fn mongodb_bson_test() {
+        let document = encode_document(&[
+            ("ok", Bson::Double(1.0)),
+            ("version", Bson::String("7.0.5".to_string())),
+            ("maxWireVersion", Bson::Int32(21)),
+            ("readOnly", Bson::Bool(false)),
+        ]);
+        let (fields, rest) = decode_document(&document).unwrap();
+        assert!(rest.is_empty());
+        let reply = Bson::Document(fields);
+        assert_eq!(reply.get("version").unwrap().as_str(), Some("7.0.5"));
+        assert_eq!(reply.get("maxWireVersion").unwrap().as_i64(), Some(21));
+        assert_eq!(reply.get("ok").unwrap().as_i64(), Some(1));
+
+        // an array of documents, the form listDatabases answers with.
+        let mut inner = encode_document(&[("name", Bson::String("admin".to_string()))]);
+        let mut array = vec![0x03, b'0', 0];
+        array.append(&mut inner);
+        let mut body = vec![0x04];
+        body.extend(b"databases\0");
+        body.extend(((array.len() + 5) as i32).to_le_bytes());
+        body.extend(array);
+        body.push(0);
+        let mut document = ((body.len() + 5) as i32).to_le_bytes().to_vec();
+        document.extend(body);
+        document.push(0);
+        let mut reply = 0i32.to_le_bytes().to_vec();
+        reply.push(0);
+        reply.extend(document);
+        let reply = parse_reply(OP_MSG, &reply).unwrap();
+        match reply.get("databases") {
+            Some(Bson::Array(list)) => {
+                assert_eq!(list[0].get("name").unwrap().as_str(), Some("admin"))
+            }
+            _ => panic!("expected the databases array"),
+        }
+
+        let request = command_message(1, true, &[("buildInfo", Bson::Int32(1))]);
+        assert_eq!(
+            i32::from_le_bytes(request[..4].try_into().unwrap()) as usize,
+            request.len()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::scanner::database::{connect, read_exact, DatabaseInfo, Engine};
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+
+const PRELOGIN: u8 = 0x12;
+const TABULAR_RESULT: u8 = 0x04;
+const END_OF_MESSAGE: u8 = 0x01;
+const OPTION_VERSION: u8 = 0x00;
+const OPTION_ENCRYPTION: u8 = 0x01;
+const OPTION_TERMINATOR: u8 = 0xff;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Prelogin {
+    version: Option<(u8, u8, u16)>,
+    encryption: Option<u8>,
+}
+
+fn prelogin_request() -> Vec<u8> {
+    // version, encryption off, instance, thread id and MARS.
+    let options: [(u8, Vec<u8>); 5] = [
+        (OPTION_VERSION, vec![0; 6]),
+        (OPTION_ENCRYPTION, vec![0]),
+        (0x02, vec![0]),
+        (0x03, vec![0; 4]),
+        (0x04, vec![0]),
+    ];
+    let mut offset = options.len() * 5 + 1;
+    let mut header = vec![];
+    let mut data = vec![];
+    for (token, value) in options.iter() {
+        header.push(*token);
+        header.extend((offset as u16).to_be_bytes());
+        header.extend((value.len() as u16).to_be_bytes());
+        data.extend_from_slice(value);
+        offset += value.len();
+    }
+    header.push(OPTION_TERMINATOR);
+    header.extend(data);
+    let mut packet = vec![PRELOGIN, END_OF_MESSAGE];
+    packet.extend(((header.len() + 8) as u16).to_be_bytes());
+    // spid, packet id and window.
+    packet.extend([0, 0, 1, 0]);
+    packet.extend(header);
+    packet
+}
+
+fn parse_prelogin(payload: &[u8]) -> Option<Prelogin> {
+    let mut prelogin = Prelogin {
+        version: None,
+        encryption: None,
+    };
+    let mut index = 0;
+    while *payload.get(index)? != OPTION_TERMINATOR {
+        let option = payload.get(index..index + 5)?;
+        let offset = u16::from_be_bytes([option[1], option[2]]) as usize;
+        let length = u16::from_be_bytes([option[3], option[4]]) as usize;
+        let value = payload.get(offset..offset + length)?;
+        match option[0] {
+            OPTION_VERSION if length >= 4 => {
+                prelogin.version =
+                    Some((value[0], value[1], u16::from_be_bytes([value[2], value[3]])))
+            }
+            OPTION_ENCRYPTION if length >= 1 => prelogin.encryption = Some(value[0]),
+            _ => {}
+        }
+        index += 5;
+    }
+    Some(prelogin)
+}
+
+fn product_name(major: u8) -> Option<&'static str> {
+    let name = match major {
+        8 => "SQL Server 2000",
+        9 => "SQL Server 2005",
+        10 => "SQL Server 2008",
+        11 => "SQL Server 2012",
+        12 => "SQL Server 2014",
+        13 => "SQL Server 2016",
+        14 => "SQL Server 2017",
+        15 => "SQL Server 2019",
+        16 => "SQL Server 2022",
+        _ => return None,
+    };
+    Some(name)
+}
+
+pub async fn probe(socket_addr: SocketAddr) -> Option<DatabaseInfo> {
+    let mut stream = connect(socket_addr).await?;
+    stream.write_all(&prelogin_request()).await.ok()?;
+    let header = read_exact(&mut stream, 8).await?;
+    if header[0] != TABULAR_RESULT {
+        return None;
+    }
+    let length = (u16::from_be_bytes([header[2], header[3]]) as usize).checked_sub(8)?;
+    let prelogin = parse_prelogin(&read_exact(&mut stream, length).await?)?;
+    let mut info = DatabaseInfo::new(Engine::MsSql);
+    if let Some((major, minor, build)) = prelogin.version {
+        info.version = Some(format!("{}.{}.{}", major, minor, build));
+        if let Some(product) = product_name(major) {
+            info.details.push(product.to_string());
+        }
+    }
+    if let Some(encryption) = prelogin.encryption {
+        // off still encrypts the login, not supported leaves even the password in clear.
+        info.tls = Some(encryption != 2);
+        let mode = match encryption {
+            0 => "encryption off",
+            1 => "encryption on",
+            2 => "encryption not supported",
+            3 => "encryption required",
+            _ => "encryption unknown",
+        };
+        info.details.push(mode.to_string());
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod mssql_test {
+    use crate::scanner::database::mssql::{parse_prelogin, prelogin_request};
+
+    #[test]
+    fn mssql_prelogin_test() {
+        let request = prelogin_request();
+        assert_eq!(request[0], 0x12);
+        assert_eq!(
+            u16::from_be_bytes([request[2], request[3]]) as usize,
+            request.len()
+        );
+        // the request options parse the same way a reply does.
+        let prelogin = parse_prelogin(&request[8..]).unwrap();
+        assert_eq!(prelogin.version, Some((0, 0, 0)));
+
+        let reply = [
+            0x00, 0x00, 0x0b, 0x00, 0x06, 0x01, 0x00, 0x11, 0x00, 0x01, 0xff, 0x0f, 0x00, 0x07,
+            0xd0, 0x00, 0x00, 0x03,
+        ];
+        let prelogin = parse_prelogin(&reply).unwrap();
+        assert_eq!(prelogin.version, Some((15, 0, 2000)));
+        assert_eq!(prelogin.encryption, Some(3));
+        assert!(parse_prelogin(&reply[..8]).is_none());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::scanner::database::{connect, read_exact, DatabaseInfo, Engine};
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+/// MariaDB prefixes its version so old clients accept it as a 5.5 server.
+const MARIADB_VERSION_PREFIX: &str = "5.5.5-";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Handshake {
+    version: String,
+    capabilities: u32,
+    auth_plugin: Option<String>,
+}
+
+fn parse_handshake(payload: &[u8]) -> Option<Handshake> {
+    if *payload.first()? != 10 {
+        return None;
+    }
+    let end = payload[1..].iter().position(|byte| *byte == 0)? + 1;
+    let version = String::from_utf8_lossy(&payload[1..end]).to_string();
+    // thread id, the first 8 bytes of the scramble and a filler.
+    let mut index = end + 1 + 4 + 8 + 1;
+    let lower = payload.get(index..index + 2)?;
+    let mut capabilities = u16::from_le_bytes([lower[0], lower[1]]) as u32;
+    index += 2;
+    let mut auth_plugin = None;
+    // character set and status flags, the rest is only sent by 4.1+ servers.
+    if let Some(upper) = payload.get(index + 3..index + 5) {
+        capabilities |= (u16::from_le_bytes([upper[0], upper[1]]) as u32) << 16;
+        let scramble_length = *payload.get(index + 5)? as usize;
+        index += 5 + 1 + 10;
+        if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+            index += std::cmp::max(13, scramble_length.saturating_sub(8));
+        }
+        if capabilities & CLIENT_PLUGIN_AUTH != 0 {
+            auth_plugin = payload.get(index..).map(|name| {
+                let end = name
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..end]).to_string()
+            });
+        }
+    }
+    Some(Handshake {
+        version,
+        capabilities,
+        auth_plugin: auth_plugin.filter(|name| !name.is_empty()),
+    })
+}
+
+fn parse_error(payload: &[u8]) -> Option<String> {
+    if *payload.first()? != 0xff {
+        return None;
+    }
+    let message = payload.get(3..)?;
+    // 4.1 servers put `#` and a five character SQL state before the message.
+    let message = match message.first() {
+        Some(b'#') => message.get(6..)?,
+        _ => message,
+    };
+    Some(String::from_utf8_lossy(message).to_string())
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let header = read_exact(stream, 4).await?;
+    let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    Some((header[3], read_exact(stream, length).await?))
+}
+
+async fn write_packet(stream: &mut TcpStream, sequence: u8, payload: &[u8]) -> Option<()> {
+    let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+    packet.push(sequence);
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet).await.ok()
+}
+
+fn login_request(user: &str, auth_plugin: Option<&str>) -> Vec<u8> {
+    let mut capabilities = CLIENT_LONG_PASSWORD | CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION;
+    if auth_plugin.is_some() {
+        capabilities |= CLIENT_PLUGIN_AUTH;
+    }
+    let mut payload = capabilities.to_le_bytes().to_vec();
+    payload.extend(16_777_216u32.to_le_bytes());
+    // utf8 general collation and the reserved bytes.
+    payload.push(33);
+    payload.extend([0u8; 23]);
+    payload.extend(user.as_bytes());
+    payload.push(0);
+    // empty auth response, the password is empty.
+    payload.push(0);
+    if let Some(plugin) = auth_plugin {
+        payload.extend(plugin.as_bytes());
+        payload.push(0);
+    }
+    payload
+}
+
+/// Logs in as `root` without a password, following auth switch requests.
+async fn empty_root_login(stream: &mut TcpStream, handshake: &Handshake) -> Option<bool> {
+    let mut sequence = 1;
+    write_packet(
+        stream,
+        sequence,
+        &login_request("root", handshake.auth_plugin.as_deref()),
+    )
+    .await?;
+    for _ in 0..4 {
+        let (received, payload) = read_packet(stream).await?;
+        sequence = received.wrapping_add(1);
+        match payload.first()? {
+            0x00 => return Some(true),
+            0xfe => write_packet(stream, sequence, &[]).await?,
+            // caching_sha2_password fast auth success is followed by the OK packet.
+            0x01 if payload.get(1) == Some(&3) => continue,
+            _ => return Some(false),
+        }
+    }
+    Some(false)
+}
+
+pub async fn probe(socket_addr: SocketAddr) -> Option<DatabaseInfo> {
+    let mut stream = connect(socket_addr).await?;
+    let (_, payload) = read_packet(&mut stream).await?;
+    let mut info = DatabaseInfo::new(Engine::MySql);
+    if let Some(message) = parse_error(&payload) {
+        // e.g. the host is not allowed to connect, the server still identified itself.
+        info.details.push(message);
+        return Some(info);
+    }
+    let handshake = parse_handshake(&payload)?;
+    info.version = Some(
+        handshake
+            .version
+            .trim_start_matches(MARIADB_VERSION_PREFIX)
+            .to_string(),
+    );
+    info.auth = handshake.auth_plugin.clone();
+    info.tls = Some(handshake.capabilities & CLIENT_SSL != 0);
+    info.unauthenticated = empty_root_login(&mut stream, &handshake).await;
+    if info.unauthenticated == Some(true) {
+        info.details.push("root login without password".to_string());
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod mysql_test {
+    use crate::scanner::database::mysql::{parse_error, parse_handshake, CLIENT_SSL};
+
+    #[test]
+    fn mysql_handshake_test() {
+        let mut payload = vec![10];
+        payload.extend(b"8.0.36\0");
+        payload.extend([1, 0, 0, 0]);
+        payload.extend(b"abcdefgh\0");
+        payload.extend([0xff, 0xff, 0xff, 0x02, 0x00, 0xff, 0xdf, 21]);
+        payload.extend([0u8; 10]);
+        payload.extend(b"ijklmnopqrst\0");
+        payload.extend(b"caching_sha2_password\0");
+        let handshake = parse_handshake(&payload).unwrap();
+        assert_eq!(handshake.version, "8.0.36");
+        assert_ne!(handshake.capabilities & CLIENT_SSL, 0);
+        assert_eq!(
+            handshake.auth_plugin.as_deref(),
+            Some("caching_sha2_password")
+        );
+
+        let mut error = vec![0xff, 0x6a, 0x04];
+        error.extend(b"Host '10.0.0.9' is not allowed to connect to this MySQL server");
+        assert!(parse_error(&error).unwrap().starts_with("Host '10.0.0.9'"));
+        assert!(parse_handshake(&error).is_none());
+    }
+}
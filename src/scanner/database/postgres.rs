@@ -0,0 +1,192 @@
+use crate::scanner::database::{connect, read_exact, DatabaseInfo, Engine};
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+const SSL_REQUEST_CODE: u32 = 80877103;
+const PROTOCOL_VERSION: u32 = 3 << 16;
+/// A major protocol no server speaks, major 1234 is taken by the SSL and cancel requests.
+/// The rejection tells which versions the server does speak.
+const UNSUPPORTED_PROTOCOL_VERSION: u32 = 99 << 16;
+/// Messages read after authentication while looking for `server_version`.
+const MAX_STARTUP_MESSAGES: usize = 32;
+
+fn startup_message(protocol: u32, user: &str) -> Vec<u8> {
+    let mut body = protocol.to_be_bytes().to_vec();
+    for (key, value) in [
+        ("user", user),
+        ("database", user),
+        ("application_name", "magic-scanner"),
+    ] {
+        body.extend(key.as_bytes());
+        body.push(0);
+        body.extend(value.as_bytes());
+        body.push(0);
+    }
+    body.push(0);
+    let mut message = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+    message.extend(body);
+    message
+}
+
+/// Fields of an ErrorResponse keyed by their type byte, `M` is the message.
+fn parse_error_fields(body: &[u8]) -> Vec<(char, String)> {
+    body.split(|byte| *byte == 0)
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            (
+                field[0] as char,
+                String::from_utf8_lossy(&field[1..]).to_string(),
+            )
+        })
+        .collect()
+}
+
+fn auth_method(body: &[u8]) -> Option<String> {
+    let code = u32::from_be_bytes(body.get(..4)?.try_into().ok()?);
+    let method = match code {
+        0 => "trust".to_string(),
+        2 => "kerberos".to_string(),
+        3 => "password".to_string(),
+        5 => "md5".to_string(),
+        7 => "gss".to_string(),
+        9 => "sspi".to_string(),
+        // SASL lists its mechanisms as null terminated strings.
+        10 => body[4..]
+            .split(|byte| *byte == 0)
+            .filter(|mechanism| !mechanism.is_empty())
+            .map(|mechanism| String::from_utf8_lossy(mechanism).to_string())
+            .collect::<Vec<String>>()
+            .join(","),
+        _ => format!("unknown ({})", code),
+    };
+    Some(method)
+}
+
+async fn read_message(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let header = read_exact(stream, 5).await?;
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    Some((header[0], read_exact(stream, length.checked_sub(4)?).await?))
+}
+
+async fn ssl_supported(socket_addr: SocketAddr) -> Option<bool> {
+    let mut stream = connect(socket_addr).await?;
+    let mut request = 8u32.to_be_bytes().to_vec();
+    request.extend(SSL_REQUEST_CODE.to_be_bytes());
+    stream.write_all(&request).await.ok()?;
+    match read_exact(&mut stream, 1).await?[0] {
+        b'S' => Some(true),
+        b'N' => Some(false),
+        _ => None,
+    }
+}
+
+/// Starts a session as `postgres`, records the authentication the server asks for and
+/// reads `server_version` when it lets the session in without a password.
+async fn startup(socket_addr: SocketAddr, info: &mut DatabaseInfo) -> Option<()> {
+    let mut stream = connect(socket_addr).await?;
+    stream
+        .write_all(&startup_message(PROTOCOL_VERSION, "postgres"))
+        .await
+        .ok()?;
+    for _ in 0..MAX_STARTUP_MESSAGES {
+        let (kind, body) = read_message(&mut stream).await?;
+        match kind {
+            b'R' => {
+                let method = auth_method(&body)?;
+                let trust = method == "trust";
+                info.unauthenticated = Some(trust);
+                info.auth = Some(method);
+                if !trust {
+                    return Some(());
+                }
+            }
+            b'S' => {
+                let fields = body.split(|byte| *byte == 0).collect::<Vec<&[u8]>>();
+                if fields.first() == Some(&&b"server_version"[..]) {
+                    info.version = fields
+                        .get(1)
+                        .map(|version| String::from_utf8_lossy(version).to_string());
+                }
+            }
+            b'E' => {
+                let fields = parse_error_fields(&body);
+                if let Some((_, message)) = fields.iter().find(|(field, _)| *field == 'M') {
+                    info.details.push(message.clone());
+                }
+                info.unauthenticated.get_or_insert(false);
+                return Some(());
+            }
+            b'Z' => return Some(()),
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+/// The rejection of an unknown protocol names the supported range and the source
+/// location of the check, both of which differ between releases.
+async fn protocol_error(socket_addr: SocketAddr) -> Option<String> {
+    let mut stream = connect(socket_addr).await?;
+    stream
+        .write_all(&startup_message(UNSUPPORTED_PROTOCOL_VERSION, "postgres"))
+        .await
+        .ok()?;
+    let (kind, body) = read_message(&mut stream).await?;
+    if kind != b'E' {
+        return None;
+    }
+    let fields = parse_error_fields(&body);
+    let field = |name: char| {
+        fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let mut error = field('M')?.to_string();
+    if let (Some(file), Some(line)) = (field('F'), field('L')) {
+        error.push_str(&format!(" ({}:{})", file, line));
+    }
+    Some(error)
+}
+
+pub async fn probe(socket_addr: SocketAddr) -> Option<DatabaseInfo> {
+    let mut info = DatabaseInfo::new(Engine::PostgreSql);
+    info.tls = Some(ssl_supported(socket_addr).await?);
+    startup(socket_addr, &mut info).await;
+    if let Some(error) = protocol_error(socket_addr).await {
+        info.details.push(error);
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod postgres_test {
+    use crate::scanner::database::postgres::{auth_method, parse_error_fields, startup_message};
+
+    #[test]
+    fn postgres_message_test() {
+        let message = startup_message(3 << 16, "postgres");
+        assert_eq!(
+            u32::from_be_bytes(message[..4].try_into().unwrap()) as usize,
+            message.len()
+        );
+        assert_eq!(&message[4..8], [0, 3, 0, 0]);
+
+        assert_eq!(
+            auth_method(&[0, 0, 0, 5, 1, 2, 3, 4]).as_deref(),
+            Some("md5")
+        );
+        let mut sasl = vec![0, 0, 0, 10];
+        sasl.extend(b"SCRAM-SHA-256-PLUS\0SCRAM-SHA-256\0\0");
+        assert_eq!(
+            auth_method(&sasl).as_deref(),
+            Some("SCRAM-SHA-256-PLUS,SCRAM-SHA-256")
+        );
+
+        let body = b"SFATAL\0C0A000\0Munsupported frontend protocol 99.0: server supports 3.0 to 3.0\0Fpostmaster.c\0L2188\0\0";
+        let fields = parse_error_fields(body);
+        assert_eq!(fields[1], ('C', "0A000".to_string()));
+        assert_eq!(fields[4], ('L', "2188".to_string()));
+    }
+}
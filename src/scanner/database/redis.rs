@@ -0,0 +1,110 @@
+use crate::scanner::database::{connect, DatabaseInfo, Engine, DATABASE_TIMEOUT};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// INFO replies are a few kilobytes, anything longer is not worth waiting for.
+const MAX_REPLY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Reply {
+    /// `key:value` lines of an INFO bulk string.
+    Info(Vec<(String, String)>),
+    Error(String),
+}
+
+/// Parses a complete reply, None while more data is needed or when it is not RESP.
+fn parse_reply(data: &[u8]) -> Option<Reply> {
+    let end = data.windows(2).position(|window| window == b"\r\n")?;
+    let line = String::from_utf8_lossy(data.get(1..end)?).to_string();
+    match data.first()? {
+        b'-' => Some(Reply::Error(line)),
+        b'$' => {
+            let length = line.parse::<usize>().ok()?;
+            let body = data.get(end + 2..end + 2 + length)?;
+            let fields = String::from_utf8_lossy(body)
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+                .collect();
+            Some(Reply::Info(fields))
+        }
+        _ => None,
+    }
+}
+
+pub async fn probe(socket_addr: SocketAddr) -> Option<DatabaseInfo> {
+    let mut stream = connect(socket_addr).await?;
+    stream.write_all(b"INFO server\r\n").await.ok()?;
+    let mut data = vec![];
+    let mut buf = [0u8; 4096];
+    let reply = loop {
+        let size = tokio::time::timeout(DATABASE_TIMEOUT, stream.read(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        if size == 0 || data.len() > MAX_REPLY_SIZE {
+            return None;
+        }
+        data.extend_from_slice(&buf[..size]);
+        if let Some(reply) = parse_reply(&data) {
+            break reply;
+        }
+    };
+    let mut info = DatabaseInfo::new(Engine::Redis);
+    match reply {
+        Reply::Info(fields) => {
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+            };
+            info.version = field("redis_version");
+            info.auth = Some("none".to_string());
+            info.unauthenticated = Some(true);
+            for name in ["redis_mode", "os", "executable", "config_file"] {
+                if let Some(value) = field(name).filter(|value| !value.is_empty()) {
+                    info.details.push(format!("{}: {}", name, value));
+                }
+            }
+        }
+        Reply::Error(message) => {
+            // NOAUTH for requirepass, DENIED for protected mode.
+            if message.starts_with("NOAUTH") {
+                info.auth = Some("password".to_string());
+            }
+            info.unauthenticated = Some(false);
+            info.details.push(message);
+        }
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod redis_test {
+    use crate::scanner::database::redis::{parse_reply, Reply};
+
+    #[test]
+    fn redis_reply_test() {
+        let body =
+            "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\nos:Linux 6.1.0 x86_64\r\n";
+        let data = format!("${}\r\n{}\r\n", body.len(), body);
+        assert!(parse_reply(&data.as_bytes()[..20]).is_none());
+        match parse_reply(data.as_bytes()).unwrap() {
+            Reply::Info(fields) => {
+                assert_eq!(
+                    fields[0],
+                    ("redis_version".to_string(), "7.2.4".to_string())
+                );
+                assert_eq!(fields.len(), 3);
+            }
+            Reply::Error(_) => panic!("expected an info reply"),
+        }
+        assert_eq!(
+            parse_reply(b"-NOAUTH Authentication required.\r\n"),
+            Some(Reply::Error("NOAUTH Authentication required.".to_string()))
+        );
+        assert_eq!(parse_reply(b"\r\n"), None);
+        assert_eq!(parse_reply(b""), None);
+    }
+}
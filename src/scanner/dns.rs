@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::{DNSClass, Name, RData, RecordType};
+
+pub const DNS_PORT: u16 = 53;
+const DNS_TIMEOUT: Duration = Duration::from_millis(3000);
+const AXFR_TIMEOUT: Duration = Duration::from_millis(10000);
+/// A name no scanned server should be authoritative for, answering it means recursing.
+const RECURSION_TEST_NAME: &str = "www.wikipedia.org.";
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum DnsTransport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ZoneTransfer {
+    pub zone: String,
+    pub records: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DnsInfo {
+    pub version: Option<String>,
+    pub server_id: Option<String>,
+    pub recursion: bool,
+    pub zone_transfers: Vec<ZoneTransfer>,
+    pub weaknesses: Vec<String>,
+}
+
+impl std::fmt::Display for DnsInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|recursion {}",
+            self.version.as_deref().unwrap_or("*"),
+            self.server_id.as_deref().unwrap_or("*"),
+            if self.recursion { "open" } else { "closed" }
+        )?;
+        for transfer in self.zone_transfers.iter() {
+            write!(f, "|axfr {} ({})", transfer.zone, transfer.records.len())?;
+        }
+        Ok(())
+    }
+}
+
+fn query_message(name: Name, record_type: RecordType, class: DNSClass, recursion: bool) -> Message {
+    let mut query = Query::query(name, record_type);
+    query.set_query_class(class);
+    let mut message = Message::new();
+    message
+        .set_id(rand::random::<u16>())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(recursion)
+        .add_query(query);
+    message
+}
+
+/// Text of the first TXT answer, how `version.bind` and `id.server` are answered.
+fn txt_answer(response: &Message) -> Option<String> {
+    if response.response_code() != ResponseCode::NoError {
+        return None;
+    }
+    response
+        .answers()
+        .iter()
+        .find_map(|record| match record.data() {
+            Some(RData::TXT(txt)) => Some(
+                txt.txt_data()
+                    .iter()
+                    .map(|data| String::from_utf8_lossy(data).to_string())
+                    .collect::<Vec<String>>()
+                    .join(""),
+            ),
+            _ => None,
+        })
+        .filter(|text| !text.is_empty())
+}
+
+fn is_open_recursion(response: &Message) -> bool {
+    response.recursion_available()
+        && response.response_code() == ResponseCode::NoError
+        && !response.authoritative()
+        && !response.answers().is_empty()
+}
+
+async fn exchange(
+    socket_addr: SocketAddr,
+    transport: DnsTransport,
+    request: &Message,
+) -> Option<Message> {
+    let buf = request.to_vec().ok()?;
+    let response = match transport {
+        DnsTransport::Udp => {
+            let bind_addr = match socket_addr {
+                SocketAddr::V4(_) => "0.0.0.0:0",
+                SocketAddr::V6(_) => "[::]:0",
+            };
+            let socket = UdpSocket::bind(bind_addr).await.ok()?;
+            socket.connect(socket_addr).await.ok()?;
+            socket.send(&buf).await.ok()?;
+            let mut response = vec![0u8; 4096];
+            let size = tokio::time::timeout(DNS_TIMEOUT, socket.recv(&mut response))
+                .await
+                .ok()?
+                .ok()?;
+            response.truncate(size);
+            Message::from_vec(&response).ok()?
+        }
+        DnsTransport::Tcp => {
+            let mut stream = tokio::time::timeout(DNS_TIMEOUT, TcpStream::connect(socket_addr))
+                .await
+                .ok()?
+                .ok()?;
+            write_tcp_message(&mut stream, &buf).await?;
+            read_tcp_message(&mut stream, DNS_TIMEOUT).await?
+        }
+    };
+    (response.id() == request.id()).then_some(response)
+}
+
+async fn write_tcp_message(stream: &mut TcpStream, buf: &[u8]) -> Option<()> {
+    let mut framed = (buf.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(buf);
+    stream.write_all(&framed).await.ok()
+}
+
+async fn read_tcp_message(stream: &mut TcpStream, timeout: Duration) -> Option<Message> {
+    let read = async {
+        let length = stream.read_u16().await.ok()? as usize;
+        let mut buf = vec![0u8; length];
+        stream.read_exact(&mut buf).await.ok()?;
+        Message::from_vec(&buf).ok()
+    };
+    tokio::time::timeout(timeout, read).await.ok()?
+}
+
+/// Requests a full zone transfer, the records are returned once the closing SOA arrives.
+async fn zone_transfer(socket_addr: SocketAddr, zone: &str) -> Option<ZoneTransfer> {
+    let name = Name::from_ascii(zone).ok()?;
+    let request = query_message(name, RecordType::AXFR, DNSClass::IN, false);
+    let mut stream = tokio::time::timeout(DNS_TIMEOUT, TcpStream::connect(socket_addr))
+        .await
+        .ok()?
+        .ok()?;
+    write_tcp_message(&mut stream, &request.to_vec().ok()?).await?;
+    let mut records = vec![];
+    let mut soa_count = 0;
+    while soa_count < 2 {
+        let response = read_tcp_message(&mut stream, AXFR_TIMEOUT).await?;
+        if response.id() != request.id()
+            || response.response_code() != ResponseCode::NoError
+            || response.answers().is_empty()
+        {
+            return None;
+        }
+        for record in response.answers() {
+            if record.record_type() == RecordType::SOA {
+                soa_count += 1;
+            }
+            records.push(record.to_string());
+        }
+    }
+    Some(ZoneTransfer {
+        zone: zone.to_string(),
+        records,
+    })
+}
+
+/// Queries the CHAOS identity records and checks for open recursion, then tries AXFR
+/// for every zone in `zones`. Returns None when nothing answered on `transport`.
+pub async fn probe(
+    socket_addr: SocketAddr,
+    transport: DnsTransport,
+    zones: &[String],
+) -> Option<DnsInfo> {
+    let mut answered = false;
+    let mut chaos_txt = |response: Option<Message>| {
+        answered |= response.is_some();
+        response.as_ref().and_then(txt_answer)
+    };
+    let version_bind = Name::from_ascii("version.bind.").ok()?;
+    let id_server = Name::from_ascii("id.server.").ok()?;
+    let version = chaos_txt(
+        exchange(
+            socket_addr,
+            transport,
+            &query_message(version_bind, RecordType::TXT, DNSClass::CH, false),
+        )
+        .await,
+    );
+    let server_id = chaos_txt(
+        exchange(
+            socket_addr,
+            transport,
+            &query_message(id_server, RecordType::TXT, DNSClass::CH, false),
+        )
+        .await,
+    );
+    let recursion_name = Name::from_ascii(RECURSION_TEST_NAME).ok()?;
+    let response = exchange(
+        socket_addr,
+        transport,
+        &query_message(recursion_name, RecordType::A, DNSClass::IN, true),
+    )
+    .await;
+    if !answered && response.is_none() {
+        return None;
+    }
+    let recursion = response.as_ref().is_some_and(is_open_recursion);
+
+    let mut zone_transfers = vec![];
+    if transport == DnsTransport::Tcp {
+        for zone in zones.iter() {
+            if let Some(transfer) = zone_transfer(socket_addr, zone).await {
+                zone_transfers.push(transfer);
+            }
+        }
+    }
+
+    let mut weaknesses = vec![];
+    if let Some(version) = &version {
+        weaknesses.push(format!("version disclosed: {}", version));
+    }
+    if recursion {
+        weaknesses.push("open recursion".to_string());
+    }
+    for transfer in zone_transfers.iter() {
+        weaknesses.push(format!("zone transfer allowed: {}", transfer.zone));
+    }
+    Some(DnsInfo {
+        version,
+        server_id,
+        recursion,
+        zone_transfers,
+        weaknesses,
+    })
+}
+
+#[cfg(test)]
+pub mod dns_test {
+    use crate::scanner::dns::{is_open_recursion, query_message, txt_answer};
+    use trust_dns_resolver::proto::op::{Message, MessageType};
+    use trust_dns_resolver::proto::rr::rdata::TXT;
+    use trust_dns_resolver::proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+    #[test]
+    fn dns_answer_test() {
+        let name = Name::from_ascii("version.bind.").unwrap();
+        let request = query_message(name.clone(), RecordType::TXT, DNSClass::CH, false);
+        let request = Message::from_vec(&request.to_vec().unwrap()).unwrap();
+        assert_eq!(request.queries()[0].query_class(), DNSClass::CH);
+
+        let mut response = request.clone();
+        response.set_message_type(MessageType::Response);
+        let txt = TXT::new(vec!["9.18.".to_string(), "19".to_string()]);
+        let mut record = Record::from_rdata(name, 0, RData::TXT(txt));
+        record.set_dns_class(DNSClass::CH);
+        response.add_answer(record);
+        let response = Message::from_vec(&response.to_vec().unwrap()).unwrap();
+        assert_eq!(txt_answer(&response).as_deref(), Some("9.18.19"));
+        assert!(!is_open_recursion(&response));
+
+        let mut response = response.clone();
+        response.set_recursion_available(true);
+        assert!(is_open_recursion(&response));
+        response.set_authoritative(true);
+        assert!(!is_open_recursion(&response));
+    }
+}
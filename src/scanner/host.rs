@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+use crate::os::active::TestResults;
+use crate::os::passive::PassiveObservation;
+use crate::os::OsGuess;
+use crate::scanner::netbios::NetbiosInfo;
+use crate::scanner::smb::SmbInfo;
+use crate::scanner::snmp::SnmpInfo;
+use crate::scanner::socket::{ScannerReply, TransportLayerProtocol};
+use crate::vuln::Vulnerability;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Everything found about a single host: the OS information, the open ports, the
+/// Windows details read over SMB and NetBIOS and the SNMP system information.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostReply {
+    ip_addr: IpAddr,
+    os_observation: PassiveObservation,
+    os_guess: Option<OsGuess>,
+    os_fingerprint: Option<TestResults>,
+    ports: Vec<ScannerReply>,
+    smb: Option<SmbInfo>,
+    netbios: Option<NetbiosInfo>,
+}
+
+impl HostReply {
+    pub fn new(ip_addr: IpAddr) -> HostReply {
+        HostReply {
+            ip_addr,
+            os_observation: PassiveObservation::default(),
+            os_guess: None,
+            os_fingerprint: None,
+            ports: vec![],
+            smb: None,
+            netbios: None,
+        }
+    }
+    pub fn ip_addr(&self) -> IpAddr {
+        self.ip_addr
+    }
+    pub fn os_observation(&self) -> &PassiveObservation {
+        &self.os_observation
+    }
+    pub fn os_observation_mut(&mut self) -> &mut PassiveObservation {
+        &mut self.os_observation
+    }
+    pub fn os_guess(&self) -> Option<&OsGuess> {
+        self.os_guess.as_ref()
+    }
+    pub fn set_os_guess(&mut self, os_guess: Option<OsGuess>) {
+        self.os_guess = os_guess;
+    }
+    pub fn os_fingerprint(&self) -> Option<&TestResults> {
+        self.os_fingerprint.as_ref()
+    }
+    pub fn set_os_fingerprint(&mut self, os_fingerprint: Option<TestResults>) {
+        self.os_fingerprint = os_fingerprint;
+    }
+    pub fn ports(&self) -> &[ScannerReply] {
+        &self.ports
+    }
+    pub fn add_port(&mut self, port: ScannerReply) {
+        self.ports.push(port);
+    }
+    pub fn smb(&self) -> Option<&SmbInfo> {
+        self.smb.as_ref()
+    }
+    pub fn set_smb(&mut self, smb: Option<SmbInfo>) {
+        self.smb = smb;
+    }
+    pub fn netbios(&self) -> Option<&NetbiosInfo> {
+        self.netbios.as_ref()
+    }
+    pub fn set_netbios(&mut self, netbios: Option<NetbiosInfo>) {
+        self.netbios = netbios;
+    }
+    /// System information read over SNMP, kept with the UDP 161 port it was read from.
+    pub fn snmp(&self) -> Option<&SnmpInfo> {
+        self.ports.iter().find_map(|reply| reply.snmp())
+    }
+    /// The CVEs found on every port, with the port they were found on.
+    pub fn vulnerabilities(&self) -> Vec<(u16, &Vulnerability)> {
+        self.ports
+            .iter()
+            .flat_map(|reply| {
+                reply
+                    .vulnerabilities()
+                    .iter()
+                    .map(|vulnerability| (reply.port().unwrap_or_default(), vulnerability))
+            })
+            .collect()
+    }
+    /// Adds what a scan of other ports of the same host found, e.g. by another worker. The
+    /// most confident OS guess is kept.
+    pub fn merge(&mut self, other: HostReply) {
+        self.os_observation.merge(&other.os_observation);
+        let more_confident = match (&self.os_guess, &other.os_guess) {
+            (None, _) => true,
+            (Some(guess), Some(other)) => other.confidence > guess.confidence,
+            (Some(_), None) => false,
+        };
+        if more_confident {
+            self.os_guess = other.os_guess;
+            self.os_fingerprint = other.os_fingerprint.or(self.os_fingerprint.take());
+        }
+        self.ports.extend(other.ports);
+        self.smb = self.smb.take().or(other.smb);
+        self.netbios = self.netbios.take().or(other.netbios);
+    }
+    pub fn open_tcp_ports(&self) -> Vec<u16> {
+        self.ports
+            .iter()
+            .filter(|reply| {
+                matches!(
+                    reply.transport_layer_protocol(),
+                    Some(TransportLayerProtocol::TCP)
+                )
+            })
+            .filter_map(|reply| reply.port())
+            .collect()
+    }
+}
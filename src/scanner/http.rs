@@ -0,0 +1,429 @@
+use crate::scanner::{tls, ProbeStream};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const HTTP_TIMEOUT: Duration = Duration::from_millis(5000);
+const MAX_REDIRECTS: usize = 5;
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; magic-scanner)";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HttpInfo {
+    /// the url of the final response after following redirects.
+    pub url: String,
+    pub status: u16,
+    pub server: Option<String>,
+    pub powered_by: Option<String>,
+    pub title: Option<String>,
+    pub content_length: usize,
+    /// every url that answered with a redirect, in order.
+    pub redirects: Vec<String>,
+    /// Shodan compatible mmh3 hash of the favicon.
+    pub favicon_hash: Option<i32>,
+}
+
+impl std::fmt::Display for HttpInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}",
+            self.status,
+            self.url,
+            self.server.as_deref().unwrap_or("*"),
+            self.title.as_deref().unwrap_or("*")
+        )?;
+        if let Some(favicon_hash) = self.favicon_hash {
+            write!(f, "|favicon:{}", favicon_hash)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// First value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Where a request goes: the connection and the url parts sent to the server.
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    socket_addr: SocketAddr,
+    tls: bool,
+    host: String,
+    path: String,
+}
+
+impl Target {
+    /// The `Host` header value, the port is left out when it's the scheme default.
+    fn authority(&self) -> String {
+        let default_port = if self.tls { 443 } else { 80 };
+        match self.socket_addr.port() == default_port {
+            true => self.host.clone(),
+            false => format!("{}:{}", self.host, self.socket_addr.port()),
+        }
+    }
+
+    fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}{}", scheme, self.authority(), self.path)
+    }
+
+    /// Resolves a `Location` header against this target. Redirects never leave the scanned
+    /// address, a host name is only used as the `Host` header.
+    fn redirect(&self, location: &str) -> Option<Target> {
+        let location = location.trim();
+        let (tls, rest) = if let Some(rest) = location.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = location.strip_prefix("http://") {
+            (false, rest)
+        } else if let Some(rest) = location.strip_prefix("//") {
+            (self.tls, rest)
+        } else if location.starts_with('/') {
+            return Some(Target {
+                path: location.to_string(),
+                ..self.clone()
+            });
+        } else {
+            let base = &self.path[..self.path.rfind('/').map_or(0, |index| index + 1)];
+            return Some(Target {
+                path: format!("{}{}", if base.is_empty() { "/" } else { base }, location),
+                ..self.clone()
+            });
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            None => (rest, "/".to_string()),
+            Some(index) if rest[index..].starts_with('?') => {
+                (&rest[..index], format!("/{}", &rest[index..]))
+            }
+            Some(index) => (&rest[..index], rest[index..].to_string()),
+        };
+        let (host, port) = split_authority(authority)?;
+        let port = port.unwrap_or(if tls { 443 } else { 80 });
+        if let Ok(ip) = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            if ip != self.socket_addr.ip() {
+                return None;
+            }
+        }
+        Some(Target {
+            socket_addr: SocketAddr::new(self.socket_addr.ip(), port),
+            tls,
+            host: host.to_string(),
+            path,
+        })
+    }
+}
+
+fn split_authority(authority: &str) -> Option<(&str, Option<u16>)> {
+    let authority = authority.rsplit('@').next()?;
+    if authority.is_empty() {
+        return None;
+    }
+    let port_index = match authority.starts_with('[') {
+        true => authority.find("]:").map(|index| index + 1),
+        false => authority.rfind(':'),
+    };
+    match port_index {
+        None => Some((authority, None)),
+        Some(index) => Some((&authority[..index], authority[index + 1..].parse().ok())),
+    }
+}
+
+fn host_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
+/// Fetches the index page of the web service at `socket_addr`, following redirects, and
+/// collects what identifies the site. The final response is returned for further matching.
+pub async fn enrich(socket_addr: SocketAddr, tls: bool) -> Option<(HttpInfo, HttpResponse)> {
+    let mut target = Target {
+        socket_addr,
+        tls,
+        host: host_name(socket_addr.ip()),
+        path: "/".to_string(),
+    };
+    let mut redirects = vec![];
+    let mut response = get(&target, None).await.ok()?;
+    while matches!(response.status, 301 | 302 | 303 | 307 | 308) && redirects.len() < MAX_REDIRECTS
+    {
+        let next = match response
+            .header("location")
+            .and_then(|location| target.redirect(location))
+        {
+            None => break,
+            Some(next) => next,
+        };
+        redirects.push(target.url());
+        target = next;
+        response = match get(&target, None).await {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::debug!("follow redirect to {} failed: {}", target.url(), error);
+                return None;
+            }
+        };
+    }
+
+    let body = String::from_utf8_lossy(&response.body);
+    let favicon = favicon_path(&body).unwrap_or_else(|| "/favicon.ico".to_string());
+    let favicon_hash = match target.redirect(&favicon) {
+        None => None,
+        Some(favicon) => match get(&favicon, None).await {
+            Ok(favicon) if favicon.status == 200 && !favicon.body.is_empty() => {
+                Some(favicon_hash(&favicon.body))
+            }
+            _ => None,
+        },
+    };
+    let http_info = HttpInfo {
+        url: target.url(),
+        status: response.status,
+        server: response.header("server").map(str::to_string),
+        powered_by: response.header("x-powered-by").map(str::to_string),
+        title: parse_title(&body),
+        content_length: response
+            .header("content-length")
+            .and_then(|length| length.trim().parse().ok())
+            .unwrap_or(response.body.len()),
+        redirects,
+        favicon_hash,
+    };
+    Some((http_info, response))
+}
+
+/// Requests `path` once, without following redirects, sending `authorization` as the
+/// `Authorization` header when given.
+pub async fn fetch(
+    socket_addr: SocketAddr,
+    tls: bool,
+    path: &str,
+    authorization: Option<&str>,
+) -> Result<HttpResponse, String> {
+    let target = Target {
+        socket_addr,
+        tls,
+        host: host_name(socket_addr.ip()),
+        path: path.to_string(),
+    };
+    get(&target, authorization).await
+}
+
+async fn get(target: &Target, authorization: Option<&str>) -> Result<HttpResponse, String> {
+    let exchange = async {
+        let mut stream: Box<dyn ProbeStream> = match target.tls {
+            true => Box::new(tls::tls_connect(target.socket_addr).await?.0),
+            false => Box::new(
+                TcpStream::connect(target.socket_addr)
+                    .await
+                    .map_err(|error| error.to_string())?,
+            ),
+        };
+        let authorization = authorization
+            .map(|value| format!("Authorization: {}\r\n", value))
+            .unwrap_or_default();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\n{}Connection: close\r\n\r\n",
+            target.path,
+            target.authority(),
+            USER_AGENT,
+            authorization
+        );
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|error| error.to_string())?;
+        let mut buf = vec![];
+        // servers that drop the connection without a TLS close_notify still sent a response.
+        let _ = (&mut stream)
+            .take(MAX_RESPONSE_SIZE)
+            .read_to_end(&mut buf)
+            .await;
+        parse_response(&buf).ok_or_else(|| format!("invalid http response from {}", target.url()))
+    };
+    tokio::time::timeout(HTTP_TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("http request to {} timed out", target.url()))?
+}
+
+pub fn parse_response(buf: &[u8]) -> Option<HttpResponse> {
+    let header_end = buf.windows(4).position(|window| window == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&buf[..header_end]);
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next()?;
+    if !status_line.starts_with("HTTP/") {
+        return None;
+    }
+    let status = status_line.split_whitespace().nth(1)?.parse().ok()?;
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect::<Vec<(String, String)>>();
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: buf[header_end + 4..].to_vec(),
+    };
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.to_lowercase().contains("chunked"));
+    if chunked {
+        response.body = decode_chunked(&response.body);
+    }
+    Some(response)
+}
+
+fn decode_chunked(mut data: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+    while let Some(line_end) = data.windows(2).position(|window| window == b"\r\n") {
+        let size = String::from_utf8_lossy(&data[..line_end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = match usize::from_str_radix(size, 16) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => size,
+        };
+        let chunk = &data[line_end + 2..];
+        let size = size.min(chunk.len());
+        body.extend_from_slice(&chunk[..size]);
+        data = chunk.get(size + 2..).unwrap_or_default();
+    }
+    body
+}
+
+pub fn parse_title(body: &str) -> Option<String> {
+    let regex = regex::Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+    let title = regex.captures(body)?.get(1)?.as_str();
+    let title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if title.is_empty() {
+        return None;
+    }
+    Some(title)
+}
+
+fn favicon_path(body: &str) -> Option<String> {
+    let link = regex::Regex::new(r"(?is)<link\b[^>]*>").unwrap();
+    let rel = regex::Regex::new(r#"(?is)\brel\s*=\s*["']?[^"'>]*\bicon\b"#).unwrap();
+    let href = regex::Regex::new(r#"(?is)\bhref\s*=\s*["']?([^"'\s>]+)"#).unwrap();
+    let favicon = link
+        .find_iter(body)
+        .map(|tag| tag.as_str())
+        .filter(|tag| rel.is_match(tag))
+        .find_map(|tag| href.captures(tag)?.get(1))
+        .map(|href| href.as_str().to_string());
+    favicon.filter(|href| !href.starts_with("data:"))
+}
+
+/// The hash Shodan indexes favicons by: mmh3 over the base64 encoding with a newline every
+/// 76 characters, as python's `base64.encodebytes` produces.
+pub fn favicon_hash(data: &[u8]) -> i32 {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let mut lines = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for chunk in encoded.as_bytes().chunks(76) {
+        lines.push_str(&String::from_utf8_lossy(chunk));
+        lines.push('\n');
+    }
+    murmur3_32(lines.as_bytes(), 0) as i32
+}
+
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    let mut hash = seed;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        hash ^= mix(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe6546b64);
+    }
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0u32, |k, (index, byte)| k | (*byte as u32) << (8 * index));
+        hash ^= mix(k);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+pub mod http_test {
+    use crate::scanner::http::{favicon_path, murmur3_32, parse_response, parse_title, Target};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn parse_response_test() {
+        let buf = b"HTTP/1.1 200 OK\r\nServer: nginx/1.24.0\r\nTransfer-Encoding: chunked\r\n\r\n7\r\n<title>\r\n5;x=1\r\n home\r\n8\r\n</title>\r\n0\r\n\r\n";
+        let response = parse_response(buf).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("SERVER"), Some("nginx/1.24.0"));
+        assert_eq!(response.body, b"<title> home</title>");
+        assert_eq!(
+            parse_title(&String::from_utf8_lossy(&response.body)),
+            Some("home".to_string())
+        );
+        assert_eq!(parse_response(b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+    }
+
+    #[test]
+    fn redirect_test() {
+        let target = Target {
+            socket_addr: "10.0.0.1:8080".parse::<SocketAddr>().unwrap(),
+            tls: false,
+            host: "10.0.0.1".to_string(),
+            path: "/app/index".to_string(),
+        };
+        assert_eq!(target.redirect("login").unwrap().path, "/app/login");
+        assert_eq!(
+            target.redirect("/login").unwrap().url(),
+            "http://10.0.0.1:8080/login"
+        );
+        let https = target.redirect("https://example.com/a?b=1").unwrap();
+        assert_eq!(https.socket_addr, "10.0.0.1:443".parse().unwrap());
+        assert_eq!(https.url(), "https://example.com/a?b=1");
+        assert!(target.redirect("http://10.0.0.2/").is_none());
+    }
+
+    #[test]
+    fn favicon_test() {
+        assert_eq!(murmur3_32(b"", 0), 0);
+        assert_eq!(murmur3_32(b"hello", 0), 0x248bfa47);
+        assert_eq!(
+            favicon_path(r#"<link href="/static/icon.png" rel="shortcut icon">"#),
+            Some("/static/icon.png".to_string())
+        );
+        assert_eq!(
+            favicon_path(r#"<link rel="stylesheet" href="a.css">"#),
+            None
+        );
+    }
+}
@@ -1,28 +1,109 @@
-use crate::fingerprint::Fingerprint;
-use crate::scanner::ping::resolve_ping_ip;
+use crate::fingerprint::{Fingerprint, ServiceMatch};
+use crate::os::active::{self, OsDatabase};
+use crate::os::passive::{self, PassiveSignature};
+use crate::scanner::credential::{CredentialCheck, CredentialChecker};
+use crate::scanner::dns::DnsTransport;
+use crate::scanner::host::HostReply;
+use crate::scanner::plugin::{ProbeContext, ProbeRegistry, ResultSink, ServiceProbe};
+use crate::scanner::script::{Script, ScriptRunner};
 use crate::scanner::socket::{IpType, ScannerReply, Socket, TransportLayerProtocol};
 use crate::utils::address::SocketIterator;
+use crate::utils::port::PortSpec;
+use crate::vuln::VulnDatabase;
 use futures::future::join_all;
+use std::collections::{BTreeMap, HashMap};
 use std::net::{IpAddr, SocketAddr};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpStream, UdpSocket};
 
+mod credential;
+mod database;
+mod dns;
+mod host;
+mod http;
+mod netbios;
 mod ping;
+pub mod plugin;
+mod script;
+mod smb;
+mod snmp;
 mod socket;
+mod ssh;
+mod tls;
+mod tls_enum;
+
+pub use ping::ping_hosts;
+
+/// Hosts fingerprinted at the same time during active OS detection.
+const OS_DETECTION_CONCURRENCY: usize = 16;
+const PROBE_READ_TIMEOUT: Duration = Duration::from_millis(5000);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// A connected stream the service probes run over, plain TCP or a TLS tunnel.
+trait ProbeStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ProbeStream for T {}
+
+/// Optional per port checks, passed down to every socket scan.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeOptions {
+    /// enumerate protocol versions and cipher suites of TLS services.
+    pub tls_enumeration: bool,
+    /// zones to request a transfer (AXFR) of from DNS services.
+    pub dns_zones: Vec<String>,
+    /// community strings tried against SNMP agents.
+    pub snmp_communities: Vec<String>,
+    /// default and anonymous logins tried once a service is identified, off unless enabled.
+    pub credential_checker: Option<Arc<CredentialChecker>>,
+    /// offline CVE database the detected CPEs are matched against, off unless enabled.
+    pub vulnerability_database: Option<Arc<VulnDatabase>>,
+    /// check scripts run against every identified port, off unless selected.
+    pub script_runner: Option<Arc<ScriptRunner>>,
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Scanner {
     ips: Vec<IpAddr>,
-    ports: Vec<u16>,
-    batch_size: u16,
+    ports: PortSpec,
+    batch_size: usize,
+    /// connect timeout of the port scan.
+    connect_timeout: Duration,
     ping: bool,
+    ping_count: u16,
+    ping_ttl: HashMap<IpAddr, u8>,
+    os_database: Option<String>,
+    probe_options: ProbeOptions,
+    /// probes run after the built-in ones and the sinks receiving the results.
+    registry: ProbeRegistry,
 }
 
 impl Scanner {
-    pub async fn new(ips: Vec<IpAddr>, ports: Vec<u16>, batch_size: u16, ping: bool) -> Self {
+    /// The UDP ports with a probe, the others can't tell open from filtered.
+    const UDP_PROBE_PORTS: [u16; 2] = [dns::DNS_PORT, snmp::SNMP_PORT];
+
+    pub async fn new(
+        ips: Vec<IpAddr>,
+        ports: PortSpec,
+        batch_size: usize,
+        ping: bool,
+        ping_count: u16,
+    ) -> Self {
+        let mut ping_ttl = HashMap::new();
         let ips = if ping {
-            resolve_ping_ip(ips).await
+            let mut alive = vec![];
+            for statistics in ping_hosts(ips, ping_count).await {
+                if !statistics.is_alive() {
+                    continue;
+                }
+                if let Some(ttl) = statistics.ttl {
+                    ping_ttl.insert(statistics.host, ttl);
+                }
+                alive.push(statistics.host);
+            }
+            alive
         } else {
             ips
         };
@@ -30,142 +111,478 @@ impl Scanner {
             ips,
             ports,
             batch_size,
+            connect_timeout: CONNECT_TIMEOUT,
             ping,
+            ping_count,
+            ping_ttl,
+            os_database: None,
+            probe_options: ProbeOptions::default(),
+            registry: ProbeRegistry::default(),
         }
     }
-    pub async fn run(self) {
-        let ports = self.ports;
-        let ips = self.ips;
 
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    /// Enables active OS detection, matching against the signature database at `os_database`.
+    pub fn set_os_detection(&mut self, os_database: Option<String>) {
+        self.os_database = os_database;
+    }
+
+    pub fn set_tls_enumeration(&mut self, enabled: bool) {
+        self.probe_options.tls_enumeration = enabled;
+    }
+
+    pub fn set_dns_zones(&mut self, zones: Vec<String>) {
+        self.probe_options.dns_zones = zones;
+    }
+
+    /// Loads the community wordlist SNMP agents on UDP 161 are probed with.
+    pub fn set_snmp_communities(&mut self, path: &str) {
+        self.probe_options.snmp_communities = snmp::read_communities(path);
+    }
+
+    /// Enables the default credential checks of the file at `path`, trying at most
+    /// `max_attempts` logins against each host.
+    pub fn set_credential_checks(&mut self, path: &str, max_attempts: usize) {
+        let checks = CredentialCheck::from_file(path);
+        self.probe_options.credential_checker =
+            Some(Arc::new(CredentialChecker::new(checks, max_attempts)));
+    }
+
+    /// Loads the CVE feeds or index at `path` and flags ports whose CPEs are affected.
+    pub fn set_vulnerability_database(&mut self, path: &str) {
+        self.probe_options.vulnerability_database = Some(Arc::new(VulnDatabase::from_path(path)));
+    }
+
+    /// Loads the scripts of `dir` and keeps the ones named or categorized by `selectors`.
+    pub fn set_scripts(&mut self, dir: &str, selectors: &[String]) {
+        let runner = ScriptRunner::new(Script::from_dir(dir), selectors);
+        self.probe_options.script_runner = Some(Arc::new(runner));
+    }
+
+    /// Adds a probe run against every open TCP port after the built-in ones.
+    pub fn register_probe(&mut self, probe: Arc<dyn ServiceProbe>) {
+        self.registry.register_probe(probe);
+    }
+
+    /// Adds a sink receiving every port and host as the scan progresses.
+    pub fn register_sink(&mut self, sink: Arc<dyn ResultSink>) {
+        self.registry.register_sink(sink);
+    }
+
+    pub async fn run(self) -> Vec<HostReply> {
+        let mut ports = self.ports.clone();
+        let ips = self.ips.clone();
+        let mut hosts: BTreeMap<IpAddr, HostReply> = BTreeMap::new();
+        for (ip_addr, ttl) in self.ping_ttl.iter() {
+            let host = hosts
+                .entry(*ip_addr)
+                .or_insert_with(|| HostReply::new(*ip_addr));
+            host.os_observation_mut().ttl = Some(*ttl);
+        }
+
+        let mut registry = ProbeRegistry::builtin(&self.probe_options);
+        registry.extend(self.registry.clone());
+        let registry = Arc::new(registry);
+
+        if !ports.sctp.is_empty() {
+            tracing::warn!(
+                "SCTP scanning is not supported, skipping {} SCTP ports.",
+                ports.sctp.len()
+            );
+        }
+        let unprobed = ports
+            .udp
+            .iter()
+            .filter(|port| !Scanner::UDP_PROBE_PORTS.contains(port))
+            .count();
+        if unprobed > 0 {
+            tracing::warn!(
+                "no probe for UDP ports other than DNS (53) and SNMP (161), skipping {} UDP ports.",
+                unprobed
+            );
+            ports
+                .udp
+                .retain(|port| Scanner::UDP_PROBE_PORTS.contains(port));
+        }
         let (tx, mut rx) = tokio::sync::mpsc::channel(10);
 
-        tokio::spawn(Scanner::scan_socket_list(ips, ports, tx));
+        tokio::spawn(Scanner::scan_socket_list(
+            ips,
+            ports,
+            self.batch_size,
+            self.connect_timeout,
+            self.probe_options.clone(),
+            registry.clone(),
+            tx,
+        ));
 
         while let Some(msg) = rx.recv().await {
-            let transport_layer_protocol = msg.transport_layer_protocol().unwrap();
-            match transport_layer_protocol {
-                TransportLayerProtocol::TCP => {
-                    tracing::info!(
-                        "{}:{} [TCP|{}|{}]",
-                        msg.ip_addr().unwrap(),
-                        msg.port().unwrap(),
-                        msg.name(),
-                        match msg.version() {
-                            None => "*".to_string(),
-                            Some(version) => version,
-                        }
-                    )
+            for sink in registry.sinks() {
+                sink.port(&msg);
+            }
+            let ip_addr = msg.ip_addr().unwrap();
+            let host = hosts
+                .entry(ip_addr)
+                .or_insert_with(|| HostReply::new(ip_addr));
+            if let Some(observation) = msg.os_observation() {
+                host.os_observation_mut().merge(observation);
+            }
+            host.add_port(msg);
+        }
+        tracing::info!("run scan socket finished.");
+
+        let mut hosts = hosts.into_values().collect::<Vec<HostReply>>();
+        Scanner::guess_os(&mut hosts);
+        if let Some(os_database) = &self.os_database {
+            self.detect_os(&mut hosts, os_database).await;
+        }
+        Scanner::enumerate_smb(&mut hosts).await;
+        for host in hosts.iter() {
+            for sink in registry.sinks() {
+                sink.host(host);
+            }
+        }
+        for sink in registry.sinks() {
+            sink.finish(&hosts);
+        }
+        hosts
+    }
+
+    /// Negotiates SMB on hosts with 445 or 139 open and asks them for their NetBIOS names.
+    async fn enumerate_smb(hosts: &mut [HostReply]) {
+        let mut tasks = vec![];
+        for (index, host) in hosts.iter().enumerate() {
+            let open_ports = host.open_tcp_ports();
+            let port = [smb::SMB_PORT, smb::NETBIOS_SESSION_PORT]
+                .into_iter()
+                .find(|port| open_ports.contains(port));
+            let port = match port {
+                None => continue,
+                Some(port) => port,
+            };
+            let ip_addr = host.ip_addr();
+            tasks.push(async move {
+                let (smb_info, netbios_info) = tokio::join!(
+                    smb::probe(SocketAddr::new(ip_addr, port)),
+                    netbios::query(ip_addr)
+                );
+                (index, smb_info, netbios_info)
+            });
+        }
+        for (index, smb_info, netbios_info) in join_all(tasks).await {
+            hosts[index].set_smb(smb_info);
+            hosts[index].set_netbios(netbios_info);
+        }
+    }
+
+    fn guess_os(hosts: &mut [HostReply]) {
+        if hosts.is_empty() {
+            return;
+        }
+        let signatures = PassiveSignature::get_signatures();
+        for host in hosts.iter_mut() {
+            let os_guess = passive::guess(host.os_observation(), &signatures);
+            host.set_os_guess(os_guess);
+        }
+    }
+
+    /// Runs the active probe battery against every host with an open TCP port, a match
+    /// replaces the passive guess.
+    async fn detect_os(&self, hosts: &mut [HostReply], os_database: &str) {
+        let database = OsDatabase::from_file(os_database);
+        for chunk in hosts.chunks_mut(OS_DETECTION_CONCURRENCY) {
+            let mut tasks = vec![];
+            for host in chunk.iter() {
+                let target = match host.ip_addr() {
+                    IpAddr::V4(ip) => ip,
+                    IpAddr::V6(_) => continue,
+                };
+                let open_ports = host.open_tcp_ports();
+                let open_port = match open_ports.first() {
+                    None => continue,
+                    Some(port) => *port,
+                };
+                let closed_port = self
+                    .ports
+                    .tcp
+                    .iter()
+                    .find(|port| !open_ports.contains(port))
+                    .copied()
+                    .unwrap_or_else(|| Scanner::random_closed_port(&open_ports));
+                let closed_udp_port = Scanner::random_closed_port(&[]);
+                tasks.push(tokio::task::spawn_blocking(move || {
+                    let result =
+                        active::fingerprint_host(target, open_port, closed_port, closed_udp_port);
+                    (IpAddr::V4(target), result)
+                }));
+            }
+            for task in join_all(tasks).await {
+                let (ip_addr, result) = match task {
+                    Ok(data) => data,
+                    Err(_) => continue,
+                };
+                let fingerprint = match result {
+                    Ok(fingerprint) => fingerprint,
+                    Err(error) => {
+                        tracing::warn!("os detection on {} failed: {}", ip_addr, error);
+                        continue;
+                    }
+                };
+                tracing::debug!(
+                    "{} os fingerprint:\n{}",
+                    ip_addr,
+                    active::format_tests(&fingerprint)
+                );
+                let host = chunk.iter_mut().find(|host| host.ip_addr() == ip_addr);
+                if let Some(host) = host {
+                    if let Some(os_guess) = database.matches(&fingerprint) {
+                        host.set_os_guess(Some(os_guess));
+                    }
+                    host.set_os_fingerprint(Some(fingerprint));
                 }
-                TransportLayerProtocol::UDP => {}
             }
         }
+    }
 
-        tracing::info!("run scan socket finished.")
+    fn random_closed_port(open_ports: &[u16]) -> u16 {
+        loop {
+            let port = 30000 + rand::random::<u16>() % 30000;
+            if !open_ports.contains(&port) {
+                return port;
+            }
+        }
     }
 
     async fn scan_socket_list(
         ips: Vec<IpAddr>,
-        ports: Vec<u16>,
+        ports: PortSpec,
+        batch_size: usize,
+        connect_timeout: Duration,
+        options: ProbeOptions,
+        registry: Arc<ProbeRegistry>,
         tx: tokio::sync::mpsc::Sender<ScannerReply>,
     ) {
-        let mut socket_iterator: SocketIterator = SocketIterator::new(&ips, &ports);
+        let tcp_ports = Vec::from_iter(ports.tcp);
+        let udp_ports = Vec::from_iter(ports.udp);
         let mut handles = vec![];
-        while let Some(socket_addr) = socket_iterator.next() {
+        for socket_addr in SocketIterator::new(&ips, &tcp_ports) {
+            let tx1 = tx.clone();
+            handles.push(Scanner::scan_socket(
+                socket_addr,
+                TransportLayerProtocol::TCP,
+                connect_timeout,
+                &options,
+                &registry,
+                tx1,
+            ));
+        }
+        for socket_addr in SocketIterator::new(&ips, &udp_ports) {
             let tx1 = tx.clone();
-            handles.push(Scanner::scan_socket(socket_addr, tx1));
+            handles.push(Scanner::scan_socket(
+                socket_addr,
+                TransportLayerProtocol::UDP,
+                connect_timeout,
+                &options,
+                &registry,
+                tx1,
+            ));
         }
 
-        let batch_size = 1000;
-        while let false = handles.is_empty() {
+        let batch_size = batch_size.max(1);
+        let total = handles.len();
+        while !handles.is_empty() {
+            // the scan was cancelled, nobody receives the results anymore.
+            if tx.is_closed() {
+                tracing::info!("scan cancelled, {} sockets left.", handles.len());
+                return;
+            }
             let mut drain = vec![];
             if handles.len() > batch_size {
                 drain = handles.drain(batch_size..).collect::<Vec<_>>();
             }
             join_all(handles).await;
             handles = drain;
+            for sink in registry.sinks() {
+                sink.progress(total - handles.len(), total);
+            }
         }
     }
-    async fn scan_socket(socket_addr: SocketAddr, tx: tokio::sync::mpsc::Sender<ScannerReply>) {
-        match Scanner::tcp_connect(socket_addr).await {
-            Ok(tcp_stream) => {
-                Self::check_tcp_connect(tcp_stream, tx).await;
+    /// UDP ports are only reported for the services there is a probe for, DNS and SNMP.
+    async fn scan_socket(
+        socket_addr: SocketAddr,
+        protocol: TransportLayerProtocol,
+        connect_timeout: Duration,
+        options: &ProbeOptions,
+        registry: &ProbeRegistry,
+        tx: tokio::sync::mpsc::Sender<ScannerReply>,
+    ) {
+        // the scan was cancelled, nobody receives the results anymore.
+        if tx.is_closed() {
+            return;
+        }
+        let cancelled = tx.clone();
+        let scan = async move {
+            match protocol {
+                TransportLayerProtocol::TCP => {
+                    if let Ok(tcp_stream) = Scanner::tcp_connect(socket_addr, connect_timeout).await
+                    {
+                        Self::check_tcp_connect(tcp_stream, registry, tx).await;
+                    }
+                }
+                TransportLayerProtocol::UDP => match socket_addr.port() {
+                    dns::DNS_PORT => Self::check_udp_dns(socket_addr, tx).await,
+                    snmp::SNMP_PORT => Self::check_udp_snmp(socket_addr, options, tx).await,
+                    _ => {}
+                },
             }
-            Err(_error) => {
-                // todo!("when tcp can't connect used udp socket check")
+        };
+        tokio::select! {
+            _ = scan => {}
+            _ = cancelled.closed() => {}
+        }
+    }
+
+    /// SNMP agents stay silent unless the community is right, so the port is only
+    /// reported once one from the wordlist was accepted.
+    async fn check_udp_snmp(
+        socket_addr: SocketAddr,
+        options: &ProbeOptions,
+        tx: tokio::sync::mpsc::Sender<ScannerReply>,
+    ) {
+        let mut communities = options.snmp_communities.clone();
+        if let Some(checker) = &options.credential_checker {
+            for community in checker.snmp_communities() {
+                if !communities.contains(&community) {
+                    communities.push(community);
+                }
             }
+        }
+        let snmp_info = match snmp::probe(socket_addr, &communities).await {
+            None => return,
+            Some(data) => data,
+        };
+        let mut scanner_reply = ScannerReply::new();
+        scanner_reply.set_activity(true);
+        scanner_reply.set_transport_layer_protocol(Some(TransportLayerProtocol::UDP));
+        scanner_reply.set_ip_addr(Some(socket_addr.ip()));
+        scanner_reply.set_port(Some(socket_addr.port()));
+        scanner_reply.set_name("snmp".to_string());
+        scanner_reply.set_version(snmp_info.version.to_string());
+        if let Some(checker) = &options.credential_checker {
+            scanner_reply.set_credentials(checker.snmp_findings(&snmp_info.communities));
+        }
+        scanner_reply.set_snmp(Some(snmp_info));
+        // fails once the scan is cancelled, the reply is dropped then.
+        let _ = tx.send(scanner_reply).await;
+    }
+
+    /// DNS is mostly served over UDP, the port is reported when the server answers a query.
+    async fn check_udp_dns(socket_addr: SocketAddr, tx: tokio::sync::mpsc::Sender<ScannerReply>) {
+        let dns_info = match dns::probe(socket_addr, DnsTransport::Udp, &[]).await {
+            None => return,
+            Some(data) => data,
         };
+        let mut scanner_reply = ScannerReply::new();
+        scanner_reply.set_activity(true);
+        scanner_reply.set_transport_layer_protocol(Some(TransportLayerProtocol::UDP));
+        scanner_reply.set_ip_addr(Some(socket_addr.ip()));
+        scanner_reply.set_port(Some(socket_addr.port()));
+        scanner_reply.set_name("domain".to_string());
+        if let Some(version) = &dns_info.version {
+            scanner_reply.set_version(version.clone());
+        }
+        scanner_reply.set_dns(Some(dns_info));
+        let _ = tx.send(scanner_reply).await;
     }
 
     async fn check_tcp_connect(
-        mut tcp_stream: TcpStream,
+        tcp_stream: TcpStream,
+        registry: &ProbeRegistry,
         tx: tokio::sync::mpsc::Sender<ScannerReply>,
     ) {
+        let socket_addr = tcp_stream.peer_addr().unwrap();
         let mut scanner_reply = ScannerReply::new();
         scanner_reply.set_activity(true);
         scanner_reply.set_transport_layer_protocol(Some(TransportLayerProtocol::TCP));
-        scanner_reply.set_ip_addr(Some(tcp_stream.peer_addr().unwrap().ip()));
-        scanner_reply.set_port(Some(tcp_stream.peer_addr().unwrap().port()));
-
-        let fingerprint = match Fingerprint::get_tcp_fingerprint() {
-            None => {
-                println!("send1");
-                tx.send(scanner_reply).await.unwrap();
-                return;
-            }
-            Some(data) => data,
+        scanner_reply.set_ip_addr(Some(socket_addr.ip()));
+        scanner_reply.set_port(Some(socket_addr.port()));
+        scanner_reply.set_os_observation(passive::tcp_info_observation(&tcp_stream));
+        let mut context = ProbeContext {
+            socket_addr,
+            stream: Some(tcp_stream),
         };
-        for probe in fingerprint.probes {
-            let probe_string = probe.probe_string;
-            tcp_stream.write_all(probe_string.as_bytes()).await.unwrap();
-            let mut buf = Vec::new();
-            tcp_stream.flush().await.unwrap();
-            tcp_stream.shutdown().await.unwrap();
-            tcp_stream.read_to_end(&mut buf).await.unwrap();
-            for match_info in probe.matches {
-                let pattern = match_info.pattern;
-                if pattern.is_empty() {
-                    tx.send(scanner_reply).await.unwrap();
-                    return;
+        registry.probe(&mut context, &mut scanner_reply).await;
+        let _ = tx.send(scanner_reply).await;
+    }
+
+    /// Runs the probes until a match rule hits and returns the service name, version and
+    /// CPE. The first probe reuses `stream`, the following ones open a new connection each.
+    async fn match_probes(
+        stream: Box<dyn ProbeStream>,
+        socket_addr: SocketAddr,
+        tls: bool,
+        fingerprint: &Fingerprint,
+    ) -> Option<ServiceMatch> {
+        let mut stream = Some(stream);
+        for probe in fingerprint.probes.iter() {
+            let mut current = match stream.take() {
+                Some(data) => data,
+                None => Scanner::open_probe_stream(socket_addr, tls).await?,
+            };
+            let buf = Scanner::read_banner(&mut current, &probe.probe_string).await;
+            let banner = String::from_utf8_lossy(&buf);
+            for match_info in probe.matches.iter() {
+                if match_info.pattern.is_empty() {
+                    return None;
                 }
-                let server_name = match_info.name;
-                let regex = regex::Regex::new(&pattern).unwrap();
-                let banner = format!("{:#}", String::from_utf8_lossy(&buf)).clone();
-                let caps = regex.captures(&banner);
-                if caps.is_some() {
-                    scanner_reply.set_name(server_name);
-                    let caps = caps.unwrap();
-                    if caps.name("version").is_some() {
-                        scanner_reply
-                            .set_version(caps.name("version").unwrap().as_str().to_string())
-                    }
-                    tx.send(scanner_reply).await.unwrap();
-                    return;
+                if let Some(service) = match_info.match_banner(&banner) {
+                    return Some(service);
                 }
-                // if regex.is_match(&banner) {
-                //     if caps.is_some() {
-                //         let caps = caps.unwrap();
-                //         println!("{:?}", &caps.name("title"));
-                //     }
-                // }
             }
         }
-        tx.send(scanner_reply).await.unwrap();
+        None
     }
 
-    async fn tcp_connect(socket_addr: SocketAddr) -> Result<TcpStream, SocketAddr> {
+    async fn open_probe_stream(socket_addr: SocketAddr, tls: bool) -> Option<Box<dyn ProbeStream>> {
+        if tls {
+            let (tls_stream, _) = tls::tls_connect(socket_addr).await.ok()?;
+            return Some(Box::new(tls_stream));
+        }
+        let tcp_stream = Scanner::tcp_connect(socket_addr, CONNECT_TIMEOUT)
+            .await
+            .ok()?;
+        Some(Box::new(tcp_stream))
+    }
+
+    /// Sends `probe_string`, closes the write half and reads whatever the service answers.
+    async fn read_banner(stream: &mut Box<dyn ProbeStream>, probe_string: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if stream.write_all(probe_string.as_bytes()).await.is_err() || stream.flush().await.is_err()
+        {
+            return buf;
+        }
+        let _ = stream.shutdown().await;
+        let _ = tokio::time::timeout(PROBE_READ_TIMEOUT, stream.read_to_end(&mut buf)).await;
+        buf
+    }
+
+    async fn tcp_connect(
+        socket_addr: SocketAddr,
+        connect_timeout: Duration,
+    ) -> Result<TcpStream, SocketAddr> {
         let tcp_socket = Socket::get_tcp_socket(IpType::V4);
-        let tcp_stream = tokio::time::timeout(
-            tokio::time::Duration::from_millis(1000),
-            tcp_socket.connect(socket_addr),
-        )
-        .await;
-        return match tcp_stream {
+        let tcp_stream =
+            tokio::time::timeout(connect_timeout, tcp_socket.connect(socket_addr)).await;
+        match tcp_stream {
             Ok(connection_result) => match connection_result {
                 Ok(tcp_stream) => Ok(tcp_stream),
                 Err(_error) => Err(socket_addr),
             },
             Err(_error) => Err(socket_addr),
-        };
+        }
     }
 
     #[allow(dead_code)]
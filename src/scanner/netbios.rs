@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const NETBIOS_NAME_PORT: u16 = 137;
+const NBSTAT_TIMEOUT: Duration = Duration::from_millis(2000);
+const NBSTAT_TYPE: u16 = 0x0021;
+const GROUP_NAME_FLAG: u16 = 0x8000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetbiosName {
+    pub name: String,
+    pub suffix: u8,
+    pub group: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NetbiosInfo {
+    pub names: Vec<NetbiosName>,
+    pub computer_name: Option<String>,
+    pub workgroup: Option<String>,
+    pub mac_address: Option<String>,
+}
+
+impl std::fmt::Display for NetbiosInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}",
+            self.computer_name.as_deref().unwrap_or("*"),
+            self.workgroup.as_deref().unwrap_or("*"),
+            self.mac_address.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// First level NetBIOS name encoding, `name` is padded to 15 bytes plus the suffix.
+pub fn encode_name(name: &[u8], padding: u8, suffix: u8) -> Vec<u8> {
+    let mut raw = name.iter().take(15).copied().collect::<Vec<u8>>();
+    raw.resize(15, padding);
+    raw.push(suffix);
+    let mut encoded = vec![32];
+    for byte in raw {
+        encoded.push(b'A' + (byte >> 4));
+        encoded.push(b'A' + (byte & 0x0f));
+    }
+    encoded.push(0);
+    encoded
+}
+
+fn node_status_request(transaction_id: u16) -> Vec<u8> {
+    let mut request = transaction_id.to_be_bytes().to_vec();
+    // flags, one question, no answer, authority or additional records.
+    request.extend([0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    request.extend(encode_name(b"*", 0, 0));
+    request.extend(NBSTAT_TYPE.to_be_bytes());
+    request.extend(1u16.to_be_bytes());
+    request
+}
+
+fn parse_node_status(response: &[u8], transaction_id: u16) -> Option<NetbiosInfo> {
+    let header = response.get(..12)?;
+    if header[..2] != transaction_id.to_be_bytes() || header[6..8] == [0, 0] {
+        return None;
+    }
+    // the answer name is either a full encoded name or a compression pointer.
+    let mut index = 12;
+    index += match response.get(index)? {
+        length if length & 0xc0 == 0xc0 => 2,
+        length => *length as usize + 2,
+    };
+    if u16::from_be_bytes([*response.get(index)?, *response.get(index + 1)?]) != NBSTAT_TYPE {
+        return None;
+    }
+    // type, class, ttl and rdlength.
+    index += 10;
+    let count = *response.get(index)? as usize;
+    index += 1;
+    let mut info = NetbiosInfo::default();
+    for _ in 0..count {
+        let entry = response.get(index..index + 18)?;
+        let name = String::from_utf8_lossy(&entry[..15]).trim_end().to_string();
+        let suffix = entry[15];
+        let group = u16::from_be_bytes([entry[16], entry[17]]) & GROUP_NAME_FLAG != 0;
+        if suffix == 0 {
+            match group {
+                false if info.computer_name.is_none() => info.computer_name = Some(name.clone()),
+                true if info.workgroup.is_none() => info.workgroup = Some(name.clone()),
+                _ => {}
+            }
+        }
+        info.names.push(NetbiosName {
+            name,
+            suffix,
+            group,
+        });
+        index += 18;
+    }
+    let mac = response
+        .get(index..index + 6)
+        .filter(|mac| mac.iter().any(|byte| *byte != 0));
+    info.mac_address = mac.map(|mac| {
+        mac.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(":")
+    });
+    Some(info)
+}
+
+/// Sends a NetBIOS node status request to UDP 137 and lists the names the host registered.
+pub async fn query(ip_addr: IpAddr) -> Option<NetbiosInfo> {
+    let bind_addr = match ip_addr {
+        IpAddr::V4(_) => "0.0.0.0:0",
+        IpAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(bind_addr).await.ok()?;
+    socket
+        .connect(SocketAddr::new(ip_addr, NETBIOS_NAME_PORT))
+        .await
+        .ok()?;
+    let transaction_id = rand::random::<u16>();
+    socket
+        .send(&node_status_request(transaction_id))
+        .await
+        .ok()?;
+    let mut buf = [0u8; 2048];
+    let size = tokio::time::timeout(NBSTAT_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+    parse_node_status(&buf[..size], transaction_id)
+}
+
+#[cfg(test)]
+pub mod netbios_test {
+    use crate::scanner::netbios::{encode_name, node_status_request, parse_node_status};
+
+    #[test]
+    fn node_status_test() {
+        let request = node_status_request(0x1234);
+        assert_eq!(request.len(), 12 + 34 + 4);
+        assert_eq!(&encode_name(b"*", 0, 0)[1..5], b"CKAA");
+
+        let mut response = vec![0x12, 0x34, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        response.extend(encode_name(b"*", 0, 0));
+        response.extend([0, 0x21, 0, 1, 0, 0, 0, 0, 0, 101, 3]);
+        for (name, suffix, flags) in [
+            ("FILESRV", 0x00u8, 0x0400u16),
+            ("CORP", 0x00, 0x8400),
+            ("FILESRV", 0x20, 0x0400),
+        ] {
+            let mut entry = format!("{:<15}", name).into_bytes();
+            entry.push(suffix);
+            entry.extend(flags.to_be_bytes());
+            response.extend(entry);
+        }
+        response.extend([0x00, 0x0c, 0x29, 0xab, 0xcd, 0xef]);
+        let info = parse_node_status(&response, 0x1234).unwrap();
+        assert_eq!(info.names.len(), 3);
+        assert_eq!(info.computer_name.as_deref(), Some("FILESRV"));
+        assert_eq!(info.workgroup.as_deref(), Some("CORP"));
+        assert_eq!(info.mac_address.as_deref(), Some("00:0c:29:ab:cd:ef"));
+        assert!(parse_node_status(&response, 0x4321).is_none());
+    }
+}
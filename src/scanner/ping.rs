@@ -1,97 +1,226 @@
 use colored::Colorize;
-use pnet::packet::icmp::echo_request::MutableEchoRequestPacket;
-use pnet::packet::icmp::IcmpTypes;
+use pnet::packet::icmp::destination_unreachable::DestinationUnreachablePacket;
+use pnet::packet::icmp::echo_reply::EchoReplyPacket;
+use pnet::packet::icmp::echo_request::{EchoRequestPacket, MutableEchoRequestPacket};
+use pnet::packet::icmp::{IcmpPacket, IcmpTypes};
+use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::util;
 use pnet::packet::Packet;
-use socket2::{Domain, Protocol, Socket, Type};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::collections::HashMap;
-use std::net::{IpAddr, SocketAddr};
-use std::os::fd::{FromRawFd, IntoRawFd};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::io::Interest;
 use tokio::sync::oneshot::Receiver;
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
 
-pub async fn resolve_ping_ip(ips: Vec<IpAddr>) -> Vec<IpAddr> {
-    let client = Client::new().unwrap();
+const PING_TIMEOUT: Duration = Duration::from_millis(500);
+const PING_PAYLOAD_SIZE: usize = 56;
+
+/// Pings every host `count` times and returns the per host statistics.
+pub async fn ping_hosts(ips: Vec<IpAddr>, count: u16) -> Vec<PingStatistics> {
+    let client = match Client::new() {
+        Ok(client) => client,
+        Err(error) => {
+            tracing::warn!("create icmp socket error: {}", error);
+            return vec![];
+        }
+    };
     let mut task = vec![];
     for ip in ips {
-        task.push(ping(client.clone(), ip));
+        task.push(ping(client.clone(), ip, count));
     }
-    let mut result: Vec<Result<String, String>> = futures::future::join_all(task).await;
-    result.retain(|item| {
-        if item.is_err() {
+    let result: Vec<PingStatistics> = futures::future::join_all(task).await;
+    for statistics in result.iter() {
+        if statistics.is_alive() {
             tracing::info!(
-                "ping {} {}",
-                item.as_ref().err().unwrap(),
-                "don't connection".to_string().red()
+                "ping {} {} {}",
+                statistics.host,
+                "connection".to_string().green(),
+                statistics
             );
-        }
-        item.is_ok()
-    });
-    result
-        .into_iter()
-        .map(|item| {
+        } else {
             tracing::info!(
-                "ping {} {}",
-                &item.as_ref().unwrap(),
-                "connection".to_string().green()
+                "ping {} {} {}",
+                statistics.host,
+                "don't connection".to_string().red(),
+                statistics
             );
-            item.unwrap().parse::<IpAddr>().unwrap()
-        })
-        .collect::<Vec<IpAddr>>()
+        }
+    }
+    result
 }
 
-async fn ping(client: Client, addr: IpAddr) -> Result<String, String> {
-    let payload = [0; 56];
+async fn ping(client: Client, addr: IpAddr, count: u16) -> PingStatistics {
+    let payload = [0; PING_PAYLOAD_SIZE];
     let mut pinger = client.pinger(addr).await;
-    let result = pinger.ping(&payload).await;
-    result
+    let mut statistics = PingStatistics::new(addr);
+    for _ in 0..count.max(1) {
+        let outcome = pinger.ping(&payload).await;
+        statistics.record(&outcome);
+        if let EchoOutcome::Unreachable { .. } = outcome {
+            break;
+        }
+    }
+    statistics
+}
+
+/// The result of a single echo request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EchoOutcome {
+    Reply { rtt: Duration, ttl: Option<u8> },
+    Unreachable { from: IpAddr, code: u8 },
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct PingStatistics {
+    pub host: IpAddr,
+    pub transmitted: u16,
+    pub received: u16,
+    pub unreachable: Option<u8>,
+    pub min_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    pub avg_rtt: Option<Duration>,
+    pub ttl: Option<u8>,
+}
+
+impl PingStatistics {
+    pub fn new(host: IpAddr) -> Self {
+        Self {
+            host,
+            transmitted: 0,
+            received: 0,
+            unreachable: None,
+            min_rtt: None,
+            max_rtt: None,
+            avg_rtt: None,
+            ttl: None,
+        }
+    }
+
+    pub fn record(&mut self, outcome: &EchoOutcome) {
+        self.transmitted += 1;
+        match outcome {
+            EchoOutcome::Reply { rtt, ttl } => {
+                let total = self.avg_rtt.unwrap_or_default() * self.received as u32 + *rtt;
+                self.received += 1;
+                self.avg_rtt = Some(total / self.received as u32);
+                self.min_rtt = Some(self.min_rtt.map_or(*rtt, |min| min.min(*rtt)));
+                self.max_rtt = Some(self.max_rtt.map_or(*rtt, |max| max.max(*rtt)));
+                if ttl.is_some() {
+                    self.ttl = *ttl;
+                }
+            }
+            EchoOutcome::Unreachable { code, .. } => self.unreachable = Some(*code),
+            EchoOutcome::Timeout => {}
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.received > 0
+    }
+
+    /// Packet loss as a percentage of the transmitted echo requests.
+    pub fn loss(&self) -> f64 {
+        if self.transmitted == 0 {
+            return 100.0;
+        }
+        (self.transmitted - self.received) as f64 * 100.0 / self.transmitted as f64
+    }
+}
+
+impl std::fmt::Display for PingStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}/{} received, {:.1}% loss",
+            self.received,
+            self.transmitted,
+            self.loss()
+        )?;
+        if let (Some(min), Some(avg), Some(max)) = (self.min_rtt, self.avg_rtt, self.max_rtt) {
+            write!(
+                f,
+                ", rtt min/avg/max {:.3}/{:.3}/{:.3} ms",
+                min.as_secs_f64() * 1000.0,
+                avg.as_secs_f64() * 1000.0,
+                max.as_secs_f64() * 1000.0
+            )?;
+        }
+        if let Some(ttl) = self.ttl {
+            write!(f, ", ttl {}", ttl)?;
+        }
+        if let Some(code) = self.unreachable {
+            write!(f, ", destination unreachable (code {})", code)?;
+        }
+        write!(f, "]")
+    }
 }
 
 #[derive(Clone)]
 struct AsyncSocket {
     inner: Arc<tokio::net::UdpSocket>,
+    ident: u16,
 }
 
 impl AsyncSocket {
-    pub fn new() -> Self {
-        let socket = make_socket();
-        Self {
+    pub fn new() -> std::io::Result<Self> {
+        let (socket, ident) = make_socket()?;
+        Ok(Self {
             inner: Arc::new(socket),
-        }
+            ident,
+        })
     }
 
-    pub async fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
-        self.inner.recv_from(buf).await
+    /// Receives a packet along with the TTL the kernel reports for it, if any.
+    pub async fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, SocketAddr, Option<u8>)> {
+        let fd = self.inner.as_raw_fd();
+        self.inner
+            .async_io(Interest::READABLE, || recv_with_ttl(fd, buf))
+            .await
     }
 
-    pub async fn send_to(&self, buf: &mut [u8], target: &SocketAddr) -> std::io::Result<usize> {
+    pub async fn send_to(&self, buf: &[u8], target: &SocketAddr) -> std::io::Result<usize> {
         self.inner.send_to(buf, target).await
     }
 }
 
+/// Echo replies are correlated by the probed host, the ICMP identifier and the sequence number.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct ReplyKey {
+    host: IpAddr,
+    ident: u16,
+    sequence: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum IcmpEvent {
+    EchoReply { ttl: Option<u8>, payload: Vec<u8> },
+    Unreachable { from: IpAddr, code: u8 },
+}
+
 #[derive(Clone, Default)]
-struct ReplyMap(Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<String>>>>);
+struct ReplyMap(Arc<Mutex<HashMap<ReplyKey, tokio::sync::oneshot::Sender<IcmpEvent>>>>);
 
 impl ReplyMap {
-    pub fn new_waiter(&self, host: IpAddr) -> Result<Receiver<String>, String> {
+    pub fn new_waiter(&self, key: ReplyKey) -> Result<Receiver<IcmpEvent>, String> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        if self
-            .0
-            .lock()
-            .unwrap()
-            .insert(host.to_string(), tx)
-            .is_some()
-        {
-            return Err(format!("new waiter error: {}", host.to_string()));
+        if self.0.lock().unwrap().insert(key, tx).is_some() {
+            return Err(format!("new waiter error: {:?}", key));
         }
         Ok(rx)
     }
 
-    pub fn remove(&self, host: IpAddr) -> Option<tokio::sync::oneshot::Sender<String>> {
-        self.0.lock().unwrap().remove(&host.to_string())
+    pub fn remove(&self, key: &ReplyKey) -> Option<tokio::sync::oneshot::Sender<IcmpEvent>> {
+        self.0.lock().unwrap().remove(key)
     }
 }
 
@@ -99,23 +228,30 @@ impl ReplyMap {
 struct Client {
     socket: AsyncSocket,
     reply_map: ReplyMap,
+    sequence: Arc<AtomicU16>,
     recv: Arc<JoinHandle<()>>,
 }
 
 impl Client {
     pub fn new() -> std::io::Result<Self> {
-        let socket = AsyncSocket::new();
+        let socket = AsyncSocket::new()?;
         let reply_map = ReplyMap::default();
         let recv = tokio::spawn(recv_task(socket.clone(), reply_map.clone()));
         Ok(Self {
             socket,
             reply_map,
+            sequence: Arc::new(AtomicU16::new(0)),
             recv: Arc::new(recv),
         })
     }
 
     pub async fn pinger(&self, host: IpAddr) -> Pinger {
-        Pinger::new(host, self.socket.clone(), self.reply_map.clone())
+        Pinger::new(
+            host,
+            self.socket.clone(),
+            self.reply_map.clone(),
+            self.sequence.clone(),
+        )
     }
 }
 
@@ -130,13 +266,67 @@ impl Drop for Client {
 async fn recv_task(socket: AsyncSocket, reply_map: ReplyMap) {
     let mut buf = [0; 2048];
     loop {
-        if let Ok((_size, addr)) = socket.recv_from(&mut buf).await {
-            if let Some(waiter) = reply_map.remove(addr.ip()) {
-                let _ = waiter.send(addr.to_string());
+        if let Ok((size, addr, ttl)) = socket.recv_from(&mut buf).await {
+            let (key, event) = match parse_icmp_packet(&buf[..size], addr.ip(), ttl) {
+                Some(data) => data,
+                None => continue,
+            };
+            if key.ident != socket.ident {
+                continue;
+            }
+            if let Some(waiter) = reply_map.remove(&key) {
+                let _ = waiter.send(event);
             } else {
-                tracing::warn!("no one is waiting for ICMP packet.");
+                tracing::debug!("no one is waiting for ICMP packet: {:?}", key);
+            }
+        }
+    }
+}
+
+/// Parses an ICMP packet received from `from`.
+///
+/// Raw sockets deliver the IPv4 header in front of the ICMP message while unprivileged
+/// datagram sockets only deliver the ICMP message, so both layouts are accepted. The TTL of
+/// the latter is the `ttl` the kernel reported alongside the packet.
+fn parse_icmp_packet(buf: &[u8], from: IpAddr, ttl: Option<u8>) -> Option<(ReplyKey, IcmpEvent)> {
+    let (icmp_buf, ttl) = match buf.first() {
+        Some(byte) if byte >> 4 == 4 => {
+            let ipv4 = Ipv4Packet::new(buf)?;
+            let header_length = ipv4.get_header_length() as usize * 4;
+            (buf.get(header_length..)?, Some(ipv4.get_ttl()))
+        }
+        _ => (buf, ttl),
+    };
+    let icmp = IcmpPacket::new(icmp_buf)?;
+    match icmp.get_icmp_type() {
+        IcmpTypes::EchoReply => {
+            let reply = EchoReplyPacket::new(icmp_buf)?;
+            let key = ReplyKey {
+                host: from,
+                ident: reply.get_identifier(),
+                sequence: reply.get_sequence_number(),
+            };
+            let payload = reply.payload().to_vec();
+            Some((key, IcmpEvent::EchoReply { ttl, payload }))
+        }
+        IcmpTypes::DestinationUnreachable => {
+            let unreachable = DestinationUnreachablePacket::new(icmp_buf)?;
+            // the message quotes the original IPv4 header and the first 8 bytes of our echo request.
+            let original = Ipv4Packet::new(unreachable.payload())?;
+            let header_length = original.get_header_length() as usize * 4;
+            let request = EchoRequestPacket::new(unreachable.payload().get(header_length..)?)?;
+            if request.get_icmp_type() != IcmpTypes::EchoRequest {
+                return None;
             }
+            let key = ReplyKey {
+                host: IpAddr::V4(original.get_destination()),
+                ident: request.get_identifier(),
+                sequence: request.get_sequence_number(),
+            };
+            let code = unreachable.get_icmp_code().0;
+            Some((key, IcmpEvent::Unreachable { from, code }))
         }
+        _ => None,
     }
 }
 
@@ -144,47 +334,153 @@ struct Pinger {
     pub host: IpAddr,
     socket: AsyncSocket,
     reply_map: ReplyMap,
+    sequence: Arc<AtomicU16>,
 }
 impl Pinger {
-    pub fn new(host: IpAddr, socket: AsyncSocket, response_map: ReplyMap) -> Pinger {
+    pub fn new(
+        host: IpAddr,
+        socket: AsyncSocket,
+        response_map: ReplyMap,
+        sequence: Arc<AtomicU16>,
+    ) -> Pinger {
         Pinger {
             host,
             socket,
             reply_map: response_map,
+            sequence,
         }
     }
-    pub async fn ping(&mut self, payload: &[u8]) -> Result<String, String> {
-        let reply_waiter = self.reply_map.new_waiter(self.host).unwrap();
-        let mut packet = make_icmp_echo_packet(rand::random::<u16>(), 0, &payload);
-        self.socket
-            .send_to(&mut packet, &SocketAddr::new(self.host, 0))
+    pub async fn ping(&mut self, payload: &[u8]) -> EchoOutcome {
+        if !self.host.is_ipv4() {
+            return EchoOutcome::Timeout;
+        }
+        let key = ReplyKey {
+            host: self.host,
+            ident: self.socket.ident,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+        };
+        let reply_waiter = match self.reply_map.new_waiter(key) {
+            Ok(waiter) => waiter,
+            Err(error) => {
+                tracing::warn!("{}", error);
+                return EchoOutcome::Timeout;
+            }
+        };
+        let packet = make_icmp_echo_packet(key.ident, key.sequence, payload);
+        let start = Instant::now();
+        if let Err(error) = self
+            .socket
+            .send_to(&packet, &SocketAddr::new(self.host, 0))
             .await
-            .unwrap();
-        let result = timeout(Duration::from_millis(500), reply_waiter).await;
-        return match result {
-            Ok(data) => match data {
-                Ok(_) => Ok(self.host.to_string()),
-                Err(_) => Err(self.host.to_string()),
+        {
+            tracing::debug!("send icmp packet to {} error: {}", self.host, error);
+            self.reply_map.remove(&key);
+            return EchoOutcome::Timeout;
+        }
+        let result = timeout(PING_TIMEOUT, reply_waiter).await;
+        match result {
+            Ok(Ok(IcmpEvent::EchoReply {
+                ttl,
+                payload: reply,
+            })) if reply == payload => EchoOutcome::Reply {
+                rtt: start.elapsed(),
+                ttl,
             },
-            Err(_) => Err(self.host.to_string()),
-        };
+            Ok(Ok(IcmpEvent::Unreachable { from, code })) => {
+                EchoOutcome::Unreachable { from, code }
+            }
+            Ok(_) => EchoOutcome::Timeout,
+            Err(_) => {
+                self.reply_map.remove(&key);
+                EchoOutcome::Timeout
+            }
+        }
     }
 }
-fn make_socket() -> tokio::net::UdpSocket {
-    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).unwrap();
-    socket.set_nonblocking(false).unwrap();
-    socket.set_ttl(30).unwrap();
-    socket
-        .set_read_timeout(Some(Duration::from_millis(500)))
-        .unwrap();
-    socket
-        .set_write_timeout(Some(Duration::from_millis(500)))
-        .unwrap();
+
+/// Opens a raw ICMP socket when privileged, otherwise falls back to an unprivileged datagram
+/// socket. The kernel rewrites the identifier of datagram sockets to the bound port, so the
+/// identifier used for correlation is returned alongside the socket.
+fn make_socket() -> std::io::Result<(tokio::net::UdpSocket, u16)> {
+    let (socket, raw) = match Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)) {
+        Ok(socket) => (socket, true),
+        Err(_) => (
+            Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4))?,
+            false,
+        ),
+    };
+    socket.set_ttl(64)?;
+    let ident = if raw {
+        rand::random::<u16>()
+    } else {
+        // datagram sockets strip the IPv4 header, the kernel passes the TTL as a cmsg.
+        let enable: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_RECVTTL,
+                &enable as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            tracing::debug!(
+                "enable IP_RECVTTL error: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        socket.bind(&SockAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)))?;
+        socket
+            .local_addr()?
+            .as_socket()
+            .map(|addr| addr.port())
+            .unwrap_or_default()
+    };
+    socket.set_nonblocking(true)?;
     let socket = tokio::net::UdpSocket::from_std(unsafe {
         std::net::UdpSocket::from_raw_fd(socket.into_raw_fd())
-    })
-    .unwrap();
-    socket
+    })?;
+    Ok((socket, ident))
+}
+
+/// `recvfrom` with the `IP_TTL` control message of the packet, when the socket has
+/// `IP_RECVTTL` enabled.
+fn recv_with_ttl(fd: RawFd, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr, Option<u8>)> {
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // u64 keeps the control buffer aligned for cmsghdr.
+    let mut control = [0u64; 8];
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_name = &mut addr as *mut libc::sockaddr_in as *mut libc::c_void;
+    message.msg_namelen = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+    let size = unsafe { libc::recvmsg(fd, &mut message, 0) };
+    if size < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut ttl = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&message) };
+    while !cmsg.is_null() {
+        let header = unsafe { &*cmsg };
+        if header.cmsg_level == libc::IPPROTO_IP && header.cmsg_type == libc::IP_TTL {
+            let value =
+                unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int) };
+            ttl = u8::try_from(value).ok();
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&message, cmsg) };
+    }
+    let addr = SocketAddrV4::new(
+        Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+        u16::from_be(addr.sin_port),
+    );
+    Ok((size as usize, SocketAddr::V4(addr), ttl))
 }
 
 fn make_icmp_echo_packet(ident: u16, seq_cnt: u16, payload: &[u8]) -> Vec<u8> {
@@ -194,8 +490,84 @@ fn make_icmp_echo_packet(ident: u16, seq_cnt: u16, payload: &[u8]) -> Vec<u8> {
     package.set_identifier(ident);
     package.set_sequence_number(seq_cnt);
     package.set_payload(payload);
-    let checksum = util::checksum(package.packet(), 10);
+    let checksum = util::checksum(package.packet(), 1);
     package.set_checksum(checksum);
-    let package_vec = package.packet().to_vec();
-    package_vec
+    package.packet().to_vec()
+}
+
+#[cfg(test)]
+pub mod ping_test {
+    use crate::scanner::ping::{
+        make_icmp_echo_packet, parse_icmp_packet, EchoOutcome, IcmpEvent, PingStatistics,
+    };
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_echo_reply_test() {
+        let mut packet = make_icmp_echo_packet(0x1234, 7, &[1, 2, 3]);
+        // turn the request into a reply.
+        packet[0] = 0;
+        let host: IpAddr = "10.0.0.1".parse().unwrap();
+        let (key, event) = parse_icmp_packet(&packet, host, None).unwrap();
+        assert_eq!(key.host, host);
+        assert_eq!(key.ident, 0x1234);
+        assert_eq!(key.sequence, 7);
+        assert_eq!(
+            event,
+            IcmpEvent::EchoReply {
+                ttl: None,
+                payload: vec![1, 2, 3]
+            }
+        );
+        // datagram sockets get the TTL from the kernel instead of an IPv4 header.
+        let (_, event) = parse_icmp_packet(&packet, host, Some(57)).unwrap();
+        assert!(matches!(event, IcmpEvent::EchoReply { ttl: Some(57), .. }));
+        // a request sent to ourselves is not a reply.
+        packet[0] = 8;
+        assert!(parse_icmp_packet(&packet, host, None).is_none());
+    }
+
+    #[test]
+    fn parse_destination_unreachable_test() {
+        let request = make_icmp_echo_packet(42, 3, &[0; 8]);
+        let mut original_ip = vec![0u8; 20];
+        original_ip[0] = 0x45;
+        original_ip[9] = 1;
+        original_ip[16..20].copy_from_slice(&[192, 168, 1, 9]);
+        let mut packet = vec![3, 1, 0, 0, 0, 0, 0, 0];
+        packet.extend(original_ip);
+        packet.extend(&request[..8]);
+        let router: IpAddr = "192.168.1.1".parse().unwrap();
+        let (key, event) = parse_icmp_packet(&packet, router, None).unwrap();
+        assert_eq!(key.host, "192.168.1.9".parse::<IpAddr>().unwrap());
+        assert_eq!((key.ident, key.sequence), (42, 3));
+        assert_eq!(
+            event,
+            IcmpEvent::Unreachable {
+                from: router,
+                code: 1
+            }
+        );
+    }
+
+    #[test]
+    fn ping_statistics_test() {
+        let mut statistics = PingStatistics::new("127.0.0.1".parse().unwrap());
+        statistics.record(&EchoOutcome::Reply {
+            rtt: Duration::from_millis(10),
+            ttl: Some(64),
+        });
+        statistics.record(&EchoOutcome::Timeout);
+        statistics.record(&EchoOutcome::Reply {
+            rtt: Duration::from_millis(30),
+            ttl: None,
+        });
+        assert!(statistics.is_alive());
+        assert_eq!(statistics.min_rtt, Some(Duration::from_millis(10)));
+        assert_eq!(statistics.max_rtt, Some(Duration::from_millis(30)));
+        assert_eq!(statistics.avg_rtt, Some(Duration::from_millis(20)));
+        assert_eq!(statistics.ttl, Some(64));
+        assert!((statistics.loss() - 33.3).abs() < 0.1);
+    }
 }
@@ -0,0 +1,36 @@
+use crate::report::{self, ScanResult};
+use crate::scanner::plugin::{HostReply, ResultSink};
+
+/// Writes the scan result to a JSON file once the scan finished, the input of the report
+/// and diff commands.
+pub struct JsonSink {
+    path: String,
+    started: String,
+}
+
+impl JsonSink {
+    pub fn new(path: &str) -> JsonSink {
+        JsonSink {
+            path: path.to_string(),
+            started: report::now(),
+        }
+    }
+}
+
+impl ResultSink for JsonSink {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn finish(&self, hosts: &[HostReply]) {
+        let result = ScanResult {
+            started: self.started.clone(),
+            finished: report::now(),
+            hosts: hosts.to_vec(),
+        };
+        match result.to_file(&self.path) {
+            Ok(()) => tracing::info!("scan result written to {}", self.path),
+            Err(error) => tracing::warn!("{}", error),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::scanner::plugin::{HostReply, ResultSink, ScannerReply, TransportLayerProtocol};
+
+/// Writes the results to the log as `ip:port [TAG|...]` lines, weaknesses as warnings.
+pub struct LogSink;
+
+impl ResultSink for LogSink {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn port(&self, reply: &ScannerReply) {
+        let transport_layer_protocol = reply.transport_layer_protocol().unwrap();
+        match transport_layer_protocol {
+            TransportLayerProtocol::TCP => {
+                tracing::info!(
+                    "{}:{} [TCP|{}|{}]",
+                    reply.ip_addr().unwrap(),
+                    reply.port().unwrap(),
+                    reply.name(),
+                    match reply.version() {
+                        None => "*".to_string(),
+                        Some(version) => version,
+                    }
+                );
+                if let Some(tls_info) = reply.tls() {
+                    tracing::info!(
+                        "{}:{} [TLS|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        tls_info
+                    );
+                }
+                if let Some(http_info) = reply.http() {
+                    tracing::info!(
+                        "{}:{} [HTTP|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        http_info
+                    );
+                }
+                if !reply.technologies().is_empty() {
+                    tracing::info!(
+                        "{}:{} [WEB|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        reply
+                            .technologies()
+                            .iter()
+                            .map(|technology| technology.to_string())
+                            .collect::<Vec<String>>()
+                            .join(",")
+                    );
+                }
+                if let Some(ssh_info) = reply.ssh() {
+                    tracing::info!(
+                        "{}:{} [SSH|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        ssh_info
+                    );
+                    for weakness in ssh_info.weaknesses.iter() {
+                        tracing::warn!(
+                            "{}:{} [SSH|weak {}]",
+                            reply.ip_addr().unwrap(),
+                            reply.port().unwrap(),
+                            weakness
+                        );
+                    }
+                }
+                if let Some(database_info) = reply.database() {
+                    tracing::info!(
+                        "{}:{} [DB|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        database_info
+                    );
+                    for detail in database_info.details.iter() {
+                        tracing::info!(
+                            "{}:{} [DB|{}]",
+                            reply.ip_addr().unwrap(),
+                            reply.port().unwrap(),
+                            detail
+                        );
+                    }
+                    if database_info.unauthenticated == Some(true) {
+                        tracing::warn!(
+                            "{}:{} [DB|unauthenticated access]",
+                            reply.ip_addr().unwrap(),
+                            reply.port().unwrap()
+                        );
+                    }
+                }
+                if let Some(enumeration) = reply.tls_enumeration() {
+                    tracing::info!(
+                        "{}:{} [TLS|{}]",
+                        reply.ip_addr().unwrap(),
+                        reply.port().unwrap(),
+                        enumeration
+                    );
+                    for weakness in enumeration.weaknesses.iter() {
+                        tracing::warn!(
+                            "{}:{} [TLS|{}]",
+                            reply.ip_addr().unwrap(),
+                            reply.port().unwrap(),
+                            weakness
+                        );
+                    }
+                }
+            }
+            TransportLayerProtocol::UDP => {
+                tracing::info!(
+                    "{}:{} [UDP|{}|{}]",
+                    reply.ip_addr().unwrap(),
+                    reply.port().unwrap(),
+                    reply.name(),
+                    reply.version().unwrap_or_else(|| "*".to_string())
+                );
+            }
+        }
+        for vulnerability in reply.vulnerabilities() {
+            tracing::warn!(
+                "{}:{} [CVE|{}]",
+                reply.ip_addr().unwrap(),
+                reply.port().unwrap(),
+                vulnerability
+            );
+        }
+        for finding in reply.scripts() {
+            tracing::info!(
+                "{}:{} [SCRIPT|{}]",
+                reply.ip_addr().unwrap(),
+                reply.port().unwrap(),
+                finding
+            );
+        }
+        for finding in reply.credentials() {
+            tracing::warn!(
+                "{}:{} [CRED|{}]",
+                reply.ip_addr().unwrap(),
+                reply.port().unwrap(),
+                finding
+            );
+        }
+        if let Some(snmp_info) = reply.snmp() {
+            tracing::info!(
+                "{}:{} [SNMP|{}]",
+                reply.ip_addr().unwrap(),
+                reply.port().unwrap(),
+                snmp_info
+            );
+            for community in snmp_info.communities.iter() {
+                tracing::warn!(
+                    "{}:{} [SNMP|community accepted: {}]",
+                    reply.ip_addr().unwrap(),
+                    reply.port().unwrap(),
+                    community
+                );
+            }
+            for interface in snmp_info.interfaces.iter() {
+                tracing::info!(
+                    "{}:{} [SNMP|if {}|{}|{}|{}]",
+                    reply.ip_addr().unwrap(),
+                    reply.port().unwrap(),
+                    interface.index,
+                    interface.description.as_deref().unwrap_or("*"),
+                    interface.mac_address.as_deref().unwrap_or("*"),
+                    interface.oper_status.as_deref().unwrap_or("*")
+                );
+            }
+        }
+        if let Some(dns_info) = reply.dns() {
+            tracing::info!(
+                "{}:{} [DNS|{:?}|{}]",
+                reply.ip_addr().unwrap(),
+                reply.port().unwrap(),
+                transport_layer_protocol,
+                dns_info
+            );
+            for weakness in dns_info.weaknesses.iter() {
+                tracing::warn!(
+                    "{}:{} [DNS|{:?}|{}]",
+                    reply.ip_addr().unwrap(),
+                    reply.port().unwrap(),
+                    transport_layer_protocol,
+                    weakness
+                );
+            }
+        }
+    }
+
+    fn host(&self, host: &HostReply) {
+        if let Some(os_guess) = host.os_guess() {
+            tracing::info!("{} [OS|{}]", host.ip_addr(), os_guess);
+        }
+        if let Some(smb_info) = host.smb() {
+            tracing::info!("{}:{} [SMB|{}]", host.ip_addr(), smb_info.port, smb_info);
+            if smb_info.smb1 {
+                tracing::warn!("{}:{} [SMB|SMBv1 enabled]", host.ip_addr(), smb_info.port);
+            }
+            if !smb_info.signing_required {
+                tracing::warn!(
+                    "{}:{} [SMB|signing not required]",
+                    host.ip_addr(),
+                    smb_info.port
+                );
+            }
+        }
+        if let Some(netbios_info) = host.netbios() {
+            tracing::info!("{} [NetBIOS|{}]", host.ip_addr(), netbios_info);
+        }
+        let vulnerabilities = host.vulnerabilities();
+        if !vulnerabilities.is_empty() {
+            let count = |severity: &str| {
+                vulnerabilities
+                    .iter()
+                    .filter(|(_, vulnerability)| {
+                        vulnerability.severity.as_deref() == Some(severity)
+                    })
+                    .count()
+            };
+            let max_score = vulnerabilities
+                .iter()
+                .filter_map(|(_, vulnerability)| vulnerability.score)
+                .fold(0.0, f32::max);
+            tracing::warn!(
+                "{} [VULN|{} cves|{} critical|{} high|{} medium|{} low|max cvss {:.1}]",
+                host.ip_addr(),
+                vulnerabilities.len(),
+                count("CRITICAL"),
+                count("HIGH"),
+                count("MEDIUM"),
+                count("LOW"),
+                max_score
+            );
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::scanner::ProbeOptions;
+use futures::future::BoxFuture;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+
+pub use crate::scanner::host::HostReply;
+pub use crate::scanner::socket::{ScannerReply, TransportLayerProtocol};
+pub use json::JsonSink;
+pub use log::LogSink;
+pub use store::StoreSink;
+
+mod json;
+mod log;
+mod probes;
+mod store;
+
+/// What a probe gets to work with besides the results of the probes before it.
+pub struct ProbeContext {
+    pub socket_addr: SocketAddr,
+    /// the connection the port scan opened, taken by the first probe reading from it.
+    pub stream: Option<TcpStream>,
+}
+
+/// A check run against every open TCP port. Probes run one after the other in the order
+/// they were registered, each one sees what the previous ones added to the reply.
+pub trait ServiceProbe: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the probe runs, e.g. only once a given service was identified.
+    fn applies(&self, reply: &ScannerReply) -> bool;
+
+    /// Adds what the probe learns about the service to `reply`.
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()>;
+}
+
+/// Receives the scan results, e.g. to print them or write a report.
+pub trait ResultSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The sockets the port scan went through out of all of them, after every batch.
+    fn progress(&self, _done: usize, _total: usize) {}
+
+    /// Every open TCP or UDP port, as soon as its probes finished.
+    fn port(&self, _reply: &ScannerReply) {}
+
+    /// Every host, once the host wide checks (OS detection, SMB and NetBIOS) ran.
+    fn host(&self, _host: &HostReply) {}
+
+    /// All hosts at the end of the scan.
+    fn finish(&self, _hosts: &[HostReply]) {}
+}
+
+/// The probes and sinks a scan runs, the built-in ones are registered the same way as
+/// extensions.
+#[derive(Clone, Default)]
+pub struct ProbeRegistry {
+    probes: Vec<Arc<dyn ServiceProbe>>,
+    sinks: Vec<Arc<dyn ResultSink>>,
+}
+
+impl ProbeRegistry {
+    /// The built-in probes enabled by `options`. Fingerprinting comes first as the
+    /// others depend on the service name, the vulnerability and script checks last as
+    /// they use what the rest found.
+    pub fn builtin(options: &ProbeOptions) -> ProbeRegistry {
+        let mut registry = ProbeRegistry::default();
+        registry.register_probe(Arc::new(probes::FingerprintProbe::new()));
+        if options.tls_enumeration {
+            registry.register_probe(Arc::new(probes::TlsEnumerationProbe));
+        }
+        registry.register_probe(Arc::new(probes::HttpProbe::new()));
+        registry.register_probe(Arc::new(probes::SshProbe));
+        registry.register_probe(Arc::new(probes::DnsProbe {
+            zones: options.dns_zones.clone(),
+        }));
+        registry.register_probe(Arc::new(probes::DatabaseProbe));
+        if let Some(checker) = &options.credential_checker {
+            registry.register_probe(Arc::new(probes::CredentialProbe {
+                checker: checker.clone(),
+            }));
+        }
+        if let Some(database) = &options.vulnerability_database {
+            registry.register_probe(Arc::new(probes::VulnerabilityProbe {
+                database: database.clone(),
+            }));
+        }
+        if let Some(runner) = &options.script_runner {
+            registry.register_probe(Arc::new(probes::ScriptProbe {
+                runner: runner.clone(),
+            }));
+        }
+        registry
+    }
+
+    pub fn register_probe(&mut self, probe: Arc<dyn ServiceProbe>) {
+        self.probes.push(probe);
+    }
+
+    pub fn register_sink(&mut self, sink: Arc<dyn ResultSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Appends the probes and sinks of `other` after the ones already registered.
+    pub fn extend(&mut self, other: ProbeRegistry) {
+        self.probes.extend(other.probes);
+        self.sinks.extend(other.sinks);
+    }
+
+    pub fn sinks(&self) -> &[Arc<dyn ResultSink>] {
+        &self.sinks
+    }
+
+    pub async fn probe(&self, context: &mut ProbeContext, reply: &mut ScannerReply) {
+        for probe in self.probes.iter() {
+            if probe.applies(reply) {
+                probe.probe(context, reply).await;
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ProbeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProbeRegistry")
+            .field(
+                "probes",
+                &self
+                    .probes
+                    .iter()
+                    .map(|probe| probe.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "sinks",
+                &self
+                    .sinks
+                    .iter()
+                    .map(|sink| sink.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub mod plugin_test {
+    use crate::scanner::plugin::{
+        ProbeContext, ProbeRegistry, ResultSink, ScannerReply, ServiceProbe,
+    };
+    use futures::future::{BoxFuture, FutureExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct BannerProbe;
+
+    impl ServiceProbe for BannerProbe {
+        fn name(&self) -> &str {
+            "banner"
+        }
+
+        fn applies(&self, reply: &ScannerReply) -> bool {
+            reply.port() == Some(2323)
+        }
+
+        fn probe<'a>(
+            &'a self,
+            _context: &'a mut ProbeContext,
+            reply: &'a mut ScannerReply,
+        ) -> BoxFuture<'a, ()> {
+            async move {
+                reply.set_name("telnet".to_string());
+            }
+            .boxed()
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingSink {
+        ports: AtomicUsize,
+    }
+
+    impl ResultSink for CountingSink {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn port(&self, _reply: &ScannerReply) {
+            self.ports.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn plugin_registry_test() {
+        let mut registry = ProbeRegistry::default();
+        registry.register_probe(Arc::new(BannerProbe));
+        let sink = Arc::new(CountingSink::default());
+        registry.register_sink(sink.clone());
+        assert_eq!(
+            format!("{:?}", registry),
+            "ProbeRegistry { probes: [\"banner\"], sinks: [\"counting\"] }"
+        );
+
+        let mut context = ProbeContext {
+            socket_addr: "127.0.0.1:2323".parse().unwrap(),
+            stream: None,
+        };
+        let mut reply = ScannerReply::new();
+        reply.set_port(Some(2323));
+        registry.probe(&mut context, &mut reply).await;
+        assert_eq!(reply.name(), "telnet");
+
+        let mut other = ScannerReply::new();
+        other.set_port(Some(80));
+        registry.probe(&mut context, &mut other).await;
+        assert_eq!(other.name(), "*");
+
+        for sink in registry.sinks() {
+            sink.port(&reply);
+        }
+        assert_eq!(sink.ports.load(Ordering::SeqCst), 1);
+    }
+}
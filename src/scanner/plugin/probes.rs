@@ -0,0 +1,361 @@
+use crate::fingerprint::technology::{self, Technology, WebPage};
+use crate::fingerprint::Fingerprint;
+use crate::scanner::credential::CredentialChecker;
+use crate::scanner::database::{self, Engine};
+use crate::scanner::dns::{self, DnsTransport};
+use crate::scanner::plugin::{ProbeContext, ServiceProbe};
+use crate::scanner::script::{ScriptRunner, ServiceContext};
+use crate::scanner::socket::ScannerReply;
+use crate::scanner::{http, ssh, tls, tls_enum, Scanner};
+use crate::vuln::VulnDatabase;
+use futures::future::{BoxFuture, FutureExt};
+use std::sync::Arc;
+
+/// Identifies the service with the match rules of the TCP fingerprint, over TLS when the
+/// port completes a handshake.
+pub struct FingerprintProbe {
+    fingerprint: Option<Fingerprint>,
+}
+
+impl FingerprintProbe {
+    pub fn new() -> FingerprintProbe {
+        FingerprintProbe {
+            fingerprint: Fingerprint::get_tcp_fingerprint(),
+        }
+    }
+}
+
+impl ServiceProbe for FingerprintProbe {
+    fn name(&self) -> &str {
+        "fingerprint"
+    }
+
+    fn applies(&self, _reply: &ScannerReply) -> bool {
+        self.fingerprint.is_some()
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let fingerprint = match &self.fingerprint {
+                None => return,
+                Some(data) => data,
+            };
+            let socket_addr = context.socket_addr;
+            let stream = context.stream.take();
+            let plaintext = async {
+                match stream {
+                    Some(tcp_stream) => {
+                        Scanner::match_probes(Box::new(tcp_stream), socket_addr, false, fingerprint)
+                            .await
+                    }
+                    None => match Scanner::open_probe_stream(socket_addr, false).await {
+                        Some(stream) => {
+                            Scanner::match_probes(stream, socket_addr, false, fingerprint).await
+                        }
+                        None => None,
+                    },
+                }
+            };
+            // the handshake goes first, an HTTPS port answering the plain text request with
+            // a 400 error would be taken for plain HTTP otherwise.
+            let (tls, plaintext) = tokio::join!(tls::tls_connect(socket_addr), plaintext);
+            let (tls_stream, tls_info) = match tls {
+                Ok(data) => data,
+                Err(_) => {
+                    if let Some(service) = plaintext {
+                        reply.set_name(service.name);
+                        if let Some(version) = service.version {
+                            reply.set_version(version);
+                        }
+                        reply.set_cpe(service.cpe);
+                    }
+                    return;
+                }
+            };
+            let service =
+                Scanner::match_probes(Box::new(tls_stream), socket_addr, true, fingerprint)
+                    .await
+                    .or(plaintext);
+            match service {
+                Some(service) => {
+                    reply.set_name(format!("ssl/{}", service.name));
+                    if let Some(version) = service.version {
+                        reply.set_version(version);
+                    }
+                    reply.set_cpe(service.cpe);
+                }
+                None => reply.set_name("ssl".to_string()),
+            }
+            reply.set_tls(Some(tls_info));
+        }
+        .boxed()
+    }
+}
+
+/// Enumerates the protocol versions and cipher suites of TLS services. Every open port is
+/// tried with raw ClientHellos, the rustls handshake of the fingerprint probe failing on
+/// the SSLv3 to TLS 1.1 only servers this is meant to flag.
+pub struct TlsEnumerationProbe;
+
+impl ServiceProbe for TlsEnumerationProbe {
+    fn name(&self) -> &str {
+        "tls-enumeration"
+    }
+
+    fn applies(&self, _reply: &ScannerReply) -> bool {
+        true
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let enumeration = tls_enum::enumerate(context.socket_addr).await;
+            if enumeration.protocols.is_empty() {
+                return;
+            }
+            if reply.name() == "*" {
+                reply.set_name("ssl".to_string());
+            }
+            reply.set_tls_enumeration(Some(enumeration));
+        }
+        .boxed()
+    }
+}
+
+/// Reads the default page of web servers and detects the technologies behind it.
+pub struct HttpProbe {
+    technologies: Vec<Technology>,
+}
+
+impl HttpProbe {
+    pub fn new() -> HttpProbe {
+        HttpProbe {
+            technologies: Technology::get_technologies(),
+        }
+    }
+}
+
+impl ServiceProbe for HttpProbe {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn applies(&self, reply: &ScannerReply) -> bool {
+        reply.name().trim_start_matches("ssl/") == "http"
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let tls = reply.tls().is_some();
+            if let Some((http_info, response)) = http::enrich(context.socket_addr, tls).await {
+                let body = String::from_utf8_lossy(&response.body);
+                let page = WebPage {
+                    headers: &response.headers,
+                    body: &body,
+                };
+                reply.set_technologies(technology::detect(&page, &self.technologies));
+                reply.set_http(Some(http_info));
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Reads the SSH key exchange algorithms and host keys.
+pub struct SshProbe;
+
+impl ServiceProbe for SshProbe {
+    fn name(&self) -> &str {
+        "ssh"
+    }
+
+    fn applies(&self, reply: &ScannerReply) -> bool {
+        reply.name() == "ssh"
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            reply.set_ssh(ssh::probe(context.socket_addr).await);
+        }
+        .boxed()
+    }
+}
+
+/// Asks DNS servers over TCP for their identity, recursion and zone transfers.
+pub struct DnsProbe {
+    pub zones: Vec<String>,
+}
+
+impl ServiceProbe for DnsProbe {
+    fn name(&self) -> &str {
+        "dns"
+    }
+
+    fn applies(&self, reply: &ScannerReply) -> bool {
+        reply.name() == "domain" || (reply.name() == "*" && reply.port() == Some(dns::DNS_PORT))
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let dns_info = dns::probe(context.socket_addr, DnsTransport::Tcp, &self.zones).await;
+            if let Some(dns_info) = &dns_info {
+                reply.set_name("domain".to_string());
+                if let (None, Some(version)) = (reply.version(), &dns_info.version) {
+                    reply.set_version(version.clone());
+                }
+            }
+            reply.set_dns(dns_info);
+        }
+        .boxed()
+    }
+}
+
+/// Speaks the native protocol of database engines for their version and auth details.
+pub struct DatabaseProbe;
+
+impl ServiceProbe for DatabaseProbe {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    fn applies(&self, reply: &ScannerReply) -> bool {
+        Engine::detect(&reply.name(), reply.port().unwrap_or_default()).is_some()
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let engine = match Engine::detect(&reply.name(), context.socket_addr.port()) {
+                None => return,
+                Some(engine) => engine,
+            };
+            if let Some(database_info) = database::probe(context.socket_addr, engine).await {
+                reply.set_name(engine.name().to_string());
+                if let Some(version) = &database_info.version {
+                    reply.set_version(version.clone());
+                }
+                reply.set_database(Some(database_info));
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Tries the default and anonymous logins of the credentials file.
+pub struct CredentialProbe {
+    pub checker: Arc<CredentialChecker>,
+}
+
+impl ServiceProbe for CredentialProbe {
+    fn name(&self) -> &str {
+        "credentials"
+    }
+
+    fn applies(&self, _reply: &ScannerReply) -> bool {
+        true
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let tls = reply.tls().is_some();
+            let findings = self
+                .checker
+                .run(context.socket_addr, &reply.name(), tls)
+                .await;
+            reply.set_credentials(findings);
+        }
+        .boxed()
+    }
+}
+
+/// Matches the detected CPEs against the offline CVE database.
+pub struct VulnerabilityProbe {
+    pub database: Arc<VulnDatabase>,
+}
+
+impl ServiceProbe for VulnerabilityProbe {
+    fn name(&self) -> &str {
+        "vulnerabilities"
+    }
+
+    fn applies(&self, _reply: &ScannerReply) -> bool {
+        true
+    }
+
+    fn probe<'a>(
+        &'a self,
+        _context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let vulnerabilities = self.database.matches(&reply.cpes());
+            reply.set_vulnerabilities(vulnerabilities);
+        }
+        .boxed()
+    }
+}
+
+/// Runs the selected check scripts.
+pub struct ScriptProbe {
+    pub runner: Arc<ScriptRunner>,
+}
+
+impl ServiceProbe for ScriptProbe {
+    fn name(&self) -> &str {
+        "scripts"
+    }
+
+    fn applies(&self, _reply: &ScannerReply) -> bool {
+        true
+    }
+
+    fn probe<'a>(
+        &'a self,
+        context: &'a mut ProbeContext,
+        reply: &'a mut ScannerReply,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            let service = ServiceContext {
+                ip_addr: context.socket_addr.ip(),
+                port: context.socket_addr.port(),
+                name: reply.name(),
+                version: reply.version(),
+                tls: reply.tls().is_some(),
+                cpe: reply.cpes(),
+                technologies: reply
+                    .technologies()
+                    .iter()
+                    .map(|technology| technology.name.clone())
+                    .collect(),
+            };
+            reply.set_scripts(self.runner.run(&service).await);
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,113 @@
+use crate::report;
+use crate::scanner::plugin::{HostReply, ResultSink, ScannerReply};
+use crate::store::Store;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+enum Write {
+    Port(Box<ScannerReply>),
+    Host(Box<HostReply>),
+    Finish,
+}
+
+/// Writes the results to the SQLite store as they arrive, the input of the store commands.
+/// The writes run on a thread of their own, off the runtime threads the scan runs on.
+pub struct StoreSink {
+    scan_id: Option<i64>,
+    sender: Mutex<Option<Sender<Write>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StoreSink {
+    /// Records the scan of `target`, run for the monitor `group` if any, as started now.
+    /// The sink drops the results when that fails.
+    pub fn new(store: Arc<Store>, target: &str, group: Option<&str>) -> StoreSink {
+        let scan_id = match store.begin_scan(target, group, &report::now()) {
+            Ok(scan_id) => scan_id,
+            Err(error) => {
+                tracing::warn!("{}", error);
+                return StoreSink {
+                    scan_id: None,
+                    sender: Mutex::new(None),
+                    writer: Mutex::new(None),
+                };
+            }
+        };
+        let (sender, receiver) = mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            for write in receiver {
+                let result = match write {
+                    Write::Port(reply) => store.add_port(scan_id, &reply),
+                    Write::Host(host) => store.add_host(scan_id, &host),
+                    Write::Finish => {
+                        match store.finish_scan(scan_id, &report::now()) {
+                            Ok(()) => tracing::info!("scan result stored as scan {}", scan_id),
+                            Err(error) => tracing::warn!("{}", error),
+                        }
+                        return;
+                    }
+                };
+                if let Err(error) = result {
+                    tracing::warn!("{}", error);
+                }
+            }
+        });
+        StoreSink {
+            scan_id: Some(scan_id),
+            sender: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+        }
+    }
+
+    /// The id the scan is stored under, `None` when it could not be recorded.
+    pub fn scan_id(&self) -> Option<i64> {
+        self.scan_id
+    }
+
+    /// Waits for the writer to store everything once the scan finished, the scan is
+    /// complete in the store when it returns.
+    pub async fn flushed(&self) {
+        let writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some(writer) = writer {
+            let _ = tokio::task::spawn_blocking(move || writer.join()).await;
+        }
+    }
+
+    fn send(&self, write: Write) {
+        if let Some(sender) = self
+            .sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+        {
+            let _ = sender.send(write);
+        }
+    }
+}
+
+impl ResultSink for StoreSink {
+    fn name(&self) -> &str {
+        "store"
+    }
+
+    fn port(&self, reply: &ScannerReply) {
+        self.send(Write::Port(Box::new(reply.clone())));
+    }
+
+    fn host(&self, host: &HostReply) {
+        self.send(Write::Host(Box::new(host.clone())));
+    }
+
+    fn finish(&self, _hosts: &[HostReply]) {
+        self.send(Write::Finish);
+        self.sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+    }
+}
@@ -0,0 +1,488 @@
+use crate::scanner::{CONNECT_TIMEOUT, PROBE_READ_TIMEOUT};
+use futures::future::join_all;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Blob, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Connections a single script run may open.
+const MAX_CONNECTIONS: usize = 8;
+/// Bytes a single receive returns at most.
+const MAX_RECEIVE_SIZE: usize = 64 * 1024;
+/// Operations a script run may execute, stops runaway loops.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_STRING_SIZE: usize = 1024 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+const MAX_CALL_LEVELS: usize = 32;
+/// Wall-clock time a script run may take, stops loops waiting on the network.
+const MAX_RUN_TIME: Duration = Duration::from_secs(30);
+
+/// What a script learns about the port it runs against, exposed as the `service` map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceContext {
+    pub ip_addr: IpAddr,
+    pub port: u16,
+    pub name: String,
+    pub version: Option<String>,
+    pub tls: bool,
+    pub cpe: Vec<String>,
+    pub technologies: Vec<String>,
+}
+
+impl ServiceContext {
+    fn to_map(&self) -> Map {
+        let mut map = Map::new();
+        map.insert("ip".into(), self.ip_addr.to_string().into());
+        map.insert("port".into(), (self.port as i64).into());
+        map.insert("protocol".into(), "tcp".into());
+        map.insert("name".into(), self.name.clone().into());
+        map.insert(
+            "version".into(),
+            self.version
+                .clone()
+                .map(Dynamic::from)
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert("tls".into(), self.tls.into());
+        map.insert("cpe".into(), self.cpe.clone().into());
+        map.insert("technologies".into(), self.technologies.clone().into());
+        map
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScriptFinding {
+    pub script: String,
+    pub title: String,
+    pub detail: Option<String>,
+    pub severity: Option<String>,
+}
+
+impl std::fmt::Display for ScriptFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}", self.script, self.title)?;
+        if let Some(severity) = &self.severity {
+            write!(f, "|{}", severity)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, "|{}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// A TCP connection to the scanned host, the only network access scripts have.
+#[derive(Clone)]
+struct ScriptSocket {
+    stream: Arc<Mutex<Option<TcpStream>>>,
+    deadline: Instant,
+}
+
+impl ScriptSocket {
+    fn with_stream<T>(
+        &mut self,
+        action: impl FnOnce(&mut TcpStream) -> std::io::Result<T>,
+    ) -> Result<T, Box<EvalAltResult>> {
+        let mut stream = self.stream.lock().unwrap();
+        let stream = stream.as_mut().ok_or("socket closed")?;
+        let timeout = Some(remaining_time(self.deadline)?.min(PROBE_READ_TIMEOUT));
+        stream
+            .set_read_timeout(timeout)
+            .and_then(|_| stream.set_write_timeout(timeout))
+            .and_then(|_| action(stream))
+            .map_err(|error| error.to_string().into())
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), Box<EvalAltResult>> {
+        self.with_stream(|stream| stream.write_all(data.as_bytes()))
+    }
+
+    fn send_bytes(&mut self, data: Blob) -> Result<(), Box<EvalAltResult>> {
+        self.with_stream(|stream| stream.write_all(&data))
+    }
+
+    /// Whatever arrives next, empty once the peer closed or nothing came in time.
+    fn receive_bytes(&mut self) -> Result<Blob, Box<EvalAltResult>> {
+        self.with_stream(|stream| {
+            let mut buf = vec![0u8; MAX_RECEIVE_SIZE];
+            match stream.read(&mut buf) {
+                Ok(size) => {
+                    buf.truncate(size);
+                    Ok(buf)
+                }
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    Ok(vec![])
+                }
+                Err(error) => Err(error),
+            }
+        })
+    }
+
+    fn receive(&mut self) -> Result<String, Box<EvalAltResult>> {
+        let data = self.receive_bytes()?;
+        Ok(String::from_utf8_lossy(&data).to_string())
+    }
+
+    fn close(&mut self) {
+        self.stream.lock().unwrap().take();
+    }
+}
+
+/// What is left of a script run's time, an error once it is used up.
+fn remaining_time(deadline: Instant) -> Result<Duration, Box<EvalAltResult>> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match remaining.is_zero() {
+        true => Err("script time budget used up".into()),
+        false => Ok(remaining),
+    }
+}
+
+/// An engine without file or process access, bounded in operations, memory and time up to
+/// `deadline`. Scripts run by it may open up to `MAX_CONNECTIONS` connections to `target`
+/// and nowhere else.
+fn sandbox_engine(target: Option<IpAddr>, deadline: Instant) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .disable_symbol("eval");
+    engine.on_progress(move |_| {
+        (Instant::now() >= deadline).then(|| Dynamic::from("script time budget used up"))
+    });
+    engine.on_print(|text| tracing::debug!("script: {}", text));
+    engine.on_debug(|text, _, _| tracing::debug!("script: {}", text));
+    engine
+        .register_type_with_name::<ScriptSocket>("Socket")
+        .register_fn("send", ScriptSocket::send)
+        .register_fn("send", ScriptSocket::send_bytes)
+        .register_fn("receive", ScriptSocket::receive)
+        .register_fn("receive_bytes", ScriptSocket::receive_bytes)
+        .register_fn("close", ScriptSocket::close);
+    if let Some(ip_addr) = target {
+        let connections = Arc::new(AtomicUsize::new(0));
+        engine.register_fn(
+            "connect",
+            move |port: i64| -> Result<ScriptSocket, Box<EvalAltResult>> {
+                let port = u16::try_from(port).map_err(|_| format!("invalid port {}", port))?;
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    return Err("connection limit reached".into());
+                }
+                let timeout = remaining_time(deadline)?.min(CONNECT_TIMEOUT);
+                let stream = TcpStream::connect_timeout(&SocketAddr::new(ip_addr, port), timeout)
+                    .map_err(|error| error.to_string())?;
+                Ok(ScriptSocket {
+                    stream: Arc::new(Mutex::new(Some(stream))),
+                    deadline,
+                })
+            },
+        );
+    }
+    engine
+}
+
+/// A compiled check script. Scripts define `run(service)` and optionally
+/// `applies(service)`, and set `description` and `categories` at the top level.
+#[derive(Debug)]
+pub struct Script {
+    pub name: String,
+    pub description: String,
+    pub categories: Vec<String>,
+    ast: AST,
+    has_applies: bool,
+}
+
+impl Script {
+    pub fn compile(name: &str, source: &str) -> Result<Script, String> {
+        let engine = sandbox_engine(None, Instant::now() + MAX_RUN_TIME);
+        let ast = engine.compile(source).map_err(|error| error.to_string())?;
+        if !ast.iter_functions().any(|function| function.name == "run") {
+            return Err("no run(service) function".to_string());
+        }
+        let has_applies = ast
+            .iter_functions()
+            .any(|function| function.name == "applies");
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|error| error.to_string())?;
+        let description = scope.get_value::<String>("description").unwrap_or_default();
+        let categories = scope
+            .get_value::<rhai::Array>("categories")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|category| category.to_string())
+            .collect();
+        Ok(Script {
+            name: name.to_string(),
+            description,
+            categories,
+            ast,
+            has_applies,
+        })
+    }
+
+    /// Compiles every `.rhai` file of `dir`, named after the file.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Vec<Script> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::warn!("open script directory error: {}", error);
+                return vec![];
+            }
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "rhai")
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        let mut scripts = vec![];
+        for path in paths {
+            let name = match path.file_stem() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+            let script = std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|source| Script::compile(&name, &source));
+            match script {
+                Ok(script) => scripts.push(script),
+                Err(error) => tracing::warn!("load script {:?} error: {}", path, error),
+            }
+        }
+        scripts
+    }
+
+    /// Whether a `--script` selector picks this script, by name, category or `all`.
+    fn selected_by(&self, selector: &str) -> bool {
+        selector == "all"
+            || selector == self.name
+            || self.categories.iter().any(|category| category == selector)
+    }
+
+    fn run(&self, context: &ServiceContext, budget: Duration) -> Vec<ScriptFinding> {
+        let engine = sandbox_engine(Some(context.ip_addr), Instant::now() + budget);
+        let service = Dynamic::from_map(context.to_map());
+        if self.has_applies {
+            match engine.call_fn::<bool>(
+                &mut Scope::new(),
+                &self.ast,
+                "applies",
+                (service.clone(),),
+            ) {
+                Ok(true) => {}
+                Ok(false) => return vec![],
+                Err(error) => {
+                    tracing::warn!("script {} applies error: {}", self.name, error);
+                    return vec![];
+                }
+            }
+        }
+        match engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "run", (service,)) {
+            Ok(result) => self.findings(result),
+            Err(error) => {
+                tracing::debug!(
+                    "script {} against {}:{} error: {}",
+                    self.name,
+                    context.ip_addr,
+                    context.port,
+                    error
+                );
+                vec![]
+            }
+        }
+    }
+
+    /// Turns what `run` returned into findings: nothing, a title string, a map with
+    /// `title`, `detail` and `severity`, or an array of those.
+    fn findings(&self, result: Dynamic) -> Vec<ScriptFinding> {
+        if result.is_unit() {
+            return vec![];
+        }
+        if result.is_array() {
+            return result
+                .into_array()
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|result| self.findings(result))
+                .collect();
+        }
+        let text = |map: &Map, key: &str| {
+            map.get(key)
+                .filter(|value| !value.is_unit())
+                .map(|value| value.to_string())
+        };
+        let finding = match result.is_map() {
+            true => {
+                let map = result.cast::<Map>();
+                ScriptFinding {
+                    script: self.name.clone(),
+                    title: text(&map, "title").unwrap_or_default(),
+                    detail: text(&map, "detail"),
+                    severity: text(&map, "severity"),
+                }
+            }
+            false => ScriptFinding {
+                script: self.name.clone(),
+                title: result.to_string(),
+                detail: None,
+                severity: None,
+            },
+        };
+        vec![finding]
+    }
+}
+
+/// The scripts chosen with `--script`, run against every identified TCP port.
+#[derive(Debug, Default)]
+pub struct ScriptRunner {
+    scripts: Vec<Arc<Script>>,
+}
+
+impl ScriptRunner {
+    /// Keeps the scripts matching any of the selectors.
+    pub fn new(scripts: Vec<Script>, selectors: &[String]) -> ScriptRunner {
+        for selector in selectors.iter() {
+            if !scripts.iter().any(|script| script.selected_by(selector)) {
+                tracing::warn!("no script matches {}", selector);
+            }
+        }
+        let scripts = scripts
+            .into_iter()
+            .filter(|script| {
+                selectors
+                    .iter()
+                    .any(|selector| script.selected_by(selector))
+            })
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        for script in scripts.iter() {
+            tracing::info!("script {}: {}", script.name, script.description);
+        }
+        ScriptRunner { scripts }
+    }
+
+    /// Runs the scripts on the blocking pool, the socket API they get is synchronous.
+    pub async fn run(&self, context: &ServiceContext) -> Vec<ScriptFinding> {
+        let tasks = self.scripts.iter().map(|script| {
+            let script = script.clone();
+            let context = context.clone();
+            tokio::task::spawn_blocking(move || script.run(&context, MAX_RUN_TIME))
+        });
+        join_all(tasks)
+            .await
+            .into_iter()
+            .filter_map(|findings| findings.ok())
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod script_test {
+    use crate::scanner::script::{Script, ScriptRunner, ServiceContext};
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn script_run_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"220 test ready\r\n").unwrap();
+        });
+        let source = r#"
+            let description = "Greeting check.";
+            let categories = ["safe", "discovery"];
+            fn applies(service) { service.name == "ftp" }
+            fn run(service) {
+                let socket = connect(service.port);
+                let banner = socket.receive();
+                banner.trim();
+                [#{ title: "banner", detail: banner, severity: "info" }, "second"]
+            }
+        "#;
+        let script = Script::compile("greeting", source).unwrap();
+        assert_eq!(script.description, "Greeting check.");
+        assert_eq!(script.categories, vec!["safe", "discovery"]);
+        assert!(Script::compile("empty", "let x = 1;").is_err());
+        let module = std::env::temp_dir().join(format!("script-module-{}", std::process::id()));
+        std::fs::write(module.with_extension("rhai"), "export const secret = 42;").unwrap();
+        let source = format!(
+            r#"import "{}" as leaked; fn run(service) {{ leaked::secret }}"#,
+            module.display()
+        );
+        let imported = Script::compile("import", &source);
+        std::fs::remove_file(module.with_extension("rhai")).unwrap();
+        assert!(imported.is_err());
+
+        let runner = ScriptRunner::new(vec![script], &["discovery".to_string()]);
+        let mut context = ServiceContext {
+            ip_addr: "127.0.0.1".parse().unwrap(),
+            port,
+            name: "ftp".to_string(),
+            version: None,
+            tls: false,
+            cpe: vec![],
+            technologies: vec![],
+        };
+        let findings = runner.run(&context).await;
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].to_string(),
+            "greeting|banner|info|220 test ready"
+        );
+        assert_eq!(findings[1].title, "second");
+
+        context.name = "http".to_string();
+        assert!(runner.run(&context).await.is_empty());
+        assert!(ScriptRunner::new(vec![], &["vuln".to_string()])
+            .run(&context)
+            .await
+            .is_empty());
+    }
+
+    #[test]
+    fn script_time_budget_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let _connection = listener.accept();
+            std::thread::sleep(Duration::from_secs(10));
+        });
+        let source = r#"
+            fn run(service) {
+                let socket = connect(service.port);
+                loop { socket.receive(); }
+            }
+        "#;
+        let script = Script::compile("silent", source).unwrap();
+        let context = ServiceContext {
+            ip_addr: "127.0.0.1".parse().unwrap(),
+            port,
+            name: "unknown".to_string(),
+            version: None,
+            tls: false,
+            cpe: vec![],
+            technologies: vec![],
+        };
+        let start = Instant::now();
+        assert!(script.run(&context, Duration::from_millis(500)).is_empty());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
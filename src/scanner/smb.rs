@@ -0,0 +1,581 @@
+use crate::scanner::netbios::encode_name;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SMB_TIMEOUT: Duration = Duration::from_millis(5000);
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+pub const NETBIOS_SESSION_PORT: u16 = 139;
+pub const SMB_PORT: u16 = 445;
+
+const SMB1_DIALECTS: [&str; 6] = [
+    "PC NETWORK PROGRAM 1.0",
+    "LANMAN1.0",
+    "Windows for Workgroups 3.1a",
+    "LM1.2X002",
+    "LANMAN2.1",
+    "NT LM 0.12",
+];
+const SMB2_DIALECTS: [(u16, &str); 5] = [
+    (0x0202, "2.0.2"),
+    (0x0210, "2.1"),
+    (0x0300, "3.0"),
+    (0x0302, "3.0.2"),
+    (0x0311, "3.1.1"),
+];
+
+const SMB2_NEGOTIATE: u16 = 0;
+const SMB2_SESSION_SETUP: u16 = 1;
+const SMB2_SIGNING_ENABLED: u16 = 0x01;
+const SMB2_SIGNING_REQUIRED: u16 = 0x02;
+const SMB1_SIGNING_ENABLED: u8 = 0x04;
+const SMB1_SIGNING_REQUIRED: u8 = 0x08;
+const STATUS_SUCCESS: u32 = 0;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+const NTLMSSP_SIGNATURE: &[u8] = b"NTLMSSP\0";
+/// unicode, request target, NTLM, always sign, extended session security, target info,
+/// version, 128 bit and 56 bit encryption.
+const NTLMSSP_NEGOTIATE_FLAGS: u32 = 0xa288_8205;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SmbInfo {
+    pub port: u16,
+    /// every dialect the server accepted, SMB1 dialects keep their negotiate string.
+    pub dialects: Vec<String>,
+    pub smb1: bool,
+    pub signing_enabled: bool,
+    pub signing_required: bool,
+    pub netbios_name: Option<String>,
+    pub netbios_domain: Option<String>,
+    pub dns_name: Option<String>,
+    pub dns_domain: Option<String>,
+    pub dns_forest: Option<String>,
+    /// Windows version from the NTLMSSP challenge, `major.minor.build`.
+    pub os_version: Option<String>,
+    pub system_time: Option<String>,
+}
+
+impl std::fmt::Display for SmbInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|signing {}",
+            self.dialects.join(","),
+            match (self.signing_enabled, self.signing_required) {
+                (_, true) => "required",
+                (true, false) => "enabled",
+                (false, false) => "disabled",
+            }
+        )?;
+        if let Some(netbios_name) = &self.netbios_name {
+            write!(f, "|{}", netbios_name)?;
+        }
+        if let Some(netbios_domain) = &self.netbios_domain {
+            write!(f, "|{}", netbios_domain)?;
+        }
+        if let Some(os_version) = &self.os_version {
+            write!(f, "|{}", os_version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts a Windows FILETIME, 100ns intervals since 1601, to an RFC 3339 time.
+pub fn filetime_to_string(filetime: u64) -> Option<String> {
+    if filetime == 0 {
+        return None;
+    }
+    let unix = (filetime / 10_000_000) as i64 - 11_644_473_600;
+    let time = time::OffsetDateTime::from_unix_timestamp(unix).ok()?;
+    time.format(&time::format_description::well_known::Rfc3339)
+        .ok()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// A connection speaking SMB over direct TCP or a NetBIOS session, both use the same
+/// four byte length prefix once the session is up.
+struct SmbConnection {
+    stream: TcpStream,
+}
+
+impl SmbConnection {
+    async fn connect(socket_addr: SocketAddr) -> Result<SmbConnection, String> {
+        let mut stream = TcpStream::connect(socket_addr)
+            .await
+            .map_err(|error| error.to_string())?;
+        if socket_addr.port() == NETBIOS_SESSION_PORT {
+            let mut request = encode_name(b"*SMBSERVER", b' ', 0x20);
+            request.extend(encode_name(b"MAGICSCANNER", b' ', 0x00));
+            let mut packet = vec![0x81, 0];
+            packet.extend((request.len() as u16).to_be_bytes());
+            packet.extend(request);
+            stream
+                .write_all(&packet)
+                .await
+                .map_err(|error| error.to_string())?;
+            let mut response = [0u8; 4];
+            stream
+                .read_exact(&mut response)
+                .await
+                .map_err(|error| error.to_string())?;
+            if response[0] != 0x82 {
+                return Err(format!("netbios session refused: 0x{:02x}", response[0]));
+            }
+        }
+        Ok(SmbConnection { stream })
+    }
+
+    async fn send(&mut self, message: &[u8]) -> Result<Vec<u8>, String> {
+        let mut packet = (message.len() as u32).to_be_bytes().to_vec();
+        packet[0] = 0;
+        packet.extend_from_slice(message);
+        self.stream
+            .write_all(&packet)
+            .await
+            .map_err(|error| error.to_string())?;
+        let mut header = [0u8; 4];
+        self.stream
+            .read_exact(&mut header)
+            .await
+            .map_err(|error| error.to_string())?;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(format!("smb message too large: {}", length));
+        }
+        let mut response = vec![0u8; length];
+        self.stream
+            .read_exact(&mut response)
+            .await
+            .map_err(|error| error.to_string())?;
+        Ok(response)
+    }
+}
+
+fn smb1_negotiate_request() -> Vec<u8> {
+    let mut message = vec![0xff, b'S', b'M', b'B', 0x72];
+    // status, flags (canonical paths, case insensitive), flags2 (unicode, nt status,
+    // extended security, long names), pid high, signature, reserved, tid, pid, uid, mid.
+    message.extend([0, 0, 0, 0, 0x18, 0x53, 0xc8, 0, 0]);
+    message.extend([0u8; 8]);
+    message.extend([0, 0, 0xff, 0xff, 0xfe, 0xff, 0, 0, 0, 0]);
+    let mut dialects = vec![];
+    for dialect in SMB1_DIALECTS {
+        dialects.push(0x02);
+        dialects.extend(dialect.as_bytes());
+        dialects.push(0);
+    }
+    message.push(0);
+    message.extend((dialects.len() as u16).to_le_bytes());
+    message.extend(dialects);
+    message
+}
+
+#[derive(Debug, PartialEq)]
+struct Smb1Negotiate {
+    dialect: String,
+    security_mode: u8,
+    system_time: Option<u64>,
+}
+
+fn parse_smb1_negotiate(response: &[u8]) -> Option<Smb1Negotiate> {
+    if response.get(..5)? != [0xff, b'S', b'M', b'B', 0x72] || u32_at(response, 5)? != 0 {
+        return None;
+    }
+    let word_count = *response.get(32)?;
+    let dialect_index = u16_at(response, 33)?;
+    let dialect = SMB1_DIALECTS.get(dialect_index as usize)?.to_string();
+    // the NT LM 0.12 response carries 17 words: security mode and the system time.
+    let (security_mode, system_time) = match word_count >= 17 {
+        true => (
+            *response.get(35)?,
+            u64_at(response, 33 + 2 + 1 + 2 + 2 + 4 + 4 + 4 + 4),
+        ),
+        false => (0, None),
+    };
+    Some(Smb1Negotiate {
+        dialect,
+        security_mode,
+        system_time,
+    })
+}
+
+fn smb2_header(command: u16, message_id: u64) -> Vec<u8> {
+    let mut header = vec![0xfe, b'S', b'M', b'B'];
+    header.extend(64u16.to_le_bytes());
+    // credit charge, status.
+    header.extend([0u8; 6]);
+    header.extend(command.to_le_bytes());
+    // credit request.
+    header.extend(31u16.to_le_bytes());
+    // flags, next command.
+    header.extend([0u8; 8]);
+    header.extend(message_id.to_le_bytes());
+    // process id, tree id, session id and signature.
+    header.extend(0xfeffu32.to_le_bytes());
+    header.extend([0u8; 4 + 8 + 16]);
+    header
+}
+
+fn smb2_negotiate_request(dialects: &[u16]) -> Vec<u8> {
+    let mut message = smb2_header(SMB2_NEGOTIATE, 0);
+    let smb311 = dialects.contains(&0x0311);
+    let dialects_end = 64 + 36 + dialects.len() * 2;
+    let contexts_offset = dialects_end.div_ceil(8) * 8;
+    message.extend(36u16.to_le_bytes());
+    message.extend((dialects.len() as u16).to_le_bytes());
+    message.extend(SMB2_SIGNING_ENABLED.to_le_bytes());
+    message.extend([0, 0]);
+    // capabilities: dfs, leasing, large mtu.
+    message.extend(0x07u32.to_le_bytes());
+    message.extend((0..16).map(|_| rand::random::<u8>()));
+    match smb311 {
+        true => {
+            message.extend((contexts_offset as u32).to_le_bytes());
+            message.extend(2u16.to_le_bytes());
+            message.extend([0, 0]);
+        }
+        false => message.extend([0u8; 8]),
+    }
+    for dialect in dialects {
+        message.extend(dialect.to_le_bytes());
+    }
+    if smb311 {
+        message.resize(contexts_offset, 0);
+        // preauth integrity: one SHA-512 hash with a 32 byte salt.
+        let mut preauth = vec![];
+        preauth.extend(1u16.to_le_bytes());
+        preauth.extend(32u16.to_le_bytes());
+        preauth.extend(1u16.to_le_bytes());
+        preauth.extend((0..32).map(|_| rand::random::<u8>()));
+        // encryption: AES-128-GCM and AES-128-CCM.
+        let encryption = [2u8, 0, 2, 0, 1, 0];
+        for (index, (context_type, data)) in [(1u16, &preauth[..]), (2u16, &encryption[..])]
+            .into_iter()
+            .enumerate()
+        {
+            if index > 0 {
+                message.resize(message.len().div_ceil(8) * 8, 0);
+            }
+            message.extend(context_type.to_le_bytes());
+            message.extend((data.len() as u16).to_le_bytes());
+            message.extend([0u8; 4]);
+            message.extend(data);
+        }
+    }
+    message
+}
+
+#[derive(Debug, PartialEq)]
+struct Smb2Negotiate {
+    dialect: u16,
+    security_mode: u16,
+    system_time: u64,
+}
+
+fn smb2_status(response: &[u8]) -> Option<u32> {
+    if response.get(..4)? != [0xfe, b'S', b'M', b'B'] {
+        return None;
+    }
+    u32_at(response, 8)
+}
+
+fn parse_smb2_negotiate(response: &[u8]) -> Option<Smb2Negotiate> {
+    if smb2_status(response)? != STATUS_SUCCESS || u16_at(response, 12)? != SMB2_NEGOTIATE {
+        return None;
+    }
+    Some(Smb2Negotiate {
+        security_mode: u16_at(response, 64 + 2)?,
+        dialect: u16_at(response, 64 + 4)?,
+        system_time: u64_at(response, 64 + 40)?,
+    })
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match content.len() {
+        length @ 0..=0x7f => encoded.push(length as u8),
+        length @ 0x80..=0xff => encoded.extend([0x81, length as u8]),
+        length => {
+            encoded.push(0x82);
+            encoded.extend((length as u16).to_be_bytes());
+        }
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+/// An NTLMSSP NEGOTIATE message wrapped in a SPNEGO NegTokenInit.
+fn ntlmssp_negotiate_token() -> Vec<u8> {
+    let mut ntlmssp = NTLMSSP_SIGNATURE.to_vec();
+    ntlmssp.extend(1u32.to_le_bytes());
+    ntlmssp.extend(NTLMSSP_NEGOTIATE_FLAGS.to_le_bytes());
+    // domain and workstation fields.
+    ntlmssp.extend([0u8; 16]);
+    // version 6.1.7601, NTLM revision 15.
+    ntlmssp.extend([6, 1, 0xb1, 0x1d, 0, 0, 0, 0x0f]);
+
+    let ntlmssp_oid = [0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a];
+    let mech_types = der(0xa0, &der(0x30, &der(0x06, &ntlmssp_oid)));
+    let mech_token = der(0xa2, &der(0x04, &ntlmssp));
+    let neg_token_init = der(0xa0, &der(0x30, &[mech_types, mech_token].concat()));
+    let spnego_oid = der(0x06, &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x02]);
+    der(0x60, &[spnego_oid, neg_token_init].concat())
+}
+
+fn smb2_session_setup_request(message_id: u64) -> Vec<u8> {
+    let token = ntlmssp_negotiate_token();
+    let mut message = smb2_header(SMB2_SESSION_SETUP, message_id);
+    message.extend(25u16.to_le_bytes());
+    message.push(0);
+    message.push(SMB2_SIGNING_ENABLED as u8);
+    // capabilities, channel.
+    message.extend([0u8; 8]);
+    message.extend((64u16 + 24).to_le_bytes());
+    message.extend((token.len() as u16).to_le_bytes());
+    // previous session id.
+    message.extend([0u8; 8]);
+    message.extend(token);
+    message
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct NtlmChallenge {
+    netbios_name: Option<String>,
+    netbios_domain: Option<String>,
+    dns_name: Option<String>,
+    dns_domain: Option<String>,
+    dns_forest: Option<String>,
+    timestamp: Option<u64>,
+    os_version: Option<String>,
+}
+
+/// Finds the NTLMSSP CHALLENGE in a session setup response and reads the target info.
+fn parse_ntlm_challenge(response: &[u8]) -> Option<NtlmChallenge> {
+    let start = response
+        .windows(NTLMSSP_SIGNATURE.len())
+        .position(|window| window == NTLMSSP_SIGNATURE)?;
+    let message = &response[start..];
+    if u32_at(message, 8)? != 2 {
+        return None;
+    }
+    let flags = u32_at(message, 20)?;
+    let mut challenge = NtlmChallenge::default();
+    let info_length = u16_at(message, 40)? as usize;
+    let info_offset = u32_at(message, 44)? as usize;
+    let mut info = message.get(info_offset..info_offset + info_length)?;
+    while info.len() >= 4 {
+        let id = u16_at(info, 0)?;
+        let length = u16_at(info, 2)? as usize;
+        let value = info.get(4..4 + length)?;
+        match id {
+            0 => break,
+            1 => challenge.netbios_name = Some(utf16(value)),
+            2 => challenge.netbios_domain = Some(utf16(value)),
+            3 => challenge.dns_name = Some(utf16(value)),
+            4 => challenge.dns_domain = Some(utf16(value)),
+            5 => challenge.dns_forest = Some(utf16(value)),
+            7 => challenge.timestamp = u64_at(value, 0),
+            _ => {}
+        }
+        info = &info[4 + length..];
+    }
+    // NTLMSSP_NEGOTIATE_VERSION
+    if flags & 0x0200_0000 != 0 {
+        let version = message.get(48..52)?;
+        let build = u16::from_le_bytes([version[2], version[3]]);
+        challenge.os_version = Some(format!("{}.{}.{}", version[0], version[1], build));
+    }
+    Some(challenge)
+}
+
+async fn smb1_negotiate(socket_addr: SocketAddr) -> Result<Option<Smb1Negotiate>, String> {
+    let mut connection = SmbConnection::connect(socket_addr).await?;
+    // servers with SMB1 disabled reset the connection or answer in SMB2.
+    match connection.send(&smb1_negotiate_request()).await {
+        Ok(response) => Ok(parse_smb1_negotiate(&response)),
+        Err(_) => Ok(None),
+    }
+}
+
+async fn smb2_negotiate(
+    socket_addr: SocketAddr,
+    dialects: &[u16],
+) -> Result<(SmbConnection, Option<Smb2Negotiate>), String> {
+    let mut connection = SmbConnection::connect(socket_addr).await?;
+    let response = connection.send(&smb2_negotiate_request(dialects)).await?;
+    Ok((connection, parse_smb2_negotiate(&response)))
+}
+
+/// Negotiates every SMB1 and SMB2 dialect separately, then starts an NTLM session setup
+/// with the best dialect to read the host names and OS version from the challenge.
+pub async fn probe(socket_addr: SocketAddr) -> Option<SmbInfo> {
+    let result = tokio::time::timeout(SMB_TIMEOUT, enumerate(socket_addr)).await;
+    match result {
+        Ok(Ok(smb_info)) if !smb_info.dialects.is_empty() => Some(smb_info),
+        Ok(Ok(_)) => None,
+        Ok(Err(error)) => {
+            tracing::debug!("smb probe on {} failed: {}", socket_addr, error);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+async fn enumerate(socket_addr: SocketAddr) -> Result<SmbInfo, String> {
+    let mut smb_info = SmbInfo {
+        port: socket_addr.port(),
+        ..Default::default()
+    };
+    if let Some(negotiate) = smb1_negotiate(socket_addr).await? {
+        smb_info.smb1 = true;
+        smb_info.dialects.push(negotiate.dialect);
+        smb_info.signing_enabled = negotiate.security_mode & SMB1_SIGNING_ENABLED != 0;
+        smb_info.signing_required = negotiate.security_mode & SMB1_SIGNING_REQUIRED != 0;
+        smb_info.system_time = negotiate.system_time.and_then(filetime_to_string);
+    }
+    for (dialect, name) in SMB2_DIALECTS {
+        if let Ok((_, Some(negotiate))) = smb2_negotiate(socket_addr, &[dialect]).await {
+            if negotiate.dialect == dialect {
+                smb_info.dialects.push(name.to_string());
+            }
+        }
+    }
+
+    let dialects = SMB2_DIALECTS.map(|(dialect, _)| dialect);
+    let (mut connection, negotiate) = smb2_negotiate(socket_addr, &dialects).await?;
+    let negotiate = match negotiate {
+        None => return Ok(smb_info),
+        Some(negotiate) => negotiate,
+    };
+    smb_info.signing_enabled = negotiate.security_mode & SMB2_SIGNING_ENABLED != 0;
+    smb_info.signing_required = negotiate.security_mode & SMB2_SIGNING_REQUIRED != 0;
+    smb_info.system_time = filetime_to_string(negotiate.system_time);
+
+    let response = connection.send(&smb2_session_setup_request(1)).await?;
+    if smb2_status(&response) != Some(STATUS_MORE_PROCESSING_REQUIRED) {
+        return Ok(smb_info);
+    }
+    if let Some(challenge) = parse_ntlm_challenge(&response) {
+        smb_info.netbios_name = challenge.netbios_name;
+        smb_info.netbios_domain = challenge.netbios_domain;
+        smb_info.dns_name = challenge.dns_name;
+        smb_info.dns_domain = challenge.dns_domain;
+        smb_info.dns_forest = challenge.dns_forest;
+        smb_info.os_version = challenge.os_version;
+        if smb_info.system_time.is_none() {
+            smb_info.system_time = challenge.timestamp.and_then(filetime_to_string);
+        }
+    }
+    Ok(smb_info)
+}
+
+#[cfg(test)]
+pub mod smb_test {
+    use crate::scanner::smb::{
+        filetime_to_string, parse_ntlm_challenge, parse_smb1_negotiate, smb1_negotiate_request,
+        smb2_negotiate_request, NTLMSSP_SIGNATURE,
+    };
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn ntlm_challenge_test() {
+        let mut info = vec![];
+        for (id, value) in [(1u16, "FILESRV"), (2, "CORP"), (4, "corp.local")] {
+            info.extend(id.to_le_bytes());
+            info.extend((utf16(value).len() as u16).to_le_bytes());
+            info.extend(utf16(value));
+        }
+        info.extend([0u8; 4]);
+        let mut message = NTLMSSP_SIGNATURE.to_vec();
+        message.extend(2u32.to_le_bytes());
+        message.extend([0u8; 8]);
+        message.extend(0x0280_0000u32.to_le_bytes());
+        message.extend([0u8; 16]);
+        message.extend((info.len() as u16).to_le_bytes());
+        message.extend((info.len() as u16).to_le_bytes());
+        message.extend(56u32.to_le_bytes());
+        message.extend([10, 0, 0x61, 0x4a, 0, 0, 0, 15]);
+        message.extend(info);
+        let mut response = vec![0u8; 72];
+        response.extend(message);
+        let challenge = parse_ntlm_challenge(&response).unwrap();
+        assert_eq!(challenge.netbios_name.as_deref(), Some("FILESRV"));
+        assert_eq!(challenge.netbios_domain.as_deref(), Some("CORP"));
+        assert_eq!(challenge.dns_domain.as_deref(), Some("corp.local"));
+        assert_eq!(challenge.os_version.as_deref(), Some("10.0.19041"));
+    }
+
+    #[test]
+    fn smb1_negotiate_test() {
+        let request = smb1_negotiate_request();
+        assert_eq!(&request[..5], &[0xff, b'S', b'M', b'B', 0x72]);
+        assert_eq!(
+            request.len(),
+            32 + 3 + u16::from_le_bytes([request[33], request[34]]) as usize
+        );
+
+        let mut response = request[..32].to_vec();
+        response.push(17);
+        response.extend(5u16.to_le_bytes());
+        response.push(0x03);
+        response.extend([0u8; 20]);
+        response.extend(133_485_408_000_000_000u64.to_le_bytes());
+        response.extend([0u8; 4]);
+        let negotiate = parse_smb1_negotiate(&response).unwrap();
+        assert_eq!(negotiate.dialect, "NT LM 0.12");
+        assert_eq!(negotiate.security_mode, 0x03);
+        assert_eq!(
+            negotiate
+                .system_time
+                .and_then(filetime_to_string)
+                .as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn smb2_negotiate_test() {
+        let request = smb2_negotiate_request(&[0x0202, 0x0311]);
+        assert_eq!(&request[..4], &[0xfe, b'S', b'M', b'B']);
+        let contexts_offset = u32::from_le_bytes(request[64 + 28..64 + 32].try_into().unwrap());
+        assert_eq!(contexts_offset % 8, 0);
+        assert_eq!(
+            u16::from_le_bytes([request[contexts_offset as usize], 0]),
+            1
+        );
+    }
+}
@@ -0,0 +1,614 @@
+use crate::scanner::socket::{IpType, Socket};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+pub const SNMP_PORT: u16 = 161;
+const SNMP_TIMEOUT: Duration = Duration::from_millis(2000);
+/// Upper bound on the interface rows walked, switches can have thousands of them.
+const MAX_INTERFACES: usize = 128;
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const IP_ADDRESS: u8 = 0x40;
+const COUNTER32: u8 = 0x41;
+const GAUGE32: u8 = 0x42;
+const TIME_TICKS: u8 = 0x43;
+const COUNTER64: u8 = 0x46;
+const GET_REQUEST: u8 = 0xa0;
+const GET_NEXT_REQUEST: u8 = 0xa1;
+const GET_RESPONSE: u8 = 0xa2;
+
+const SYS_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 1, 0];
+const SYS_OBJECT_ID: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 2, 0];
+const SYS_UP_TIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+const SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5, 0];
+const IF_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 2];
+const IF_TYPE: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 3];
+const IF_SPEED: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 5];
+const IF_PHYS_ADDRESS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 6];
+const IF_OPER_STATUS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 8];
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum SnmpVersion {
+    V1,
+    V2c,
+}
+
+impl SnmpVersion {
+    fn code(&self) -> i64 {
+        match self {
+            SnmpVersion::V1 => 0,
+            SnmpVersion::V2c => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for SnmpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnmpVersion::V1 => write!(f, "v1"),
+            SnmpVersion::V2c => write!(f, "v2c"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SnmpValue {
+    Integer(i64),
+    String(Vec<u8>),
+    Oid(Vec<u32>),
+    Unsigned(u64),
+    IpAddress([u8; 4]),
+    /// NULL and the v2c noSuchObject, noSuchInstance and endOfMibView exceptions.
+    Empty,
+}
+
+impl SnmpValue {
+    fn as_string(&self) -> Option<String> {
+        match self {
+            SnmpValue::String(data) => Some(String::from_utf8_lossy(data).trim().to_string()),
+            SnmpValue::Oid(oid) => Some(oid_to_string(oid)),
+            SnmpValue::Integer(value) => Some(value.to_string()),
+            SnmpValue::Unsigned(value) => Some(value.to_string()),
+            SnmpValue::IpAddress(ip) => Some(std::net::Ipv4Addr::from(*ip).to_string()),
+            SnmpValue::Empty => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            SnmpValue::Integer(value) => u64::try_from(*value).ok(),
+            SnmpValue::Unsigned(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnmpInterface {
+    pub index: u32,
+    pub description: Option<String>,
+    pub if_type: Option<u64>,
+    pub speed: Option<u64>,
+    pub mac_address: Option<String>,
+    pub oper_status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnmpInfo {
+    pub version: SnmpVersion,
+    pub communities: Vec<String>,
+    pub sys_descr: Option<String>,
+    pub sys_object_id: Option<String>,
+    pub sys_up_time: Option<String>,
+    pub sys_name: Option<String>,
+    pub interfaces: Vec<SnmpInterface>,
+}
+
+impl std::fmt::Display for SnmpInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|{}|{} interfaces",
+            self.version,
+            self.communities.join(","),
+            self.sys_name.as_deref().unwrap_or("*"),
+            self.sys_descr
+                .as_deref()
+                .and_then(|descr| descr.lines().next())
+                .unwrap_or("*"),
+            self.sys_up_time.as_deref().unwrap_or("*"),
+            self.interfaces.len()
+        )
+    }
+}
+
+/// Reads a community wordlist, one string per line, `#` starts a comment.
+pub fn read_communities(path: &str) -> Vec<String> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => data
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect(),
+        Err(error) => {
+            tracing::warn!("read snmp communities {} failed: {}", path, error);
+            vec![]
+        }
+    }
+}
+
+fn oid_to_string(oid: &[u32]) -> String {
+    oid.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+fn encode_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut buf = vec![tag];
+    let length = content.len();
+    if length < 0x80 {
+        buf.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+        buf.push(0x80 | (bytes.len() - skip) as u8);
+        buf.extend_from_slice(&bytes[skip..]);
+    }
+    buf.extend_from_slice(content);
+    buf
+}
+
+fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // drop leading bytes that only repeat the sign bit.
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    encode_tlv(INTEGER, &bytes[start..])
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut content = vec![];
+    if oid.len() >= 2 {
+        content.push((oid[0] * 40 + oid[1]) as u8);
+    }
+    for arc in oid.iter().skip(2) {
+        let mut arc = *arc;
+        let mut encoded = vec![(arc & 0x7f) as u8];
+        arc >>= 7;
+        while arc > 0 {
+            encoded.push(0x80 | (arc & 0x7f) as u8);
+            arc >>= 7;
+        }
+        encoded.reverse();
+        content.extend(encoded);
+    }
+    encode_tlv(OBJECT_IDENTIFIER, &content)
+}
+
+fn encode_request(
+    version: SnmpVersion,
+    community: &str,
+    pdu_type: u8,
+    request_id: i32,
+    oids: &[&[u32]],
+) -> Vec<u8> {
+    let varbinds = oids
+        .iter()
+        .flat_map(|oid| encode_tlv(SEQUENCE, &[encode_oid(oid), encode_tlv(NULL, &[])].concat()))
+        .collect::<Vec<u8>>();
+    let pdu = [
+        encode_integer(request_id as i64),
+        encode_integer(0),
+        encode_integer(0),
+        encode_tlv(SEQUENCE, &varbinds),
+    ]
+    .concat();
+    let message = [
+        encode_integer(version.code()),
+        encode_tlv(OCTET_STRING, community.as_bytes()),
+        encode_tlv(pdu_type, &pdu),
+    ]
+    .concat();
+    encode_tlv(SEQUENCE, &message)
+}
+
+/// Reads one TLV from the front of `data`, returns the tag, its content and the rest.
+fn decode_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (length, header) = if first & 0x80 == 0 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let length = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0usize, |length, byte| length << 8 | *byte as usize);
+        (length, 2 + count)
+    };
+    let content = data.get(header..header + length)?;
+    Some((tag, content, &data[header + length..]))
+}
+
+fn decode_integer(content: &[u8]) -> Option<i64> {
+    if content.is_empty() || content.len() > 8 {
+        return None;
+    }
+    let negative = content[0] & 0x80 != 0;
+    let start = if negative { -1i64 } else { 0 };
+    Some(
+        content
+            .iter()
+            .fold(start, |value, byte| value << 8 | *byte as i64),
+    )
+}
+
+fn decode_oid(content: &[u8]) -> Option<Vec<u32>> {
+    let first = *content.first()? as u32;
+    let mut oid = vec![first / 40, first % 40];
+    let mut arc = 0u32;
+    for byte in content[1..].iter() {
+        arc = arc.checked_mul(128)? | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            oid.push(arc);
+            arc = 0;
+        }
+    }
+    Some(oid)
+}
+
+fn decode_value(tag: u8, content: &[u8]) -> Option<SnmpValue> {
+    let value = match tag {
+        INTEGER => SnmpValue::Integer(decode_integer(content)?),
+        OCTET_STRING => SnmpValue::String(content.to_vec()),
+        OBJECT_IDENTIFIER => SnmpValue::Oid(decode_oid(content)?),
+        IP_ADDRESS => SnmpValue::IpAddress(content.try_into().ok()?),
+        COUNTER32 | GAUGE32 | TIME_TICKS | COUNTER64 => SnmpValue::Unsigned(
+            content
+                .iter()
+                .fold(0u64, |value, byte| value << 8 | *byte as u64),
+        ),
+        _ => SnmpValue::Empty,
+    };
+    Some(value)
+}
+
+/// A decoded GetResponse PDU.
+#[derive(Debug, Clone, PartialEq)]
+struct SnmpResponse {
+    community: String,
+    request_id: i32,
+    error_status: i64,
+    varbinds: Vec<(Vec<u32>, SnmpValue)>,
+}
+
+fn parse_response(data: &[u8]) -> Option<SnmpResponse> {
+    let (tag, message, _) = decode_tlv(data)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (_, _version, rest) = decode_tlv(message)?;
+    let (_, community, rest) = decode_tlv(rest)?;
+    let (tag, pdu, _) = decode_tlv(rest)?;
+    if tag != GET_RESPONSE {
+        return None;
+    }
+    let (_, request_id, rest) = decode_tlv(pdu)?;
+    let (_, error_status, rest) = decode_tlv(rest)?;
+    let (_, _error_index, rest) = decode_tlv(rest)?;
+    let (_, mut list, _) = decode_tlv(rest)?;
+    let mut varbinds = vec![];
+    while !list.is_empty() {
+        let (_, varbind, rest) = decode_tlv(list)?;
+        let (_, oid, value) = decode_tlv(varbind)?;
+        let (tag, value, _) = decode_tlv(value)?;
+        varbinds.push((decode_oid(oid)?, decode_value(tag, value)?));
+        list = rest;
+    }
+    Some(SnmpResponse {
+        community: String::from_utf8_lossy(community).to_string(),
+        request_id: decode_integer(request_id)? as i32,
+        error_status: decode_integer(error_status)?,
+        varbinds,
+    })
+}
+
+fn format_up_time(ticks: u64) -> String {
+    let seconds = ticks / 100;
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+fn format_mac(value: &SnmpValue) -> Option<String> {
+    match value {
+        SnmpValue::String(data) if data.len() == 6 => Some(
+            data.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<String>>()
+                .join(":"),
+        ),
+        _ => None,
+    }
+}
+
+fn oper_status(value: &SnmpValue) -> Option<String> {
+    let status = match value.as_u64()? {
+        1 => "up",
+        2 => "down",
+        3 => "testing",
+        5 => "dormant",
+        6 => "notPresent",
+        7 => "lowerLayerDown",
+        _ => "unknown",
+    };
+    Some(status.to_string())
+}
+
+struct SnmpClient {
+    socket: UdpSocket,
+    socket_addr: SocketAddr,
+    version: SnmpVersion,
+    community: String,
+}
+
+impl SnmpClient {
+    async fn request(&self, pdu_type: u8, oids: &[&[u32]]) -> Option<SnmpResponse> {
+        let request_id = rand::random::<i32>() & 0x7fffffff;
+        let request = encode_request(self.version, &self.community, pdu_type, request_id, oids);
+        self.socket.send_to(&request, self.socket_addr).await.ok()?;
+        let mut buf = vec![0u8; 65535];
+        let receive = async {
+            loop {
+                let (size, from) = self.socket.recv_from(&mut buf).await.ok()?;
+                if from != self.socket_addr {
+                    continue;
+                }
+                match parse_response(&buf[..size]) {
+                    Some(response) if response.request_id == request_id => return Some(response),
+                    _ => continue,
+                }
+            }
+        };
+        let response = tokio::time::timeout(SNMP_TIMEOUT, receive).await.ok()??;
+        (response.error_status == 0).then_some(response)
+    }
+
+    /// Walks the interface table columns side by side with GetNext requests.
+    async fn walk_interfaces(&self) -> Vec<SnmpInterface> {
+        let columns = [IF_DESCR, IF_TYPE, IF_SPEED, IF_PHYS_ADDRESS, IF_OPER_STATUS];
+        let mut cursor = columns.iter().map(|oid| oid.to_vec()).collect::<Vec<_>>();
+        let mut interfaces = vec![];
+        while interfaces.len() < MAX_INTERFACES {
+            let oids = cursor.iter().map(|oid| oid.as_slice()).collect::<Vec<_>>();
+            let response = match self.request(GET_NEXT_REQUEST, &oids).await {
+                None => break,
+                Some(data) => data,
+            };
+            if response.varbinds.len() != columns.len() {
+                break;
+            }
+            let (oid, _) = &response.varbinds[0];
+            if !oid.starts_with(IF_DESCR) || oid.len() != IF_DESCR.len() + 1 {
+                break;
+            }
+            let index = oid[IF_DESCR.len()];
+            let value = |column: usize| {
+                let (oid, value) = &response.varbinds[column];
+                (oid.starts_with(columns[column]) && oid.last() == Some(&index)).then_some(value)
+            };
+            interfaces.push(SnmpInterface {
+                index,
+                description: value(0).and_then(|value| value.as_string()),
+                if_type: value(1).and_then(|value| value.as_u64()),
+                speed: value(2).and_then(|value| value.as_u64()),
+                mac_address: value(3).and_then(format_mac),
+                oper_status: value(4).and_then(oper_status),
+            });
+            cursor = response.varbinds.into_iter().map(|(oid, _)| oid).collect();
+        }
+        interfaces
+    }
+}
+
+async fn udp_socket(socket_addr: SocketAddr) -> Option<UdpSocket> {
+    match socket_addr {
+        SocketAddr::V4(_) => Some(Socket::get_udp_socket(IpType::V4)),
+        SocketAddr::V6(_) => UdpSocket::bind("[::]:0").await.ok(),
+    }
+}
+
+/// Sends a sysDescr request for every community and version at once and collects the
+/// ones the agent answered. Agents silently drop requests with a wrong community.
+async fn guess_communities(
+    socket: &UdpSocket,
+    socket_addr: SocketAddr,
+    communities: &[String],
+) -> Vec<(SnmpVersion, String)> {
+    let versions = [SnmpVersion::V2c, SnmpVersion::V1];
+    let mut requests = vec![];
+    for version in versions {
+        for community in communities.iter() {
+            let request_id = requests.len() as i32 + 1;
+            let request = encode_request(version, community, GET_REQUEST, request_id, &[SYS_DESCR]);
+            if socket.send_to(&request, socket_addr).await.is_err() {
+                return vec![];
+            }
+            requests.push((version, community.clone()));
+        }
+    }
+    let mut accepted = vec![];
+    let mut buf = vec![0u8; 65535];
+    let deadline = tokio::time::Instant::now() + SNMP_TIMEOUT;
+    while let Ok(Ok((size, from))) =
+        tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await
+    {
+        if from != socket_addr {
+            continue;
+        }
+        let response = match parse_response(&buf[..size]) {
+            None => continue,
+            Some(data) => data,
+        };
+        let request = usize::try_from(response.request_id - 1)
+            .ok()
+            .and_then(|index| requests.get(index));
+        if let Some((version, community)) = request {
+            if *community == response.community
+                && !accepted.contains(&(*version, community.clone()))
+            {
+                accepted.push((*version, community.clone()));
+            }
+        }
+    }
+    accepted
+}
+
+/// Guesses v1/v2c communities from `communities`, then reads the system group and the
+/// interface table with the first accepted one, preferring v2c.
+pub async fn probe(socket_addr: SocketAddr, communities: &[String]) -> Option<SnmpInfo> {
+    if communities.is_empty() {
+        return None;
+    }
+    let socket = udp_socket(socket_addr).await?;
+    let accepted = guess_communities(&socket, socket_addr, communities).await;
+    let (version, community) = accepted.first()?.clone();
+    let mut names = vec![];
+    for (_, name) in accepted.iter() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    let client = SnmpClient {
+        socket,
+        socket_addr,
+        version,
+        community,
+    };
+
+    let mut info = SnmpInfo {
+        version,
+        communities: names,
+        sys_descr: None,
+        sys_object_id: None,
+        sys_up_time: None,
+        sys_name: None,
+        interfaces: vec![],
+    };
+    let system = client
+        .request(
+            GET_REQUEST,
+            &[SYS_DESCR, SYS_OBJECT_ID, SYS_UP_TIME, SYS_NAME],
+        )
+        .await;
+    for (oid, value) in system.map(|response| response.varbinds).unwrap_or_default() {
+        match oid.as_slice() {
+            SYS_DESCR => info.sys_descr = value.as_string(),
+            SYS_OBJECT_ID => info.sys_object_id = value.as_string(),
+            SYS_UP_TIME => info.sys_up_time = value.as_u64().map(format_up_time),
+            SYS_NAME => info.sys_name = value.as_string(),
+            _ => {}
+        }
+    }
+    info.interfaces = client.walk_interfaces().await;
+    Some(info)
+}
+
+#[cfg(test)]
+pub mod snmp_test {
+    use crate::scanner::snmp::{
+        decode_tlv, encode_integer, encode_oid, encode_request, encode_tlv, format_up_time,
+        parse_response, SnmpValue, SnmpVersion, GET_REQUEST, GET_RESPONSE, OCTET_STRING, SEQUENCE,
+        SYS_DESCR, TIME_TICKS,
+    };
+
+    #[test]
+    fn snmp_ber_test() {
+        assert_eq!(
+            encode_oid(SYS_DESCR),
+            [0x06, 0x08, 0x2b, 6, 1, 2, 1, 1, 1, 0]
+        );
+        assert_eq!(encode_oid(&[1, 3, 6, 1, 4, 1, 9]).len(), 8);
+        assert_eq!(
+            encode_oid(&[1, 3, 6, 1, 4, 1, 311]),
+            [6, 7, 0x2b, 6, 1, 4, 1, 0x82, 0x37]
+        );
+        assert_eq!(encode_integer(0), [2, 1, 0]);
+        assert_eq!(encode_integer(128), [2, 2, 0, 128]);
+        assert_eq!(encode_integer(-1), [2, 1, 0xff]);
+        let long = encode_tlv(OCTET_STRING, &[0u8; 300]);
+        assert_eq!(&long[..4], [4, 0x82, 1, 44]);
+        assert_eq!(decode_tlv(&long).unwrap().1.len(), 300);
+
+        let request = encode_request(SnmpVersion::V2c, "public", GET_REQUEST, 7, &[SYS_DESCR]);
+        let (tag, message, rest) = decode_tlv(&request).unwrap();
+        assert_eq!((tag, rest.len()), (SEQUENCE, 0));
+        assert_eq!(&message[..3], [2, 1, 1]);
+
+        // turn the request into a response carrying a sysDescr and the uptime.
+        let varbinds = [
+            encode_tlv(
+                SEQUENCE,
+                &[
+                    encode_oid(SYS_DESCR),
+                    encode_tlv(OCTET_STRING, b"Linux gw 6.1"),
+                ]
+                .concat(),
+            ),
+            encode_tlv(
+                SEQUENCE,
+                &[
+                    encode_oid(&[1, 3, 6, 1, 2, 1, 1, 3, 0]),
+                    encode_tlv(TIME_TICKS, &[0x01, 0x00, 0x00, 0x00]),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat();
+        let pdu = [
+            encode_integer(7),
+            encode_integer(0),
+            encode_integer(0),
+            encode_tlv(SEQUENCE, &varbinds),
+        ]
+        .concat();
+        let message = [
+            encode_integer(1),
+            encode_tlv(OCTET_STRING, b"public"),
+            encode_tlv(GET_RESPONSE, &pdu),
+        ]
+        .concat();
+        let response = parse_response(&encode_tlv(SEQUENCE, &message)).unwrap();
+        assert_eq!(response.community, "public");
+        assert_eq!(response.request_id, 7);
+        assert_eq!(response.varbinds.len(), 2);
+        assert_eq!(response.varbinds[0].0, SYS_DESCR);
+        assert_eq!(
+            response.varbinds[0].1.as_string().as_deref(),
+            Some("Linux gw 6.1")
+        );
+        assert_eq!(response.varbinds[1].1, SnmpValue::Unsigned(0x01000000));
+        assert_eq!(format_up_time(0x01000000), "1d 22:36:12");
+        assert!(parse_response(&request).is_none());
+    }
+}
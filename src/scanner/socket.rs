@@ -1,4 +1,17 @@
 #![allow(dead_code)]
+use crate::fingerprint::technology::DetectedTechnology;
+use crate::os::passive::PassiveObservation;
+use crate::scanner::credential::CredentialFinding;
+use crate::scanner::database::DatabaseInfo;
+use crate::scanner::dns::DnsInfo;
+use crate::scanner::http::HttpInfo;
+use crate::scanner::script::ScriptFinding;
+use crate::scanner::snmp::SnmpInfo;
+use crate::scanner::ssh::SshInfo;
+use crate::scanner::tls::TlsInfo;
+use crate::scanner::tls_enum::TlsEnumeration;
+use crate::vuln::{self, Vulnerability};
+use serde::{Deserialize, Serialize};
 use socket2::{Domain, Protocol, SockAddr, Type};
 use std::net::IpAddr;
 use std::os::fd::FromRawFd;
@@ -11,7 +24,8 @@ pub enum IpType {
     V4,
     V6,
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TransportLayerProtocol {
     TCP,
     UDP,
@@ -20,6 +34,7 @@ pub enum TransportLayerProtocol {
 pub struct Socket;
 
 impl Socket {
+    #[allow(deprecated)]
     fn v4_tcp_socket() -> TcpSocket {
         let socket = TcpSocket::new_v4().unwrap();
         socket.set_reuseaddr(true).unwrap();
@@ -31,15 +46,14 @@ impl Socket {
     }
 
     fn v6_tcp_socket() -> TcpSocket {
-        let socket = TcpSocket::new_v6().unwrap();
-        socket
+        TcpSocket::new_v6().unwrap()
     }
 
     pub fn get_tcp_socket(ip_type: IpType) -> TcpSocket {
-        return match ip_type {
+        match ip_type {
             IpType::V4 => Socket::v4_tcp_socket(),
             IpType::V6 => Socket::v6_tcp_socket(),
-        };
+        }
     }
 
     pub fn get_udp_socket(ip_type: IpType) -> UdpSocket {
@@ -62,21 +76,38 @@ impl Socket {
         socket
             .set_write_timeout(Some(Duration::from_millis(500)))
             .unwrap();
-        let socket =
-            UdpSocket::from_std(unsafe { std::net::UdpSocket::from_raw_fd(socket.into_raw_fd()) })
-                .unwrap();
-        socket
+        UdpSocket::from_std(unsafe { std::net::UdpSocket::from_raw_fd(socket.into_raw_fd()) })
+            .unwrap()
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ScannerReply {
     activity: bool,
     name: String,
     ip_addr: Option<IpAddr>,
     port: Option<u16>,
     version: Option<String>,
+    cpe: Option<String>,
     transport_layer_protocol: Option<TransportLayerProtocol>,
+    os_observation: Option<PassiveObservation>,
+    tls: Option<TlsInfo>,
+    tls_enumeration: Option<TlsEnumeration>,
+    http: Option<HttpInfo>,
+    technologies: Vec<DetectedTechnology>,
+    ssh: Option<SshInfo>,
+    dns: Option<DnsInfo>,
+    snmp: Option<SnmpInfo>,
+    database: Option<DatabaseInfo>,
+    credentials: Vec<CredentialFinding>,
+    vulnerabilities: Vec<Vulnerability>,
+    scripts: Vec<ScriptFinding>,
+}
+
+impl Default for ScannerReply {
+    fn default() -> Self {
+        ScannerReply::new()
+    }
 }
 
 impl ScannerReply {
@@ -87,7 +118,20 @@ impl ScannerReply {
             ip_addr: None,
             port: None,
             version: None,
+            cpe: None,
             transport_layer_protocol: None,
+            os_observation: None,
+            tls: None,
+            tls_enumeration: None,
+            http: None,
+            technologies: vec![],
+            ssh: None,
+            dns: None,
+            snmp: None,
+            database: None,
+            credentials: vec![],
+            vulnerabilities: vec![],
+            scripts: vec![],
         }
     }
     pub fn activity(&self) -> bool {
@@ -108,6 +152,12 @@ impl ScannerReply {
     pub fn set_version(&mut self, version: String) {
         self.version = Some(version);
     }
+    pub fn cpe(&self) -> Option<String> {
+        self.cpe.clone()
+    }
+    pub fn set_cpe(&mut self, cpe: Option<String>) {
+        self.cpe = cpe;
+    }
     pub fn ip_addr(&self) -> Option<IpAddr> {
         self.ip_addr
     }
@@ -129,4 +179,91 @@ impl ScannerReply {
     ) {
         self.transport_layer_protocol = transport_layer_protocol;
     }
+    pub fn os_observation(&self) -> Option<&PassiveObservation> {
+        self.os_observation.as_ref()
+    }
+    pub fn set_os_observation(&mut self, os_observation: Option<PassiveObservation>) {
+        self.os_observation = os_observation;
+    }
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+    pub fn set_tls(&mut self, tls: Option<TlsInfo>) {
+        self.tls = tls;
+    }
+    pub fn tls_enumeration(&self) -> Option<&TlsEnumeration> {
+        self.tls_enumeration.as_ref()
+    }
+    pub fn set_tls_enumeration(&mut self, tls_enumeration: Option<TlsEnumeration>) {
+        self.tls_enumeration = tls_enumeration;
+    }
+    pub fn http(&self) -> Option<&HttpInfo> {
+        self.http.as_ref()
+    }
+    pub fn set_http(&mut self, http: Option<HttpInfo>) {
+        self.http = http;
+    }
+    pub fn technologies(&self) -> &[DetectedTechnology] {
+        &self.technologies
+    }
+    pub fn set_technologies(&mut self, technologies: Vec<DetectedTechnology>) {
+        self.technologies = technologies;
+    }
+    pub fn ssh(&self) -> Option<&SshInfo> {
+        self.ssh.as_ref()
+    }
+    pub fn set_ssh(&mut self, ssh: Option<SshInfo>) {
+        self.ssh = ssh;
+    }
+    pub fn dns(&self) -> Option<&DnsInfo> {
+        self.dns.as_ref()
+    }
+    pub fn set_dns(&mut self, dns: Option<DnsInfo>) {
+        self.dns = dns;
+    }
+    pub fn snmp(&self) -> Option<&SnmpInfo> {
+        self.snmp.as_ref()
+    }
+    pub fn set_snmp(&mut self, snmp: Option<SnmpInfo>) {
+        self.snmp = snmp;
+    }
+    pub fn database(&self) -> Option<&DatabaseInfo> {
+        self.database.as_ref()
+    }
+    pub fn set_database(&mut self, database: Option<DatabaseInfo>) {
+        self.database = database;
+    }
+    pub fn credentials(&self) -> &[CredentialFinding] {
+        &self.credentials
+    }
+    pub fn set_credentials(&mut self, credentials: Vec<CredentialFinding>) {
+        self.credentials = credentials;
+    }
+    pub fn vulnerabilities(&self) -> &[Vulnerability] {
+        &self.vulnerabilities
+    }
+    pub fn set_vulnerabilities(&mut self, vulnerabilities: Vec<Vulnerability>) {
+        self.vulnerabilities = vulnerabilities;
+    }
+    pub fn scripts(&self) -> &[ScriptFinding] {
+        &self.scripts
+    }
+    pub fn set_scripts(&mut self, scripts: Vec<ScriptFinding>) {
+        self.scripts = scripts;
+    }
+    /// CPEs of the matched service, the web technologies behind it and the database
+    /// engine, with the detected versions filled in.
+    pub fn cpes(&self) -> Vec<String> {
+        let mut cpes = vec![];
+        cpes.extend(self.cpe.clone());
+        for technology in self.technologies.iter() {
+            if let (Some(cpe), Some(version)) = (&technology.cpe, &technology.version) {
+                cpes.push(vuln::cpe_with_version(cpe, version));
+            }
+        }
+        cpes.extend(self.database.as_ref().and_then(|database| database.cpe()));
+        cpes.sort();
+        cpes.dedup();
+        cpes
+    }
 }
@@ -0,0 +1,539 @@
+use base64::Engine;
+use ring::agreement::{self, EphemeralPrivateKey};
+use ring::rand::SystemRandom;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SSH_TIMEOUT: Duration = Duration::from_millis(5000);
+const CLIENT_IDENTIFICATION: &str = "SSH-2.0-magic_scanner\r\n";
+const MAX_PACKET_SIZE: usize = 256 * 1024;
+const MAX_IDENTIFICATION_LINES: usize = 32;
+
+const SSH_MSG_DISCONNECT: u8 = 1;
+const SSH_MSG_KEXINIT: u8 = 20;
+const SSH_MSG_KEX_ECDH_INIT: u8 = 30;
+const SSH_MSG_KEX_ECDH_REPLY: u8 = 31;
+
+/// Key exchange methods the scanner can run to get the host key, in preference order.
+const SUPPORTED_KEX: [&str; 4] = [
+    "curve25519-sha256",
+    "curve25519-sha256@libssh.org",
+    "ecdh-sha2-nistp256",
+    "ecdh-sha2-nistp384",
+];
+
+const WEAK_KEX: [&str; 5] = [
+    "diffie-hellman-group1-sha1",
+    "diffie-hellman-group14-sha1",
+    "diffie-hellman-group-exchange-sha1",
+    "gss-group1-sha1-",
+    "gss-gex-sha1-",
+];
+const WEAK_HOST_KEY: [&str; 3] = ["ssh-dss", "ssh-rsa", "x509v3-sign-dss"];
+const WEAK_ENCRYPTION: [&str; 8] = [
+    "none",
+    "des",
+    "3des-cbc",
+    "arcfour",
+    "arcfour128",
+    "arcfour256",
+    "blowfish-cbc",
+    "cast128-cbc",
+];
+const WEAK_MAC: [&str; 6] = [
+    "none",
+    "hmac-md5",
+    "hmac-md5-96",
+    "hmac-sha1-96",
+    "hmac-md5-etm@openssh.com",
+    "umac-64@openssh.com",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SshIdentification {
+    pub protocol: String,
+    pub software: String,
+    pub comments: Option<String>,
+}
+
+impl SshIdentification {
+    /// Parses an identification string: `SSH-protoversion-softwareversion SP comments`.
+    pub fn parse(line: &str) -> Option<SshIdentification> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let rest = line.strip_prefix("SSH-")?;
+        let (protocol, rest) = rest.split_once('-')?;
+        let (software, comments) = match rest.split_once(' ') {
+            None => (rest, None),
+            Some((software, comments)) => (software, Some(comments.to_string())),
+        };
+        Some(SshIdentification {
+            protocol: protocol.to_string(),
+            software: software.to_string(),
+            comments,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SshHostKey {
+    pub key_type: String,
+    pub bits: usize,
+    /// OpenSSH style `SHA256:` base64 fingerprint.
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SshAlgorithms {
+    pub kex: Vec<String>,
+    pub host_key: Vec<String>,
+    pub encryption: Vec<String>,
+    pub mac: Vec<String>,
+    pub compression: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SshInfo {
+    pub identification: SshIdentification,
+    pub algorithms: SshAlgorithms,
+    pub host_keys: Vec<SshHostKey>,
+    pub weaknesses: Vec<String>,
+}
+
+impl std::fmt::Display for SshInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}",
+            self.identification.protocol, self.identification.software
+        )?;
+        for host_key in self.host_keys.iter() {
+            write!(f, "|{} {}", host_key.key_type, host_key.fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+/// The server KEXINIT name-lists, in the order they appear on the wire.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct KexInit {
+    name_lists: Vec<Vec<String>>,
+}
+
+impl KexInit {
+    fn parse(payload: &[u8]) -> Option<KexInit> {
+        let mut reader = Reader::new(payload.get(17..)?);
+        let mut name_lists = vec![];
+        for _ in 0..10 {
+            let list = String::from_utf8_lossy(reader.string()?).to_string();
+            name_lists.push(
+                list.split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            );
+        }
+        Some(KexInit { name_lists })
+    }
+
+    fn algorithms(&self) -> SshAlgorithms {
+        let mut encryption = self.name_lists[2].clone();
+        let mut mac = self.name_lists[4].clone();
+        let mut compression = self.name_lists[6].clone();
+        for (merged, index) in [(&mut encryption, 3), (&mut mac, 5), (&mut compression, 7)] {
+            for name in self.name_lists[index].iter() {
+                if !merged.contains(name) {
+                    merged.push(name.clone());
+                }
+            }
+        }
+        SshAlgorithms {
+            kex: self.name_lists[0].clone(),
+            host_key: self.name_lists[1].clone(),
+            encryption,
+            mac,
+            compression,
+        }
+    }
+
+    /// A client KEXINIT that only leaves the server `kex` and `host_key` to agree on, the
+    /// other lists are echoed back so negotiation can't fail on them.
+    fn client_payload(&self, kex: &str, host_key: &str) -> Vec<u8> {
+        let mut payload = vec![SSH_MSG_KEXINIT];
+        payload.extend((0..16).map(|_| rand::random::<u8>()));
+        write_string(&mut payload, kex.as_bytes());
+        write_string(&mut payload, host_key.as_bytes());
+        for list in self.name_lists[2..].iter() {
+            write_string(&mut payload, list.join(",").as_bytes());
+        }
+        // first_kex_packet_follows and the reserved field.
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+        payload
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let value = u32::from_be_bytes(self.data.get(..4)?.try_into().ok()?);
+        self.data = &self.data[4..];
+        Some(value)
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let length = self.u32()? as usize;
+        let value = self.data.get(..length)?;
+        self.data = &self.data[length..];
+        Some(value)
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Wraps `payload` in an unencrypted binary packet, padded to the 8 byte block size.
+fn packet(payload: &[u8]) -> Vec<u8> {
+    let mut padding = 8 - (payload.len() + 5) % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+    let mut packet = ((payload.len() + padding + 1) as u32)
+        .to_be_bytes()
+        .to_vec();
+    packet.push(padding as u8);
+    packet.extend_from_slice(payload);
+    packet.extend((0..padding).map(|_| rand::random::<u8>()));
+    packet
+}
+
+struct Connection {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Connection {
+    async fn fill(&mut self) -> Result<(), String> {
+        let mut chunk = [0u8; 4096];
+        let size = self
+            .stream
+            .read(&mut chunk)
+            .await
+            .map_err(|error| error.to_string())?;
+        if size == 0 {
+            return Err("connection closed".to_string());
+        }
+        self.buf.extend_from_slice(&chunk[..size]);
+        Ok(())
+    }
+
+    /// Reads lines until the identification string, servers may send other lines first.
+    async fn read_identification(&mut self) -> Result<String, String> {
+        for _ in 0..MAX_IDENTIFICATION_LINES {
+            let line_end = loop {
+                if let Some(index) = self.buf.iter().position(|byte| *byte == b'\n') {
+                    break index;
+                }
+                if self.buf.len() > 255 {
+                    return Err("identification line too long".to_string());
+                }
+                self.fill().await?;
+            };
+            let line = String::from_utf8_lossy(&self.buf[..line_end]).to_string();
+            self.buf.drain(..=line_end);
+            if line.starts_with("SSH-") {
+                return Ok(line);
+            }
+        }
+        Err("no identification string".to_string())
+    }
+
+    async fn read_payload(&mut self) -> Result<Vec<u8>, String> {
+        while self.buf.len() < 4 {
+            self.fill().await?;
+        }
+        let length = u32::from_be_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]]);
+        let length = length as usize;
+        if !(5..=MAX_PACKET_SIZE).contains(&length) {
+            return Err(format!("invalid packet length {}", length));
+        }
+        while self.buf.len() < length + 4 {
+            self.fill().await?;
+        }
+        let packet = self.buf.drain(..length + 4).collect::<Vec<u8>>();
+        let padding = packet[4] as usize;
+        let payload = packet
+            .get(5..length + 4 - padding)
+            .ok_or_else(|| "invalid padding length".to_string())?;
+        Ok(payload.to_vec())
+    }
+
+    async fn write_payload(&mut self, payload: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(&packet(payload))
+            .await
+            .map_err(|error| error.to_string())
+    }
+}
+
+async fn handshake(socket_addr: SocketAddr) -> Result<(Connection, String, Vec<u8>), String> {
+    let stream = TcpStream::connect(socket_addr)
+        .await
+        .map_err(|error| error.to_string())?;
+    let mut connection = Connection {
+        stream,
+        buf: vec![],
+    };
+    connection
+        .stream
+        .write_all(CLIENT_IDENTIFICATION.as_bytes())
+        .await
+        .map_err(|error| error.to_string())?;
+    let identification = connection.read_identification().await?;
+    let payload = connection.read_payload().await?;
+    if payload.first() != Some(&SSH_MSG_KEXINIT) {
+        return Err(format!(
+            "expected KEXINIT, got message {:?}",
+            payload.first()
+        ));
+    }
+    Ok((connection, identification, payload))
+}
+
+/// Runs the key exchange far enough for the server to send its `host_key` public key and
+/// returns the key blob.
+async fn fetch_host_key(
+    socket_addr: SocketAddr,
+    kex: &str,
+    host_key: &str,
+) -> Result<Vec<u8>, String> {
+    let (mut connection, _, payload) = handshake(socket_addr).await?;
+    let kex_init = KexInit::parse(&payload).ok_or("invalid KEXINIT")?;
+    connection
+        .write_payload(&kex_init.client_payload(kex, host_key))
+        .await?;
+
+    let algorithm = match kex {
+        "ecdh-sha2-nistp256" => &agreement::ECDH_P256,
+        "ecdh-sha2-nistp384" => &agreement::ECDH_P384,
+        _ => &agreement::X25519,
+    };
+    let private_key = EphemeralPrivateKey::generate(algorithm, &SystemRandom::new())
+        .map_err(|_| "generate ephemeral key failed".to_string())?;
+    let public_key = private_key
+        .compute_public_key()
+        .map_err(|_| "compute public key failed".to_string())?;
+    let mut init = vec![SSH_MSG_KEX_ECDH_INIT];
+    write_string(&mut init, public_key.as_ref());
+    connection.write_payload(&init).await?;
+
+    loop {
+        let payload = connection.read_payload().await?;
+        match payload.first() {
+            Some(&SSH_MSG_KEX_ECDH_REPLY) => {
+                let mut reader = Reader::new(&payload[1..]);
+                return reader
+                    .string()
+                    .map(|blob| blob.to_vec())
+                    .ok_or_else(|| "invalid KEX_ECDH_REPLY".to_string());
+            }
+            Some(&SSH_MSG_DISCONNECT) => return Err("server disconnected".to_string()),
+            _ => continue,
+        }
+    }
+}
+
+/// Host key algorithms that share a key are fetched once, certificates are skipped.
+fn host_key_type(algorithm: &str) -> Option<&str> {
+    if algorithm.contains("-cert-") {
+        return None;
+    }
+    match algorithm {
+        "rsa-sha2-256" | "rsa-sha2-512" | "ssh-rsa" => Some("ssh-rsa"),
+        _ => Some(algorithm),
+    }
+}
+
+fn parse_host_key(blob: &[u8]) -> Option<SshHostKey> {
+    let mut reader = Reader::new(blob);
+    let key_type = String::from_utf8_lossy(reader.string()?).to_string();
+    let bits = match key_type.as_str() {
+        "ssh-rsa" => {
+            let _exponent = reader.string()?;
+            let modulus = reader.string()?;
+            let modulus = match modulus.iter().position(|byte| *byte != 0) {
+                None => &[][..],
+                Some(index) => &modulus[index..],
+            };
+            match modulus.first() {
+                None => 0,
+                Some(first) => modulus.len() * 8 - first.leading_zeros() as usize,
+            }
+        }
+        "ssh-dss" => {
+            let prime = reader.string()?;
+            prime.iter().skip_while(|byte| **byte == 0).count() * 8
+        }
+        "ssh-ed25519" => 256,
+        "ssh-ed448" => 456,
+        "ecdsa-sha2-nistp256" => 256,
+        "ecdsa-sha2-nistp384" => 384,
+        "ecdsa-sha2-nistp521" => 521,
+        _ => 0,
+    };
+    let digest = ring::digest::digest(&ring::digest::SHA256, blob);
+    let fingerprint = base64::engine::general_purpose::STANDARD_NO_PAD.encode(digest.as_ref());
+    Some(SshHostKey {
+        key_type,
+        bits,
+        fingerprint: format!("SHA256:{}", fingerprint),
+    })
+}
+
+fn weaknesses(identification: &SshIdentification, algorithms: &SshAlgorithms) -> Vec<String> {
+    let mut weaknesses = vec![];
+    if identification.protocol.starts_with('1') && identification.protocol != "1.99" {
+        weaknesses.push(format!("protocol {}", identification.protocol));
+    }
+    let groups: [(&str, &Vec<String>, &[&str]); 4] = [
+        ("kex", &algorithms.kex, &WEAK_KEX),
+        ("host key", &algorithms.host_key, &WEAK_HOST_KEY),
+        ("encryption", &algorithms.encryption, &WEAK_ENCRYPTION),
+        ("mac", &algorithms.mac, &WEAK_MAC),
+    ];
+    for (kind, offered, weak) in groups {
+        for name in offered.iter() {
+            let is_weak = weak
+                .iter()
+                .any(|item| name == item || (item.ends_with('-') && name.starts_with(item)));
+            let cbc = kind == "encryption" && name.ends_with("-cbc");
+            if is_weak || cbc {
+                weaknesses.push(format!("{} {}", kind, name));
+            }
+        }
+    }
+    weaknesses
+}
+
+/// Reads the identification and the offered algorithms, then fetches every host key the
+/// server offers with a key exchange per key type.
+pub async fn probe(socket_addr: SocketAddr) -> Option<SshInfo> {
+    let (_, identification, payload) = tokio::time::timeout(SSH_TIMEOUT, handshake(socket_addr))
+        .await
+        .ok()?
+        .map_err(|error| tracing::debug!("ssh handshake with {} failed: {}", socket_addr, error))
+        .ok()?;
+    let identification = SshIdentification::parse(&identification)?;
+    let kex_init = KexInit::parse(&payload)?;
+    let algorithms = kex_init.algorithms();
+
+    let mut host_keys = vec![];
+    let kex = SUPPORTED_KEX
+        .iter()
+        .find(|kex| algorithms.kex.iter().any(|item| item == *kex));
+    if let Some(kex) = kex {
+        let mut key_types = vec![];
+        for algorithm in algorithms.host_key.iter() {
+            let key_type = match host_key_type(algorithm) {
+                Some(key_type) if !key_types.contains(&key_type) => key_type,
+                _ => continue,
+            };
+            key_types.push(key_type);
+            let blob =
+                tokio::time::timeout(SSH_TIMEOUT, fetch_host_key(socket_addr, kex, algorithm))
+                    .await
+                    .map_err(|_| "timed out".to_string())
+                    .and_then(|result| result);
+            match blob {
+                Ok(blob) => host_keys.extend(parse_host_key(&blob)),
+                Err(error) => {
+                    tracing::debug!(
+                        "fetch {} host key of {} failed: {}",
+                        algorithm,
+                        socket_addr,
+                        error
+                    )
+                }
+            }
+        }
+    }
+    let weaknesses = weaknesses(&identification, &algorithms);
+    Some(SshInfo {
+        identification,
+        algorithms,
+        host_keys,
+        weaknesses,
+    })
+}
+
+#[cfg(test)]
+pub mod ssh_test {
+    use crate::scanner::ssh::{
+        packet, parse_host_key, write_string, KexInit, SshIdentification, SSH_MSG_KEXINIT,
+    };
+
+    #[test]
+    fn identification_test() {
+        let identification = SshIdentification::parse("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n");
+        assert_eq!(
+            identification,
+            Some(SshIdentification {
+                protocol: "2.0".to_string(),
+                software: "OpenSSH_9.6p1".to_string(),
+                comments: Some("Ubuntu-3ubuntu13".to_string()),
+            })
+        );
+        assert_eq!(SshIdentification::parse("HTTP/1.1 400"), None);
+    }
+
+    #[test]
+    fn kex_init_test() {
+        let mut payload = vec![SSH_MSG_KEXINIT];
+        payload.extend([7u8; 16]);
+        let lists = [
+            "curve25519-sha256,diffie-hellman-group14-sha1",
+            "ssh-ed25519,rsa-sha2-512",
+            "aes128-ctr,aes256-cbc",
+            "aes128-ctr",
+            "hmac-sha2-256",
+            "hmac-sha2-256,hmac-md5",
+            "none",
+            "none",
+            "",
+            "",
+        ];
+        for list in lists {
+            write_string(&mut payload, list.as_bytes());
+        }
+        payload.extend([0u8; 5]);
+        let kex_init = KexInit::parse(&payload).unwrap();
+        let algorithms = kex_init.algorithms();
+        assert_eq!(algorithms.encryption, vec!["aes128-ctr", "aes256-cbc"]);
+        assert_eq!(algorithms.mac, vec!["hmac-sha2-256", "hmac-md5"]);
+        let client = KexInit::parse(&kex_init.client_payload("curve25519-sha256", "ssh-ed25519"));
+        assert_eq!(client.unwrap().name_lists[1], vec!["ssh-ed25519"]);
+        assert_eq!(packet(&payload).len() % 8, 0);
+    }
+
+    #[test]
+    fn host_key_test() {
+        let mut blob = vec![];
+        write_string(&mut blob, b"ssh-ed25519");
+        write_string(&mut blob, &[1u8; 32]);
+        let host_key = parse_host_key(&blob).unwrap();
+        assert_eq!(host_key.key_type, "ssh-ed25519");
+        assert_eq!(host_key.bits, 256);
+        assert!(host_key.fingerprint.starts_with("SHA256:"));
+        assert_eq!(host_key.fingerprint.len(), 7 + 43);
+    }
+}
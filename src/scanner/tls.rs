@@ -0,0 +1,240 @@
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ServerName};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use x509_parser::public_key::PublicKey;
+
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(3000);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub subject_alt_names: Vec<String>,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub expired: bool,
+    pub key_type: String,
+    pub key_size: usize,
+    pub signature_algorithm: String,
+    /// lowercase hex SHA-256 of the DER encoded certificate.
+    pub sha256_fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TlsInfo {
+    pub version: String,
+    pub cipher_suite: String,
+    /// the peer certificate chain, leaf first.
+    pub certificates: Vec<CertificateInfo>,
+}
+
+impl TlsInfo {
+    pub fn leaf(&self) -> Option<&CertificateInfo> {
+        self.certificates.first()
+    }
+}
+
+impl std::fmt::Display for TlsInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}", self.version, self.cipher_suite)?;
+        if let Some(leaf) = self.leaf() {
+            write!(f, "|{}|{}", leaf.subject, leaf.not_after)?;
+        }
+        Ok(())
+    }
+}
+
+/// Accepts any certificate, the scanner only records what the server presents.
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+fn tls_connector() -> TlsConnector {
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(NoVerification))
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Opens a new connection to `socket_addr` and completes a TLS handshake without verifying
+/// the server. rustls only speaks TLS 1.2 and 1.3, older servers are reported as non TLS.
+pub async fn tls_connect(
+    socket_addr: SocketAddr,
+) -> Result<(TlsStream<TcpStream>, TlsInfo), String> {
+    let handshake = async {
+        let tcp_stream = TcpStream::connect(socket_addr)
+            .await
+            .map_err(|error| error.to_string())?;
+        let server_name = ServerName::IpAddress(socket_addr.ip());
+        tls_connector()
+            .connect(server_name, tcp_stream)
+            .await
+            .map_err(|error| error.to_string())
+    };
+    let tls_stream = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| format!("tls handshake with {} timed out", socket_addr))??;
+    let (_, connection) = tls_stream.get_ref();
+    let version = match connection.protocol_version() {
+        Some(rustls::ProtocolVersion::TLSv1_3) => "TLSv1.3".to_string(),
+        Some(rustls::ProtocolVersion::TLSv1_2) => "TLSv1.2".to_string(),
+        Some(version) => format!("{:?}", version),
+        None => "*".to_string(),
+    };
+    let cipher_suite = connection
+        .negotiated_cipher_suite()
+        .map(|suite| format!("{:?}", suite.suite()))
+        .unwrap_or_else(|| "*".to_string());
+    let certificates = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|certificate| parse_certificate(&certificate.0))
+        .collect();
+    let tls_info = TlsInfo {
+        version,
+        cipher_suite,
+        certificates,
+    };
+    Ok((tls_stream, tls_info))
+}
+
+fn curve_name(oid: &str) -> Option<&'static str> {
+    match oid {
+        "1.2.840.10045.3.1.7" => Some("P-256"),
+        "1.3.132.0.34" => Some("P-384"),
+        "1.3.132.0.35" => Some("P-521"),
+        "1.3.132.0.10" => Some("secp256k1"),
+        _ => None,
+    }
+}
+
+fn key_type(certificate: &X509Certificate) -> (String, usize) {
+    let public_key = certificate.public_key();
+    let algorithm = public_key.algorithm.algorithm.to_id_string();
+    match public_key.parsed() {
+        Ok(PublicKey::RSA(key)) => ("RSA".to_string(), key.key_size()),
+        Ok(PublicKey::EC(key)) => {
+            let curve = public_key
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|parameters| parameters.as_oid().ok())
+                .and_then(|oid| curve_name(&oid.to_id_string()));
+            let name = match curve {
+                None => "EC".to_string(),
+                Some(curve) => format!("EC {}", curve),
+            };
+            (name, key.key_size())
+        }
+        Ok(PublicKey::DSA(key)) => ("DSA".to_string(), key.len() * 8),
+        _ => match algorithm.as_str() {
+            "1.3.101.112" => ("Ed25519".to_string(), 256),
+            "1.3.101.113" => ("Ed448".to_string(), 456),
+            _ => (algorithm, 0),
+        },
+    }
+}
+
+pub fn parse_certificate(der: &[u8]) -> Option<CertificateInfo> {
+    let (_, certificate) = X509Certificate::from_der(der).ok()?;
+    let mut subject_alt_names = vec![];
+    if let Ok(Some(extension)) = certificate.subject_alternative_name() {
+        for name in extension.value.general_names.iter() {
+            match name {
+                GeneralName::DNSName(name) => subject_alt_names.push(format!("DNS:{}", name)),
+                GeneralName::IPAddress(bytes) => {
+                    let ip = match bytes.len() {
+                        4 => <[u8; 4]>::try_from(*bytes).ok().map(std::net::IpAddr::from),
+                        16 => <[u8; 16]>::try_from(*bytes)
+                            .ok()
+                            .map(std::net::IpAddr::from),
+                        _ => None,
+                    };
+                    if let Some(ip) = ip {
+                        subject_alt_names.push(format!("IP:{}", ip));
+                    }
+                }
+                GeneralName::RFC822Name(name) => subject_alt_names.push(format!("email:{}", name)),
+                GeneralName::URI(uri) => subject_alt_names.push(format!("URI:{}", uri)),
+                _ => {}
+            }
+        }
+    }
+    let validity = certificate.validity();
+    let (key_type, key_size) = key_type(&certificate);
+    let fingerprint = ring::digest::digest(&ring::digest::SHA256, der);
+    Some(CertificateInfo {
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        subject_alt_names,
+        serial: certificate.raw_serial_as_string(),
+        not_before: validity.not_before.to_string(),
+        not_after: validity.not_after.to_string(),
+        expired: !validity.is_valid(),
+        key_type,
+        key_size,
+        signature_algorithm: certificate.signature_algorithm.algorithm.to_id_string(),
+        sha256_fingerprint: fingerprint
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+pub mod tls_test {
+    use crate::scanner::tls::parse_certificate;
+
+    #[test]
+    fn parse_certificate_test() {
+        let der = include_bytes!("testdata/certificate.der");
+        let certificate = parse_certificate(der).unwrap();
+        assert_eq!(certificate.subject, "CN=scanner.test, O=Magic Scanner");
+        assert_eq!(certificate.issuer, certificate.subject);
+        assert_eq!(
+            certificate.subject_alt_names,
+            [
+                "DNS:scanner.test",
+                "IP:10.0.0.1",
+                "email:admin@scanner.test"
+            ]
+        );
+        assert_eq!(certificate.serial, "12:34");
+        assert!(certificate.not_after.starts_with("Jan  1 00:00:00 2021"));
+        assert!(certificate.expired);
+        assert_eq!(
+            (certificate.key_type.as_str(), certificate.key_size),
+            ("EC P-256", 256)
+        );
+        assert_eq!(certificate.signature_algorithm, "1.2.840.10045.4.3.2");
+        assert_eq!(
+            certificate.sha256_fingerprint,
+            "86bac0d125433ba03eb6b71ca9792f69d558820f1cd012728a5d07e39a2c330b"
+        );
+        assert!(parse_certificate(&der[..100]).is_none());
+    }
+}
@@ -0,0 +1,586 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const HELLO_TIMEOUT: Duration = Duration::from_millis(3000);
+const MAX_RESPONSE_SIZE: usize = 16 * 1024;
+const RENEGOTIATION_INFO_SCSV: u16 = 0x00ff;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Ssl3,
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    pub const ALL: [TlsVersion; 5] = [
+        TlsVersion::Ssl3,
+        TlsVersion::Tls10,
+        TlsVersion::Tls11,
+        TlsVersion::Tls12,
+        TlsVersion::Tls13,
+    ];
+
+    pub fn code(self) -> u16 {
+        match self {
+            TlsVersion::Ssl3 => 0x0300,
+            TlsVersion::Tls10 => 0x0301,
+            TlsVersion::Tls11 => 0x0302,
+            TlsVersion::Tls12 => 0x0303,
+            TlsVersion::Tls13 => 0x0304,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TlsVersion::Ssl3 => "SSLv3",
+            TlsVersion::Tls10 => "TLSv1.0",
+            TlsVersion::Tls11 => "TLSv1.1",
+            TlsVersion::Tls12 => "TLSv1.2",
+            TlsVersion::Tls13 => "TLSv1.3",
+        }
+    }
+
+    fn cipher_suites(self) -> Vec<u16> {
+        let tls13 = self == TlsVersion::Tls13;
+        CIPHER_SUITES
+            .iter()
+            .filter(|(code, _)| (code >> 8 == 0x13) == tls13)
+            .map(|(code, _)| *code)
+            .collect()
+    }
+}
+
+/// IANA cipher suites offered during enumeration.
+const CIPHER_SUITES: &[(u16, &str)] = &[
+    (0x0001, "TLS_RSA_WITH_NULL_MD5"),
+    (0x0002, "TLS_RSA_WITH_NULL_SHA"),
+    (0x0003, "TLS_RSA_EXPORT_WITH_RC4_40_MD5"),
+    (0x0004, "TLS_RSA_WITH_RC4_128_MD5"),
+    (0x0005, "TLS_RSA_WITH_RC4_128_SHA"),
+    (0x0006, "TLS_RSA_EXPORT_WITH_RC2_CBC_40_MD5"),
+    (0x0007, "TLS_RSA_WITH_IDEA_CBC_SHA"),
+    (0x0008, "TLS_RSA_EXPORT_WITH_DES40_CBC_SHA"),
+    (0x0009, "TLS_RSA_WITH_DES_CBC_SHA"),
+    (0x000a, "TLS_RSA_WITH_3DES_EDE_CBC_SHA"),
+    (0x0011, "TLS_DHE_DSS_EXPORT_WITH_DES40_CBC_SHA"),
+    (0x0012, "TLS_DHE_DSS_WITH_DES_CBC_SHA"),
+    (0x0013, "TLS_DHE_DSS_WITH_3DES_EDE_CBC_SHA"),
+    (0x0014, "TLS_DHE_RSA_EXPORT_WITH_DES40_CBC_SHA"),
+    (0x0015, "TLS_DHE_RSA_WITH_DES_CBC_SHA"),
+    (0x0016, "TLS_DHE_RSA_WITH_3DES_EDE_CBC_SHA"),
+    (0x0017, "TLS_DH_anon_EXPORT_WITH_RC4_40_MD5"),
+    (0x0018, "TLS_DH_anon_WITH_RC4_128_MD5"),
+    (0x0019, "TLS_DH_anon_EXPORT_WITH_DES40_CBC_SHA"),
+    (0x001a, "TLS_DH_anon_WITH_DES_CBC_SHA"),
+    (0x001b, "TLS_DH_anon_WITH_3DES_EDE_CBC_SHA"),
+    (0x002f, "TLS_RSA_WITH_AES_128_CBC_SHA"),
+    (0x0032, "TLS_DHE_DSS_WITH_AES_128_CBC_SHA"),
+    (0x0033, "TLS_DHE_RSA_WITH_AES_128_CBC_SHA"),
+    (0x0034, "TLS_DH_anon_WITH_AES_128_CBC_SHA"),
+    (0x0035, "TLS_RSA_WITH_AES_256_CBC_SHA"),
+    (0x0038, "TLS_DHE_DSS_WITH_AES_256_CBC_SHA"),
+    (0x0039, "TLS_DHE_RSA_WITH_AES_256_CBC_SHA"),
+    (0x003a, "TLS_DH_anon_WITH_AES_256_CBC_SHA"),
+    (0x003b, "TLS_RSA_WITH_NULL_SHA256"),
+    (0x003c, "TLS_RSA_WITH_AES_128_CBC_SHA256"),
+    (0x003d, "TLS_RSA_WITH_AES_256_CBC_SHA256"),
+    (0x0040, "TLS_DHE_DSS_WITH_AES_128_CBC_SHA256"),
+    (0x0041, "TLS_RSA_WITH_CAMELLIA_128_CBC_SHA"),
+    (0x0044, "TLS_DHE_DSS_WITH_CAMELLIA_128_CBC_SHA"),
+    (0x0045, "TLS_DHE_RSA_WITH_CAMELLIA_128_CBC_SHA"),
+    (0x0067, "TLS_DHE_RSA_WITH_AES_128_CBC_SHA256"),
+    (0x006a, "TLS_DHE_DSS_WITH_AES_256_CBC_SHA256"),
+    (0x006b, "TLS_DHE_RSA_WITH_AES_256_CBC_SHA256"),
+    (0x006c, "TLS_DH_anon_WITH_AES_128_CBC_SHA256"),
+    (0x006d, "TLS_DH_anon_WITH_AES_256_CBC_SHA256"),
+    (0x0084, "TLS_RSA_WITH_CAMELLIA_256_CBC_SHA"),
+    (0x0087, "TLS_DHE_DSS_WITH_CAMELLIA_256_CBC_SHA"),
+    (0x0088, "TLS_DHE_RSA_WITH_CAMELLIA_256_CBC_SHA"),
+    (0x008a, "TLS_PSK_WITH_RC4_128_SHA"),
+    (0x008b, "TLS_PSK_WITH_3DES_EDE_CBC_SHA"),
+    (0x008c, "TLS_PSK_WITH_AES_128_CBC_SHA"),
+    (0x008d, "TLS_PSK_WITH_AES_256_CBC_SHA"),
+    (0x0096, "TLS_RSA_WITH_SEED_CBC_SHA"),
+    (0x009a, "TLS_DHE_RSA_WITH_SEED_CBC_SHA"),
+    (0x009c, "TLS_RSA_WITH_AES_128_GCM_SHA256"),
+    (0x009d, "TLS_RSA_WITH_AES_256_GCM_SHA384"),
+    (0x009e, "TLS_DHE_RSA_WITH_AES_128_GCM_SHA256"),
+    (0x009f, "TLS_DHE_RSA_WITH_AES_256_GCM_SHA384"),
+    (0x00a2, "TLS_DHE_DSS_WITH_AES_128_GCM_SHA256"),
+    (0x00a3, "TLS_DHE_DSS_WITH_AES_256_GCM_SHA384"),
+    (0x00a6, "TLS_DH_anon_WITH_AES_128_GCM_SHA256"),
+    (0x00a7, "TLS_DH_anon_WITH_AES_256_GCM_SHA384"),
+    (0x00ba, "TLS_RSA_WITH_CAMELLIA_128_CBC_SHA256"),
+    (0x00be, "TLS_DHE_RSA_WITH_CAMELLIA_128_CBC_SHA256"),
+    (0x00c0, "TLS_RSA_WITH_CAMELLIA_256_CBC_SHA256"),
+    (0x00c4, "TLS_DHE_RSA_WITH_CAMELLIA_256_CBC_SHA256"),
+    (0x1301, "TLS_AES_128_GCM_SHA256"),
+    (0x1302, "TLS_AES_256_GCM_SHA384"),
+    (0x1303, "TLS_CHACHA20_POLY1305_SHA256"),
+    (0x1304, "TLS_AES_128_CCM_SHA256"),
+    (0x1305, "TLS_AES_128_CCM_8_SHA256"),
+    (0xc001, "TLS_ECDH_ECDSA_WITH_NULL_SHA"),
+    (0xc002, "TLS_ECDH_ECDSA_WITH_RC4_128_SHA"),
+    (0xc003, "TLS_ECDH_ECDSA_WITH_3DES_EDE_CBC_SHA"),
+    (0xc004, "TLS_ECDH_ECDSA_WITH_AES_128_CBC_SHA"),
+    (0xc005, "TLS_ECDH_ECDSA_WITH_AES_256_CBC_SHA"),
+    (0xc006, "TLS_ECDHE_ECDSA_WITH_NULL_SHA"),
+    (0xc007, "TLS_ECDHE_ECDSA_WITH_RC4_128_SHA"),
+    (0xc008, "TLS_ECDHE_ECDSA_WITH_3DES_EDE_CBC_SHA"),
+    (0xc009, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA"),
+    (0xc00a, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA"),
+    (0xc00b, "TLS_ECDH_RSA_WITH_NULL_SHA"),
+    (0xc00c, "TLS_ECDH_RSA_WITH_RC4_128_SHA"),
+    (0xc00d, "TLS_ECDH_RSA_WITH_3DES_EDE_CBC_SHA"),
+    (0xc00e, "TLS_ECDH_RSA_WITH_AES_128_CBC_SHA"),
+    (0xc00f, "TLS_ECDH_RSA_WITH_AES_256_CBC_SHA"),
+    (0xc010, "TLS_ECDHE_RSA_WITH_NULL_SHA"),
+    (0xc011, "TLS_ECDHE_RSA_WITH_RC4_128_SHA"),
+    (0xc012, "TLS_ECDHE_RSA_WITH_3DES_EDE_CBC_SHA"),
+    (0xc013, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA"),
+    (0xc014, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA"),
+    (0xc015, "TLS_ECDH_anon_WITH_NULL_SHA"),
+    (0xc016, "TLS_ECDH_anon_WITH_RC4_128_SHA"),
+    (0xc017, "TLS_ECDH_anon_WITH_3DES_EDE_CBC_SHA"),
+    (0xc018, "TLS_ECDH_anon_WITH_AES_128_CBC_SHA"),
+    (0xc019, "TLS_ECDH_anon_WITH_AES_256_CBC_SHA"),
+    (0xc023, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256"),
+    (0xc024, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384"),
+    (0xc025, "TLS_ECDH_ECDSA_WITH_AES_128_CBC_SHA256"),
+    (0xc026, "TLS_ECDH_ECDSA_WITH_AES_256_CBC_SHA384"),
+    (0xc027, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256"),
+    (0xc028, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384"),
+    (0xc029, "TLS_ECDH_RSA_WITH_AES_128_CBC_SHA256"),
+    (0xc02a, "TLS_ECDH_RSA_WITH_AES_256_CBC_SHA384"),
+    (0xc02b, "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+    (0xc02c, "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384"),
+    (0xc02d, "TLS_ECDH_ECDSA_WITH_AES_128_GCM_SHA256"),
+    (0xc02e, "TLS_ECDH_ECDSA_WITH_AES_256_GCM_SHA384"),
+    (0xc02f, "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"),
+    (0xc030, "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"),
+    (0xc031, "TLS_ECDH_RSA_WITH_AES_128_GCM_SHA256"),
+    (0xc032, "TLS_ECDH_RSA_WITH_AES_256_GCM_SHA384"),
+    (0xc072, "TLS_ECDHE_ECDSA_WITH_CAMELLIA_128_CBC_SHA256"),
+    (0xc073, "TLS_ECDHE_ECDSA_WITH_CAMELLIA_256_CBC_SHA384"),
+    (0xc076, "TLS_ECDHE_RSA_WITH_CAMELLIA_128_CBC_SHA256"),
+    (0xc077, "TLS_ECDHE_RSA_WITH_CAMELLIA_256_CBC_SHA384"),
+    (0xc09c, "TLS_RSA_WITH_AES_128_CCM"),
+    (0xc09d, "TLS_RSA_WITH_AES_256_CCM"),
+    (0xc09e, "TLS_DHE_RSA_WITH_AES_128_CCM"),
+    (0xc09f, "TLS_DHE_RSA_WITH_AES_256_CCM"),
+    (0xc0ac, "TLS_ECDHE_ECDSA_WITH_AES_128_CCM"),
+    (0xc0ad, "TLS_ECDHE_ECDSA_WITH_AES_256_CCM"),
+    (0xcca8, "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256"),
+    (0xcca9, "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"),
+    (0xccaa, "TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256"),
+];
+
+pub fn cipher_suite_name(code: u16) -> String {
+    CIPHER_SUITES
+        .iter()
+        .find(|(item, _)| *item == code)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("0x{:04x}", code))
+}
+
+/// Returns why a cipher suite is considered weak, empty when it's fine.
+pub fn cipher_suite_weaknesses(name: &str) -> Vec<&'static str> {
+    let mut weaknesses = vec![];
+    if name.contains("NULL") {
+        weaknesses.push("NULL encryption");
+    }
+    if name.contains("EXPORT") {
+        weaknesses.push("export grade");
+    }
+    if name.contains("RC4") {
+        weaknesses.push("RC4");
+    }
+    if name.contains("3DES") {
+        weaknesses.push("3DES");
+    } else if name.contains("DES") {
+        weaknesses.push("DES");
+    }
+    if name.contains("anon") {
+        weaknesses.push("anonymous key exchange");
+    }
+    // TLS 1.3 suites always use an ephemeral key exchange.
+    let static_key_exchange = ["TLS_RSA_", "TLS_DH_", "TLS_ECDH_", "TLS_PSK_"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix));
+    if static_key_exchange && !name.contains("anon") {
+        weaknesses.push("no forward secrecy");
+    }
+    weaknesses
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TlsProtocolSupport {
+    pub version: String,
+    /// accepted cipher suites in the order the server picked them.
+    pub cipher_suites: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TlsEnumeration {
+    pub protocols: Vec<TlsProtocolSupport>,
+    pub weaknesses: Vec<String>,
+}
+
+impl std::fmt::Display for TlsEnumeration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let protocols = self
+            .protocols
+            .iter()
+            .map(|protocol| format!("{}:{}", protocol.version, protocol.cipher_suites.len()))
+            .collect::<Vec<String>>()
+            .join(",");
+        write!(f, "{}|{} weaknesses", protocols, self.weaknesses.len())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum HelloResult {
+    Accepted {
+        version: u16,
+        cipher_suite: u16,
+    },
+    Rejected,
+    /// the service answered with something else than a TLS record, e.g. a banner.
+    NotTls,
+}
+
+/// Tries every protocol version and walks the accepted cipher suites by removing the one the
+/// server picked from the next ClientHello until the server refuses the handshake.
+pub async fn enumerate(socket_addr: SocketAddr) -> TlsEnumeration {
+    let mut enumeration = TlsEnumeration::default();
+    let mut speaks_tls = false;
+    for version in TlsVersion::ALL {
+        let mut remaining = version.cipher_suites();
+        let mut accepted = vec![];
+        while !remaining.is_empty() {
+            let cipher_suite = match client_hello(socket_addr, version, &remaining).await {
+                Ok(HelloResult::Accepted {
+                    version: negotiated,
+                    cipher_suite,
+                }) if negotiated == version.code() && remaining.contains(&cipher_suite) => {
+                    cipher_suite
+                }
+                // no point in trying the other versions on a service that does not speak
+                // TLS, a silent one would cost the hello timeout for each of them.
+                Ok(HelloResult::NotTls) => return enumeration,
+                Err(_) if !speaks_tls => return enumeration,
+                Ok(_) | Err(_) => {
+                    speaks_tls = true;
+                    break;
+                }
+            };
+            speaks_tls = true;
+            remaining.retain(|item| *item != cipher_suite);
+            accepted.push(cipher_suite_name(cipher_suite));
+        }
+        if accepted.is_empty() {
+            continue;
+        }
+        if version < TlsVersion::Tls12 {
+            enumeration
+                .weaknesses
+                .push(format!("{} is deprecated", version.name()));
+        }
+        for name in accepted.iter() {
+            let weaknesses = cipher_suite_weaknesses(name);
+            if !weaknesses.is_empty() {
+                enumeration.weaknesses.push(format!(
+                    "{} {}: {}",
+                    version.name(),
+                    name,
+                    weaknesses.join(", ")
+                ));
+            }
+        }
+        enumeration.protocols.push(TlsProtocolSupport {
+            version: version.name().to_string(),
+            cipher_suites: accepted,
+        });
+    }
+    enumeration
+}
+
+async fn client_hello(
+    socket_addr: SocketAddr,
+    version: TlsVersion,
+    cipher_suites: &[u16],
+) -> Result<HelloResult, String> {
+    let exchange = async {
+        let mut tcp_stream = TcpStream::connect(socket_addr)
+            .await
+            .map_err(|error| error.to_string())?;
+        tcp_stream
+            .write_all(&build_client_hello(version, cipher_suites))
+            .await
+            .map_err(|error| error.to_string())?;
+        let mut buf = vec![];
+        let mut chunk = [0u8; 4096];
+        loop {
+            let size = tcp_stream
+                .read(&mut chunk)
+                .await
+                .map_err(|error| error.to_string())?;
+            if size == 0 {
+                return Ok(HelloResult::Rejected);
+            }
+            buf.extend_from_slice(&chunk[..size]);
+            if let Some(result) = parse_server_hello(&buf) {
+                return Ok(result);
+            }
+            if buf.len() > MAX_RESPONSE_SIZE {
+                return Ok(HelloResult::Rejected);
+            }
+        }
+    };
+    tokio::time::timeout(HELLO_TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("client hello to {} timed out", socket_addr))?
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_extension(buf: &mut Vec<u8>, extension_type: u16, data: &[u8]) {
+    push_u16(buf, extension_type);
+    push_u16(buf, data.len() as u16);
+    buf.extend_from_slice(data);
+}
+
+fn build_client_hello(version: TlsVersion, cipher_suites: &[u16]) -> Vec<u8> {
+    let client_version = version.code().min(TlsVersion::Tls12.code());
+    let mut hello = vec![];
+    push_u16(&mut hello, client_version);
+    hello.extend((0..32).map(|_| rand::random::<u8>()));
+    hello.push(32);
+    hello.extend((0..32).map(|_| rand::random::<u8>()));
+    push_u16(&mut hello, (cipher_suites.len() as u16 + 1) * 2);
+    for cipher_suite in cipher_suites {
+        push_u16(&mut hello, *cipher_suite);
+    }
+    push_u16(&mut hello, RENEGOTIATION_INFO_SCSV);
+    // null compression only.
+    hello.extend_from_slice(&[1, 0]);
+
+    if version != TlsVersion::Ssl3 {
+        let mut extensions = vec![];
+        // x25519, secp256r1, secp384r1, secp521r1, ffdhe2048.
+        let groups = [0x001d, 0x0017, 0x0018, 0x0019, 0x0100];
+        let mut data = vec![];
+        push_u16(&mut data, groups.len() as u16 * 2);
+        groups.iter().for_each(|group| push_u16(&mut data, *group));
+        push_extension(&mut extensions, 0x000a, &data);
+        push_extension(&mut extensions, 0x000b, &[1, 0]);
+        let signature_algorithms = [
+            0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0201, 0x0203,
+        ];
+        let mut data = vec![];
+        push_u16(&mut data, signature_algorithms.len() as u16 * 2);
+        signature_algorithms
+            .iter()
+            .for_each(|algorithm| push_u16(&mut data, *algorithm));
+        push_extension(&mut extensions, 0x000d, &data);
+        if version == TlsVersion::Tls13 {
+            push_extension(&mut extensions, 0x002b, &[2, 0x03, 0x04]);
+            // any 32 bytes are a valid x25519 public key.
+            let mut data = vec![];
+            push_u16(&mut data, 36);
+            push_u16(&mut data, 0x001d);
+            push_u16(&mut data, 32);
+            data.extend((0..32).map(|_| rand::random::<u8>()));
+            push_extension(&mut extensions, 0x0033, &data);
+        }
+        push_u16(&mut hello, extensions.len() as u16);
+        hello.extend(extensions);
+    }
+
+    let mut handshake = vec![1];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend(hello);
+
+    let record_version = if version == TlsVersion::Ssl3 {
+        TlsVersion::Ssl3.code()
+    } else {
+        TlsVersion::Tls10.code()
+    };
+    let mut record = vec![0x16];
+    push_u16(&mut record, record_version);
+    push_u16(&mut record, handshake.len() as u16);
+    record.extend(handshake);
+    record
+}
+
+/// Parses the first record of a server response, `None` means more data is needed. A
+/// response not starting like a handshake or alert record is rejected right away.
+fn parse_server_hello(buf: &[u8]) -> Option<HelloResult> {
+    match (buf.first(), buf.get(1)) {
+        (Some(0x15 | 0x16), None | Some(0x03)) => {}
+        (None, _) => return None,
+        _ => return Some(HelloResult::NotTls),
+    }
+    if buf.len() < 5 {
+        return None;
+    }
+    let content_type = buf[0];
+    let length = u16::from_be_bytes([buf[3], buf[4]]) as usize;
+    let record = buf.get(5..5 + length)?;
+    if content_type != 0x16 || record.first() != Some(&2) {
+        return Some(HelloResult::Rejected);
+    }
+    Some(read_server_hello(record.get(4..)?).unwrap_or(HelloResult::Rejected))
+}
+
+fn read_server_hello(body: &[u8]) -> Option<HelloResult> {
+    let mut version = u16::from_be_bytes([*body.first()?, *body.get(1)?]);
+    let session_id_length = *body.get(34)? as usize;
+    let mut index = 35 + session_id_length;
+    let cipher_suite = u16::from_be_bytes([*body.get(index)?, *body.get(index + 1)?]);
+    // skip the cipher suite and the compression method.
+    index += 3;
+    if let Some(extensions_length) = body.get(index..index + 2) {
+        let end =
+            index + 2 + u16::from_be_bytes([extensions_length[0], extensions_length[1]]) as usize;
+        index += 2;
+        while index + 4 <= end.min(body.len()) {
+            let extension_type = u16::from_be_bytes([body[index], body[index + 1]]);
+            let length = u16::from_be_bytes([body[index + 2], body[index + 3]]) as usize;
+            let data = body.get(index + 4..index + 4 + length)?;
+            if extension_type == 0x002b && length == 2 {
+                version = u16::from_be_bytes([data[0], data[1]]);
+            }
+            index += 4 + length;
+        }
+    }
+    Some(HelloResult::Accepted {
+        version,
+        cipher_suite,
+    })
+}
+
+#[cfg(test)]
+pub mod tls_enum_test {
+    use crate::scanner::tls_enum::{
+        build_client_hello, cipher_suite_weaknesses, enumerate, parse_server_hello, HelloResult,
+        TlsVersion,
+    };
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    fn server_hello(version: u16, cipher_suite: u16, supported_version: Option<u16>) -> Vec<u8> {
+        let mut body = version.to_be_bytes().to_vec();
+        body.extend([0u8; 32]);
+        body.push(0);
+        body.extend(cipher_suite.to_be_bytes());
+        body.push(0);
+        if let Some(supported_version) = supported_version {
+            body.extend(6u16.to_be_bytes());
+            body.extend(0x002bu16.to_be_bytes());
+            body.extend(2u16.to_be_bytes());
+            body.extend(supported_version.to_be_bytes());
+        }
+        let mut handshake = vec![2];
+        handshake.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend(body);
+        let mut record = vec![0x16, 0x03, 0x03];
+        record.extend((handshake.len() as u16).to_be_bytes());
+        record.extend(handshake);
+        record
+    }
+
+    #[test]
+    fn parse_server_hello_test() {
+        let record = server_hello(0x0303, 0xc02f, None);
+        assert_eq!(parse_server_hello(&record[..20]), None);
+        assert_eq!(
+            parse_server_hello(&record),
+            Some(HelloResult::Accepted {
+                version: 0x0303,
+                cipher_suite: 0xc02f
+            })
+        );
+        let record = server_hello(0x0303, 0x1301, Some(0x0304));
+        assert_eq!(
+            parse_server_hello(&record),
+            Some(HelloResult::Accepted {
+                version: 0x0304,
+                cipher_suite: 0x1301
+            })
+        );
+        let alert = [0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28];
+        assert_eq!(parse_server_hello(&alert), Some(HelloResult::Rejected));
+        assert_eq!(parse_server_hello(&[0x16]), None);
+        assert_eq!(
+            parse_server_hello(b"SSH-2.0-OpenSSH_9.6\r\n"),
+            Some(HelloResult::NotTls)
+        );
+        assert_eq!(
+            parse_server_hello(&[0x16, 0x48, 0x54]),
+            Some(HelloResult::NotTls)
+        );
+    }
+
+    #[test]
+    fn build_client_hello_test() {
+        let hello = build_client_hello(TlsVersion::Tls12, &[0xc02f, 0x009c]);
+        assert_eq!(&hello[..3], &[0x16, 0x03, 0x01]);
+        assert_eq!(
+            u16::from_be_bytes([hello[3], hello[4]]) as usize,
+            hello.len() - 5
+        );
+        assert_eq!(hello[5], 1);
+        // version, random and the session id precede the cipher suites.
+        let suites = 9 + 2 + 32 + 33;
+        assert_eq!(&hello[suites..suites + 2], &[0, 6]);
+    }
+
+    #[test]
+    fn cipher_suite_weaknesses_test() {
+        assert!(cipher_suite_weaknesses("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256").is_empty());
+        assert!(cipher_suite_weaknesses("TLS_AES_128_GCM_SHA256").is_empty());
+        assert_eq!(
+            cipher_suite_weaknesses("TLS_RSA_WITH_3DES_EDE_CBC_SHA"),
+            vec!["3DES", "no forward secrecy"]
+        );
+        assert_eq!(
+            cipher_suite_weaknesses("TLS_ECDHE_RSA_WITH_RC4_128_SHA"),
+            vec!["RC4"]
+        );
+        assert_eq!(
+            cipher_suite_weaknesses("TLS_DH_anon_EXPORT_WITH_RC4_40_MD5"),
+            vec!["export grade", "RC4", "anonymous key exchange"]
+        );
+    }
+
+    #[tokio::test]
+    async fn enumerate_non_tls_service_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut connections = 0;
+            while let Ok(Ok((mut stream, _))) =
+                tokio::time::timeout(Duration::from_secs(1), listener.accept()).await
+            {
+                connections += 1;
+                let _ = stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").await;
+            }
+            connections
+        });
+        let start = Instant::now();
+        let enumeration = enumerate(socket_addr).await;
+        assert!(enumeration.protocols.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(server.await.unwrap(), 1);
+    }
+}
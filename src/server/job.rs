@@ -0,0 +1,324 @@
+use crate::args::Args;
+use crate::report::{self, ScanResult};
+use crate::scanner::plugin::{HostReply, ResultSink, ScannerReply, StoreSink};
+use crate::store::Store;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{broadcast, Semaphore};
+use tokio::task::AbortHandle;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// waiting for one of the concurrent scan slots.
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            JobState::Finished | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// What the API reports about a scan job.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub id: u64,
+    pub address: String,
+    pub state: JobState,
+    /// RFC 3339 times the job was created, started and finished at.
+    pub created: String,
+    pub started: Option<String>,
+    pub finished: Option<String>,
+    /// sockets the port scan went through out of `total`.
+    pub done: usize,
+    pub total: usize,
+    /// open ports found so far.
+    pub ports: usize,
+    pub error: Option<String>,
+}
+
+/// What a job streams as it runs.
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Status(JobStatus),
+    Port(Box<ScannerReply>),
+    Host(Box<HostReply>),
+}
+
+struct JobInner {
+    status: JobStatus,
+    replies: Vec<ScannerReply>,
+    hosts: Vec<HostReply>,
+}
+
+/// A scan submitted to the API.
+pub struct Job {
+    inner: Mutex<JobInner>,
+    events: broadcast::Sender<JobEvent>,
+    handle: Mutex<Option<AbortHandle>>,
+}
+
+impl Job {
+    fn new(id: u64, address: &str) -> Job {
+        let (events, _) = broadcast::channel(1024);
+        Job {
+            inner: Mutex::new(JobInner {
+                status: JobStatus {
+                    id,
+                    address: address.to_string(),
+                    state: JobState::Queued,
+                    created: report::now(),
+                    started: None,
+                    finished: None,
+                    done: 0,
+                    total: 0,
+                    ports: 0,
+                    error: None,
+                },
+                replies: vec![],
+                hosts: vec![],
+            }),
+            events,
+            handle: Mutex::new(None),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, JobInner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.inner().status.clone()
+    }
+
+    /// Updates the status and streams it, the lock is held while sending so that a
+    /// subscriber sees every event after its snapshot.
+    fn update(&self, update: impl FnOnce(&mut JobInner)) {
+        let mut inner = self.inner();
+        update(&mut inner);
+        let _ = self.events.send(JobEvent::Status(inner.status.clone()));
+    }
+
+    fn set_state(&self, state: JobState, error: Option<String>) {
+        self.update(|inner| {
+            if inner.status.state.is_done() {
+                return;
+            }
+            match state {
+                JobState::Running => inner.status.started = Some(report::now()),
+                state if state.is_done() => inner.status.finished = Some(report::now()),
+                _ => {}
+            }
+            inner.status.state = state;
+            inner.status.error = error;
+        });
+    }
+
+    /// The events so far, ending with the current status, and the receiver of the next
+    /// ones.
+    pub fn subscribe(&self) -> (Vec<JobEvent>, broadcast::Receiver<JobEvent>) {
+        let inner = self.inner();
+        let mut events = inner
+            .replies
+            .iter()
+            .map(|reply| JobEvent::Port(Box::new(reply.clone())))
+            .chain(
+                inner
+                    .hosts
+                    .iter()
+                    .map(|host| JobEvent::Host(Box::new(host.clone()))),
+            )
+            .collect::<Vec<_>>();
+        events.push(JobEvent::Status(inner.status.clone()));
+        (events, self.events.subscribe())
+    }
+
+    /// The result of a finished or cancelled job, the hosts of a cancelled one holding
+    /// the ports found until then.
+    pub fn result(&self) -> Option<ScanResult> {
+        let inner = self.inner();
+        let hosts = match inner.status.state {
+            JobState::Finished => inner.hosts.clone(),
+            JobState::Cancelled => {
+                let mut hosts: BTreeMap<_, HostReply> = BTreeMap::new();
+                for reply in inner.replies.iter() {
+                    if let Some(ip_addr) = reply.ip_addr() {
+                        hosts
+                            .entry(ip_addr)
+                            .or_insert_with(|| HostReply::new(ip_addr))
+                            .add_port(reply.clone());
+                    }
+                }
+                hosts.into_values().collect()
+            }
+            _ => return None,
+        };
+        Some(ScanResult {
+            started: inner.status.started.clone().unwrap_or_default(),
+            finished: inner.status.finished.clone().unwrap_or_default(),
+            hosts,
+        })
+    }
+}
+
+/// Streams the scan results into the job.
+struct JobSink {
+    job: Arc<Job>,
+}
+
+impl ResultSink for JobSink {
+    fn name(&self) -> &str {
+        "job"
+    }
+
+    fn progress(&self, done: usize, total: usize) {
+        self.job.update(|inner| {
+            inner.status.done = done;
+            inner.status.total = total;
+        });
+    }
+
+    fn port(&self, reply: &ScannerReply) {
+        let mut inner = self.job.inner();
+        inner.replies.push(reply.clone());
+        inner.status.ports += 1;
+        let _ = self
+            .job
+            .events
+            .send(JobEvent::Port(Box::new(reply.clone())));
+    }
+
+    fn host(&self, host: &HostReply) {
+        let _inner = self.job.inner();
+        let _ = self.job.events.send(JobEvent::Host(Box::new(host.clone())));
+    }
+
+    fn finish(&self, hosts: &[HostReply]) {
+        self.job.inner().hosts = hosts.to_vec();
+    }
+}
+
+/// The scan jobs submitted to the API, at most `max_running` of them scanning at a time,
+/// the others queued in submission order. Only the `max_done` latest done jobs are kept,
+/// the store holds the history.
+pub struct JobQueue {
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+    next_id: AtomicU64,
+    slots: Arc<Semaphore>,
+    max_done: usize,
+    store: Option<Arc<Store>>,
+}
+
+impl JobQueue {
+    pub fn new(max_running: usize, max_done: usize, store: Option<Store>) -> JobQueue {
+        JobQueue {
+            jobs: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            slots: Arc::new(Semaphore::new(max_running.max(1))),
+            max_done,
+            store: store.map(Arc::new),
+        }
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, BTreeMap<u64, Arc<Job>>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.jobs().values().map(|job| job.status()).collect()
+    }
+
+    /// Queues a scan of `args`, returning its job.
+    pub fn submit(self: &Arc<JobQueue>, args: Args) -> Arc<Job> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let job = Arc::new(Job::new(id, args.address.as_deref().unwrap_or_default()));
+        {
+            let mut jobs = self.jobs();
+            JobQueue::prune(&mut jobs, self.max_done);
+            jobs.insert(id, job.clone());
+        }
+        let handle = tokio::spawn(self.clone().run(job.clone(), args));
+        *job.handle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(handle.abort_handle());
+        job
+    }
+
+    /// Drops the oldest done jobs beyond `max_done`, with their replies and hosts.
+    fn prune(jobs: &mut BTreeMap<u64, Arc<Job>>, max_done: usize) {
+        let done = jobs
+            .iter()
+            .filter(|(_, job)| job.status().state.is_done())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in done.iter().take(done.len().saturating_sub(max_done)) {
+            jobs.remove(id);
+        }
+    }
+
+    async fn run(self: Arc<JobQueue>, job: Arc<Job>, args: Args) {
+        let _slot = match self.slots.clone().acquire_owned().await {
+            Ok(slot) => slot,
+            Err(error) => {
+                job.set_state(JobState::Failed, Some(error.to_string()));
+                return;
+            }
+        };
+        job.set_state(JobState::Running, None);
+        let mut scanner = match crate::new_scanner(&args).await {
+            Ok(scanner) => scanner,
+            Err(error) => {
+                job.set_state(JobState::Failed, Some(error));
+                return;
+            }
+        };
+        scanner.register_sink(Arc::new(JobSink { job: job.clone() }));
+        let store_sink = self.store.as_ref().map(|store| {
+            let address = args.address.clone().unwrap_or_default();
+            Arc::new(StoreSink::new(store.clone(), &address, None))
+        });
+        if let Some(sink) = &store_sink {
+            scanner.register_sink(sink.clone());
+        }
+        scanner.run().await;
+        if let Some(sink) = store_sink {
+            sink.flushed().await;
+        }
+        job.set_state(JobState::Finished, None);
+    }
+
+    /// Stops a queued or running job, `false` when it was done already.
+    pub fn cancel(&self, job: &Job) -> bool {
+        if job.status().state.is_done() {
+            return false;
+        }
+        if let Some(handle) = job
+            .handle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+        {
+            handle.abort();
+        }
+        job.set_state(JobState::Cancelled, None);
+        true
+    }
+}
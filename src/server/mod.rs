@@ -0,0 +1,498 @@
+use crate::args::config::Config;
+use crate::args::Args;
+use crate::report::{self, ReportFormat};
+use crate::server::job::{JobEvent, JobQueue};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::ValueEnum;
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+pub mod job;
+
+/// The `Args` fields a scan request may set, the file paths and outputs stay the ones of
+/// the server. `check_credentials` and `script` also need `--allow-intrusive`.
+const REQUEST_OPTIONS: &[&str] = &[
+    "address",
+    "profile",
+    "ports",
+    "top_ports",
+    "ping",
+    "ping_count",
+    "os_detect",
+    "tls_enum",
+    "dns_zone",
+    "check_credentials",
+    "max_login_attempts",
+    "script",
+    "timeout",
+    "batch_size",
+];
+
+struct AppState {
+    config: Config,
+    jobs: Arc<JobQueue>,
+    /// bearer token every request must carry, when set.
+    token: Option<String>,
+    /// whether requests may check credentials and run scripts.
+    allow_intrusive: bool,
+}
+
+/// An error answered as `{"error": "..."}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn not_found(id: u64) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("scan {} not found", id))
+}
+
+/// The routes of the REST API:
+///
+/// - `POST /scans` queues a scan, the body holding `Args` fields, e.g.
+///   `{"address": "10.0.0.0/24", "ports": "web", "tls_enum": true}`
+/// - `GET /scans` lists the scans, `GET /scans/{id}` reports the state and progress of one
+/// - `GET /scans/{id}/events` streams its status, ports and hosts as server-sent events
+/// - `GET /scans/{id}/report?format=markdown` renders a finished or cancelled scan
+/// - `DELETE /scans/{id}` cancels a queued or running scan
+pub fn router(
+    config: Config,
+    jobs: Arc<JobQueue>,
+    token: Option<String>,
+    allow_intrusive: bool,
+) -> Router {
+    let state = Arc::new(AppState {
+        config,
+        jobs,
+        token,
+        allow_intrusive,
+    });
+    Router::new()
+        .route("/scans", get(list_scans).post(create_scan))
+        .route("/scans/{id}", get(get_scan).delete(cancel_scan))
+        .route("/scans/{id}/events", get(scan_events))
+        .route("/scans/{id}/report", get(scan_report))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+pub async fn serve(
+    listen: &str,
+    config: Config,
+    jobs: Arc<JobQueue>,
+    token: Option<String>,
+    allow_intrusive: bool,
+) -> Result<(), String> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|error| format!("listen on {} error: {}", listen, error))?;
+    tracing::info!("serving the API on {}.", listen);
+    axum::serve(listener, router(config, jobs, token, allow_intrusive))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .map_err(|error| error.to_string())
+}
+
+async fn authorize(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let token = match &state.token {
+        None => return next.run(request).await,
+        Some(token) => token,
+    };
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(json!({ "error": "missing or invalid bearer token" })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// The scan arguments of a request: the defaults with the server config and the profile
+/// of the request applied, then the fields of the request. Credential checks and scripts
+/// the server config does not run already, asked for by the fields or the profile, need
+/// `allow_intrusive`.
+pub fn scan_args(config: &Config, request: Value, allow_intrusive: bool) -> Result<Args, String> {
+    let request = match request {
+        Value::Object(request) => request,
+        _ => return Err("the scan request must be a JSON object".to_string()),
+    };
+    let profile = request
+        .get("profile")
+        .and_then(|profile| profile.as_str())
+        .map(|profile| profile.to_string());
+    let mut args = serde_json::to_value(config.default_args(profile.clone())?)
+        .map_err(|error| error.to_string())?;
+    for (key, value) in request {
+        if !REQUEST_OPTIONS.contains(&key.as_str()) {
+            return Err(format!("unknown or forbidden scan option {}", key));
+        }
+        args[key] = value;
+    }
+    let args: Args =
+        serde_json::from_value(args).map_err(|error| format!("invalid scan request: {}", error))?;
+    if args.address.is_none() {
+        return Err("the scan request has no address".to_string());
+    }
+    if !allow_intrusive {
+        let server = config.default_args(None)?;
+        if (args.check_credentials && !server.check_credentials)
+            || args
+                .script
+                .iter()
+                .any(|script| !server.script.contains(script))
+        {
+            return Err(
+                "credential checks and scripts need the server started with --allow-intrusive"
+                    .to_string(),
+            );
+        }
+    }
+    Ok(args)
+}
+
+async fn create_scan(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    let args = scan_args(&state.config, request, state.allow_intrusive)
+        .map_err(|error| ApiError(StatusCode::BAD_REQUEST, error))?;
+    let job = state.jobs.submit(args);
+    let status = job.status();
+    tracing::info!("scan {} of {} queued.", status.id, status.address);
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+async fn list_scans(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.jobs.list())
+}
+
+async fn get_scan(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = state.jobs.get(id).ok_or_else(|| not_found(id))?;
+    Ok(Json(job.status()))
+}
+
+async fn cancel_scan(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = state.jobs.get(id).ok_or_else(|| not_found(id))?;
+    if !state.jobs.cancel(&job) {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("scan {} is done already", id),
+        ));
+    }
+    tracing::info!("scan {} cancelled.", id);
+    Ok(Json(job.status()))
+}
+
+fn sse_event(event: &JobEvent) -> Event {
+    let event = match event {
+        JobEvent::Status(status) => Event::default().event("status").json_data(status),
+        JobEvent::Port(reply) => Event::default().event("port").json_data(reply),
+        JobEvent::Host(host) => Event::default().event("host").json_data(host),
+    };
+    event.unwrap_or_default()
+}
+
+fn is_done(event: &JobEvent) -> bool {
+    matches!(event, JobEvent::Status(status) if status.state.is_done())
+}
+
+/// The events so far, then the live ones until the scan is done.
+async fn scan_events(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let job = state.jobs.get(id).ok_or_else(|| not_found(id))?;
+    let (history, receiver) = job.subscribe();
+    let done = history.iter().any(is_done);
+    let live = stream::unfold((receiver, done), |(mut receiver, done)| async move {
+        if done {
+            return None;
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let done = is_done(&event);
+                    return Some((event, (receiver, done)));
+                }
+                // a slow client misses the events the channel dropped.
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = stream::iter(history)
+        .chain(live)
+        .map(|event| Ok(sse_event(&event)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    /// json, csv, text or markdown, json by default.
+    format: Option<String>,
+}
+
+async fn scan_report(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+    Query(query): Query<ReportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = state.jobs.get(id).ok_or_else(|| not_found(id))?;
+    let result = job.result().ok_or_else(|| {
+        ApiError(
+            StatusCode::CONFLICT,
+            format!("scan {} is {:?}", id, job.status().state).to_lowercase(),
+        )
+    })?;
+    let format = match query.format {
+        None => ReportFormat::Json,
+        Some(format) => ReportFormat::from_str(&format, true).map_err(|_| {
+            ApiError(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "unknown report format {}, expected json, csv, text or markdown",
+                    format
+                ),
+            )
+        })?,
+    };
+    let content_type = match format {
+        ReportFormat::Json => "application/json",
+        ReportFormat::Csv => "text/csv",
+        ReportFormat::Text => "text/plain",
+        ReportFormat::Markdown => "text/markdown",
+    };
+    Ok((
+        [(header::CONTENT_TYPE, content_type)],
+        report::render(&result, format),
+    ))
+}
+
+#[cfg(test)]
+pub mod server_test {
+    use crate::args::config::Config;
+    use crate::server::job::{JobQueue, JobState};
+    use crate::server::{router, scan_args};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn scan_request_test() {
+        let config = Config::default();
+        let args = scan_args(
+            &config,
+            json!({"address": "10.0.0.1", "profile": "stealth", "tls_enum": true}),
+            false,
+        )
+        .unwrap();
+        assert_eq!(args.address.as_deref(), Some("10.0.0.1"));
+        assert_eq!((args.timeout, args.batch_size), (3000, 16));
+        assert!(args.tls_enum);
+        assert!(scan_args(&config, json!({"ports": "80"}), false).is_err());
+        assert!(scan_args(
+            &config,
+            json!({"address": "10.0.0.1", "db": "/tmp/x.db"}),
+            false
+        )
+        .is_err());
+        assert!(scan_args(
+            &config,
+            json!({"address": "10.0.0.1", "timeout": "slow"}),
+            false
+        )
+        .is_err());
+        assert!(scan_args(&config, json!(["10.0.0.1"]), false).is_err());
+
+        // credential checks and scripts are refused unless the server allows them.
+        let intrusive = json!({"address": "10.0.0.1", "check_credentials": true});
+        assert!(scan_args(&config, intrusive.clone(), false).is_err());
+        assert!(
+            scan_args(&config, intrusive, true)
+                .unwrap()
+                .check_credentials
+        );
+        let full = json!({"address": "10.0.0.1", "profile": "full"});
+        assert!(scan_args(&config, full.clone(), false).is_err());
+        assert_eq!(scan_args(&config, full, true).unwrap().script, ["default"]);
+        let config = Config::parse(
+            "[detection]\nscript = [\"safe\"]\n",
+            std::path::Path::new("config.toml"),
+        )
+        .unwrap();
+        let args = scan_args(&config, json!({"address": "10.0.0.1"}), false).unwrap();
+        assert_eq!(args.script, ["safe"]);
+    }
+
+    #[tokio::test]
+    async fn job_retention_test() {
+        let jobs = Arc::new(JobQueue::new(1, 1, None));
+        let args = scan_args(
+            &Config::default(),
+            json!({"address": "127.0.0.1", "ports": "T:1", "ping": false}),
+            false,
+        )
+        .unwrap();
+        let first = jobs.submit(args.clone());
+        assert!(jobs.cancel(&first));
+        let second = jobs.submit(args.clone());
+        assert!(jobs.get(1).is_some());
+        assert!(jobs.cancel(&second));
+        let third = jobs.submit(args);
+        assert!(jobs.get(1).is_none());
+        assert!(jobs.get(2).is_some());
+        assert_eq!(jobs.list().len(), 2);
+        jobs.cancel(&third);
+    }
+
+    #[tokio::test]
+    async fn cancel_running_job_test() {
+        // accepts the connections of the scan and never answers, the probes wait on them
+        // until the job is cancelled.
+        let target = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = target.local_addr().unwrap().port();
+        let jobs = Arc::new(JobQueue::new(1, 100, None));
+        let args = scan_args(
+            &Config::default(),
+            json!({"address": "127.0.0.1", "ports": format!("T:{}", port), "ping": false}),
+            false,
+        )
+        .unwrap();
+        let job = jobs.submit(args);
+        let mut connections = vec![];
+        while let Ok(Ok((connection, _))) =
+            tokio::time::timeout(Duration::from_millis(500), target.accept()).await
+        {
+            connections.push(connection);
+        }
+        assert!(!connections.is_empty());
+        assert_eq!(job.status().state, JobState::Running);
+        assert!(jobs.cancel(&job));
+        // the socket scan stops with the job, its next probes never connect.
+        let after = tokio::time::timeout(Duration::from_secs(7), target.accept()).await;
+        assert!(after.is_err(), "the scan went on after the cancellation");
+    }
+
+    #[tokio::test]
+    async fn scan_api_test() {
+        let target = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = target.local_addr().unwrap().port();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let jobs = Arc::new(JobQueue::new(1, 100, None));
+        let app = router(Config::default(), jobs, Some("secret".to_string()), false);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = reqwest::Client::new();
+        let request =
+            json!({"address": "127.0.0.1", "ports": format!("T:{}", port), "ping": false});
+        let response = client
+            .post(format!("{}/scans", base))
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        let response = client
+            .post(format!("{}/scans", base))
+            .bearer_auth("secret")
+            .json(&json!({"address": "127.0.0.1", "output": "/tmp/x.json"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let status: Value = client
+            .post(format!("{}/scans", base))
+            .bearer_auth("secret")
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let id = status["id"].as_u64().unwrap();
+        let events = client
+            .get(format!("{}/scans/{}/events", base, id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(events.contains("event: port"), "{}", events);
+        assert!(events.contains(r#""state":"finished""#), "{}", events);
+
+        let status: Value = client
+            .get(format!("{}/scans/{}", base, id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            (status["state"].as_str(), status["ports"].as_u64()),
+            (Some("finished"), Some(1))
+        );
+        let report = client
+            .get(format!("{}/scans/{}/report?format=csv", base, id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(
+            report.contains(&format!("127.0.0.1,TCP,{},", port)),
+            "{}",
+            report
+        );
+        let response = client
+            .delete(format!("{}/scans/{}", base, id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 409);
+        let response = client
+            .get(format!("{}/scans/42", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,504 @@
+use crate::report::{protocol, ScanResult};
+use crate::scanner::plugin::{HostReply, ScannerReply};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    target TEXT NOT NULL,
+    -- the monitor target group the scan ran for.
+    group_name TEXT,
+    started TEXT NOT NULL,
+    finished TEXT
+);
+CREATE TABLE IF NOT EXISTS hosts (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans(id),
+    ip_addr TEXT NOT NULL,
+    os_guess TEXT,
+    -- the host without its ports, they are kept in the ports table.
+    reply TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS ports (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans(id),
+    ip_addr TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    reply TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS services (
+    port_id INTEGER PRIMARY KEY REFERENCES ports(id),
+    name TEXT NOT NULL,
+    version TEXT,
+    cpe TEXT,
+    tls_fingerprint TEXT
+);
+CREATE TABLE IF NOT EXISTS findings (
+    id INTEGER PRIMARY KEY,
+    port_id INTEGER NOT NULL REFERENCES ports(id),
+    kind TEXT NOT NULL,
+    detail TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS hosts_scan ON hosts(scan_id);
+CREATE INDEX IF NOT EXISTS ports_scan ON ports(scan_id, ip_addr);
+CREATE INDEX IF NOT EXISTS ports_port ON ports(port);
+CREATE INDEX IF NOT EXISTS services_name ON services(name);
+"#;
+
+/// A scan kept in the store.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanSummary {
+    pub id: i64,
+    pub target: String,
+    pub group: Option<String>,
+    pub started: String,
+    /// `None` while the scan runs, or when it was interrupted.
+    pub finished: Option<String>,
+    pub hosts: usize,
+    pub ports: usize,
+}
+
+impl std::fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|{}|{} hosts|{} ports",
+            self.id,
+            self.target,
+            self.group.as_deref().unwrap_or("*"),
+            self.started,
+            self.finished.as_deref().unwrap_or("*"),
+            self.hosts,
+            self.ports
+        )
+    }
+}
+
+/// Which open ports `Store::hosts` lists, every port of the latest scan by default.
+#[derive(Debug, Clone, Default)]
+pub struct HostQuery {
+    pub port: Option<u16>,
+    /// matches `http` to `ssl/http` as well.
+    pub service: Option<String>,
+    pub scan: Option<i64>,
+    /// search every scan instead of only the latest one.
+    pub all: bool,
+}
+
+/// An open port found by `Store::hosts`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostMatch {
+    pub scan_id: i64,
+    pub ip_addr: String,
+    pub protocol: String,
+    pub port: u16,
+    pub service: String,
+    pub version: Option<String>,
+}
+
+impl std::fmt::Display for HostMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} [{}|{}|{}|scan {}]",
+            self.ip_addr,
+            self.port,
+            self.protocol,
+            self.service,
+            self.version.as_deref().unwrap_or("*"),
+            self.scan_id
+        )
+    }
+}
+
+/// The SQLite database the scans are kept in, written by the store sink as the results
+/// arrive and read back by the store commands.
+pub struct Store {
+    connection: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Store, String> {
+        let path = path.as_ref();
+        let connection = Connection::open(path)
+            .map_err(|error| format!("open store {} error: {}", path.display(), error))?;
+        connection
+            .execute_batch(SCHEMA)
+            .and_then(|()| Store::migrate(&connection))
+            .map_err(|error| format!("create store tables error: {}", error))?;
+        Ok(Store {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Adds the columns added since to the tables of an older store.
+    fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+        if connection
+            .prepare("SELECT group_name FROM scans LIMIT 0")
+            .is_err()
+        {
+            connection.execute_batch("ALTER TABLE scans ADD COLUMN group_name TEXT")?;
+        }
+        connection.execute_batch("CREATE INDEX IF NOT EXISTS scans_group ON scans(group_name)")
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records the start of a scan, returning its id.
+    pub fn begin_scan(
+        &self,
+        target: &str,
+        group: Option<&str>,
+        started: &str,
+    ) -> Result<i64, String> {
+        let connection = self.connection();
+        connection
+            .execute(
+                "INSERT INTO scans (target, group_name, started) VALUES (?1, ?2, ?3)",
+                params![target, group, started],
+            )
+            .map_err(|error| format!("store scan error: {}", error))?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn finish_scan(&self, scan_id: i64, finished: &str) -> Result<(), String> {
+        self.connection()
+            .execute(
+                "UPDATE scans SET finished = ?1 WHERE id = ?2",
+                params![finished, scan_id],
+            )
+            .map_err(|error| format!("store scan error: {}", error))?;
+        Ok(())
+    }
+
+    /// Stores an open port with its service and the CVE, credential and script findings.
+    pub fn add_port(&self, scan_id: i64, reply: &ScannerReply) -> Result<(), String> {
+        let ip_addr = reply
+            .ip_addr()
+            .map(|ip_addr| ip_addr.to_string())
+            .unwrap_or_default();
+        let content = serde_json::to_string(reply).map_err(|error| error.to_string())?;
+        let mut connection = self.connection();
+        let transaction = connection
+            .transaction()
+            .map_err(|error| format!("store port error: {}", error))?;
+        let result = (|| {
+            transaction.execute(
+                "INSERT INTO ports (scan_id, ip_addr, protocol, port, reply) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![scan_id, ip_addr, protocol(reply), reply.port().unwrap_or_default(), content],
+            )?;
+            let port_id = transaction.last_insert_rowid();
+            transaction.execute(
+                "INSERT INTO services (port_id, name, version, cpe, tls_fingerprint) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    port_id,
+                    reply.name(),
+                    reply.version(),
+                    reply.cpe(),
+                    reply
+                        .tls()
+                        .and_then(|tls_info| tls_info.leaf())
+                        .map(|leaf| leaf.sha256_fingerprint.clone())
+                ],
+            )?;
+            let findings = reply
+                .vulnerabilities()
+                .iter()
+                .map(|vulnerability| ("cve", vulnerability.to_string()))
+                .chain(
+                    reply
+                        .credentials()
+                        .iter()
+                        .map(|finding| ("credential", finding.to_string())),
+                )
+                .chain(
+                    reply
+                        .scripts()
+                        .iter()
+                        .map(|finding| ("script", finding.to_string())),
+                );
+            for (kind, detail) in findings {
+                transaction.execute(
+                    "INSERT INTO findings (port_id, kind, detail) VALUES (?1, ?2, ?3)",
+                    params![port_id, kind, detail],
+                )?;
+            }
+            Ok::<(), rusqlite::Error>(())
+        })();
+        result
+            .and_then(|()| transaction.commit())
+            .map_err(|error| format!("store port error: {}", error))
+    }
+
+    /// Stores the host wide results, the ports were stored by `add_port` already.
+    pub fn add_host(&self, scan_id: i64, host: &HostReply) -> Result<(), String> {
+        let mut content = serde_json::to_value(host).map_err(|error| error.to_string())?;
+        content["ports"] = serde_json::Value::Array(vec![]);
+        self.connection()
+            .execute(
+                "INSERT INTO hosts (scan_id, ip_addr, os_guess, reply) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    scan_id,
+                    host.ip_addr().to_string(),
+                    host.os_guess().map(|os_guess| os_guess.to_string()),
+                    content.to_string()
+                ],
+            )
+            .map_err(|error| format!("store host error: {}", error))?;
+        Ok(())
+    }
+
+    /// Every stored scan, the latest last.
+    pub fn scans(&self) -> Result<Vec<ScanSummary>, String> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT id, target, group_name, started, finished,
+                    (SELECT COUNT(DISTINCT ip_addr) FROM ports WHERE ports.scan_id = scans.id),
+                    (SELECT COUNT(*) FROM ports WHERE ports.scan_id = scans.id)
+                FROM scans ORDER BY id",
+            )
+            .map_err(|error| format!("query scans error: {}", error))?;
+        let scans = statement
+            .query_map([], |row| {
+                Ok(ScanSummary {
+                    id: row.get(0)?,
+                    target: row.get(1)?,
+                    group: row.get(2)?,
+                    started: row.get(3)?,
+                    finished: row.get(4)?,
+                    hosts: row.get(5)?,
+                    ports: row.get(6)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|error| format!("query scans error: {}", error))?;
+        Ok(scans)
+    }
+
+    pub fn latest_scan(&self) -> Result<Option<i64>, String> {
+        self.connection()
+            .query_row("SELECT MAX(id) FROM scans", [], |row| row.get(0))
+            .map_err(|error| format!("query scans error: {}", error))
+    }
+
+    /// The latest finished scan of a monitor group.
+    pub fn latest_group_scan(&self, group: &str) -> Result<Option<i64>, String> {
+        self.connection()
+            .query_row(
+                "SELECT MAX(id) FROM scans WHERE group_name = ?1 AND finished IS NOT NULL",
+                [group],
+                |row| row.get(0),
+            )
+            .map_err(|error| format!("query scans error: {}", error))
+    }
+
+    /// The open ports matching `query`, ordered by scan, host and port.
+    pub fn hosts(&self, query: &HostQuery) -> Result<Vec<HostMatch>, String> {
+        let scan = match (query.scan, query.all) {
+            (Some(scan), _) => Some(scan),
+            (None, true) => None,
+            (None, false) => match self.latest_scan()? {
+                Some(scan) => Some(scan),
+                None => return Ok(vec![]),
+            },
+        };
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT ports.scan_id, ports.ip_addr, ports.protocol, ports.port,
+                    services.name, services.version
+                FROM ports JOIN services ON services.port_id = ports.id
+                WHERE (?1 IS NULL OR ports.scan_id = ?1)
+                    AND (?2 IS NULL OR ports.port = ?2)
+                    AND (?3 IS NULL OR services.name = ?3 OR services.name LIKE '%/' || ?3)
+                ORDER BY ports.scan_id, ports.ip_addr, ports.port",
+            )
+            .map_err(|error| format!("query hosts error: {}", error))?;
+        let hosts = statement
+            .query_map(params![scan, query.port, query.service], |row| {
+                Ok(HostMatch {
+                    scan_id: row.get(0)?,
+                    ip_addr: row.get(1)?,
+                    protocol: row.get(2)?,
+                    port: row.get(3)?,
+                    service: row.get(4)?,
+                    version: row.get(5)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|error| format!("query hosts error: {}", error))?;
+        Ok(hosts)
+    }
+
+    /// Rebuilds a stored scan, the input of the report and diff renderers.
+    pub fn load(&self, scan_id: i64) -> Result<ScanResult, String> {
+        let connection = self.connection();
+        let (started, finished): (String, Option<String>) = connection
+            .query_row(
+                "SELECT started, finished FROM scans WHERE id = ?1",
+                [scan_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|error| format!("query scan {} error: {}", scan_id, error))?
+            .ok_or_else(|| format!("scan {} not found", scan_id))?;
+
+        let mut hosts: BTreeMap<IpAddr, HostReply> = BTreeMap::new();
+        let mut statement = connection
+            .prepare("SELECT reply FROM hosts WHERE scan_id = ?1 ORDER BY id")
+            .map_err(|error| format!("query scan {} error: {}", scan_id, error))?;
+        let rows = statement
+            .query_map([scan_id], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|error| format!("query scan {} error: {}", scan_id, error))?;
+        for content in rows {
+            let host: HostReply = serde_json::from_str(&content)
+                .map_err(|error| format!("parse stored host error: {}", error))?;
+            hosts.insert(host.ip_addr(), host);
+        }
+
+        let mut statement = connection
+            .prepare("SELECT reply FROM ports WHERE scan_id = ?1 ORDER BY id")
+            .map_err(|error| format!("query scan {} error: {}", scan_id, error))?;
+        let rows = statement
+            .query_map([scan_id], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|error| format!("query scan {} error: {}", scan_id, error))?;
+        for content in rows {
+            let reply: ScannerReply = serde_json::from_str(&content)
+                .map_err(|error| format!("parse stored port error: {}", error))?;
+            // ports of an interrupted scan have no host row.
+            let ip_addr = match reply.ip_addr() {
+                Some(ip_addr) => ip_addr,
+                None => continue,
+            };
+            hosts
+                .entry(ip_addr)
+                .or_insert_with(|| HostReply::new(ip_addr))
+                .add_port(reply);
+        }
+        Ok(ScanResult {
+            started,
+            finished: finished.unwrap_or_default(),
+            hosts: hosts.into_values().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod store_test {
+    use crate::report::ScanResult;
+    use crate::scanner::plugin::{HostReply, ScannerReply, TransportLayerProtocol};
+    use crate::store::{HostQuery, Store};
+    use std::net::IpAddr;
+
+    fn reply(ip_addr: IpAddr, port: u16, name: &str, version: Option<&str>) -> ScannerReply {
+        let mut reply = ScannerReply::new();
+        reply.set_ip_addr(Some(ip_addr));
+        reply.set_port(Some(port));
+        reply.set_transport_layer_protocol(Some(TransportLayerProtocol::TCP));
+        reply.set_name(name.to_string());
+        if let Some(version) = version {
+            reply.set_version(version.to_string());
+        }
+        reply
+    }
+
+    /// Stores a scan the way the store sink does, ports first.
+    fn store_scan(store: &Store, started: &str, hosts: &[HostReply]) -> i64 {
+        let scan_id = store
+            .begin_scan("10.0.0.0/24", Some("lab"), started)
+            .unwrap();
+        for host in hosts {
+            for reply in host.ports() {
+                store.add_port(scan_id, reply).unwrap();
+            }
+        }
+        for host in hosts {
+            store.add_host(scan_id, host).unwrap();
+        }
+        store.finish_scan(scan_id, started).unwrap();
+        scan_id
+    }
+
+    #[test]
+    fn result_store_test() {
+        let path = std::env::temp_dir().join(format!("magic-scanner-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = Store::open(&path).unwrap();
+        assert!(store.hosts(&HostQuery::default()).unwrap().is_empty());
+
+        let first: IpAddr = "10.0.0.1".parse().unwrap();
+        let second: IpAddr = "10.0.0.2".parse().unwrap();
+        let mut host = HostReply::new(first);
+        host.add_port(reply(first, 22, "ssh", Some("OpenSSH_9.3")));
+        host.add_port(reply(first, 443, "ssl/http", None));
+        let before = store_scan(&store, "2026-10-18T00:00:00Z", &[host]);
+
+        let mut hosts = vec![HostReply::new(first), HostReply::new(second)];
+        hosts[0].add_port(reply(first, 22, "ssh", Some("OpenSSH_9.6")));
+        hosts[1].add_port(reply(second, 80, "http", None));
+        let after = store_scan(&store, "2026-10-19T00:00:00Z", &hosts);
+
+        let scans = store.scans().unwrap();
+        assert_eq!(scans.len(), 2);
+        assert_eq!(
+            scans[0].to_string(),
+            "1|10.0.0.0/24|lab|2026-10-18T00:00:00Z|2026-10-18T00:00:00Z|1 hosts|2 ports"
+        );
+
+        let latest = store.hosts(&HostQuery::default()).unwrap();
+        assert_eq!(latest.len(), 2);
+        assert!(latest.iter().all(|found| found.scan_id == after));
+        let http = store
+            .hosts(&HostQuery {
+                service: Some("http".to_string()),
+                all: true,
+                ..Default::default()
+            })
+            .unwrap()
+            .iter()
+            .map(|found| found.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            http,
+            vec![
+                "10.0.0.1:443 [TCP|ssl/http|*|scan 1]",
+                "10.0.0.2:80 [TCP|http|*|scan 2]"
+            ]
+        );
+        let ssh = store
+            .hosts(&HostQuery {
+                port: Some(22),
+                scan: Some(before),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(ssh.len(), 1);
+        assert_eq!(ssh[0].version.as_deref(), Some("OpenSSH_9.3"));
+
+        assert_eq!(
+            store.load(after).unwrap(),
+            ScanResult {
+                started: "2026-10-19T00:00:00Z".to_string(),
+                finished: "2026-10-19T00:00:00Z".to_string(),
+                hosts,
+            }
+        );
+        assert_eq!(store.latest_group_scan("lab").unwrap(), Some(after));
+        assert_eq!(store.latest_group_scan("dmz").unwrap(), None);
+        assert!(store.load(3).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
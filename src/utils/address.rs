@@ -10,6 +10,7 @@ use trust_dns_resolver::name_server::{GenericConnection, GenericConnectionProvid
 use trust_dns_resolver::{AsyncResolver, TokioHandle};
 
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 enum AddressType {
     IP,
     Range,
@@ -24,7 +25,7 @@ fn get_address_type(address: &str) -> AddressType {
     let address_range_regex = regex::Regex::new(&address_range_regex_str).unwrap();
     let address_mask_regex_str = format!("^{}/\\d+$", IP_REGEX);
     let address_mask_regex = regex::Regex::new(&address_mask_regex_str).unwrap();
-    return if address_rages.is_match(address) {
+    if address_rages.is_match(address) {
         AddressType::IP
     } else if address_range_regex.is_match(address) {
         AddressType::Range
@@ -32,7 +33,7 @@ fn get_address_type(address: &str) -> AddressType {
         AddressType::Mask
     } else {
         AddressType::UNKNOWN
-    };
+    }
 }
 
 fn resolve_ips_from_range_address(address: &str) -> Result<Vec<IpAddr>, String> {
@@ -41,7 +42,7 @@ fn resolve_ips_from_range_address(address: &str) -> Result<Vec<IpAddr>, String>
     }
     let ip_range = address.split("-").collect::<Vec<&str>>();
     let ip_range = Ipv4AddrRange::new(
-        ip_range.get(0).unwrap().parse().unwrap(),
+        ip_range.first().unwrap().parse().unwrap(),
         ip_range.get(1).unwrap().parse().unwrap(),
     );
     let ip_range = IpAddrRange::from(ip_range);
@@ -51,13 +52,13 @@ fn resolve_ips_from_range_address(address: &str) -> Result<Vec<IpAddr>, String>
 
 fn resolve_ips_from_mask_address(address: &str) -> Result<Vec<IpAddr>, String> {
     let cidr = IpCidr::from_str(address);
-    return match cidr {
+    match cidr {
         Ok(data) => {
             let result = data.iter().collect::<Vec<IpAddr>>();
             Ok(result)
         }
         Err(_) => Err(address.to_string()),
-    };
+    }
 }
 
 async fn resolve_ips_from_domain(
@@ -92,7 +93,7 @@ pub struct ParseAddress<'a> {
 }
 
 impl<'a> ParseAddress<'a> {
-    pub async fn resolve_ips(addresses: &'a str) -> ParseAddress {
+    pub async fn resolve_ips(addresses: &'a str) -> ParseAddress<'a> {
         let mut valid_address = HashSet::new();
         let mut invalid_address = HashSet::new();
         let async_resolver: AsyncResolver<
@@ -0,0 +1,38 @@
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Reads the file at `path`, warning about it as the `kind` file when it can't be read.
+pub fn read_file<P: AsRef<Path>>(path: P, kind: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(error) => {
+            tracing::warn!("open {} file error: {}", kind, error);
+            None
+        }
+    }
+}
+
+/// Parses `content` as JSON, warning about it as the `kind` file and falling back to the
+/// default value when it is invalid.
+pub fn parse_json<T: DeserializeOwned + Default>(content: &str, kind: &str) -> T {
+    match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(error) => {
+            tracing::warn!("parse {} file error: {}", kind, error);
+            T::default()
+        }
+    }
+}
+
+/// Reads the JSON file at `path`, warning about it as the `kind` file and falling back
+/// to the default value when it can't be opened or parsed.
+pub fn load_json_file<T, P>(path: P, kind: &str) -> T
+where
+    T: DeserializeOwned + Default,
+    P: AsRef<Path>,
+{
+    match read_file(path, kind) {
+        Some(content) => parse_json(&content, kind),
+        None => T::default(),
+    }
+}
@@ -1,46 +1,388 @@
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Named port sets usable in a port expression, `all` being every port.
+const PORT_SETS: [(&str, &[u16]); 6] = [
+    (
+        "web",
+        &[
+            80, 81, 443, 591, 593, 3000, 5000, 7001, 7443, 8000, 8008, 8080, 8081, 8088, 8443,
+            8888, 9000, 9090, 9443,
+        ],
+    ),
+    (
+        "db",
+        &[
+            1433, 1434, 1521, 2483, 2484, 3050, 3306, 5432, 5984, 6379, 7474, 7687, 8086, 9042,
+            9200, 9300, 11211, 27017, 27018, 28017, 50000,
+        ],
+    ),
+    (
+        "windows",
+        &[
+            53, 88, 135, 137, 139, 389, 445, 464, 593, 636, 3268, 3269, 3389, 5985, 5986, 9389,
+            47001,
+        ],
+    ),
+    (
+        "iot",
+        &[
+            23, 80, 102, 443, 502, 554, 1883, 2323, 5000, 7547, 8080, 8443, 8883, 9100, 20000,
+            34567, 37777, 44818, 49152,
+        ],
+    ),
+    ("mail", &[25, 110, 143, 465, 587, 993, 995]),
+    ("remote", &[22, 23, 3389, 5900, 5901, 5985, 5986]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl Protocol {
+    fn parse(protocol: &str) -> Option<Protocol> {
+        match protocol {
+            "tcp" => Some(Protocol::Tcp),
+            "udp" => Some(Protocol::Udp),
+            "sctp" => Some(Protocol::Sctp),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+            Protocol::Sctp => "SCTP",
+        }
+    }
+}
 
 pub enum PortType {
     Port,
     Range,
+    /// `topN`, the N most frequent ports of the service table.
+    Top(usize),
+    Set(&'static [u16]),
+    All,
+    /// a service name of the service table, e.g. `http`.
+    Service,
 }
 
 fn get_port_type(port: &str) -> PortType {
-    return if port.contains("-") {
+    if port == "all" || port == "-" {
+        return PortType::All;
+    }
+    if let Some((_, ports)) = PORT_SETS.iter().find(|(name, _)| *name == port) {
+        return PortType::Set(ports);
+    }
+    if let Some(count) = port
+        .strip_prefix("top")
+        .and_then(|count| count.parse::<usize>().ok())
+    {
+        return PortType::Top(count);
+    }
+    if port.chars().all(|c| c.is_ascii_digit()) {
+        return PortType::Port;
+    }
+    if port.chars().all(|c| c.is_ascii_digit() || c == '-') {
         PortType::Range
     } else {
-        PortType::Port
-    };
+        PortType::Service
+    }
 }
-pub fn resolve_ports_or_all(addresses: Option<String>) -> HashSet<u16> {
-    if addresses.is_none() {
-        let result = 1..=65535;
-        return result.collect::<HashSet<u16>>();
+
+/// The ports to scan per transport protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PortSpec {
+    pub tcp: BTreeSet<u16>,
+    pub udp: BTreeSet<u16>,
+    pub sctp: BTreeSet<u16>,
+}
+
+impl PortSpec {
+    fn ports_mut(&mut self, protocol: Protocol) -> &mut BTreeSet<u16> {
+        match protocol {
+            Protocol::Tcp => &mut self.tcp,
+            Protocol::Udp => &mut self.udp,
+            Protocol::Sctp => &mut self.sctp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tcp.len() + self.udp.len() + self.sctp.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-    resolve_ports(addresses)
+
+    /// Splits the ports into specs of at most `size` ports, TCP first, then UDP and SCTP.
+    pub fn chunks(&self, size: usize) -> Vec<PortSpec> {
+        let size = size.max(1);
+        let mut chunks = vec![];
+        let mut chunk = PortSpec::default();
+        for protocol in [Protocol::Tcp, Protocol::Udp, Protocol::Sctp] {
+            let ports = match protocol {
+                Protocol::Tcp => &self.tcp,
+                Protocol::Udp => &self.udp,
+                Protocol::Sctp => &self.sctp,
+            };
+            for port in ports {
+                chunk.ports_mut(protocol).insert(*port);
+                if chunk.len() == size {
+                    chunks.push(std::mem::take(&mut chunk));
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+}
+
+/// A port of the service table.
+#[derive(Debug, Clone)]
+struct ServiceEntry {
+    name: String,
+    port: u16,
+    protocol: Protocol,
+    frequency: f64,
 }
 
-pub fn resolve_ports(addresses: Option<String>) -> HashSet<u16> {
-    let mut result = HashSet::new();
-    if addresses.is_none() {
-        return result;
+/// Ports with their service name and open frequency, read from a file in the
+/// nmap-services layout.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceTable {
+    /// most frequent first.
+    entries: Vec<ServiceEntry>,
+}
+
+impl ServiceTable {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(error) => {
+                tracing::warn!("open service table file error: {}", error);
+                Self::default()
+            }
+        }
+    }
+
+    /// Parses `name port/protocol frequency` lines.
+    pub fn parse(content: &str) -> Self {
+        let mut entries = vec![];
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut fields = line.split_whitespace();
+            let (name, port, frequency) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(port), Some(frequency)) => (name, port, frequency),
+                _ => continue,
+            };
+            let (port, protocol) = match port.split_once('/') {
+                Some((port, protocol)) => (port.parse::<u16>(), Protocol::parse(protocol)),
+                None => continue,
+            };
+            if let (Ok(port), Some(protocol), Ok(frequency)) =
+                (port, protocol, frequency.parse::<f64>())
+            {
+                entries.push(ServiceEntry {
+                    name: name.to_string(),
+                    port,
+                    protocol,
+                    frequency,
+                });
+            }
+        }
+        entries.sort_by(|left, right| right.frequency.total_cmp(&left.frequency));
+        Self { entries }
+    }
+
+    /// The `count` most frequent `protocol` ports, fewer with a warning when the table is
+    /// shorter.
+    pub fn top(&self, protocol: Protocol, count: usize) -> Vec<u16> {
+        let ports = self
+            .entries
+            .iter()
+            .filter(|entry| entry.protocol == protocol)
+            .take(count)
+            .map(|entry| entry.port)
+            .collect::<Vec<_>>();
+        if ports.len() < count {
+            tracing::warn!(
+                "top {} {} ports requested, the service table only lists {}.",
+                count,
+                protocol.name(),
+                ports.len()
+            );
+        }
+        ports
+    }
+
+    /// Every `protocol` port of the table, most frequent first.
+    pub fn ports(&self, protocol: Protocol) -> Vec<u16> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.protocol == protocol)
+            .map(|entry| entry.port)
+            .collect()
     }
-    let address = addresses.unwrap();
-    let ports_list = address.split(",").collect::<Vec<&str>>();
-    for port in ports_list {
-        let port_type = get_port_type(port);
-        match port_type {
+
+    /// The `protocol` ports registered for the service `name`.
+    pub fn lookup(&self, protocol: Protocol, name: &str) -> Vec<u16> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.protocol == protocol && entry.name == name)
+            .map(|entry| entry.port)
+            .collect()
+    }
+}
+
+/// Resolves the `-p` expression and `--top-ports`, together when both are given. Every
+/// TCP port of the service table is scanned when neither is, UDP ports only ever come
+/// from a `U:` entry.
+pub fn resolve_ports_or_default(
+    expression: Option<String>,
+    top_ports: Option<usize>,
+    services: &ServiceTable,
+) -> Result<PortSpec, String> {
+    let mut result = match expression {
+        Some(expression) => resolve_ports(&expression, services)?,
+        None => PortSpec::default(),
+    };
+    match top_ports {
+        Some(count) => result.tcp.extend(services.top(Protocol::Tcp, count)),
+        None if result.is_empty() => result.tcp.extend(services.ports(Protocol::Tcp)),
+        None => {}
+    }
+    Ok(result)
+}
+
+/// Expands a comma separated list of ports, ranges (`8000-8100`, `-1024`, `60000-`),
+/// `topN`, named sets (`web`, `db`, `windows`, `iot`, `mail`, `remote`, `all`) and service
+/// names (`http`, `ssh`). A `T:`, `U:` or `S:` prefix makes the following entries TCP, UDP
+/// or SCTP only up to the next prefix, entries before the first one are TCP as in nmap.
+pub fn resolve_ports(expression: &str, services: &ServiceTable) -> Result<PortSpec, String> {
+    let mut result = PortSpec::default();
+    let mut protocol = Protocol::Tcp;
+    for port in expression.split(',').map(|port| port.trim()) {
+        let port = match port.split_once(':') {
+            Some((qualifier, port)) => {
+                protocol = match qualifier.trim() {
+                    "T" | "t" => Protocol::Tcp,
+                    "U" | "u" => Protocol::Udp,
+                    "S" | "s" => Protocol::Sctp,
+                    _ => return Err(format!("invalid protocol: {}", qualifier)),
+                };
+                port.trim()
+            }
+            None => port,
+        };
+        if port.is_empty() {
+            continue;
+        }
+        let invalid = || format!("invalid port: {}", port);
+        let ports = result.ports_mut(protocol);
+        match get_port_type(port) {
             PortType::Port => {
-                result.insert(port.parse::<u16>().unwrap());
+                ports.insert(port.parse::<u16>().map_err(|_| invalid())?);
             }
             PortType::Range => {
-                let mut range = port.split("-");
-                let start = range.next().unwrap().parse::<u16>().unwrap();
-                let end = range.next().unwrap().parse::<u16>().unwrap();
-                let range = start..=end;
-                result.extend(range.collect::<Vec<u16>>())
+                let (start, end) = port.split_once('-').ok_or_else(invalid)?;
+                let start = match start {
+                    "" => 1,
+                    start => start.parse::<u16>().map_err(|_| invalid())?,
+                };
+                let end = match end {
+                    "" => 65535,
+                    end => end.parse::<u16>().map_err(|_| invalid())?,
+                };
+                if start > end {
+                    return Err(invalid());
+                }
+                ports.extend(start..=end);
+            }
+            PortType::Top(count) => ports.extend(services.top(protocol, count)),
+            PortType::Set(set) => ports.extend(set),
+            PortType::All => ports.extend(1..=65535),
+            PortType::Service => {
+                let service = services.lookup(protocol, port);
+                if service.is_empty() {
+                    return Err(invalid());
+                }
+                ports.extend(service);
             }
         }
     }
-    result
+    result.tcp.remove(&0);
+    result.udp.remove(&0);
+    result.sctp.remove(&0);
+    Ok(result)
+}
+
+#[cfg(test)]
+pub mod port_test {
+    use crate::utils::port::{resolve_ports, resolve_ports_or_default, Protocol, ServiceTable};
+
+    #[test]
+    fn resolve_ports_test() {
+        let services = ServiceTable::parse(
+            "# comment\nhttp\t80/tcp\t0.48\ntelnet\t23/tcp\t0.22\nhttps\t443/tcp\t0.21 # tls\ndomain\t53/udp\t0.9\nssh\t22/tcp\t0.3\nntp\t123/udp\t0.3\nm3ua\t2905/sctp\t0.1\n",
+        );
+        assert_eq!(services.top(Protocol::Tcp, 2), vec![80, 22]);
+        assert_eq!(services.top(Protocol::Tcp, 10).len(), 4);
+        assert_eq!(services.top(Protocol::Udp, 10), vec![53, 123]);
+
+        let ports = resolve_ports("top2,web,8000-8100", &services).unwrap();
+        assert!(ports.tcp.contains(&22));
+        assert!(ports.tcp.contains(&9443));
+        assert!(ports.tcp.contains(&8050));
+        assert!(!ports.tcp.contains(&23));
+        assert!(ports.udp.is_empty());
+        assert_eq!(resolve_ports("all", &services).unwrap().tcp.len(), 65535);
+        assert_eq!(resolve_ports("21, 22 ,21", &services).unwrap().tcp.len(), 2);
+        assert!(resolve_ports("100-10", &services).is_err());
+        assert!(resolve_ports("70000", &services).is_err());
+        assert!(resolve_ports("gopher", &services).is_err());
+        assert!(resolve_ports("X:80", &services).is_err());
+
+        let ports = resolve_ports("T:80,ssh,U:53,domain,-3,S:m3ua", &services).unwrap();
+        assert_eq!(ports.tcp.iter().copied().collect::<Vec<_>>(), vec![22, 80]);
+        assert_eq!(
+            ports.udp.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 53]
+        );
+        assert_eq!(ports.sctp.iter().copied().collect::<Vec<_>>(), vec![2905]);
+        assert!(resolve_ports("U:ssh", &services).is_err());
+        assert!(resolve_ports("ntp", &services).is_err());
+        let ports = resolve_ports("80,U:123", &services).unwrap();
+        assert_eq!(ports.tcp.iter().copied().collect::<Vec<_>>(), vec![80]);
+        assert_eq!(ports.udp.iter().copied().collect::<Vec<_>>(), vec![123]);
+        assert_eq!(resolve_ports("T:65530-", &services).unwrap().len(), 6);
+        assert_eq!(resolve_ports("T:-", &services).unwrap().len(), 65535);
+
+        let ports = resolve_ports_or_default(None, None, &services).unwrap();
+        assert_eq!((ports.tcp.len(), ports.udp.len()), (4, 0));
+        let ports =
+            resolve_ports_or_default(Some("T:3306".to_string()), Some(1), &services).unwrap();
+        assert_eq!(ports.tcp.len(), 2);
+        assert!(ports.tcp.contains(&80));
+        assert!(ports.udp.is_empty());
+
+        let ports = resolve_ports("T:1-5,U:53,161", &services).unwrap();
+        let chunks = ports.chunks(3);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks[1].tcp.iter().copied().collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_eq!(chunks[1].udp.iter().copied().collect::<Vec<_>>(), vec![53]);
+        assert_eq!(chunks[2].len(), 1);
+        assert_eq!(ports.chunks(100), vec![ports.clone()]);
+    }
 }
@@ -0,0 +1,355 @@
+use crate::utils::json;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+mod nvd;
+
+/// One vulnerable CPE of a CVE, the form NVD feeds are reduced to and the entries of
+/// a compact index.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CveEntry {
+    pub id: String,
+    pub score: Option<f32>,
+    pub severity: Option<String>,
+    /// CPE 2.3 name, a `*` version is bounded by the version range.
+    pub cpe: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_start_including: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_start_excluding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_end_including: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_end_excluding: Option<String>,
+}
+
+/// A CVE a detected CPE is affected by.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Vulnerability {
+    pub id: String,
+    pub score: Option<f32>,
+    pub severity: Option<String>,
+    /// the detected CPE that matched.
+    pub cpe: String,
+}
+
+impl std::fmt::Display for Vulnerability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let score = self
+            .score
+            .map(|score| format!("{:.1}", score))
+            .unwrap_or_else(|| "*".to_string());
+        write!(
+            f,
+            "{}|{}|{}|{}",
+            self.id,
+            score,
+            self.severity.as_deref().unwrap_or("*"),
+            self.cpe
+        )
+    }
+}
+
+/// The fields of a CPE 2.2 URI or CPE 2.3 formatted string the matching looks at.
+#[derive(Debug, Clone, PartialEq)]
+struct Cpe {
+    part: String,
+    vendor: String,
+    product: String,
+    version: String,
+    update: String,
+}
+
+impl Cpe {
+    fn parse(cpe: &str) -> Option<Cpe> {
+        let cpe = cpe.to_lowercase();
+        let fields = match (cpe.strip_prefix("cpe:2.3:"), cpe.strip_prefix("cpe:/")) {
+            (Some(fields), _) | (None, Some(fields)) => fields,
+            (None, None) => return None,
+        };
+        let mut fields = fields.split(':').map(|field| field.to_string());
+        let cpe = Cpe {
+            part: fields.next()?,
+            vendor: fields.next()?,
+            product: fields.next()?,
+            version: fields.next().unwrap_or_default(),
+            update: fields.next().unwrap_or_default(),
+        };
+        if cpe.vendor.is_empty() || cpe.product.is_empty() {
+            return None;
+        }
+        Some(cpe)
+    }
+
+    fn key(&self) -> String {
+        format!("{}:{}", self.vendor, self.product)
+    }
+}
+
+/// A field matching any value, `-` (not applicable) is a concrete value.
+fn is_any(field: &str) -> bool {
+    field.is_empty() || field == "*"
+}
+
+/// Replaces the version of a CPE, e.g. the `*` of a technology rule with the detected one.
+pub fn cpe_with_version(cpe: &str, version: &str) -> String {
+    // a version is a single token, drop build and distribution suffixes.
+    let version = version.split_whitespace().next().unwrap_or_default();
+    let prefix = match ["cpe:2.3:", "cpe:/"]
+        .into_iter()
+        .find(|prefix| cpe.starts_with(prefix))
+    {
+        Some(prefix) => prefix,
+        None => return cpe.to_string(),
+    };
+    let mut fields = cpe[prefix.len()..].split(':').collect::<Vec<&str>>();
+    if fields.len() > 3 {
+        fields[3] = version;
+    } else {
+        fields.resize(3, "");
+        fields.push(version);
+    }
+    format!("{}{}", prefix, fields.join(":"))
+}
+
+/// Splits a version into numeric and alphabetic runs, `8.9p1` is `8`, `9`, `p`, `1`.
+fn version_tokens(version: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    for part in version.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut start = 0;
+        for (index, c) in part.char_indices().skip(1) {
+            let previous = part.as_bytes()[index - 1];
+            if previous.is_ascii_digit() != c.is_ascii_digit() {
+                tokens.push(&part[start..index]);
+                start = index;
+            }
+        }
+        if start < part.len() {
+            tokens.push(&part[start..]);
+        }
+    }
+    tokens
+}
+
+/// Compares dotted versions token by token, numbers numerically. A trailing alphabetic
+/// token marks a pre-release, so `1.0rc1` sorts before `1.0` and `1.0.1` after it.
+pub fn compare_versions(left: &str, right: &str) -> Ordering {
+    let left = version_tokens(left);
+    let right = version_tokens(right);
+    for index in 0..std::cmp::max(left.len(), right.len()) {
+        let ordering = match (left.get(index), right.get(index)) {
+            (Some(left), Some(right)) => match (left.parse::<u64>(), right.parse::<u64>()) {
+                (Ok(left), Ok(right)) => left.cmp(&right),
+                (Ok(_), Err(_)) => Ordering::Greater,
+                (Err(_), Ok(_)) => Ordering::Less,
+                (Err(_), Err(_)) => left.to_lowercase().cmp(&right.to_lowercase()),
+            },
+            (Some(left), None) if left.parse::<u64>().is_err() => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(right)) if right.parse::<u64>().is_err() => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+impl CveEntry {
+    /// Whether `cpe`, which must carry a concrete version, is affected.
+    fn affects(&self, cpe: &Cpe) -> bool {
+        let criteria = match Cpe::parse(&self.cpe) {
+            Some(criteria) => criteria,
+            None => return false,
+        };
+        if criteria.part != cpe.part
+            || criteria.vendor != cpe.vendor
+            || criteria.product != cpe.product
+        {
+            return false;
+        }
+        if !is_any(&criteria.version) {
+            let same_update =
+                is_any(&criteria.update) || is_any(&cpe.update) || criteria.update == cpe.update;
+            return criteria.version != "-"
+                && same_update
+                && compare_versions(&cpe.version, &criteria.version) == Ordering::Equal;
+        }
+        let compare = |bound: &Option<String>| {
+            bound
+                .as_deref()
+                .map(|bound| compare_versions(&cpe.version, bound))
+        };
+        !matches!(compare(&self.version_start_including), Some(Ordering::Less))
+            && !matches!(
+                compare(&self.version_start_excluding),
+                Some(Ordering::Less | Ordering::Equal)
+            )
+            && !matches!(
+                compare(&self.version_end_including),
+                Some(Ordering::Greater)
+            )
+            && !matches!(
+                compare(&self.version_end_excluding),
+                Some(Ordering::Greater | Ordering::Equal)
+            )
+    }
+}
+
+/// Offline CVE database, entries are looked up by vendor and product.
+#[derive(Debug, Default)]
+pub struct VulnDatabase {
+    entries: HashMap<String, Vec<CveEntry>>,
+}
+
+impl VulnDatabase {
+    pub fn new(entries: Vec<CveEntry>) -> Self {
+        let mut database = VulnDatabase::default();
+        for entry in entries {
+            if let Some(cpe) = Cpe::parse(&entry.cpe) {
+                database.entries.entry(cpe.key()).or_default().push(entry);
+            }
+        }
+        database
+    }
+
+    /// Loads a feed or index file, or every `.json` file of a directory of them.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut paths = vec![];
+        if path.is_dir() {
+            match std::fs::read_dir(path) {
+                Ok(entries) => paths.extend(
+                    entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| {
+                            path.extension()
+                                .is_some_and(|extension| extension == "json")
+                        }),
+                ),
+                Err(error) => tracing::warn!("open cve database directory error: {}", error),
+            }
+            paths.sort();
+        } else {
+            paths.push(path.to_path_buf());
+        }
+        let mut entries = vec![];
+        for path in paths {
+            let kind = format!("cve database {}", path.display());
+            let content = match json::read_file(&path, &kind) {
+                Some(content) => content,
+                None => continue,
+            };
+            match nvd::parse(&content) {
+                Ok(parsed) => entries.extend(parsed),
+                Err(error) => tracing::warn!("parse {} file error: {}", kind, error),
+            }
+        }
+        let database = Self::new(entries);
+        tracing::info!("cve database: {} products", database.entries.len());
+        database
+    }
+
+    /// The CVEs affecting any of the detected CPEs, highest score first. CPEs without a
+    /// version are skipped, they would match every CVE of the product.
+    pub fn matches(&self, cpes: &[String]) -> Vec<Vulnerability> {
+        let mut vulnerabilities: Vec<Vulnerability> = vec![];
+        for detected in cpes.iter() {
+            let cpe = match Cpe::parse(detected) {
+                Some(cpe) if !is_any(&cpe.version) && cpe.version != "-" => cpe,
+                _ => continue,
+            };
+            let entries = match self.entries.get(&cpe.key()) {
+                Some(entries) => entries,
+                None => continue,
+            };
+            for entry in entries.iter().filter(|entry| entry.affects(&cpe)) {
+                if vulnerabilities
+                    .iter()
+                    .any(|vulnerability| vulnerability.id == entry.id)
+                {
+                    continue;
+                }
+                vulnerabilities.push(Vulnerability {
+                    id: entry.id.clone(),
+                    score: entry.score,
+                    severity: entry.severity.clone(),
+                    cpe: detected.clone(),
+                });
+            }
+        }
+        vulnerabilities.sort_by(|left, right| {
+            right
+                .score
+                .partial_cmp(&left.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| left.id.cmp(&right.id))
+        });
+        vulnerabilities
+    }
+}
+
+#[cfg(test)]
+pub mod vuln_test {
+    use crate::vuln::{compare_versions, cpe_with_version, nvd, VulnDatabase};
+    use std::cmp::Ordering;
+
+    #[test]
+    fn vuln_match_test() {
+        assert_eq!(compare_versions("1.20.1", "1.20.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.9", "1.10"), Ordering::Less);
+        assert_eq!(compare_versions("2.4", "2.4.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0rc1", "1.0"), Ordering::Less);
+        assert_eq!(
+            cpe_with_version("cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*", "1.18.0"),
+            "cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*"
+        );
+        assert_eq!(
+            cpe_with_version("cpe:/a:redis:redis", "7.0.5 (00000000)"),
+            "cpe:/a:redis:redis:7.0.5"
+        );
+
+        let feed = r#"{"vulnerabilities": [{"cve": {
+            "id": "CVE-2021-23017",
+            "metrics": {"cvssMetricV31": [{"type": "Primary",
+                "cvssData": {"baseScore": 7.7, "baseSeverity": "HIGH"}}]},
+            "configurations": [{"nodes": [{"operator": "OR", "cpeMatch": [
+                {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*",
+                 "versionStartIncluding": "0.6.18", "versionEndExcluding": "1.20.1"}]}]}]
+        }}]}"#;
+        let mut entries = nvd::parse(feed).unwrap();
+        let legacy = r#"{"CVE_Items": [{
+            "cve": {"CVE_data_meta": {"ID": "CVE-2021-41773"}},
+            "configurations": {"nodes": [{"operator": "OR", "children": [], "cpe_match": [
+                {"vulnerable": true, "cpe23Uri": "cpe:2.3:a:apache:http_server:2.4.49:*:*:*:*:*:*:*"}]}]},
+            "impact": {"baseMetricV3": {"cvssV3": {"baseScore": 7.5, "baseSeverity": "HIGH"}}}
+        }]}"#;
+        entries.extend(nvd::parse(legacy).unwrap());
+        assert_eq!(entries.len(), 2);
+        let database = VulnDatabase::new(entries);
+
+        let affected = database.matches(&["cpe:2.3:a:f5:nginx:1.18.0:*:*:*:*:*:*:*".to_string()]);
+        assert_eq!(affected.len(), 1);
+        assert_eq!(affected[0].id, "CVE-2021-23017");
+        assert_eq!(affected[0].score, Some(7.7));
+        assert!(database
+            .matches(&["cpe:2.3:a:f5:nginx:1.20.1".to_string()])
+            .is_empty());
+        assert!(database
+            .matches(&["cpe:2.3:a:f5:nginx:*".to_string()])
+            .is_empty());
+        assert_eq!(
+            database
+                .matches(&["cpe:/a:apache:http_server:2.4.49".to_string()])
+                .len(),
+            1
+        );
+        assert!(database
+            .matches(&["cpe:/a:apache:http_server:2.4.50".to_string()])
+            .is_empty());
+    }
+}
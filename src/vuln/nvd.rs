@@ -0,0 +1,247 @@
+use crate::vuln::CveEntry;
+use serde::Deserialize;
+
+/// The yearly `nvdcve-1.1-*.json` data feeds.
+#[derive(Deserialize)]
+struct FeedV1 {
+    #[serde(rename = "CVE_Items")]
+    items: Vec<ItemV1>,
+}
+
+#[derive(Deserialize)]
+struct ItemV1 {
+    cve: CveV1,
+    #[serde(default)]
+    configurations: ConfigurationsV1,
+    #[serde(default)]
+    impact: ImpactV1,
+}
+
+#[derive(Deserialize)]
+struct CveV1 {
+    #[serde(rename = "CVE_data_meta")]
+    meta: MetaV1,
+}
+
+#[derive(Deserialize)]
+struct MetaV1 {
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+#[derive(Deserialize, Default)]
+struct ConfigurationsV1 {
+    #[serde(default)]
+    nodes: Vec<NodeV1>,
+}
+
+#[derive(Deserialize)]
+struct NodeV1 {
+    #[serde(default)]
+    children: Vec<NodeV1>,
+    #[serde(default)]
+    cpe_match: Vec<CpeMatchV1>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpeMatchV1 {
+    vulnerable: bool,
+    cpe23_uri: String,
+    version_start_including: Option<String>,
+    version_start_excluding: Option<String>,
+    version_end_including: Option<String>,
+    version_end_excluding: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ImpactV1 {
+    base_metric_v3: Option<BaseMetricV3>,
+    base_metric_v2: Option<BaseMetricV2>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BaseMetricV3 {
+    cvss_v3: Cvss,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BaseMetricV2 {
+    cvss_v2: Cvss,
+    severity: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Cvss {
+    base_score: f32,
+    base_severity: Option<String>,
+}
+
+/// Responses of the NVD 2.0 `cves` API, saved page by page.
+#[derive(Deserialize)]
+struct FeedV2 {
+    vulnerabilities: Vec<ItemV2>,
+}
+
+#[derive(Deserialize)]
+struct ItemV2 {
+    cve: CveV2,
+}
+
+#[derive(Deserialize)]
+struct CveV2 {
+    id: String,
+    #[serde(default)]
+    metrics: MetricsV2,
+    #[serde(default)]
+    configurations: Vec<ConfigurationV2>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct MetricsV2 {
+    #[serde(default)]
+    cvss_metric_v31: Vec<MetricV2>,
+    #[serde(default)]
+    cvss_metric_v30: Vec<MetricV2>,
+    #[serde(default)]
+    cvss_metric_v40: Vec<MetricV2>,
+    #[serde(default)]
+    cvss_metric_v2: Vec<MetricV2>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricV2 {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    cvss_data: Cvss,
+    base_severity: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ConfigurationV2 {
+    #[serde(default)]
+    nodes: Vec<NodeV2>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeV2 {
+    #[serde(default)]
+    cpe_match: Vec<CpeMatchV2>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpeMatchV2 {
+    vulnerable: bool,
+    criteria: String,
+    version_start_including: Option<String>,
+    version_start_excluding: Option<String>,
+    version_end_including: Option<String>,
+    version_end_excluding: Option<String>,
+}
+
+/// Flattens the configuration tree, AND nodes (e.g. an application on a given OS)
+/// are treated as if each vulnerable CPE was affected on its own.
+fn flatten_v1(nodes: Vec<NodeV1>, matches: &mut Vec<CpeMatchV1>) {
+    for node in nodes {
+        matches.extend(node.cpe_match);
+        flatten_v1(node.children, matches);
+    }
+}
+
+fn parse_v1(feed: FeedV1) -> Vec<CveEntry> {
+    let mut entries = vec![];
+    for item in feed.items {
+        let (score, severity) = match (item.impact.base_metric_v3, item.impact.base_metric_v2) {
+            (Some(v3), _) => (Some(v3.cvss_v3.base_score), v3.cvss_v3.base_severity),
+            (None, Some(v2)) => (Some(v2.cvss_v2.base_score), v2.severity),
+            (None, None) => (None, None),
+        };
+        let mut matches = vec![];
+        flatten_v1(item.configurations.nodes, &mut matches);
+        for cpe_match in matches.into_iter().filter(|cpe_match| cpe_match.vulnerable) {
+            entries.push(CveEntry {
+                id: item.cve.meta.id.clone(),
+                score,
+                severity: severity.clone(),
+                cpe: cpe_match.cpe23_uri,
+                version_start_including: cpe_match.version_start_including,
+                version_start_excluding: cpe_match.version_start_excluding,
+                version_end_including: cpe_match.version_end_including,
+                version_end_excluding: cpe_match.version_end_excluding,
+            });
+        }
+    }
+    entries
+}
+
+fn parse_v2(feed: FeedV2) -> Vec<CveEntry> {
+    let mut entries = vec![];
+    for item in feed.vulnerabilities {
+        let metrics = item.cve.metrics;
+        // NVD's own (primary) score of the newest CVSS version wins over the CNA's.
+        let metric = [
+            metrics.cvss_metric_v31,
+            metrics.cvss_metric_v30,
+            metrics.cvss_metric_v40,
+            metrics.cvss_metric_v2,
+        ]
+        .into_iter()
+        .find(|metrics| !metrics.is_empty())
+        .and_then(|metrics| {
+            let primary = metrics
+                .iter()
+                .position(|metric| metric.kind.as_deref() == Some("Primary"))
+                .unwrap_or(0);
+            metrics.into_iter().nth(primary)
+        });
+        let (score, severity) = match metric {
+            Some(metric) => (
+                Some(metric.cvss_data.base_score),
+                metric.cvss_data.base_severity.or(metric.base_severity),
+            ),
+            None => (None, None),
+        };
+        let matches = item
+            .cve
+            .configurations
+            .into_iter()
+            .flat_map(|configuration| configuration.nodes)
+            .flat_map(|node| node.cpe_match)
+            .filter(|cpe_match| cpe_match.vulnerable);
+        for cpe_match in matches {
+            entries.push(CveEntry {
+                id: item.cve.id.clone(),
+                score,
+                severity: severity.clone(),
+                cpe: cpe_match.criteria,
+                version_start_including: cpe_match.version_start_including,
+                version_start_excluding: cpe_match.version_start_excluding,
+                version_end_including: cpe_match.version_end_including,
+                version_end_excluding: cpe_match.version_end_excluding,
+            });
+        }
+    }
+    entries
+}
+
+/// Parses a compact index (a JSON list of entries), an NVD 1.1 data feed or an NVD 2.0
+/// API response.
+pub fn parse(content: &str) -> Result<Vec<CveEntry>, String> {
+    if content.trim_start().starts_with('[') {
+        return serde_json::from_str(content).map_err(|error| error.to_string());
+    }
+    if content.contains("\"CVE_Items\"") {
+        let feed = serde_json::from_str(content).map_err(|error| error.to_string())?;
+        return Ok(parse_v1(feed));
+    }
+    let feed = serde_json::from_str(content).map_err(|error| error.to_string())?;
+    Ok(parse_v2(feed))
+}